@@ -0,0 +1,93 @@
+//! Right click sends every unit to the cursor. The debug plugin draws the grid and the flowfield
+//! of the last order. Orders name their units explicitly, see the `obstacles` example for
+//! ordering a `Selected` group with `SelectionPlugin`.
+
+use bevy::prelude::*;
+use bevy_rts_pathfinding::prelude::*;
+
+const MAP_SIZE: IVec2 = IVec2::new(30, 30);
+const CELL_DIAMETER: f32 = 2.0;
+const UNIT_SPEED: f32 = 8.0;
+
+fn main() {
+    App::new()
+        .add_plugins((
+            DefaultPlugins,
+            BevyRtsPathFindingPlugin,
+            BevyRtsPathFindingDebugPlugin,
+        ))
+        .insert_resource(Grid::new(MAP_SIZE, CELL_DIAMETER, |_| false))
+        .add_systems(Startup, setup)
+        .add_systems(
+            Update,
+            (order_units, move_units.after(PathfindingSet::Steering)),
+        )
+        .run();
+}
+
+fn setup(
+    mut cmds: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    cmds.spawn((
+        Camera3d::default(),
+        Transform::from_xyz(0.0, 50.0, 40.0).looking_at(Vec3::ZERO, Vec3::Y),
+        GameCamera,
+    ));
+    cmds.spawn((
+        DirectionalLight::default(),
+        Transform::from_xyz(10.0, 30.0, 10.0).looking_at(Vec3::ZERO, Vec3::Y),
+    ));
+
+    // The cursor is projected onto the map base to find where units are sent
+    let map_size = MAP_SIZE.as_vec2() * CELL_DIAMETER;
+    cmds.spawn((
+        Mesh3d(meshes.add(Plane3d::default().mesh().size(map_size.x, map_size.y))),
+        MeshMaterial3d(materials.add(Color::srgb(0.3, 0.45, 0.3))),
+        MapBase,
+    ));
+
+    let mesh = meshes.add(Cuboid::new(1.0, 1.0, 1.0));
+    let material = materials.add(Color::srgb(0.2, 0.4, 0.8));
+    for i in 0..16 {
+        let pos = Vec3::new(
+            (i % 4) as f32 * 2.5 - 20.0,
+            0.5,
+            (i / 4) as f32 * 2.5 - 20.0,
+        );
+        cmds.spawn((
+            Mesh3d(mesh.clone()),
+            MeshMaterial3d(material.clone()),
+            Transform::from_translation(pos),
+            RtsObj,
+            RtsObjSize(Vec2::splat(0.5)),
+        ));
+    }
+}
+
+fn order_units(
+    mut cmds: Commands,
+    mouse: Res<ButtonInput<MouseButton>>,
+    q_units: Query<Entity, With<RtsObj>>,
+) {
+    if !mouse.just_pressed(MouseButton::Right) {
+        return;
+    }
+
+    let units: Vec<Entity> = q_units.iter().collect();
+    for unit in units.iter() {
+        cmds.entity(*unit).insert(Destination);
+    }
+
+    // Sends the units to the cursor position on the map base
+    cmds.trigger(InitializeFlowFieldEv(units));
+}
+
+/// Moves units along their `Steering`, which is removed once they arrive
+fn move_units(time: Res<Time>, mut q_units: Query<(&mut Transform, &Steering)>) {
+    for (mut transform, steering) in q_units.iter_mut() {
+        let step = steering.direction * steering.speed_factor * UNIT_SPEED * time.delta_secs();
+        transform.translation += Vec3::new(step.x, 0.0, step.y);
+    }
+}
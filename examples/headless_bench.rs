@@ -0,0 +1,71 @@
+//! Builds flowfields without a window or renderer and reports how long they took. Run with
+//! `cargo run --release --example headless_bench --no-default-features` to profile the crate on
+//! a given map size.
+
+use bevy::{prelude::*, utils::Instant};
+use bevy_rts_pathfinding::{
+    astar::AStarSettings, prelude::*, resources::PathfindingStats, scheduler::FlowFieldScheduler,
+};
+use std::time::Duration;
+
+const MAP_SIZE: IVec2 = IVec2::new(256, 256);
+const CELL_DIAMETER: f32 = 1.0;
+const ORDERS: usize = 50;
+
+fn main() {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, BevyRtsPathFindingPlugin))
+        .insert_resource(Grid::new(MAP_SIZE, CELL_DIAMETER, is_rock))
+        // Single unit orders would path with A* instead of building a field
+        .insert_resource(AStarSettings { max_units: 0 });
+
+    // Build every queued order in a single frame
+    let mut scheduler = app.world_mut().resource_mut::<FlowFieldScheduler>();
+    scheduler.max_builds = usize::MAX;
+    scheduler.time_budget = Duration::MAX;
+
+    // Let the grid settle before the orders are given
+    app.update();
+
+    let reach = MAP_SIZE.as_vec2() * CELL_DIAMETER * 0.45;
+    for i in 0..ORDERS {
+        // Spread units and their destinations on opposite sides of the map
+        let angle = i as f32 * 2.4;
+        let distance = (i + 1) as f32 / ORDERS as f32;
+        let offset = Vec3::new(angle.cos() * reach.x, 0.0, angle.sin() * reach.y) * distance;
+
+        let unit = app
+            .world_mut()
+            .spawn((
+                Transform::from_translation(-offset),
+                RtsObj,
+                RtsObjSize(Vec2::splat(0.4)),
+                Destination,
+            ))
+            .id();
+
+        app.world_mut()
+            .trigger(InitializeFlowFieldAtEv::new(vec![unit], offset));
+    }
+
+    let start = Instant::now();
+    app.update();
+    let frame = start.elapsed();
+
+    let stats = app.world().resource::<PathfindingStats>();
+    println!(
+        "{}x{} grid, {} orders: {} fields built in {:?}, {:?} per field on average",
+        MAP_SIZE.x,
+        MAP_SIZE.y,
+        ORDERS,
+        stats.integrations,
+        frame,
+        stats.average_integration(),
+    );
+}
+
+/// Scattered rock formations, 4 cells wide
+fn is_rock(pos: Vec3) -> bool {
+    let block = (pos.xz() / (CELL_DIAMETER * 4.0)).floor().as_ivec2();
+    (block.x * 7 + block.y * 13).rem_euclid(11) == 0
+}
@@ -0,0 +1,56 @@
+//! Times the two `IntegrationMethod`s against each other on uniform costs and on grids where a
+//! growing share of the cells costs between 1 and 9, the figures quoted on `IntegrationMethod`.
+//! Run with `cargo run --release --example integration_bench --no-default-features`.
+
+use bevy::{prelude::*, utils::Instant};
+use bevy_rts_pathfinding::{flowfield::IntegrationMethod, grid::Connectivity, prelude::*};
+use std::time::Duration;
+
+const MAP_SIZE: IVec2 = IVec2::new(200, 200);
+const CELL_DIAMETER: f32 = 1.0;
+const RUNS: u32 = 20;
+/// Share of the cells with a cost between 1 and 9, the rest cost 1
+const MIXED_SHARES: [f32; 7] = [0.0, 0.1, 0.25, 0.4, 0.5, 0.75, 1.0];
+
+fn main() {
+    let destination = Vec3::ZERO;
+    for share in MIXED_SHARES {
+        let grid = mixed_grid(share);
+        let breadth = bench(&grid, destination, IntegrationMethod::Breadth);
+        let dijkstra = bench(&grid, destination, IntegrationMethod::Dijkstra);
+        println!(
+            "{:>3.0}% mixed costs: Breadth {breadth:?}, Dijkstra {dijkstra:?}, Breadth / Dijkstra {:.2}",
+            share * 100.0,
+            breadth.as_secs_f64() / dijkstra.as_secs_f64(),
+        );
+    }
+}
+
+/// A grid where `share` of the cells get a cost between 1 and 9, scattered the same every run
+fn mixed_grid(share: f32) -> Grid {
+    let mut grid = Grid::new(MAP_SIZE, CELL_DIAMETER, |_| false);
+    for y in 0..MAP_SIZE.y {
+        for x in 0..MAP_SIZE.x {
+            let hash = (x as u32).wrapping_mul(73_856_093) ^ (y as u32).wrapping_mul(19_349_663);
+            let hash = hash.wrapping_mul(2_654_435_761);
+            if (hash % 1000) as f32 >= share * 1000.0 {
+                continue;
+            }
+
+            grid.set_base_cost(IVec2::new(x, y), (hash >> 16) as u8 % 9 + 1);
+        }
+    }
+
+    grid
+}
+
+/// The average time of integrating `grid` from `destination` with `method`
+fn bench(grid: &Grid, destination: Vec3, method: IntegrationMethod) -> Duration {
+    let start = Instant::now();
+    for _ in 0..RUNS {
+        let field = IntegrationField::new(grid, destination, Connectivity::default(), method);
+        assert!(field.distance_at(destination).is_some());
+    }
+
+    start.elapsed() / RUNS
+}
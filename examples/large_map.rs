@@ -0,0 +1,152 @@
+//! A 256x256 map with a few hundred units. There is no hierarchical pathfinding mode: large maps
+//! are kept responsive by spreading flowfield builds over frames with the `FlowFieldScheduler`,
+//! steering big selections as a group and only drawing debug markers near the camera.
+//!
+//! WASD pans the camera, right click sends every unit to the cursor.
+
+use bevy::prelude::*;
+use bevy_rts_pathfinding::{
+    debug::{DebugOptions, DrawMode},
+    prelude::*,
+    scheduler::FlowFieldScheduler,
+    steering::GroupSteeringSettings,
+};
+use std::time::Duration;
+
+const MAP_SIZE: IVec2 = IVec2::new(256, 256);
+const CELL_DIAMETER: f32 = 2.0;
+const UNITS: usize = 400;
+const UNIT_SPEED: f32 = 8.0;
+const CAMERA_SPEED: f32 = 60.0;
+
+fn main() {
+    App::new()
+        .add_plugins((
+            DefaultPlugins,
+            BevyRtsPathFindingPlugin,
+            SelectionPlugin,
+            BevyRtsPathFindingDebugPlugin,
+        ))
+        .insert_resource(Grid::new(MAP_SIZE, CELL_DIAMETER, is_rock))
+        .insert_resource(GroupSteeringSettings {
+            enabled: true,
+            min_units: 100,
+            leader_speed: UNIT_SPEED,
+            ..default()
+        })
+        .add_systems(Startup, (setup, configure))
+        .add_systems(
+            Update,
+            (
+                pan_camera,
+                order_units,
+                move_units.after(PathfindingSet::Steering),
+            ),
+        )
+        .run();
+}
+
+/// Scattered rock formations, 4 cells wide
+fn is_rock(pos: Vec3) -> bool {
+    let block = (pos.xz() / (CELL_DIAMETER * 4.0)).floor().as_ivec2();
+    (block.x * 7 + block.y * 13).rem_euclid(11) == 0
+}
+
+fn configure(mut scheduler: ResMut<FlowFieldScheduler>, mut dbg: ResMut<DebugOptions>) {
+    // Builds take longer on a big grid, one per frame keeps the frame rate steady
+    scheduler.max_builds = 1;
+    scheduler.time_budget = Duration::from_millis(8);
+
+    // Drawing every cell of the grid would cost more than the pathfinding
+    dbg.draw_grid = false;
+    dbg.draw_mode_1 = DrawMode::None;
+    dbg.cull_to_camera = true;
+    dbg.max_draw_distance = Some(60.0);
+}
+
+fn setup(
+    mut cmds: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    grid: Res<Grid>,
+) {
+    cmds.spawn((
+        Camera3d::default(),
+        Transform::from_xyz(0.0, 80.0, 60.0).looking_at(Vec3::ZERO, Vec3::Y),
+        GameCamera,
+    ));
+    cmds.spawn((
+        DirectionalLight::default(),
+        Transform::from_xyz(10.0, 30.0, 10.0).looking_at(Vec3::ZERO, Vec3::Y),
+    ));
+
+    let map_size = MAP_SIZE.as_vec2() * CELL_DIAMETER;
+    cmds.spawn((
+        Mesh3d(meshes.add(Plane3d::default().mesh().size(map_size.x, map_size.y))),
+        MeshMaterial3d(materials.add(Color::srgb(0.3, 0.45, 0.3))),
+        MapBase,
+    ));
+
+    let mesh = meshes.add(Cuboid::new(1.0, 1.0, 1.0));
+    let material = materials.add(Color::srgb(0.2, 0.4, 0.8));
+    let columns = (UNITS as f32).sqrt().ceil() as usize;
+    let mut spawned = 0;
+    for i in 0.. {
+        if spawned == UNITS {
+            break;
+        }
+
+        let offset = Vec3::new((i % columns) as f32, 0.0, (i / columns) as f32) * 2.0;
+        let pos = Vec3::new(-20.0, 0.5, -20.0) + offset;
+        if grid.get_cell_from_world_position(pos).cost == u8::MAX {
+            continue;
+        }
+
+        cmds.spawn((
+            Mesh3d(mesh.clone()),
+            MeshMaterial3d(material.clone()),
+            Transform::from_translation(pos),
+            RtsObj,
+            RtsObjSize(Vec2::splat(0.5)),
+            Selected,
+        ));
+        spawned += 1;
+    }
+}
+
+fn pan_camera(
+    time: Res<Time>,
+    keys: Res<ButtonInput<KeyCode>>,
+    mut q_cam: Query<&mut Transform, With<GameCamera>>,
+) {
+    let Ok(mut transform) = q_cam.get_single_mut() else {
+        return;
+    };
+
+    let mut direction = Vec3::ZERO;
+    for (key, dir) in [
+        (KeyCode::KeyW, Vec3::NEG_Z),
+        (KeyCode::KeyS, Vec3::Z),
+        (KeyCode::KeyA, Vec3::NEG_X),
+        (KeyCode::KeyD, Vec3::X),
+    ] {
+        if keys.pressed(key) {
+            direction += dir;
+        }
+    }
+
+    transform.translation += direction.normalize_or_zero() * CAMERA_SPEED * time.delta_secs();
+}
+
+fn order_units(mut cmds: Commands, mouse: Res<ButtonInput<MouseButton>>) {
+    if mouse.just_pressed(MouseButton::Right) {
+        cmds.trigger(MoveSelectedEv::new());
+    }
+}
+
+fn move_units(time: Res<Time>, mut q_units: Query<(&mut Transform, &Steering)>) {
+    for (mut transform, steering) in q_units.iter_mut() {
+        let step = steering.direction * steering.speed_factor * UNIT_SPEED * time.delta_secs();
+        transform.translation += Vec3::new(step.x, 0.0, step.y);
+    }
+}
@@ -0,0 +1,158 @@
+//! Left click places a wall under the cursor, backspace removes the last one and right click
+//! sends every unit to the cursor. Units already on their way route around new walls.
+
+use bevy::prelude::*;
+use bevy_rts_pathfinding::{prelude::*, utils};
+
+const MAP_SIZE: IVec2 = IVec2::new(30, 30);
+const CELL_DIAMETER: f32 = 2.0;
+const UNIT_SPEED: f32 = 8.0;
+
+#[derive(Resource)]
+struct Walls {
+    /// In the order they were placed
+    placed: Vec<Entity>,
+    mesh: Handle<Mesh>,
+    material: Handle<StandardMaterial>,
+}
+
+fn main() {
+    App::new()
+        .add_plugins((
+            DefaultPlugins,
+            BevyRtsPathFindingPlugin,
+            SelectionPlugin,
+            BevyRtsPathFindingDebugPlugin,
+        ))
+        .insert_resource(Grid::new(MAP_SIZE, CELL_DIAMETER, |_| false))
+        .add_systems(Startup, setup)
+        .add_systems(
+            Update,
+            (
+                place_walls,
+                remove_walls,
+                order_units,
+                move_units.after(PathfindingSet::Steering),
+            ),
+        )
+        .run();
+}
+
+fn setup(
+    mut cmds: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    cmds.spawn((
+        Camera3d::default(),
+        Transform::from_xyz(0.0, 50.0, 40.0).looking_at(Vec3::ZERO, Vec3::Y),
+        GameCamera,
+    ));
+    cmds.spawn((
+        DirectionalLight::default(),
+        Transform::from_xyz(10.0, 30.0, 10.0).looking_at(Vec3::ZERO, Vec3::Y),
+    ));
+
+    let map_size = MAP_SIZE.as_vec2() * CELL_DIAMETER;
+    cmds.spawn((
+        Mesh3d(meshes.add(Plane3d::default().mesh().size(map_size.x, map_size.y))),
+        MeshMaterial3d(materials.add(Color::srgb(0.3, 0.45, 0.3))),
+        MapBase,
+    ));
+
+    cmds.insert_resource(Walls {
+        placed: Vec::new(),
+        mesh: meshes.add(Cuboid::new(CELL_DIAMETER, 2.0, CELL_DIAMETER)),
+        material: materials.add(Color::srgb(0.5, 0.35, 0.25)),
+    });
+
+    let mesh = meshes.add(Cuboid::new(1.0, 1.0, 1.0));
+    let material = materials.add(Color::srgb(0.2, 0.4, 0.8));
+    for i in 0..16 {
+        let pos = Vec3::new(
+            (i % 4) as f32 * 2.5 - 20.0,
+            0.5,
+            (i / 4) as f32 * 2.5 - 20.0,
+        );
+        cmds.spawn((
+            Mesh3d(mesh.clone()),
+            MeshMaterial3d(material.clone()),
+            Transform::from_translation(pos),
+            RtsObj,
+            RtsObjSize(Vec2::splat(0.5)),
+            Selected,
+        ));
+    }
+}
+
+/// A wall is any `RtsObj` without a `Destination`. Its cells are blocked once it's spawned.
+fn place_walls(
+    mut cmds: Commands,
+    mut walls: ResMut<Walls>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    grid: Res<Grid>,
+    q_window: Query<&Window>,
+    q_cam: Query<(&Camera, &GlobalTransform), With<GameCamera>>,
+    q_map_base: Query<&GlobalTransform, With<MapBase>>,
+) {
+    if !mouse.just_pressed(MouseButton::Left) {
+        return;
+    }
+
+    let (Ok(window), Ok((cam, cam_transform)), Ok(map_base)) = (
+        q_window.get_single(),
+        q_cam.get_single(),
+        q_map_base.get_single(),
+    ) else {
+        return;
+    };
+
+    let Some(cursor_pos) = window.cursor_position() else {
+        return;
+    };
+
+    let Ok(world_pos) = utils::get_world_pos(map_base, cam_transform, cam, cursor_pos) else {
+        return;
+    };
+
+    // Snap the wall to the cell under the cursor
+    let Some(cell) = grid.try_get_cell_from_world_position(world_pos) else {
+        return;
+    };
+
+    let wall = cmds
+        .spawn((
+            Mesh3d(walls.mesh.clone()),
+            MeshMaterial3d(walls.material.clone()),
+            Transform::from_translation(cell.world_pos + Vec3::Y),
+            RtsObj,
+            RtsObjSize(Vec2::splat(CELL_DIAMETER)),
+        ))
+        .id();
+
+    walls.placed.push(wall);
+}
+
+/// Despawning a wall frees its cells again
+fn remove_walls(mut cmds: Commands, mut walls: ResMut<Walls>, keys: Res<ButtonInput<KeyCode>>) {
+    if !keys.just_pressed(KeyCode::Backspace) {
+        return;
+    }
+
+    if let Some(wall) = walls.placed.pop() {
+        cmds.entity(wall).despawn();
+    }
+}
+
+fn order_units(mut cmds: Commands, mouse: Res<ButtonInput<MouseButton>>) {
+    if mouse.just_pressed(MouseButton::Right) {
+        cmds.trigger(MoveSelectedEv::new());
+    }
+}
+
+fn move_units(time: Res<Time>, mut q_units: Query<(&mut Transform, &Steering)>) {
+    for (mut transform, steering) in q_units.iter_mut() {
+        let step = steering.direction * steering.speed_factor * UNIT_SPEED * time.delta_secs();
+        transform.translation += Vec3::new(step.x, 0.0, step.y);
+    }
+}
@@ -0,0 +1,72 @@
+//! Steers thousands of units along one flowfield without a window or renderer and reports the
+//! frame time for each unit count. Units sample their field in parallel, so on machines with
+//! more cores the frame time grows slower than the unit count. Run with
+//! `cargo run --release --example steering_bench --no-default-features`.
+
+use bevy::{prelude::*, utils::Instant};
+use bevy_rts_pathfinding::{prelude::*, scheduler::FlowFieldScheduler};
+use std::time::Duration;
+
+const MAP_SIZE: IVec2 = IVec2::new(256, 256);
+const CELL_DIAMETER: f32 = 1.0;
+const UNIT_COUNTS: [usize; 3] = [1_000, 5_000, 10_000];
+const FRAMES: u32 = 60;
+
+fn main() {
+    for unit_count in UNIT_COUNTS {
+        let frame = bench(unit_count);
+        println!(
+            "{unit_count} units: {frame:?} per frame, {:?} per unit",
+            frame / unit_count as u32
+        );
+    }
+}
+
+/// The average frame time of `unit_count` units steering towards the far side of the map
+fn bench(unit_count: usize) -> Duration {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, BevyRtsPathFindingPlugin))
+        .insert_resource(Grid::new(MAP_SIZE, CELL_DIAMETER, |_| false));
+
+    let mut scheduler = app.world_mut().resource_mut::<FlowFieldScheduler>();
+    scheduler.max_builds = usize::MAX;
+    scheduler.time_budget = Duration::MAX;
+
+    app.update();
+
+    // A square block of units on the west side, half a cell apart
+    let columns = (unit_count as f32).sqrt().ceil() as usize;
+    let units: Vec<Entity> = (0..unit_count)
+        .map(|i| {
+            let offset = Vec3::new((i % columns) as f32, 0.0, (i / columns) as f32) * 0.5;
+            let pos = Vec3::new(-120.0, 0.0, -25.0) + offset;
+            app.world_mut()
+                .spawn((
+                    Transform::from_translation(pos),
+                    RtsObj,
+                    RtsObjSize(Vec2::splat(0.4)),
+                    Destination,
+                ))
+                .id()
+        })
+        .collect();
+
+    app.world_mut().trigger(InitializeFlowFieldAtEv::new(
+        units,
+        Vec3::new(110.0, 0.0, 0.0),
+    ));
+
+    // Build the field and give every unit its `Steering`
+    app.update();
+    app.update();
+
+    let mut steering = app.world_mut().query::<&Steering>();
+    assert_eq!(steering.iter(app.world()).count(), unit_count);
+
+    let start = Instant::now();
+    for _ in 0..FRAMES {
+        app.update();
+    }
+
+    start.elapsed() / FRAMES
+}
@@ -0,0 +1,412 @@
+//! A* paths for orders too small to be worth a flowfield, like a scout or a hero sent off on
+//! their own. Paths cross the shared costfield by the same rules as flowfield integration, with
+//! the same neighbors, edge costs and impassable cells, so a path costs what the `best_cost` of
+//! a flowfield to the same destination would be at its start. Move orders with at most
+//! `AStarSettings::max_units` units follow `AStarPath`s instead of a flowfield.
+
+use crate::{
+    cell::{add_cost, BestCost, Cell},
+    components::Destination,
+    events::DestinationReachedEv,
+    flowfield::{FlowFieldId, TerrainSpeed},
+    grid::{
+        coords::{self, EdgePolicies, EdgePolicy},
+        costs, Connectivity, Grid, Grids,
+    },
+    path,
+    steering::{self, HoldingPosition, Steering},
+    PathfindingSchedule, PathfindingSet,
+};
+
+use bevy::prelude::*;
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+};
+
+pub struct AStarPlugin;
+
+impl Plugin for AStarPlugin {
+    fn build(&self, app: &mut App) {
+        let schedule = PathfindingSchedule::label(app);
+        app.init_resource::<AStarSettings>()
+            .register_type::<AStarSettings>()
+            .register_type::<AStarPath>()
+            .add_systems(
+                schedule,
+                follow_astar_paths
+                    .in_set(PathfindingSet::Steering)
+                    .before(steering::limit_turn_rates),
+            );
+    }
+}
+
+#[derive(Resource, Reflect, Clone, Copy, Debug)]
+#[reflect(Resource)]
+pub struct AStarSettings {
+    /// Move orders with at most this many units path with A* instead of building a flowfield.
+    /// 0 always builds flowfields.
+    pub max_units: usize,
+}
+
+impl Default for AStarSettings {
+    fn default() -> Self {
+        Self { max_units: 1 }
+    }
+}
+
+/// The waypoints a unit follows to its destination, the last one being the exact destination.
+/// Removed along with the unit's `Destination` once it arrives.
+#[derive(Component, Reflect, Clone, Debug, Default)]
+#[reflect(Component)]
+pub struct AStarPath {
+    pub waypoints: Vec<Vec3>,
+    /// The waypoint the unit heads to
+    pub next: usize,
+    /// The map entity whose `Grid` the path crosses, `None` for the `Grid` resource
+    pub map: Option<Entity>,
+    /// The id of the move order, handed out by `FlowFieldIds` like those of flowfields
+    pub id: Option<FlowFieldId>,
+}
+
+impl AStarPath {
+    pub fn new(waypoints: Vec<Vec3>) -> Self {
+        Self {
+            waypoints,
+            ..default()
+        }
+    }
+
+    /// Follows the path on the `Grid` of the `map` entity instead of the `Grid` resource
+    pub fn on_map(mut self, map: Entity) -> Self {
+        self.map = Some(map);
+        self
+    }
+
+    pub fn with_id(mut self, id: FlowFieldId) -> Self {
+        self.id = Some(id);
+        self
+    }
+
+    pub fn destination(&self) -> Option<Vec3> {
+        self.waypoints.last().copied()
+    }
+
+    /// Plans the path again from `pos` over the current costs of `grid`, keeping its
+    /// destination. Returns false and leaves the path as it was if the destination can't be
+    /// reached anymore.
+    pub fn replan(&mut self, grid: &Grid, pos: Vec3, connectivity: Connectivity) -> bool {
+        let Some(destination) = self.destination() else {
+            return false;
+        };
+
+        let unit = [(Entity::PLACEHOLDER, pos)];
+        let Some(mut paths) = plan_order(grid, &unit, destination, connectivity) else {
+            return false;
+        };
+
+        let Some((_, waypoints)) = paths.pop() else {
+            return false;
+        };
+
+        self.waypoints = waypoints;
+        self.next = 0;
+        return true;
+    }
+}
+
+/// The cheapest path across `grid` from the cell at `from` to the cell at `to`, every visited
+/// cell included like `FlowField::extract_path`. Cells units stand in block the path, except
+/// the unit's own start cell. Returns an empty path if `to` is off the grid or unreachable.
+///
+/// The heuristic assumes cells cost at least 1, paths over cheaper cells may not be the
+/// cheapest.
+pub fn find_path(grid: &Grid, from: Vec3, to: Vec3, connectivity: Connectivity) -> Vec<Cell> {
+    let start = grid.get_cell_from_world_position(from).idx;
+    let Some(goal) = grid
+        .try_get_cell_from_world_position(to)
+        .map(|cell| cell.idx)
+    else {
+        return Vec::new();
+    };
+
+    let wrap = grid.edge_policies();
+    let cost_of = |idx: IVec2| match idx == start {
+        true => grid
+            .cost_without(idx, &[costs::UNIT_LAYER])
+            .unwrap_or(u8::MAX),
+        false => grid.grid[idx.y as usize][idx.x as usize].cost,
+    };
+
+    let mut best: HashMap<IVec2, BestCost> = HashMap::from([(start, 0)]);
+    let mut came_from: HashMap<IVec2, IVec2> = HashMap::new();
+    let mut open = BinaryHeap::from([Reverse((
+        steps_between(start, goal, grid.size, (connectivity, wrap)),
+        0,
+        start.x,
+        start.y,
+    ))]);
+
+    while let Some(Reverse((_, cost, x, y))) = open.pop() {
+        let idx = IVec2::new(x, y);
+        if idx == goal {
+            let mut path = vec![goal];
+            let mut current = goal;
+            while let Some(previous) = came_from.get(&current) {
+                path.push(*previous);
+                current = *previous;
+            }
+
+            let mut cells: Vec<Cell> = path
+                .into_iter()
+                .rev()
+                .map(|idx| grid.grid[idx.y as usize][idx.x as usize])
+                .collect();
+            cells[0].cost = cost_of(start);
+            return cells;
+        }
+
+        // Skip entries superseded by a cheaper route
+        if best.get(&idx).is_some_and(|best| cost > *best) {
+            continue;
+        }
+
+        // Like in the integration field, moving out of a cell costs the cell's cost
+        let cell_cost = cost_of(idx);
+        if cell_cost == u8::MAX {
+            continue;
+        }
+
+        for direction in connectivity.integration_directions(idx) {
+            let Some(neighbor) = wrap.wrap(idx + direction.vector(), grid.size) else {
+                continue;
+            };
+
+            let edge_cost = grid.edge_cost(idx, neighbor);
+            if edge_cost == u8::MAX {
+                continue;
+            }
+
+            let tentative = add_cost(add_cost(cost, cell_cost), edge_cost);
+            if best.get(&neighbor).is_none_or(|best| tentative < *best) {
+                best.insert(neighbor, tentative);
+                came_from.insert(neighbor, idx);
+
+                let estimate = steps_between(neighbor, goal, grid.size, (connectivity, wrap));
+                open.push(Reverse((
+                    tentative.saturating_add(estimate),
+                    tentative,
+                    neighbor.x,
+                    neighbor.y,
+                )));
+            }
+        }
+    }
+
+    return Vec::new();
+}
+
+/// `find_path` shortened to its corners with `path::smooth_on_grid`, leaving out the start cell
+/// and ending at exactly `to`. `None` if `to` can't be reached.
+pub fn find_waypoints(
+    grid: &Grid,
+    from: Vec3,
+    to: Vec3,
+    connectivity: Connectivity,
+) -> Option<Vec<Vec3>> {
+    let path = find_path(grid, from, to, connectivity);
+    if path.is_empty() {
+        return None;
+    }
+
+    let mut waypoints = path::smooth_on_grid(grid, &path);
+    waypoints.remove(0);
+    waypoints.pop();
+    waypoints.push(to);
+
+    return Some(waypoints);
+}
+
+/// The fewest moves between two cells, a lower bound of the cost between them
+fn steps_between(
+    from: IVec2,
+    to: IVec2,
+    size: IVec2,
+    (connectivity, wrap): (Connectivity, EdgePolicies),
+) -> BestCost {
+    let mut delta = (to - from).abs();
+    if wrap.x == EdgePolicy::Wrap {
+        delta.x = delta.x.min(size.x - delta.x);
+    }
+    if wrap.y == EdgePolicy::Wrap {
+        delta.y = delta.y.min(size.y - delta.y);
+    }
+
+    // Hex moves change both coordinates by at most 1, the others change only one
+    let steps = match connectivity {
+        Connectivity::Hex => delta.max_element(),
+        Connectivity::Cardinal4 | Connectivity::Octile8 => delta.x + delta.y,
+    };
+
+    return steps as BestCost;
+}
+
+/// The waypoints of every unit of a move order to `destination` on `grid`, or `None` if the
+/// order needs a flowfield: on wrapping grids, whose seams waypoints can't cross, or when a
+/// unit can't reach the destination.
+pub(crate) fn plan_order(
+    grid: &Grid,
+    units: &[(Entity, Vec3)],
+    destination: Vec3,
+    connectivity: Connectivity,
+) -> Option<Vec<(Entity, Vec<Vec3>)>> {
+    if grid.edge_policies() != EdgePolicies::default() {
+        return None;
+    }
+
+    return units
+        .iter()
+        .map(|(unit, pos)| {
+            Some((
+                *unit,
+                find_waypoints(grid, *pos, destination, connectivity)?,
+            ))
+        })
+        .collect();
+}
+
+/// Steers units along their `AStarPath`, settling them once they reach its end
+fn follow_astar_paths(
+    mut cmds: Commands,
+    grids: Grids,
+    terrain_speed: Res<TerrainSpeed>,
+    mut reached: EventWriter<DestinationReachedEv>,
+    mut q_units: Query<(Entity, &Transform, &mut AStarPath, Option<&mut Steering>)>,
+) {
+    for (unit, transform, mut path, steering) in q_units.iter_mut() {
+        let Some(grid) = grids.get(path.map) else {
+            continue;
+        };
+
+        // Waypoints count as passed within half a cell
+        let pos = transform.translation;
+        let within_reach = |waypoint: Vec3| (waypoint - pos).xz().length() < grid.cell_radius;
+        while path.next + 1 < path.waypoints.len() && within_reach(path.waypoints[path.next]) {
+            path.next += 1;
+        }
+
+        let Some(&waypoint) = path.waypoints.get(path.next) else {
+            cmds.entity(unit).remove::<AStarPath>();
+            continue;
+        };
+
+        if path.next + 1 == path.waypoints.len() && within_reach(waypoint) {
+            cmds.entity(unit)
+                .remove::<(AStarPath, Destination, Steering)>()
+                .insert(HoldingPosition);
+            let mut arrival = DestinationReachedEv::new(unit, waypoint);
+            arrival.flowfield = path.id;
+            reached.send(arrival);
+            continue;
+        }
+
+        let direction = (waypoint - pos).xz().normalize_or_zero();
+        let direction = coords::direction_to_world(direction, grid.rotation);
+        let speed_factor = terrain_speed.factor(grid.get_cell_from_world_position(pos).cost);
+        match steering {
+            Some(mut steering) => {
+                steering.direction = direction;
+                steering.speed_factor = speed_factor;
+            }
+            None => {
+                cmds.entity(unit).insert(Steering {
+                    direction,
+                    speed_factor,
+                    ..default()
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{flowfield::FlowField, layers::GridLayers};
+
+    #[test]
+    fn paths_cost_what_the_flowfield_would() {
+        // A wall at x = 0 from the top edge down to the last row, which stays open, and a
+        // unit standing in its start cell
+        let mut grid = Grid::new(IVec2::new(7, 5), 1.0, |pos| pos.x == 0.0 && pos.z < 2.0);
+        grid.set_base_cost(IVec2::new(1, 4), 6);
+        let from = Vec3::new(-3.0, 0.0, -2.0);
+        let to = Vec3::new(3.0, 0.0, -2.0);
+        grid.update_unit_cell_costs(from);
+
+        let path = find_path(&grid, from, to, Connectivity::Octile8);
+        let cost: BestCost = path
+            .iter()
+            .take(path.len() - 1)
+            .zip(path.iter().skip(1))
+            .map(|(cell, next)| {
+                cell.cost as BestCost + grid.edge_cost(cell.idx, next.idx) as BestCost
+            })
+            .sum();
+
+        let mut flowfield = FlowField::new(grid.cell_radius, grid.size, Vec::new());
+        flowfield.create_fields(&grid, &GridLayers::default(), &[], to);
+        let start = path[0].idx;
+        let without_unit = grid.cost_without(start, &[costs::UNIT_LAYER]).unwrap();
+        assert_eq!(without_unit, 1);
+        assert_eq!(path.last().unwrap().idx, flowfield.destination_cell.idx);
+        assert!(path.iter().all(|cell| cell.cost != u8::MAX));
+        assert!(path.iter().all(|cell| cell.idx.x != 3 || cell.idx.y == 4));
+
+        // The field around the unit's cell is as far as the unit's first step plus its cost
+        let next = path[1].idx;
+        let next_cost = flowfield.grid[next.y as usize][next.x as usize].best_cost;
+        assert_eq!(cost, next_cost + without_unit as BestCost);
+
+        let waypoints = find_waypoints(&grid, from, to, Connectivity::Octile8).unwrap();
+        assert!(waypoints.len() < path.len());
+        assert_eq!(waypoints.last(), Some(&to));
+
+        // Walled off destinations can't be reached
+        grid.set_base_cost(IVec2::new(4, 4), u8::MAX);
+        grid.set_base_cost(IVec2::new(5, 4), u8::MAX);
+        assert!(find_path(&grid, from, to, Connectivity::Octile8).is_empty());
+        assert!(plan_order(
+            &grid,
+            &[(Entity::PLACEHOLDER, from)],
+            to,
+            Connectivity::Octile8
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn replanned_paths_go_around_new_walls() {
+        let mut grid = Grid::new(IVec2::new(7, 5), 1.0, |_| false);
+        let from = Vec3::new(-3.0, 0.0, 0.0);
+        let to = Vec3::new(3.0, 0.0, 0.0);
+        let waypoints = find_waypoints(&grid, from, to, Connectivity::Octile8).unwrap();
+        let mut path = AStarPath::new(waypoints);
+        path.next = 1;
+
+        // A wall across the straight route, open along the bottom row
+        for y in 0..4 {
+            grid.set_base_cost(IVec2::new(3, y), u8::MAX);
+        }
+        assert!(path.replan(&grid, from, Connectivity::Octile8));
+        assert_eq!(path.next, 0);
+        assert_eq!(path.destination(), Some(to));
+        assert!(path.waypoints.iter().any(|waypoint| waypoint.z > 1.0));
+
+        // Unreachable destinations keep the old path
+        grid.set_base_cost(IVec2::new(3, 4), u8::MAX);
+        let before = path.waypoints.clone();
+        assert!(!path.replan(&grid, from, Connectivity::Octile8));
+        assert_eq!(path.waypoints, before);
+    }
+}
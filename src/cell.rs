@@ -1,28 +1,67 @@
 use bevy::prelude::*;
-use std::u16;
 
-use crate::grid_direction::GridDirection;
+use crate::{grid::Grid, grid_direction::GridDirection};
 
+/// The integrated cost of reaching the destination from a cell. Enable the `wide-costs` feature
+/// for maps where that can exceed `MAX_BEST_COST` with `u16`.
+#[cfg(not(feature = "wide-costs"))]
+pub type BestCost = u16;
+#[cfg(feature = "wide-costs")]
+pub type BestCost = u32;
+
+/// The `best_cost` of cells that can't reach the destination
+pub const UNREACHABLE: BestCost = BestCost::MAX;
+
+/// The ceiling integrated costs saturate at, so long expensive routes never look unreachable
+pub const MAX_BEST_COST: BestCost = UNREACHABLE - 1;
+
+/// `best_cost` plus the cost of entering a cell, saturating at `MAX_BEST_COST`
+pub fn add_cost(best_cost: BestCost, cost: u8) -> BestCost {
+    best_cost
+        .saturating_add(cost as BestCost)
+        .min(MAX_BEST_COST)
+}
+
+/// One cell of a `Grid` or `FlowField`. Flowfields copy the grid's cells and fill in
+/// `best_cost` and `best_direction`.
 #[derive(Clone, Default, Copy, Debug, PartialEq, Reflect)]
 pub struct Cell {
-    pub best_cost: u16,
+    /// The integrated cost of reaching the destination, `UNREACHABLE` on the grid itself
+    pub best_cost: BestCost,
+    /// The direction towards the destination, `GridDirection::None` on the grid itself
     pub best_direction: GridDirection,
+    /// The cost of entering the cell, `u8::MAX` if impassable
     pub cost: u8,
+    /// The column and row of the cell
     pub idx: IVec2,
+    /// The center of the cell
     pub world_pos: Vec3,
 }
 
 impl Cell {
-    pub fn new(world_position: Vec3, grid_idx: IVec2) -> Self {
+    pub fn new(world_pos: Vec3, idx: IVec2) -> Self {
         Cell {
-            best_cost: u16::MAX,
+            best_cost: UNREACHABLE,
             best_direction: GridDirection::None,
             cost: 1,
-            idx: grid_idx,
-            world_pos: world_position,
+            idx,
+            world_pos,
         }
     }
 
+    pub fn world_pos(&self) -> Vec3 {
+        self.world_pos
+    }
+
+    pub fn idx(&self) -> IVec2 {
+        self.idx
+    }
+
+    /// The index of the cell when the grid's rows are laid out one after another
+    pub fn id(&self, grid: &Grid) -> usize {
+        (self.idx.y * grid.size.x + self.idx.x) as usize
+    }
+
     pub fn increase_cost(&mut self, amount: u8) {
         if self.cost == u8::MAX {
             return;
@@ -35,3 +74,22 @@ impl Cell {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ids_count_cells_row_by_row() {
+        let grid = Grid::new(IVec2::new(4, 3), 1.0, |_| false);
+        let ids: Vec<usize> = grid
+            .grid
+            .iter()
+            .flatten()
+            .map(|cell| cell.id(&grid))
+            .collect();
+
+        assert_eq!(ids, (0..12).collect::<Vec<_>>());
+        assert_eq!(grid.grid[2][1].idx(), IVec2::new(1, 2));
+    }
+}
@@ -1,13 +1,66 @@
 use bevy::prelude::*;
 
-#[derive(Component)]
+#[derive(Component, Reflect)]
+#[reflect(Component)]
 pub struct MapBase;
 
-#[derive(Component)]
+#[derive(Component, Reflect)]
+#[reflect(Component)]
 pub struct GameCamera;
 
-#[derive(Component)]
+#[derive(Component, Reflect)]
+#[reflect(Component)]
 pub struct Destination;
 
-#[derive(Component)]
-pub struct UnitSize(pub Vec2);
+/// Anything that takes up space on the grid, such as units and buildings
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct RtsObj;
+
+/// Marks static geometry, such as a level's GLTF scene, whose meshes block the `Grid` resource.
+/// Every mesh of the entity and its descendants blocks the cells under its bounds, so props don't
+/// each need an `RtsObj`. Meshes flatter than `min_height`, like floors and decals, stay walkable.
+#[derive(Component, Reflect, Clone, Copy, Debug)]
+#[reflect(Component)]
+pub struct NavBlocker {
+    pub min_height: f32,
+}
+
+impl Default for NavBlocker {
+    fn default() -> Self {
+        NavBlocker { min_height: 0.1 }
+    }
+}
+
+/// Half extents of an `RtsObj`'s footprint on the XZ plane. Derived from the entity's mesh
+/// when an `RtsObj` is spawned without one.
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct RtsObjSize(pub Vec2);
+
+/// The shape an obstacle blocks on the grid
+#[derive(Component, Reflect, Clone, Debug, Default, PartialEq)]
+#[reflect(Component)]
+pub enum RtsObjFootprint {
+    /// The rectangle given by `RtsObjSize`
+    #[default]
+    Rect,
+    /// A convex polygon of XZ points relative to the entity, moved with its `Transform`
+    Polygon(Vec<Vec2>),
+}
+
+/// Marks an `RtsObjSize` derived from the entity's mesh, so it follows mesh changes
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct AutoSized;
+
+/// Put on a flowfield whose destination follows the target entity, such as for attack orders
+#[derive(Component, Clone, Copy, Debug, Reflect)]
+#[reflect(Component)]
+pub struct FollowTarget(pub Entity);
+
+/// Puts a unit or obstacle on the `Grid` component of a map entity instead of the `Grid`
+/// resource, for games with several independent maps or matches in one world
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Reflect)]
+#[reflect(Component)]
+pub struct OnGrid(pub Entity);
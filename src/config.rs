@@ -0,0 +1,229 @@
+//! Grid and debug settings loaded from `.pathfinding.ron` assets. With the asset server watching
+//! for changes, editing the file while the app runs rebuilds the grid.
+//!
+//! ```ron
+//! (
+//!     grid_size: (64, 64),
+//!     cell_diameter: 2.0,
+//!     connectivity: Octile8,
+//!     debug: (draw_grid: Some(false), draw_mode_1: Some("CostField")),
+//! )
+//! ```
+
+use crate::{
+    components::{FollowTarget, RtsObj},
+    events::InitializeFlowFieldAtEv,
+    flowfield::FlowField,
+    grid::{Connectivity, Grid, OccupiedCells},
+    obstacles::ObstacleCells,
+    PathfindingSchedule, PathfindingSet,
+};
+
+use bevy::{
+    asset::{io::Reader, AssetLoader, LoadContext},
+    prelude::*,
+};
+use serde::Deserialize;
+use std::fmt;
+
+pub struct ConfigPlugin;
+
+impl Plugin for ConfigPlugin {
+    fn build(&self, app: &mut App) {
+        // Headless apps without an asset server configure the crate in code
+        if !app.world().contains_resource::<AssetServer>() {
+            return;
+        }
+
+        let schedule = PathfindingSchedule::label(app);
+        app.init_asset::<PathfindingConfig>()
+            .init_asset_loader::<PathfindingConfigLoader>()
+            .add_systems(schedule, apply_config.before(PathfindingSet::UpdateCosts));
+    }
+}
+
+#[derive(Asset, TypePath, Deserialize, Clone, Debug, PartialEq)]
+pub struct PathfindingConfig {
+    /// Cells along x and z
+    pub grid_size: (i32, i32),
+    pub cell_diameter: f32,
+    #[serde(default)]
+    pub connectivity: Connectivity,
+    #[serde(default)]
+    pub debug: DebugDefaults,
+}
+
+/// `DebugOptions` to start with. Unset options keep their current value.
+#[derive(Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct DebugDefaults {
+    pub hide: Option<bool>,
+    pub draw_grid: Option<bool>,
+    /// A `DrawMode` variant name
+    pub draw_mode_1: Option<String>,
+    pub draw_mode_2: Option<String>,
+}
+
+/// The config the crate follows. Insert it with a handle from the `AssetServer`.
+#[derive(Resource)]
+pub struct PathfindingConfigHandle(pub Handle<PathfindingConfig>);
+
+#[derive(Default)]
+pub struct PathfindingConfigLoader;
+
+#[derive(Debug)]
+pub enum ConfigLoadError {
+    Io(std::io::Error),
+    Ron(ron::error::SpannedError),
+}
+
+impl fmt::Display for ConfigLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigLoadError::Io(error) => write!(f, "Could not read pathfinding config: {error}"),
+            ConfigLoadError::Ron(error) => write!(f, "Could not parse pathfinding config: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigLoadError {}
+
+impl AssetLoader for PathfindingConfigLoader {
+    type Asset = PathfindingConfig;
+    type Settings = ();
+    type Error = ConfigLoadError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader
+            .read_to_end(&mut bytes)
+            .await
+            .map_err(ConfigLoadError::Io)?;
+
+        return ron::de::from_bytes(&bytes).map_err(ConfigLoadError::Ron);
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["pathfinding.ron"]
+    }
+}
+
+/// A grid of the configured size that keeps the terrain cost of the cells it shares with `old`
+fn resample(old: &Grid, config: &PathfindingConfig) -> Grid {
+    let size = IVec2::new(config.grid_size.0, config.grid_size.1);
+    let terrain = |pos: Vec3| {
+        let idx = old.try_get_cell_from_world_position(pos)?.idx;
+        old.base_cost(idx)
+    };
+
+    let mut grid = Grid::new(size, config.cell_diameter, |pos| {
+        terrain(pos) == Some(u8::MAX)
+    });
+
+    let cells: Vec<(IVec2, Vec3)> = grid
+        .grid
+        .iter()
+        .flatten()
+        .map(|cell| (cell.idx, cell.world_pos))
+        .collect();
+    for (idx, pos) in cells {
+        if let Some(cost) = terrain(pos).filter(|cost| *cost != u8::MAX) {
+            grid.set_base_cost(idx, cost);
+        }
+    }
+
+    grid.set_edge_policies(old.edge_policies());
+    grid.label_regions(config.connectivity);
+    return grid;
+}
+
+/// Rebuilds the `Grid` resource whenever the config loads or changes. Obstacles are rasterized
+/// again and units moving on the old grid are ordered to their destination again.
+fn apply_config(
+    mut cmds: Commands,
+    handle: Option<Res<PathfindingConfigHandle>>,
+    configs: Res<Assets<PathfindingConfig>>,
+    mut events: EventReader<AssetEvent<PathfindingConfig>>,
+    mut grid: ResMut<Grid>,
+    mut connectivity: ResMut<Connectivity>,
+    mut obstacles: ResMut<ObstacleCells>,
+    mut occupied: ResMut<OccupiedCells>,
+    mut q_obstacles: Query<&mut Transform, With<RtsObj>>,
+    q_flowfields: Query<(Entity, &FlowField, Option<&FollowTarget>)>,
+    #[cfg(feature = "debug-draw")] dbg: Option<ResMut<crate::debug::DebugOptions>>,
+) {
+    let Some(handle) = handle else {
+        return;
+    };
+
+    let changed = events.read().any(|ev| match ev {
+        AssetEvent::LoadedWithDependencies { id } | AssetEvent::Modified { id } => {
+            *id == handle.0.id()
+        }
+        _ => false,
+    });
+    let Some(config) = configs.get(&handle.0).filter(|_| changed) else {
+        return;
+    };
+
+    *grid = resample(&grid, config);
+    *connectivity = config.connectivity;
+    *obstacles = ObstacleCells::default();
+    *occupied = OccupiedCells::default();
+    for mut transform in q_obstacles.iter_mut() {
+        transform.set_changed();
+    }
+
+    for (flowfield_entity, flowfield, follow) in q_flowfields.iter() {
+        if flowfield.map.is_some() {
+            continue;
+        }
+
+        cmds.entity(flowfield_entity).despawn_recursive();
+        cmds.trigger(match follow {
+            Some(follow) => InitializeFlowFieldAtEv::following(flowfield.units.clone(), follow.0),
+            None => InitializeFlowFieldAtEv::new(flowfield.units.clone(), flowfield.destination),
+        });
+    }
+
+    #[cfg(feature = "debug-draw")]
+    if let Some(mut dbg) = dbg {
+        let debug = &config.debug;
+        dbg.hide = debug.hide.unwrap_or(dbg.hide);
+        dbg.draw_grid = debug.draw_grid.unwrap_or(dbg.draw_grid);
+        if let Some(mode) = &debug.draw_mode_1 {
+            dbg.draw_mode_1 = crate::debug::DrawMode::cast(mode.clone());
+        }
+        if let Some(mode) = &debug.draw_mode_2 {
+            dbg.draw_mode_2 = crate::debug::DrawMode::cast(mode.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config_parses_and_resampling_keeps_terrain() {
+        let config: PathfindingConfig = ron::de::from_str(
+            "(grid_size: (6, 4), cell_diameter: 1.0, debug: (draw_grid: Some(false)))",
+        )
+        .unwrap();
+        assert_eq!(config.connectivity, Connectivity::Octile8);
+        assert_eq!(config.debug.draw_grid, Some(false));
+
+        let mut old = Grid::new(IVec2::new(4, 4), 1.0, |pos| pos.x < -1.0);
+        old.set_base_cost(IVec2::new(3, 0), 7);
+        let grid = resample(&old, &config);
+
+        // The old grid covers the middle four columns of the new one
+        assert_eq!(grid.cell(IVec2::new(1, 0)).unwrap().cost, u8::MAX);
+        assert_eq!(grid.cell(IVec2::new(4, 0)).unwrap().cost, 7);
+        assert_eq!(grid.cell(IVec2::new(0, 0)).unwrap().cost, 1);
+    }
+}
@@ -0,0 +1,185 @@
+use crate::{
+    flowfield::FlowField,
+    grid::{costs, Grid},
+    interior::InteriorGrid,
+    layers::GridLayers,
+    resources::PathfindingStats,
+    time::PathfindingTime,
+    PathfindingSchedule, PathfindingSet,
+};
+
+use bevy::{prelude::*, utils::Instant};
+use std::collections::HashMap;
+
+pub struct CongestionPlugin;
+
+impl Plugin for CongestionPlugin {
+    fn build(&self, app: &mut App) {
+        let schedule = PathfindingSchedule::label(app);
+        app.init_resource::<CongestionSettings>()
+            .init_resource::<CongestionMap>()
+            .register_type::<CongestionSettings>()
+            .register_type::<CongestionMap>()
+            .add_systems(
+                schedule,
+                (
+                    count_congestion.in_set(PathfindingSet::UpdateCosts),
+                    reintegrate_congested_flowfields.in_set(PathfindingSet::BuildFields),
+                )
+                    .run_if(congestion_enabled),
+            );
+    }
+}
+
+/// Opt-in congestion handling. When enabled, every flowfield is periodically rebuilt with a
+/// penalty on crowded cells so large groups spread over alternative routes.
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
+pub struct CongestionSettings {
+    pub enabled: bool,
+    /// Extra cost per unit standing in a cell
+    pub penalty_per_unit: u8,
+    /// Seconds between re-integrations
+    pub interval: f32,
+}
+
+impl Default for CongestionSettings {
+    fn default() -> Self {
+        CongestionSettings {
+            enabled: false,
+            penalty_per_unit: 2,
+            interval: 1.0,
+        }
+    }
+}
+
+/// Number of units in each cell of the main grid, refreshed every frame
+#[derive(Resource, Default, Reflect)]
+#[reflect(Resource)]
+pub struct CongestionMap(pub HashMap<IVec2, u32>);
+
+impl CongestionMap {
+    pub fn count_at(&self, idx: IVec2) -> u32 {
+        self.0.get(&idx).copied().unwrap_or_default()
+    }
+
+    /// Returns a copy of the grid with the congestion penalty added to every crowded cell, in
+    /// place of the units blocking it. Penalties never make a walkable cell impassable.
+    pub fn apply(&self, grid: &Grid, penalty_per_unit: u8) -> Grid {
+        let mut congested = grid.clone();
+
+        for (idx, count) in self.0.iter() {
+            let Some(cost) = grid.cost_without(*idx, &[costs::UNIT_LAYER]) else {
+                continue;
+            };
+            if cost == u8::MAX {
+                continue;
+            }
+
+            let penalty = count.saturating_mul(penalty_per_unit as u32);
+            let cost = (cost as u32 + penalty).min(u8::MAX as u32 - 1);
+            congested.grid[idx.y as usize][idx.x as usize].cost = cost as u8;
+        }
+
+        congested
+    }
+}
+
+fn congestion_enabled(settings: Res<CongestionSettings>) -> bool {
+    settings.enabled
+}
+
+fn count_congestion(
+    grid: Res<Grid>,
+    mut congestion: ResMut<CongestionMap>,
+    q_flowfields: Query<&FlowField>,
+    q_transform: Query<&Transform>,
+) {
+    congestion.0.clear();
+
+    for flowfield in q_flowfields.iter().filter(|f| f.map.is_none()) {
+        for unit in flowfield.units.iter() {
+            if let Ok(transform) = q_transform.get(*unit) {
+                let cell = grid.get_cell_from_world_position(transform.translation);
+                *congestion.0.entry(cell.idx).or_default() += 1;
+            }
+        }
+    }
+}
+
+fn reintegrate_congested_flowfields(
+    mut timer: Local<Timer>,
+    time: PathfindingTime,
+    settings: Res<CongestionSettings>,
+    congestion: Res<CongestionMap>,
+    grid: Res<Grid>,
+    layers: Res<GridLayers>,
+    mut stats: ResMut<PathfindingStats>,
+    mut q_flowfields: Query<&mut FlowField>,
+    q_interiors: Query<(Entity, &InteriorGrid)>,
+) {
+    timer.set_duration(std::time::Duration::from_secs_f32(settings.interval));
+    timer.set_mode(TimerMode::Repeating);
+    if !timer.tick(time.delta()).just_finished() {
+        return;
+    }
+
+    let start = Instant::now();
+    let congested = congestion.apply(&grid, settings.penalty_per_unit);
+    let interiors: Vec<(Entity, &InteriorGrid)> = q_interiors.iter().collect();
+
+    for mut flowfield in q_flowfields.iter_mut() {
+        if flowfield.map.is_some() {
+            continue;
+        }
+
+        let destination = flowfield.destination;
+        flowfield.create_fields(&congested, &layers, &interiors, destination);
+    }
+    stats.record_integration(start.elapsed());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{grid::Connectivity, obstacles::ObstacleCells};
+
+    #[test]
+    fn crowded_routes_are_avoided() {
+        // A wall at x = 3 with a gap in the top and the bottom row
+        let mut grid = Grid::new(IVec2::new(7, 5), 1.0, |pos| {
+            pos.x == 0.0 && pos.z.abs() < 2.0
+        });
+        let start = IVec2::new(0, 1);
+        let destination = grid.grid[1][6].world_pos;
+
+        // Units crowd the top gap, blocking their own cells like any unit with a `Destination`
+        let crowded = [IVec2::new(2, 0), IVec2::new(3, 0), IVec2::new(4, 0)];
+        let mut congestion = CongestionMap::default();
+        let mut units = Vec::new();
+        for idx in crowded {
+            let pos = grid.grid[idx.y as usize][idx.x as usize].world_pos;
+            grid.update_unit_cell_costs(pos);
+            congestion.0.insert(idx, 5);
+            units.push((Entity::PLACEHOLDER, pos));
+        }
+
+        // Fields leave out the cells of their own units when they're built
+        let mut flowfield = FlowField::new(grid.cell_radius, grid.size, Vec::new());
+        flowfield.connectivity = Connectivity::Octile8;
+        flowfield.exclude_units(&grid, &ObstacleCells::default(), &units);
+
+        let mut heading = |congested: &Grid| {
+            flowfield.create_fields(congested, &GridLayers::default(), &[], destination);
+            flowfield.grid[start.y as usize][start.x as usize].best_direction
+        };
+
+        // Without congestion the units' own cells are left out and the field takes the top gap
+        let calm = CongestionMap::default().apply(&grid, 2);
+        assert_eq!(heading(&calm).vector().y, -1);
+
+        let congested = congestion.apply(&grid, 2);
+        assert_eq!(congested.grid[0][3].cost, 11);
+        assert_eq!(heading(&congested).vector().y, 1);
+    }
+}
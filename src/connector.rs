@@ -0,0 +1,211 @@
+use crate::{
+    components::Destination, events::InitializeFlowFieldAtEv, grid::Grid, interior::InteriorGrid,
+    PathfindingSchedule, PathfindingSet,
+};
+
+use bevy::prelude::*;
+use ops::FloatPow;
+use std::collections::{HashMap, VecDeque};
+
+pub struct ConnectorPlugin;
+
+impl Plugin for ConnectorPlugin {
+    fn build(&self, app: &mut App) {
+        let schedule = PathfindingSchedule::label(app);
+        app.register_type::<GridConnector>()
+            .register_type::<GridRoute>()
+            .add_event::<GridTransitionEv>()
+            .add_systems(
+                schedule,
+                follow_grid_routes.in_set(PathfindingSet::BuildFields),
+            );
+    }
+}
+
+/// Identifies one of the navigable grids: the main grid or a building's interior
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Reflect)]
+pub enum GridId {
+    Main,
+    Interior(Entity),
+}
+
+impl GridId {
+    pub fn at(world_pos: Vec3, interiors: &[(Entity, &InteriorGrid)]) -> Self {
+        match interiors.iter().find(|(_, i)| i.contains(world_pos)) {
+            Some((building, _)) => GridId::Interior(*building),
+            None => GridId::Main,
+        }
+    }
+}
+
+/// Links two grids that aren't joined by walkable doors, e.g. map edge transitions or elevators.
+/// Units reaching `entry` are moved to `exit`. Two way connectors can also be taken backwards.
+#[derive(Component, Clone, Copy, Debug, Reflect)]
+#[reflect(Component)]
+pub struct GridConnector {
+    pub entry: Vec3,
+    pub exit: Vec3,
+    pub two_way: bool,
+}
+
+impl GridConnector {
+    pub fn new(entry: Vec3, exit: Vec3, two_way: bool) -> Self {
+        Self {
+            entry,
+            exit,
+            two_way,
+        }
+    }
+}
+
+/// One connector crossing of a route: walk to `from`, then get moved to `to`
+#[derive(Clone, Copy, Debug, PartialEq, Reflect)]
+pub struct ConnectorHop {
+    pub connector: Entity,
+    pub from: Vec3,
+    pub to: Vec3,
+}
+
+/// Added to units whose order needs to cross grids through connectors.
+/// The unit's flowfield leads to `next_hop`, the rest of the route is planned after crossing it.
+#[derive(Component, Clone, Copy, Debug, Reflect)]
+#[reflect(Component)]
+pub struct GridRoute {
+    pub destination: Vec3,
+    pub next_hop: ConnectorHop,
+    /// The entity being pursued, if the order follows a target
+    pub target: Option<Entity>,
+}
+
+/// Sent when a unit is moved across a connector
+#[derive(Event)]
+pub struct GridTransitionEv {
+    pub unit: Entity,
+    pub connector: Entity,
+    pub from: GridId,
+    pub to: GridId,
+}
+
+/// Finds the connectors to cross, in order, to get from `start` to `goal`, using as few
+/// connectors as possible. Interiors with doors are reachable from the main grid for free.
+/// Returns `None` if the goal grid can't be reached.
+pub fn find_route(
+    start: GridId,
+    goal: GridId,
+    interiors: &[(Entity, &InteriorGrid)],
+    connectors: &[(Entity, &GridConnector)],
+) -> Option<Vec<ConnectorHop>> {
+    let mut edges: Vec<(GridId, GridId, Option<ConnectorHop>)> = Vec::new();
+
+    for (building, interior) in interiors {
+        if !interior.doors.is_empty() {
+            edges.push((GridId::Main, GridId::Interior(*building), None));
+            edges.push((GridId::Interior(*building), GridId::Main, None));
+        }
+    }
+
+    for (entity, connector) in connectors {
+        let entry = GridId::at(connector.entry, interiors);
+        let exit = GridId::at(connector.exit, interiors);
+        let hop = ConnectorHop {
+            connector: *entity,
+            from: connector.entry,
+            to: connector.exit,
+        };
+
+        edges.push((entry, exit, Some(hop)));
+        if connector.two_way {
+            let back = ConnectorHop {
+                from: connector.exit,
+                to: connector.entry,
+                ..hop
+            };
+            edges.push((exit, entry, Some(back)));
+        }
+    }
+
+    // 0-1 BFS, walking through doors is free and crossing a connector costs one
+    let mut visited: HashMap<GridId, (u32, Option<(GridId, Option<ConnectorHop>)>)> =
+        HashMap::new();
+    let mut queue = VecDeque::from([start]);
+    visited.insert(start, (0, None));
+
+    while let Some(current) = queue.pop_front() {
+        let dist = visited[&current].0;
+
+        for (from, to, hop) in edges.iter().filter(|(from, _, _)| *from == current) {
+            let new_dist = dist + hop.is_some() as u32;
+            if visited.get(to).is_some_and(|(d, _)| *d <= new_dist) {
+                continue;
+            }
+
+            visited.insert(*to, (new_dist, Some((*from, *hop))));
+            match hop {
+                Some(_) => queue.push_back(*to),
+                None => queue.push_front(*to),
+            }
+        }
+    }
+
+    let mut hops = Vec::new();
+    let mut current = goal;
+    while let Some((_, Some((prev, hop)))) = visited.get(&current) {
+        hops.extend(hop);
+        current = *prev;
+    }
+
+    if current != start {
+        return None;
+    }
+
+    hops.reverse();
+    Some(hops)
+}
+
+fn follow_grid_routes(
+    mut cmds: Commands,
+    mut events: EventWriter<GridTransitionEv>,
+    grid: Res<Grid>,
+    mut q_units: Query<(Entity, &mut Transform, &GridRoute)>,
+    q_interiors: Query<(Entity, &InteriorGrid)>,
+) {
+    let interiors: Vec<(Entity, &InteriorGrid)> = q_interiors.iter().collect();
+    let mut crossed: Vec<(ConnectorHop, GridRoute, Vec<Entity>)> = Vec::new();
+
+    for (unit, mut transform, route) in q_units.iter_mut() {
+        let hop = route.next_hop;
+        let distance_squared = transform.translation.xz().distance_squared(hop.from.xz());
+        if distance_squared >= grid.cell_diameter.squared() {
+            continue;
+        }
+
+        // Keep the unit's height relative to the connector
+        transform.translation += hop.to - hop.from;
+
+        events.send(GridTransitionEv {
+            unit,
+            connector: hop.connector,
+            from: GridId::at(hop.from, &interiors),
+            to: GridId::at(hop.to, &interiors),
+        });
+
+        cmds.entity(unit).remove::<GridRoute>().insert(Destination);
+
+        match crossed.iter_mut().find(|(h, r, _)| {
+            h.connector == hop.connector
+                && r.destination == route.destination
+                && r.target == route.target
+        }) {
+            Some((_, _, units)) => units.push(unit),
+            None => crossed.push((hop, *route, vec![unit])),
+        }
+    }
+
+    // Plan the next leg for everyone that crossed the same connector together
+    for (_, route, units) in crossed {
+        match route.target {
+            Some(target) => cmds.trigger(InitializeFlowFieldAtEv::following(units, target)),
+            None => cmds.trigger(InitializeFlowFieldAtEv::new(units, route.destination)),
+        };
+    }
+}
@@ -0,0 +1,215 @@
+//! Developer console commands for inspecting and poking at pathfinding at runtime. Lines are
+//! sent as `NavCommandEv`s, so any console can drive them. With `bevy_console`, forward the
+//! lines of its `ConsoleCommandEntered` events whose command starts with `nav.`.
+//!
+//! - `nav.stats` logs the `PathfindingStats`
+//! - `nav.draw <mode>` sets the first debug draw mode: `none`, `cost`, `flow`, `integration`,
+//!   `index`, `diff` or `density`
+//! - `nav.rebuild` rebuilds every flowfield from the current costs
+//! - `nav.dump <file>` writes the costs of the `Grid` resource to a file, one row per line
+
+use crate::{
+    flowfield::FlowField,
+    grid::{Grid, Grids},
+    interior::InteriorGrid,
+    layers::GridLayers,
+    resources::PathfindingStats,
+    PathfindingSchedule, PathfindingSet,
+};
+
+use bevy::{prelude::*, utils::Instant};
+use std::{fmt, path::PathBuf};
+
+pub struct ConsolePlugin;
+
+impl Plugin for ConsolePlugin {
+    fn build(&self, app: &mut App) {
+        let schedule = PathfindingSchedule::label(app);
+        app.add_event::<NavCommandEv>().add_systems(
+            schedule,
+            run_nav_commands.before(PathfindingSet::UpdateCosts),
+        );
+    }
+}
+
+/// A console line to run, such as `nav.draw cost`
+#[derive(Event, Clone, Debug)]
+pub struct NavCommandEv(pub String);
+
+impl NavCommandEv {
+    pub fn new(line: impl Into<String>) -> Self {
+        Self(line.into())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum NavCommand {
+    Stats,
+    /// A `DrawMode` variant name
+    Draw(String),
+    Rebuild,
+    Dump(PathBuf),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum NavCommandError {
+    Unknown(String),
+    MissingArgument(&'static str),
+    UnknownDrawMode(String),
+}
+
+impl fmt::Display for NavCommandError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NavCommandError::Unknown(command) => write!(f, "Unknown command: {command}"),
+            NavCommandError::MissingArgument(usage) => write!(f, "Usage: {usage}"),
+            NavCommandError::UnknownDrawMode(mode) => write!(
+                f,
+                "Unknown draw mode {mode}, expected none, cost, flow, integration, index, diff or density"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for NavCommandError {}
+
+impl NavCommand {
+    pub fn parse(line: &str) -> Result<Self, NavCommandError> {
+        let mut words = line.split_whitespace();
+        let command = words.next().unwrap_or_default();
+        let argument = words.next();
+
+        return match command {
+            "nav.stats" => Ok(NavCommand::Stats),
+            "nav.rebuild" => Ok(NavCommand::Rebuild),
+            "nav.draw" => {
+                let mode = argument.ok_or(NavCommandError::MissingArgument("nav.draw <mode>"))?;
+                let mode = match mode {
+                    "none" => "None",
+                    "cost" => "CostField",
+                    "flow" => "FlowField",
+                    "integration" => "IntegrationField",
+                    "index" => "Index",
+                    "diff" => "Diff",
+                    "density" => "Density",
+                    _ => return Err(NavCommandError::UnknownDrawMode(mode.to_string())),
+                };
+                Ok(NavCommand::Draw(mode.to_string()))
+            }
+            "nav.dump" => {
+                let file = argument.ok_or(NavCommandError::MissingArgument("nav.dump <file>"))?;
+                Ok(NavCommand::Dump(PathBuf::from(file)))
+            }
+            _ => Err(NavCommandError::Unknown(command.to_string())),
+        };
+    }
+}
+
+/// The costs of `grid`, one row of space separated costs per line
+fn dump_costs(grid: &Grid) -> String {
+    let rows: Vec<String> = grid
+        .grid
+        .iter()
+        .map(|row| {
+            let costs: Vec<String> = row.iter().map(|cell| cell.cost.to_string()).collect();
+            costs.join(" ")
+        })
+        .collect();
+
+    return rows.join("\n") + "\n";
+}
+
+fn run_nav_commands(
+    grids: Grids,
+    layers: Res<GridLayers>,
+    mut stats: ResMut<PathfindingStats>,
+    mut events: EventReader<NavCommandEv>,
+    mut q_flowfields: Query<&mut FlowField>,
+    q_interiors: Query<(Entity, &InteriorGrid)>,
+    #[cfg(feature = "debug-draw")] mut dbg: Option<ResMut<crate::debug::DebugOptions>>,
+) {
+    for ev in events.read() {
+        let command = match NavCommand::parse(&ev.0) {
+            Ok(command) => command,
+            Err(error) => {
+                warn!("{error}");
+                continue;
+            }
+        };
+
+        match command {
+            NavCommand::Stats => info!(
+                "{} flowfields, {} integrations, last {:?}, average {:?}, {} dirty cells",
+                stats.flowfield_count,
+                stats.integrations,
+                stats.last_integration,
+                stats.average_integration(),
+                stats.dirty_cells()
+            ),
+            NavCommand::Draw(mode) => {
+                #[cfg(feature = "debug-draw")]
+                if let Some(dbg) = dbg.as_mut() {
+                    dbg.draw_mode_1 = crate::debug::DrawMode::cast(mode);
+                    continue;
+                }
+
+                warn!("Can't draw {mode} without the debug plugin");
+            }
+            NavCommand::Rebuild => {
+                let start = Instant::now();
+                let interiors: Vec<(Entity, &InteriorGrid)> = q_interiors.iter().collect();
+                let mut count = 0;
+                for mut flowfield in q_flowfields.iter_mut() {
+                    flowfield.rebuild(&grids, &layers, &interiors);
+                    count += 1;
+                }
+
+                stats.record_integration(start.elapsed());
+                info!("Rebuilt {count} flowfields");
+            }
+            NavCommand::Dump(file) => {
+                let Some(grid) = grids.get(None) else {
+                    continue;
+                };
+
+                match std::fs::write(&file, dump_costs(grid)) {
+                    Ok(()) => info!("Dumped the costfield to {}", file.display()),
+                    Err(error) => warn!("Could not dump the costfield: {error}"),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands_parse_with_their_arguments() {
+        assert_eq!(NavCommand::parse("nav.stats"), Ok(NavCommand::Stats));
+        assert_eq!(
+            NavCommand::parse("nav.draw  cost"),
+            Ok(NavCommand::Draw("CostField".to_string()))
+        );
+        assert_eq!(
+            NavCommand::parse("nav.dump costs.txt"),
+            Ok(NavCommand::Dump(PathBuf::from("costs.txt")))
+        );
+        assert_eq!(
+            NavCommand::parse("nav.draw"),
+            Err(NavCommandError::MissingArgument("nav.draw <mode>"))
+        );
+        assert_eq!(
+            NavCommand::parse("nav.draw walls"),
+            Err(NavCommandError::UnknownDrawMode("walls".to_string()))
+        );
+        assert_eq!(
+            NavCommand::parse("nav.fly"),
+            Err(NavCommandError::Unknown("nav.fly".to_string()))
+        );
+
+        let grid = Grid::new(IVec2::new(3, 2), 1.0, |pos| pos.x > 0.0 && pos.z > 0.0);
+        assert_eq!(dump_costs(&grid), "1 1 1\n1 1 255\n");
+    }
+}
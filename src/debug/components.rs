@@ -73,7 +73,7 @@ pub enum OptionsSet {
 }
 
 impl OptionsSet {
-    pub fn to_num(&self) -> i32 {
+    pub fn to_num(self) -> i32 {
         match self {
             OptionsSet::One => 1,
             OptionsSet::Two => 2,
@@ -81,14 +81,26 @@ impl OptionsSet {
     }
 }
 
-#[derive(Component, Copy, Clone)]
+#[derive(Component, Copy, Clone, Reflect)]
+#[reflect(Component)]
 pub struct Cost;
 
-#[derive(Component, Copy, Clone)]
+#[derive(Component, Copy, Clone, Reflect)]
+#[reflect(Component)]
 pub struct BestCost;
 
-#[derive(Component, Copy, Clone)]
+#[derive(Component, Copy, Clone, Reflect)]
+#[reflect(Component)]
 pub struct Index;
 
-#[derive(Component, Clone, Copy)]
+#[derive(Component, Clone, Copy, Reflect)]
+#[reflect(Component)]
 pub struct FlowFieldArrow;
+
+#[derive(Component, Clone, Copy, Reflect)]
+#[reflect(Component)]
+pub struct DiffMarker;
+
+#[derive(Component, Clone, Copy, Reflect)]
+#[reflect(Component)]
+pub struct DensityMarker;
@@ -3,11 +3,15 @@ use super::events::*;
 use super::resources::*;
 use crate::*;
 
+use bevy::render::primitives::Frustum;
 use cell::Cell;
-use debug::COLOR_GRID;
 use events::UpdateCostEv;
+use flowfield::FlowField;
 use grid::Grid;
+use placement::PlacementPreview;
+use resources::ActiveDebugFlowfield;
 use std::f32::consts::{FRAC_PI_2, FRAC_PI_4};
+use std::time::Duration;
 
 const BASE_SCALE: f32 = 0.25;
 
@@ -15,19 +19,35 @@ pub struct DrawPlugin;
 
 impl Plugin for DrawPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(
-            Update,
-            (
-                draw_grid,
-                detect_debug_change,
-                update_cell_cost.after(grid::update_costs),
-            ),
-        )
-        .add_observer(set_active_dbg_flowfield)
-        .add_observer(draw_costfield)
-        .add_observer(draw_flowfield)
-        .add_observer(draw_integration_field)
-        .add_observer(draw_index);
+        app.register_type::<Cost>()
+            .register_type::<BestCost>()
+            .register_type::<Index>()
+            .register_type::<FlowFieldArrow>()
+            .register_type::<DiffMarker>()
+            .register_type::<DensityMarker>()
+            .add_systems(
+                Update,
+                (
+                    draw_grid,
+                    draw_placement_preview,
+                    draw_unit_paths.after(PathfindingSet::Steering),
+                    draw_overlays.after(PathfindingSet::Steering),
+                    track_debug_view.before(detect_debug_change),
+                    detect_debug_change,
+                    update_cell_cost.after(grid::update_costs),
+                    (refresh_density, draw_density)
+                        .chain()
+                        .after(PathfindingSet::UpdateCosts),
+                ),
+            )
+            .add_observer(set_active_dbg_flowfield)
+            .add_observer(toggle_overlay)
+            .add_observer(draw_costfield)
+            .add_observer(draw_flowfield)
+            .add_observer(draw_integration_field)
+            .add_observer(draw_index)
+            .add_observer(draw_diff)
+            .add_observer(redraw_density);
     }
 }
 
@@ -55,19 +75,125 @@ fn set_active_dbg_flowfield(
     }
 }
 
-fn draw_grid(grid: Res<Grid>, mut gizmos: Gizmos, debug: Res<DebugOptions>) {
+fn draw_grid(
+    grid: Res<Grid>,
+    mut gizmos: Gizmos,
+    debug: Res<DebugOptions>,
+    style: Res<DebugStyle>,
+) {
     if !debug.draw_grid {
         return;
     }
 
     gizmos.grid(
-        Isometry3d::from_rotation(Quat::from_rotation_x(-std::f32::consts::FRAC_PI_2)),
+        Isometry3d::new(
+            Vec3::Y * style.line_height,
+            Quat::from_rotation_x(-std::f32::consts::FRAC_PI_2),
+        ),
         UVec2::new(grid.size.x as u32, grid.size.y as u32),
         Vec2::new(grid.cell_radius * 2.0, grid.cell_radius * 2.0),
-        COLOR_GRID,
+        style.grid_color,
     );
 }
 
+fn draw_placement_preview(
+    preview: Option<Res<PlacementPreview>>,
+    style: Res<DebugStyle>,
+    mut gizmos: Gizmos,
+) {
+    let Some(preview) = preview else {
+        return;
+    };
+
+    let lift = Vec3::new(0.0, style.line_height, 0.0);
+    let route = |path: &[Cell]| {
+        path.iter()
+            .map(|cell| cell.world_pos + lift)
+            .collect::<Vec<_>>()
+    };
+
+    gizmos.linestrip(route(&preview.path_before), style.route_before_color);
+    gizmos.linestrip(route(&preview.path_after), style.route_after_color);
+}
+
+/// Draws the route each unit's flowfield takes it along, in a color per unit
+fn draw_unit_paths(
+    mut gizmos: Gizmos,
+    dbg: Res<DebugOptions>,
+    style: Res<DebugStyle>,
+    q_flowfields: Query<&FlowField>,
+    q_units: Query<(Entity, &Transform), With<Destination>>,
+) {
+    if !dbg.draw_paths {
+        return;
+    }
+
+    let lift = Vec3::new(0.0, style.line_height, 0.0);
+    for flowfield in q_flowfields.iter() {
+        for unit in flowfield.units.iter() {
+            let Ok((entity, transform)) = q_units.get(*unit) else {
+                continue;
+            };
+
+            let path = flowfield.extract_path(transform.translation);
+            if path.is_empty() {
+                continue;
+            }
+
+            let points = std::iter::once(transform.translation)
+                .chain(path.iter().map(|cell| cell.world_pos))
+                .map(|pos| pos.with_y(0.0) + lift);
+
+            gizmos.linestrip(points, FlowfieldOverlays::color(entity));
+        }
+    }
+}
+
+fn toggle_overlay(
+    trigger: Trigger<ToggleFlowfieldOverlayEv>,
+    mut overlays: ResMut<FlowfieldOverlays>,
+) {
+    let flowfield = trigger.event().0;
+    if !overlays.fields.remove(&flowfield) {
+        overlays.fields.insert(flowfield);
+    }
+}
+
+/// Draws the arrows of every overlaid flowfield, in the flowfield's color
+fn draw_overlays(
+    mut gizmos: Gizmos,
+    dbg: Res<DebugOptions>,
+    style: Res<DebugStyle>,
+    view: Res<DebugView>,
+    mut overlays: ResMut<FlowfieldOverlays>,
+    q_flowfields: Query<(Entity, &FlowField)>,
+) {
+    // Forget despawned flowfields, only borrowing mutably when there are any
+    if overlays.fields.iter().any(|e| !q_flowfields.contains(*e)) {
+        overlays.fields.retain(|e| q_flowfields.contains(*e));
+    }
+
+    let lift = Vec3::new(0.0, style.line_height, 0.0);
+    for (entity, flowfield) in q_flowfields.iter() {
+        if !dbg.overlay_all && !overlays.fields.contains(&entity) {
+            continue;
+        }
+
+        let color = FlowfieldOverlays::color(entity);
+        let half_length = flowfield.cell_radius * 0.6;
+        for cell in flowfield.grid.iter().flatten() {
+            let direction = cell.best_direction.to_world(flowfield.rotation);
+            if direction == Vec3::ZERO || !view.shows(cell.world_pos, flowfield.cell_radius) {
+                continue;
+            }
+
+            let offset = direction * half_length;
+            let center = cell.world_pos.with_y(0.0) + lift;
+            gizmos.arrow(center - offset, center + offset, color);
+        }
+    }
+}
+
 // TODO: Cleanup this method
 fn draw_flowfield(
     _trigger: Trigger<DrawDebugEv>,
@@ -76,9 +202,12 @@ fn draw_flowfield(
     active_dbg_flowfield: Res<ActiveDebugFlowfield>,
     q_flowfield_arrow: Query<Entity, With<FlowFieldArrow>>,
     mut cmds: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
+    dbg_assets: Res<DebugAssets>,
+    style: Res<DebugStyle>,
+    view: Res<DebugView>,
 ) {
+    let _span = info_span!("debug_draw_flowfield").entered();
+
     // Remove current arrows before rendering new ones
     for arrow_entity in &q_flowfield_arrow {
         cmds.entity(arrow_entity).despawn_recursive();
@@ -95,64 +224,58 @@ fn draw_flowfield(
         marker_scale = 1.0;
     }
 
-    let offset = calculate_offset(active_dbg_flowfield.cell_diameter, dbg, DrawMode::FlowField);
+    let offset = calculate_offset(
+        active_dbg_flowfield.cell_diameter,
+        dbg,
+        &style,
+        DrawMode::FlowField,
+    );
     let Some(offset) = offset else {
         return;
     };
 
     println!("Drawing Flowfield");
 
-    let arrow_length = grid.cell_diameter * 0.6 * marker_scale;
-    let arrow_width = grid.cell_diameter * 0.1 * marker_scale;
-    let arrow_clr = Color::WHITE;
-
-    // Create the arrowhead mesh
-    let half_arrow_size = arrow_length / 2.0;
-    let d1 = half_arrow_size - grid.cell_diameter * 0.09;
-    let d2 = arrow_width + grid.cell_diameter * 0.0125;
-    let a = Vec2::new(half_arrow_size + grid.cell_diameter * 0.05, 0.0); // Tip of the arrowhead
-    let b = Vec2::new(d1, d2);
-    let c = Vec2::new(d1, -arrow_width - grid.cell_diameter * 0.0125);
-
-    // Mesh for arrow
-    let arrow_mesh = meshes.add(Plane3d::default().mesh().size(arrow_length, arrow_width));
-    let arrow_head_mesh = meshes.add(Triangle2d::new(a, b, c));
-
-    let material = materials.add(StandardMaterial {
-        base_color: arrow_clr,
-        ..default()
-    });
+    // The shared meshes are sized for a unit cell
+    let scale = Vec3::splat(grid.cell_diameter * marker_scale);
 
     // println!("Drawing flowfield");
     for cell_row in &active_dbg_flowfield.grid {
         for cell in cell_row.iter() {
-            let is_destination_cell = active_dbg_flowfield.destination_cell.idx == cell.idx;
+            if !view.shows(cell.world_pos, active_dbg_flowfield.cell_radius) {
+                continue;
+            }
+
+            let is_destination_cell =
+                active_dbg_flowfield.destination_cell.world_pos == cell.world_pos;
 
             let rotation = match is_destination_cell {
                 true => Quat::from_rotation_x(-std::f32::consts::FRAC_PI_2),
-                false => Quat::from_rotation_y(cell.best_direction.to_angle()),
+                false => cell
+                    .best_direction
+                    .to_world_rotation(active_dbg_flowfield.rotation),
             };
 
             let mesh = match is_destination_cell {
-                true => meshes.add(Circle::new(grid.cell_radius / 3.0 * marker_scale)),
-                false => arrow_mesh.clone(),
+                true => dbg_assets.destination_mesh.clone(),
+                false => dbg_assets.arrow_mesh.clone(),
             };
 
             let marker = (
                 Mesh3d(mesh.clone()),
-                MeshMaterial3d(material.clone()),
+                MeshMaterial3d(dbg_assets.arrow_material.clone()),
                 Transform {
                     translation: cell.world_pos + offset,
                     rotation,
-                    ..default()
+                    scale,
                 },
                 FlowFieldArrow,
                 Name::new("Flowfield Marker Arrow"),
             );
 
             let arrow_head = (
-                Mesh3d(arrow_head_mesh.clone()),
-                MeshMaterial3d(material.clone()),
+                Mesh3d(dbg_assets.arrow_head_mesh.clone()),
+                MeshMaterial3d(dbg_assets.arrow_material.clone()),
                 Transform {
                     translation: Vec3::ZERO,
                     rotation: Quat::from_rotation_x(-std::f32::consts::FRAC_PI_2),
@@ -164,7 +287,8 @@ fn draw_flowfield(
             if cell.cost < u8::MAX {
                 let mut draw = cmds.spawn(marker);
 
-                if !is_destination_cell {
+                // A custom arrow mesh comes with its own head
+                if !is_destination_cell && style.arrow_mesh.is_none() {
                     draw.with_children(|parent| {
                         parent.spawn(arrow_head);
                     });
@@ -172,8 +296,8 @@ fn draw_flowfield(
             } else {
                 let cross = (
                     Transform::default(),
-                    Mesh3d(mesh),
-                    MeshMaterial3d(materials.add(StandardMaterial::from_color(RED))),
+                    Mesh3d(dbg_assets.cross_mesh.clone()),
+                    MeshMaterial3d(dbg_assets.blocked_material.clone()),
                     FlowFieldArrow,
                     Name::new("Flowfield Marker 'X'"),
                 );
@@ -182,14 +306,14 @@ fn draw_flowfield(
                 cross_1.0 = Transform {
                     translation: cell.world_pos + offset,
                     rotation: Quat::from_rotation_y(3.0 * FRAC_PI_4),
-                    ..default()
+                    scale,
                 };
 
                 let mut cross_2 = cross.clone();
                 cross_2.0 = Transform {
                     translation: cell.world_pos + offset,
                     rotation: Quat::from_rotation_y(FRAC_PI_4),
-                    ..default()
+                    scale,
                 };
 
                 cmds.spawn(cross_1);
@@ -203,13 +327,15 @@ fn draw_flowfield(
 fn draw_integration_field(
     _trigger: Trigger<DrawDebugEv>,
     dbg: Res<DebugOptions>,
-    digits: Res<Digits>,
     active_dbg_flowfield: Res<ActiveDebugFlowfield>,
-    meshes: ResMut<Assets<Mesh>>,
-    materials: ResMut<Assets<StandardMaterial>>,
+    dbg_assets: Res<DebugAssets>,
+    style: Res<DebugStyle>,
+    view: Res<DebugView>,
     q_cost: Query<Entity, With<BestCost>>,
     mut cmds: Commands,
 ) {
+    let _span = info_span!("debug_draw_integration_field").entered();
+
     // Remove current cost field before rendering new one
     for cost_entity in &q_cost {
         cmds.entity(cost_entity).despawn_recursive();
@@ -219,7 +345,12 @@ fn draw_integration_field(
         return;
     };
 
-    let offset = calculate_offset(flowfield.cell_diameter, dbg, DrawMode::IntegrationField);
+    let offset = calculate_offset(
+        flowfield.cell_diameter,
+        dbg,
+        &style,
+        DrawMode::IntegrationField,
+    );
     let Some(offset) = offset else {
         return;
     };
@@ -228,12 +359,12 @@ fn draw_integration_field(
 
     let str = |cell: &Cell| format!("{}", cell.best_cost);
     draw(
-        meshes,
-        materials,
+        &dbg_assets,
+        &view,
         &flowfield.grid,
         flowfield.cell_diameter,
-        digits,
         BestCost,
+        DrawMode::IntegrationField,
         cmds,
         str,
         offset,
@@ -244,12 +375,14 @@ fn draw_index(
     _trigger: Trigger<DrawDebugEv>,
     dbg: Res<DebugOptions>,
     active_dbg_flowfield: Res<ActiveDebugFlowfield>,
-    digits: Res<Digits>,
-    meshes: ResMut<Assets<Mesh>>,
-    materials: ResMut<Assets<StandardMaterial>>,
+    dbg_assets: Res<DebugAssets>,
+    style: Res<DebugStyle>,
+    view: Res<DebugView>,
     q_idx: Query<Entity, With<Index>>,
     mut cmds: Commands,
 ) {
+    let _span = info_span!("debug_draw_index").entered();
+
     // Remove current index entities before rendering new ones
     for idx_entity in &q_idx {
         cmds.entity(idx_entity).despawn_recursive();
@@ -259,7 +392,7 @@ fn draw_index(
         return;
     };
 
-    let offset = calculate_offset(flowfield.cell_diameter, dbg, DrawMode::Index);
+    let offset = calculate_offset(flowfield.cell_diameter, dbg, &style, DrawMode::Index);
     let Some(offset) = offset else {
         return;
     };
@@ -268,35 +401,168 @@ fn draw_index(
 
     let str = |cell: &Cell| format!("{}{}", cell.idx.y, cell.idx.x);
     draw(
-        meshes,
-        materials,
+        &dbg_assets,
+        &view,
         &flowfield.grid,
         flowfield.cell_diameter,
-        digits,
         Index,
+        DrawMode::Index,
         cmds,
         str,
         offset,
     );
 }
 
+/// Highlights the cells where the active flowfield's costs or directions differ from the
+/// `DiffBaseline`
+fn draw_diff(
+    _trigger: Trigger<DrawDebugEv>,
+    dbg: Res<DebugOptions>,
+    active_dbg_flowfield: Res<ActiveDebugFlowfield>,
+    baseline: Res<DiffBaseline>,
+    dbg_assets: Res<DebugAssets>,
+    style: Res<DebugStyle>,
+    view: Res<DebugView>,
+    q_markers: Query<Entity, With<DiffMarker>>,
+    mut cmds: Commands,
+) {
+    let _span = info_span!("debug_draw_diff").entered();
+
+    // Remove current highlights before rendering new ones
+    for marker in &q_markers {
+        cmds.entity(marker).despawn_recursive();
+    }
+
+    let (Some(flowfield), Some(baseline)) = (&active_dbg_flowfield.0, &baseline.0) else {
+        return;
+    };
+
+    let offset = calculate_offset(flowfield.cell_diameter, dbg, &style, DrawMode::Diff);
+    let Some(offset) = offset else {
+        return;
+    };
+
+    let diff = flowfield.diff(baseline);
+
+    for cell_diff in diff.cells.iter() {
+        let cell = &flowfield.grid[cell_diff.idx.y as usize][cell_diff.idx.x as usize];
+        if !view.shows(cell.world_pos, flowfield.cell_radius) {
+            continue;
+        }
+
+        cmds.spawn((
+            Mesh3d(dbg_assets.diff_mesh.clone()),
+            MeshMaterial3d(dbg_assets.diff_material.clone()),
+            Transform::from_translation(cell.world_pos + offset)
+                .with_scale(Vec3::splat(flowfield.cell_diameter)),
+            DiffMarker,
+            Name::new("Flowfield Diff Marker"),
+        ));
+    }
+}
+
+/// Recounts the `DensityHeatmap` from the `UnitSpatialIndex` every
+/// `DebugOptions::density_refresh` seconds while it's drawn
+fn refresh_density(
+    time: Res<Time>,
+    dbg: Res<DebugOptions>,
+    grid: Res<Grid>,
+    index: Res<spatial::UnitSpatialIndex>,
+    mut heatmap: ResMut<DensityHeatmap>,
+) {
+    if dbg.draw_mode_1 != DrawMode::Density && dbg.draw_mode_2 != DrawMode::Density {
+        return;
+    }
+
+    let refresh = Duration::from_secs_f32(dbg.density_refresh.max(0.0));
+    if heatmap.timer.duration() != refresh {
+        heatmap.timer = Timer::new(refresh, TimerMode::Repeating);
+    }
+
+    if !heatmap.timer.tick(time.delta()).just_finished() && !heatmap.counts.is_empty() {
+        return;
+    }
+
+    let counts = index.density(&grid);
+    if counts != heatmap.counts {
+        heatmap.counts = counts;
+    }
+}
+
+/// Redraws the `DensityHeatmap` along with the other draw modes
+fn redraw_density(_trigger: Trigger<DrawDebugEv>, mut heatmap: ResMut<DensityHeatmap>) {
+    heatmap.set_changed();
+}
+
+/// Colors the cells of the `Grid` resource by the units standing in them, from
+/// `DebugStyle::density_cold_color` for one unit to `density_hot_color` for
+/// `DebugOptions::density_saturation` units
+fn draw_density(
+    mut cmds: Commands,
+    dbg: Res<DebugOptions>,
+    heatmap: Res<DensityHeatmap>,
+    dbg_assets: Res<DebugAssets>,
+    style: Res<DebugStyle>,
+    view: Res<DebugView>,
+    grid: Res<Grid>,
+    q_markers: Query<Entity, With<DensityMarker>>,
+) {
+    if !heatmap.is_changed() {
+        return;
+    }
+
+    // Remove current highlights before rendering new ones
+    for marker in &q_markers {
+        cmds.entity(marker).despawn_recursive();
+    }
+
+    let saturation = dbg.density_saturation;
+    let offset = calculate_offset(grid.cell_diameter, dbg, &style, DrawMode::Density);
+    let Some(offset) = offset else {
+        return;
+    };
+
+    for (idx, count) in heatmap.counts.iter() {
+        let Some(cell) = grid.cell(*idx) else {
+            continue;
+        };
+        if !view.shows(cell.world_pos, grid.cell_radius) {
+            continue;
+        }
+        let Some(material) = dbg_assets.density_material(*count, saturation) else {
+            continue;
+        };
+
+        cmds.spawn((
+            Mesh3d(dbg_assets.diff_mesh.clone()),
+            MeshMaterial3d(material),
+            Transform::from_translation(cell.world_pos + offset)
+                .with_scale(Vec3::splat(grid.cell_diameter)),
+            DensityMarker,
+            Name::new("Density Marker"),
+        ));
+    }
+}
+
 fn draw_costfield(
     _trigger: Trigger<DrawDebugEv>,
     mut costmap: ResMut<CostMap>,
     dbg: Res<DebugOptions>,
-    digits: Res<Digits>,
-    mut meshes: ResMut<Assets<Mesh>>,
+    dbg_assets: Res<DebugAssets>,
+    style: Res<DebugStyle>,
+    view: Res<DebugView>,
     grid: Res<Grid>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
     mut cmds: Commands,
     q_cost: Query<Entity, With<Cost>>,
 ) {
+    let _span = info_span!("debug_draw_costfield").entered();
+
     // Remove current cost field before rendering new one
     for cost_entity in &q_cost {
         cmds.entity(cost_entity).despawn_recursive();
     }
 
-    let base_offset = calculate_offset(grid.cell_diameter, dbg, DrawMode::CostField);
+    let base_offset = calculate_offset(grid.cell_diameter, dbg, &style, DrawMode::CostField);
     let Some(base_offset) = base_offset else {
         return;
     };
@@ -304,10 +570,14 @@ fn draw_costfield(
     println!("Drawing Costfield");
 
     let base_digit_spacing = grid.cell_diameter * 0.275;
-    let mesh = meshes.add(Rectangle::new(grid.cell_diameter, grid.cell_diameter));
 
+    costmap.0.clear();
     for cell_row in &grid.grid {
         for cell in cell_row.iter() {
+            if !view.shows(cell.world_pos, grid.cell_radius) {
+                continue;
+            }
+
             let digits_vec: Vec<u32> = cell
                 .cost
                 .to_string()
@@ -328,10 +598,9 @@ fn draw_costfield(
                 scale,
                 digit_spacing,
                 cell.world_pos,
-                &mut materials,
-                &digits,
-                mesh.clone(),
+                &dbg_assets,
                 Cost,
+                DrawMode::CostField,
             );
 
             costmap.0.insert(cell.idx, cost_entities);
@@ -342,25 +611,34 @@ fn draw_costfield(
 fn update_cell_cost(
     mut cmds: Commands,
     mut events: EventReader<UpdateCostEv>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
-    mut meshes: ResMut<Assets<Mesh>>,
     mut cost_map: ResMut<CostMap>,
     dbg: Res<DebugOptions>,
-    digits: Res<Digits>,
+    dbg_assets: Res<DebugAssets>,
+    style: Res<DebugStyle>,
+    view: Res<DebugView>,
     grid: Res<Grid>,
 ) {
     let base_digit_spacing = grid.cell_diameter * 0.275;
     let cell_diameter = grid.cell_diameter;
 
-    let base_offset = calculate_offset(cell_diameter, dbg, DrawMode::CostField);
+    let base_offset = calculate_offset(cell_diameter, dbg, &style, DrawMode::CostField);
     let Some(base_offset) = base_offset else {
         return;
     };
 
-    let mesh = meshes.add(Rectangle::new(cell_diameter, cell_diameter));
-
-    for ev in events.read() {
+    // Only the `Grid` resource is drawn
+    for ev in events.read().filter(|ev| ev.map.is_none()) {
         let cell = ev.cell;
+        if let Some(previous_cost) = cost_map.0.remove(&cell.idx) {
+            for entity in previous_cost {
+                cmds.entity(entity).despawn();
+            }
+        }
+
+        if !view.shows(cell.world_pos, grid.cell_radius) {
+            continue;
+        }
+
         let digits_vec: Vec<u32> = cell
             .cost
             .to_string()
@@ -378,18 +656,11 @@ fn update_cell_cost(
             scale,
             digit_spacing,
             cell.world_pos,
-            &mut materials,
-            &digits,
-            mesh.clone(),
+            &dbg_assets,
             Cost,
+            DrawMode::CostField,
         );
 
-        if let Some(previous_cost) = cost_map.0.remove(&cell.idx) {
-            for entity in previous_cost {
-                cmds.entity(entity).despawn();
-            }
-        }
-
         cost_map.0.insert(cell.idx, new_cost_entities);
     }
 }
@@ -397,30 +668,27 @@ fn update_cell_cost(
 fn calculate_offset(
     cell_diameter: f32,
     dbg: Res<DebugOptions>,
+    style: &DebugStyle,
     draw_mode: DrawMode,
 ) -> Option<Vec3> {
     let mode = if dbg.draw_mode_1 == draw_mode {
-        Some(1)
+        1
     } else if dbg.draw_mode_2 == draw_mode {
-        Some(2)
+        2
     } else {
-        None
-    };
-
-    if mode.is_none() {
         return None; // nothing to draw
-    }
+    };
 
     // Base offset when only one mode is active
-    let mut offset = Vec3::new(0.0, 0.01, 0.0);
+    let mut offset = Vec3::new(0.0, style.marker_height, 0.0);
     if (dbg.draw_mode_1 == DrawMode::None || dbg.draw_mode_2 == DrawMode::None)
         || (dbg.draw_mode_1 == draw_mode && dbg.draw_mode_2 == draw_mode)
     {
         offset.z = 0.0;
     } else {
         match mode {
-            Some(1) => offset.z = -cell_diameter * 0.25,
-            Some(2) => offset.z = cell_diameter * 0.25,
+            1 => offset.z = -cell_diameter * 0.25,
+            2 => offset.z = cell_diameter * 0.25,
             _ => (),
         };
     }
@@ -429,22 +697,24 @@ fn calculate_offset(
 }
 
 fn draw<T: Component + Copy>(
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
+    dbg_assets: &DebugAssets,
+    view: &DebugView,
     cells: &Vec<Vec<Cell>>,
     cell_diameter: f32,
-    digits: Res<Digits>,
     comp: T,
+    mode: DrawMode,
     mut cmds: Commands,
     get_str: impl Fn(&Cell) -> String,
     base_offset: Vec3,
 ) {
     let base_digit_spacing = cell_diameter * 0.275;
 
-    let mesh = meshes.add(Rectangle::new(cell_diameter, cell_diameter));
-
     for cell_row in cells {
         for cell in cell_row.iter() {
+            if !view.shows(cell.world_pos, cell_diameter / 2.0) {
+                continue;
+            }
+
             // Generate the string using the closure
             let value_str = get_str(cell);
 
@@ -463,10 +733,9 @@ fn draw<T: Component + Copy>(
                 scale,
                 digit_spacing,
                 cell.world_pos,
-                &mut materials,
-                &digits,
-                mesh.clone(),
+                dbg_assets,
                 comp,
+                mode,
             );
         }
     }
@@ -482,14 +751,15 @@ fn calculate_digit_spacing_and_scale(
     let total_spacing_width = (digit_count as f32 - 1.0) * base_digit_spacing;
     let total_width = total_digit_width + total_spacing_width;
 
+    // The shared digit mesh is a unit square
     if total_width > cell_diameter {
         let scale_factor = cell_diameter / total_width;
         (
-            Vec3::splat(BASE_SCALE * scale_factor),
+            Vec3::splat(BASE_SCALE * scale_factor * cell_diameter),
             base_digit_spacing * scale_factor,
         )
     } else {
-        (Vec3::splat(BASE_SCALE), base_digit_spacing)
+        (Vec3::splat(BASE_SCALE * cell_diameter), base_digit_spacing)
     }
 }
 
@@ -500,10 +770,9 @@ fn spawn_digit_entities<T: Component + Copy>(
     scale: Vec3,
     digit_spacing: f32,
     cell_world_pos: Vec3,
-    materials: &mut ResMut<Assets<StandardMaterial>>,
-    digits: &Res<Digits>,
-    mesh: Handle<Mesh>,
+    dbg_assets: &DebugAssets,
     comp: T,
+    mode: DrawMode,
 ) -> Vec<Entity> {
     let mut entities = Vec::new();
     let x_offset = -(digits_vec.len() as f32 - 1.0) * digit_spacing / 2.0;
@@ -512,17 +781,10 @@ fn spawn_digit_entities<T: Component + Copy>(
         let mut offset = base_offset;
         offset.x += x_offset + i as f32 * digit_spacing;
 
-        let material = materials.add(StandardMaterial {
-            base_color_texture: Some(digits.0[digit as usize].clone()),
-            alpha_mode: AlphaMode::Blend,
-            unlit: true,
-            ..default()
-        });
-
         let dig = (
             comp,
-            Mesh3d(mesh.clone()),
-            MeshMaterial3d(material),
+            Mesh3d(dbg_assets.digit_mesh.clone()),
+            MeshMaterial3d(dbg_assets.digit_material(mode, digit)),
             Transform {
                 translation: cell_world_pos + offset,
                 rotation: Quat::from_rotation_x(-FRAC_PI_2),
@@ -537,8 +799,37 @@ fn spawn_digit_entities<T: Component + Copy>(
     entities
 }
 
-fn detect_debug_change(mut cmds: Commands, debug: Res<DebugOptions>) {
-    if debug.is_changed() {
+/// Follows the `GameCamera`, redrawing the markers once it moves into another cell while they
+/// are culled to it
+fn track_debug_view(
+    mut cmds: Commands,
+    dbg: Res<DebugOptions>,
+    grid: Res<Grid>,
+    mut view: ResMut<DebugView>,
+    q_cam: Query<(&GlobalTransform, &Frustum), With<GameCamera>>,
+) {
+    let Ok((transform, frustum)) = q_cam.get_single() else {
+        return;
+    };
+
+    let camera_pos = transform.translation();
+    view.frustum = dbg.cull_to_camera.then_some(*frustum);
+    view.camera_pos = camera_pos;
+    view.max_distance = dbg.max_draw_distance;
+
+    let camera_cell = (camera_pos / grid.cell_diameter).floor().as_ivec3();
+    if view.camera_cell == Some(camera_cell) {
+        return;
+    }
+
+    view.camera_cell = Some(camera_cell);
+    if dbg.cull_to_camera || dbg.max_draw_distance.is_some() {
+        cmds.trigger(DrawDebugEv);
+    }
+}
+
+fn detect_debug_change(mut cmds: Commands, debug: Res<DebugOptions>, baseline: Res<DiffBaseline>) {
+    if debug.is_changed() || baseline.is_changed() {
         cmds.trigger(DrawDebugEv);
     }
 }
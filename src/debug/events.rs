@@ -2,3 +2,7 @@ use bevy::prelude::*;
 
 #[derive(Event)]
 pub struct DrawDebugEv;
+
+/// Shows or hides the overlay of a flowfield entity, see `FlowfieldOverlays`
+#[derive(Event)]
+pub struct ToggleFlowfieldOverlayEv(pub Entity);
@@ -1,20 +1,49 @@
-use bevy::{color::palettes::css::GRAY, prelude::*};
+use crate::PathfindingSet;
+use bevy::prelude::*;
 use draw::DrawPlugin;
+use preview::PreviewPlugin;
 use resources::ResourcesPlugin;
 use ui::UiPlugin;
 
+pub use events::ToggleFlowfieldOverlayEv;
+pub use resources::{DebugOptions, DebugStyle, DiffBaseline, DrawMode, FlowfieldOverlays};
+
 mod components;
 pub mod draw;
 mod events;
+#[cfg(feature = "debug_ui")]
+mod panel;
+pub mod preview;
 mod resources;
 mod ui;
 
-const COLOR_GRID: Srgba = GRAY;
-
 pub struct BevyRtsPathFindingDebugPlugin;
 
 impl Plugin for BevyRtsPathFindingDebugPlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugins((DrawPlugin, UiPlugin, ResourcesPlugin));
+        app.add_plugins((DrawPlugin, UiPlugin, ResourcesPlugin, PreviewPlugin))
+            .add_systems(Update, log_stats.after(PathfindingSet::BuildFields));
+
+        #[cfg(feature = "debug_ui")]
+        app.add_plugins(panel::PanelPlugin);
     }
 }
+
+fn log_stats(
+    mut last_logged: Local<u32>,
+    dbg: Res<DebugOptions>,
+    stats: Res<crate::resources::PathfindingStats>,
+) {
+    if !dbg.log_stats || stats.integrations == *last_logged {
+        return;
+    }
+
+    *last_logged = stats.integrations;
+    info!(
+        "flowfields: {}, integration: {:?} (avg {:?}), dirty cells: {}",
+        stats.flowfield_count,
+        stats.last_integration,
+        stats.average_integration(),
+        stats.dirty_cells()
+    );
+}
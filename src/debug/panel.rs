@@ -0,0 +1,170 @@
+use super::events::ToggleFlowfieldOverlayEv;
+use super::resources::*;
+use crate::*;
+
+use bevy_egui::{egui, EguiContexts, EguiPlugin};
+use flowfield::FlowField;
+use grid::Grid;
+use resources::{ActiveDebugFlowfield, PathfindingStats};
+
+const DRAW_MODES: [DrawMode; 7] = [
+    DrawMode::None,
+    DrawMode::CostField,
+    DrawMode::FlowField,
+    DrawMode::IntegrationField,
+    DrawMode::Index,
+    DrawMode::Diff,
+    DrawMode::Density,
+];
+
+/// An egui panel for editing `DebugOptions` at runtime
+pub struct PanelPlugin;
+
+impl Plugin for PanelPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<EguiPlugin>() {
+            app.add_plugins(EguiPlugin);
+        }
+
+        app.add_systems(Update, draw_panel);
+    }
+}
+
+fn draw_panel(
+    mut cmds: Commands,
+    mut contexts: EguiContexts,
+    mut dbg: ResMut<DebugOptions>,
+    grid: Res<Grid>,
+    stats: Res<PathfindingStats>,
+    active_dbg_flowfield: Res<ActiveDebugFlowfield>,
+    overlays: Res<FlowfieldOverlays>,
+    q_flowfields: Query<(Entity, &FlowField)>,
+) {
+    let Some(ctx) = contexts.try_ctx_mut() else {
+        return;
+    };
+
+    let mut draw_grid = dbg.draw_grid;
+    let mut draw_mode_1 = dbg.draw_mode_1;
+    let mut draw_mode_2 = dbg.draw_mode_2;
+    let mut log_stats = dbg.log_stats;
+    let mut draw_paths = dbg.draw_paths;
+    let mut cull_to_camera = dbg.cull_to_camera;
+    let mut overlay_all = dbg.overlay_all;
+    let mut density_refresh = dbg.density_refresh;
+    let mut selected = None;
+    let mut toggled = Vec::new();
+
+    egui::Window::new("Pathfinding").show(ctx, |ui| {
+        ui.checkbox(&mut draw_grid, "Draw grid");
+        ui.checkbox(&mut draw_paths, "Draw unit paths");
+        ui.checkbox(&mut cull_to_camera, "Cull to camera");
+        draw_mode_combo(ui, "Draw mode 1", &mut draw_mode_1);
+        draw_mode_combo(ui, "Draw mode 2", &mut draw_mode_2);
+        if draw_mode_1 == DrawMode::Density || draw_mode_2 == DrawMode::Density {
+            let slider = egui::Slider::new(&mut density_refresh, 0.05..=2.0);
+            ui.add(slider.text("Density refresh (s)"));
+        }
+
+        ui.separator();
+        ui.label("Active flowfield");
+        for (entity, flowfield) in q_flowfields.iter() {
+            let active = active_dbg_flowfield
+                .0
+                .as_ref()
+                .is_some_and(|active| active.grid == flowfield.grid);
+            let label = format!("{entity} ({} units)", flowfield.units.len());
+
+            if ui.selectable_label(active, label).clicked() && !active {
+                selected = Some(flowfield.clone());
+            }
+        }
+
+        ui.separator();
+        ui.checkbox(&mut overlay_all, "Overlay all flowfields");
+        for (entity, _) in q_flowfields.iter() {
+            let mut shown = overlay_all || overlays.fields.contains(&entity);
+            let [r, g, b, _] = FlowfieldOverlays::color(entity).to_srgba().to_u8_array();
+            let legend =
+                egui::RichText::new(format!("{entity}")).color(egui::Color32::from_rgb(r, g, b));
+
+            ui.add_enabled_ui(!overlay_all, |ui| {
+                if ui.checkbox(&mut shown, legend).changed() {
+                    toggled.push(entity);
+                }
+            });
+        }
+
+        ui.separator();
+        let cells = grid.size.x * grid.size.y;
+        let blocked = grid
+            .grid
+            .iter()
+            .flatten()
+            .filter(|cell| cell.cost == u8::MAX)
+            .count();
+        let blocked_pct = blocked as f32 / cells.max(1) as f32 * 100.0;
+
+        ui.label(format!("Cells: {cells}"));
+        ui.label(format!("Blocked: {blocked_pct:.1}%"));
+        ui.label(format!(
+            "Generation time: {:.2} ms (avg {:.2} ms)",
+            stats.last_integration.as_secs_f32() * 1000.0,
+            stats.average_integration().as_secs_f32() * 1000.0
+        ));
+        ui.label(format!("Flowfields: {}", stats.flowfield_count));
+        ui.label(format!("Dirty cells: {}", stats.dirty_cells()));
+        ui.checkbox(&mut log_stats, "Log stats");
+    });
+
+    // Only write back on change so the debug draw isn't retriggered every frame
+    if draw_grid != dbg.draw_grid {
+        dbg.draw_grid = draw_grid;
+    }
+
+    if draw_mode_1 != dbg.draw_mode_1 {
+        dbg.draw_mode_1 = draw_mode_1;
+    }
+
+    if draw_mode_2 != dbg.draw_mode_2 {
+        dbg.draw_mode_2 = draw_mode_2;
+    }
+
+    if draw_paths != dbg.draw_paths {
+        dbg.draw_paths = draw_paths;
+    }
+
+    if cull_to_camera != dbg.cull_to_camera {
+        dbg.cull_to_camera = cull_to_camera;
+    }
+
+    if log_stats != dbg.log_stats {
+        dbg.log_stats = log_stats;
+    }
+
+    if overlay_all != dbg.overlay_all {
+        dbg.overlay_all = overlay_all;
+    }
+
+    if density_refresh != dbg.density_refresh {
+        dbg.density_refresh = density_refresh;
+    }
+
+    for entity in toggled {
+        cmds.trigger(ToggleFlowfieldOverlayEv(entity));
+    }
+
+    if let Some(flowfield) = selected {
+        cmds.trigger(SetActiveFlowfieldEv(Some(flowfield)));
+    }
+}
+
+fn draw_mode_combo(ui: &mut egui::Ui, label: &str, mode: &mut DrawMode) {
+    egui::ComboBox::from_label(label)
+        .selected_text(DebugOptions::draw_mode_to_string(*mode))
+        .show_ui(ui, |ui| {
+            for option in DRAW_MODES {
+                ui.selectable_value(mode, option, DebugOptions::draw_mode_to_string(option));
+            }
+        });
+}
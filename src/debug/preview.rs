@@ -0,0 +1,118 @@
+use crate::*;
+
+use bevy::color::palettes::css::LIME;
+use flowfield::FlowField;
+
+pub struct PreviewPlugin;
+
+impl Plugin for PreviewPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PathPreviewSettings>()
+            .init_resource::<PathPreviews>()
+            .register_type::<PathPreviewSettings>()
+            .add_systems(
+                Update,
+                (preview_new_flowfields, draw_path_previews)
+                    .chain()
+                    .after(PathfindingSet::BuildFields),
+            );
+    }
+}
+
+/// Move order feedback: a line from each ordered unit along its new flowfield, fading out
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
+pub struct PathPreviewSettings {
+    /// Preview every newly spawned flowfield automatically
+    pub enabled: bool,
+    /// Seconds until a preview has faded out
+    pub duration: f32,
+    pub color: Color,
+}
+
+impl Default for PathPreviewSettings {
+    fn default() -> Self {
+        PathPreviewSettings {
+            enabled: true,
+            duration: 1.5,
+            color: LIME.into(),
+        }
+    }
+}
+
+/// The previews currently shown. Games can push their own paths.
+#[derive(Resource, Default)]
+pub struct PathPreviews(Vec<PathPreview>);
+
+struct PathPreview {
+    points: Vec<Vec3>,
+    elapsed: f32,
+}
+
+impl PathPreviews {
+    pub fn push(&mut self, points: Vec<Vec3>) {
+        if points.len() > 1 {
+            self.0.push(PathPreview {
+                points,
+                elapsed: 0.0,
+            });
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+}
+
+fn preview_new_flowfields(
+    settings: Res<PathPreviewSettings>,
+    mut previews: ResMut<PathPreviews>,
+    q_flowfields: Query<&FlowField, Added<FlowField>>,
+    q_transform: Query<&Transform>,
+) {
+    if !settings.enabled {
+        return;
+    }
+
+    for flowfield in q_flowfields.iter() {
+        for unit in flowfield.units.iter() {
+            let Ok(transform) = q_transform.get(*unit) else {
+                continue;
+            };
+
+            let mut points = vec![transform.translation];
+            points.extend(
+                flowfield
+                    .extract_path(transform.translation)
+                    .iter()
+                    .skip(1)
+                    .map(|cell| cell.world_pos),
+            );
+            previews.push(points);
+        }
+    }
+}
+
+fn draw_path_previews(
+    time: Res<Time>,
+    settings: Res<PathPreviewSettings>,
+    mut previews: ResMut<PathPreviews>,
+    mut gizmos: Gizmos,
+) {
+    if previews.0.is_empty() {
+        return;
+    }
+
+    let lift = Vec3::new(0.0, 0.05, 0.0);
+    for preview in previews.0.iter_mut() {
+        preview.elapsed += time.delta_secs();
+
+        let alpha = 1.0 - (preview.elapsed / settings.duration).clamp(0.0, 1.0);
+        let points = preview.points.iter().map(|point| *point + lift);
+        gizmos.linestrip(points, settings.color.with_alpha(alpha));
+    }
+
+    previews
+        .0
+        .retain(|preview| preview.elapsed < settings.duration);
+}
@@ -1,8 +1,19 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
-use bevy::{image::*, prelude::*, render::render_resource::*};
+use bevy::{
+    color::palettes::css::{GRAY, LIGHT_GRAY, ORANGE, RED, YELLOW},
+    image::*,
+    prelude::*,
+    render::{
+        primitives::{Frustum, Sphere},
+        render_resource::*,
+    },
+};
 use image::ImageFormat;
 
+use super::events::DrawDebugEv;
+use crate::flowfield::FlowField;
+
 const DIGIT_ATLAS: &[u8] = include_bytes!("../../assets/digits/digit_atlas.png");
 const DBG_ICON: &[u8] = include_bytes!("../../assets/dbg_icon.png");
 
@@ -14,8 +25,22 @@ impl Plugin for ResourcesPlugin {
             .init_resource::<DebugOptions>()
             .init_resource::<DbgIcon>()
             .init_resource::<Digits>()
+            .init_resource::<DebugAssets>()
+            .init_resource::<DebugStyle>()
+            .init_resource::<DebugView>()
+            .init_resource::<DiffBaseline>()
+            .init_resource::<FlowfieldOverlays>()
+            .init_resource::<DensityHeatmap>()
             .register_type::<DebugOptions>()
-            .add_systems(Startup, (load_dbg_icon, load_digit_texture_atlas));
+            .register_type::<DebugStyle>()
+            .add_systems(
+                Startup,
+                (
+                    load_dbg_icon,
+                    (load_digit_texture_atlas, load_debug_assets).chain(),
+                ),
+            )
+            .add_systems(Update, restyle_debug_assets);
     }
 }
 
@@ -28,6 +53,183 @@ pub struct Digits(pub [Handle<Image>; 10]);
 #[derive(Resource, Default)]
 pub struct DbgIcon(pub Handle<Image>);
 
+/// Flowfields drawn as arrows in a color of their own on top of the active debug flowfield, to
+/// see how concurrent groups interact. Toggle them with `ToggleFlowfieldOverlayEv`, or draw every
+/// live flowfield with `DebugOptions::overlay_all`.
+#[derive(Resource, Default)]
+pub struct FlowfieldOverlays {
+    pub fields: HashSet<Entity>,
+}
+
+impl FlowfieldOverlays {
+    /// The color of an entity's overlay and route
+    pub fn color(entity: Entity) -> Color {
+        // Spread the hues by the golden angle so neighboring entities stand apart
+        let hue = (entity.index() as f32 * 137.508) % 360.0;
+        return Color::hsl(hue, 0.9, 0.6);
+    }
+}
+
+/// Units per cell of the `Grid` resource drawn by `DrawMode::Density`, recounted from the
+/// `UnitSpatialIndex` every `DebugOptions::density_refresh` seconds
+#[derive(Resource, Default)]
+pub struct DensityHeatmap {
+    pub counts: HashMap<IVec2, u32>,
+    pub timer: Timer,
+}
+
+/// The field `DrawMode::Diff` compares the active debug flowfield against, such as a full
+/// rebuild to check an incremental repair with
+#[derive(Resource, Default)]
+pub struct DiffBaseline(pub Option<FlowField>);
+
+/// Colors, sizes and meshes of the debug overlay, so it can match a game's art style
+#[derive(Resource, Reflect, Clone)]
+#[reflect(Resource)]
+pub struct DebugStyle {
+    pub grid_color: Color,
+    pub arrow_color: Color,
+    pub blocked_color: Color,
+    pub cost_color: Color,
+    pub integration_color: Color,
+    pub index_color: Color,
+    /// Highlight of cells that differ from the `DiffBaseline`
+    pub diff_color: Color,
+    /// Highlight of cells with a single unit in `DrawMode::Density`
+    pub density_cold_color: Color,
+    /// Highlight of cells with `DebugOptions::density_saturation` units or more
+    pub density_hot_color: Color,
+    /// Route of a `PlacementPreview` without the building
+    pub route_before_color: Color,
+    /// Route of a `PlacementPreview` with the building
+    pub route_after_color: Color,
+    /// Width of the flowfield arrows and blocked cell crosses, relative to a cell
+    pub line_width: f32,
+    /// Replaces the flowfield arrow, pointing along +X and sized for a cell diameter of 1
+    pub arrow_mesh: Option<Handle<Mesh>>,
+    /// Height of the digits and arrows above the cells
+    pub marker_height: f32,
+    /// Height of the grid, routes and paths above the cells
+    pub line_height: f32,
+}
+
+impl Default for DebugStyle {
+    fn default() -> Self {
+        DebugStyle {
+            grid_color: GRAY.into(),
+            arrow_color: Color::WHITE,
+            blocked_color: RED.into(),
+            cost_color: Color::WHITE,
+            integration_color: Color::WHITE,
+            index_color: Color::WHITE,
+            diff_color: YELLOW.with_alpha(0.5).into(),
+            density_cold_color: YELLOW.with_alpha(0.25).into(),
+            density_hot_color: RED.with_alpha(0.75).into(),
+            route_before_color: LIGHT_GRAY.into(),
+            route_after_color: ORANGE.into(),
+            line_width: 0.1,
+            arrow_mesh: None,
+            marker_height: 0.01,
+            line_height: 0.05,
+        }
+    }
+}
+
+impl DebugStyle {
+    pub fn digit_color(&self, mode: DrawMode) -> Color {
+        match mode {
+            DrawMode::IntegrationField => self.integration_color,
+            DrawMode::Index => self.index_color,
+            _ => self.cost_color,
+        }
+    }
+}
+
+/// The draw modes drawn with digits, in the order of `DebugAssets::digit_materials`
+const DIGIT_MODES: [DrawMode; 3] = [
+    DrawMode::CostField,
+    DrawMode::IntegrationField,
+    DrawMode::Index,
+];
+
+/// The number of colors `DrawMode::Density` blends between
+const DENSITY_LEVELS: usize = 8;
+
+/// Meshes and materials shared by every debug marker. Markers reusing the same handles are
+/// batched by Bevy's automatic instancing. Meshes are sized for a cell diameter of 1.
+#[derive(Resource, Default)]
+pub struct DebugAssets {
+    pub digit_mesh: Handle<Mesh>,
+    /// One set of digits per draw mode in `DIGIT_MODES`
+    pub digit_materials: [[Handle<StandardMaterial>; 10]; 3],
+    pub arrow_mesh: Handle<Mesh>,
+    pub arrow_head_mesh: Handle<Mesh>,
+    /// One stroke of the cross on blocked cells
+    pub cross_mesh: Handle<Mesh>,
+    pub destination_mesh: Handle<Mesh>,
+    pub diff_mesh: Handle<Mesh>,
+    pub arrow_material: Handle<StandardMaterial>,
+    pub blocked_material: Handle<StandardMaterial>,
+    pub diff_material: Handle<StandardMaterial>,
+    /// From `DebugStyle::density_cold_color` to `density_hot_color`
+    pub density_materials: Vec<Handle<StandardMaterial>>,
+}
+
+impl DebugAssets {
+    pub fn digit_material(&self, mode: DrawMode, digit: u32) -> Handle<StandardMaterial> {
+        let set = DIGIT_MODES.iter().position(|m| *m == mode).unwrap_or(0);
+        return self.digit_materials[set][digit as usize].clone();
+    }
+
+    /// The material of a cell with `count` units, hottest at `saturation` units
+    pub fn density_material(
+        &self,
+        count: u32,
+        saturation: u32,
+    ) -> Option<Handle<StandardMaterial>> {
+        let levels = self.density_materials.len();
+        if count == 0 || levels == 0 {
+            return None;
+        }
+
+        let heat = count.min(saturation.max(1)) as f32 / saturation.max(1) as f32;
+        let level = (heat * (levels - 1) as f32).round() as usize;
+        return Some(self.density_materials[level].clone());
+    }
+}
+
+/// The camera view markers are culled to, see `DebugOptions::cull_to_camera`. Markers are
+/// redrawn whenever the camera moves into another cell.
+#[derive(Resource, Default)]
+pub struct DebugView {
+    pub frustum: Option<Frustum>,
+    pub camera_pos: Vec3,
+    pub max_distance: Option<f32>,
+    /// The cell-sized step of space the camera was in when markers were last drawn
+    pub camera_cell: Option<IVec3>,
+}
+
+impl DebugView {
+    /// True if a cell at `pos` with `radius` is close enough and in view to draw markers for
+    pub fn shows(&self, pos: Vec3, radius: f32) -> bool {
+        if let Some(max_distance) = self.max_distance {
+            if pos.distance(self.camera_pos) > max_distance + radius {
+                return false;
+            }
+        }
+
+        let Some(frustum) = &self.frustum else {
+            return true;
+        };
+
+        let sphere = Sphere {
+            center: pos.into(),
+            radius,
+        };
+        return frustum.intersects_sphere(&sphere, true);
+    }
+}
+
 #[derive(Reflect, Resource)]
 #[reflect(Resource)]
 pub struct DebugOptions {
@@ -35,6 +237,20 @@ pub struct DebugOptions {
     pub draw_grid: bool,
     pub draw_mode_1: DrawMode,
     pub draw_mode_2: DrawMode,
+    /// Log `PathfindingStats` after every integration
+    pub log_stats: bool,
+    /// Trace the path of every unit with a `Destination` to it
+    pub draw_paths: bool,
+    /// Only draw markers for cells in view of the `GameCamera`
+    pub cull_to_camera: bool,
+    /// Skip markers for cells further than this from the `GameCamera`
+    pub max_draw_distance: Option<f32>,
+    /// Overlay every live flowfield, not only those in `FlowfieldOverlays`
+    pub overlay_all: bool,
+    /// Seconds between recounts of the `DensityHeatmap`
+    pub density_refresh: f32,
+    /// Units in a cell drawn in the hottest `DrawMode::Density` color
+    pub density_saturation: u32,
 }
 
 impl Default for DebugOptions {
@@ -44,6 +260,13 @@ impl Default for DebugOptions {
             draw_grid: true,
             draw_mode_1: DrawMode::Index,
             draw_mode_2: DrawMode::FlowField,
+            log_stats: false,
+            draw_paths: false,
+            cull_to_camera: true,
+            max_draw_distance: None,
+            overlay_all: false,
+            density_refresh: 0.25,
+            density_saturation: 4,
         }
     }
 }
@@ -56,6 +279,8 @@ impl DebugOptions {
             DrawMode::FlowField => String::from("FlowField"),
             DrawMode::IntegrationField => String::from("IntegrationField"),
             DrawMode::Index => String::from("Index"),
+            DrawMode::Diff => String::from("Diff"),
+            DrawMode::Density => String::from("Density"),
         }
     }
 
@@ -83,6 +308,11 @@ pub enum DrawMode {
     FlowField,
     IntegrationField,
     Index,
+    /// Highlights the cells where the active flowfield differs from the `DiffBaseline`
+    Diff,
+    /// Colors the cells of the `Grid` resource by the units standing in them, see
+    /// `DensityHeatmap`
+    Density,
 }
 
 impl DrawMode {
@@ -93,6 +323,8 @@ impl DrawMode {
             "FlowField" => DrawMode::FlowField,
             "IntegrationField" => DrawMode::IntegrationField,
             "Index" => DrawMode::Index,
+            "Diff" => DrawMode::Diff,
+            "Density" => DrawMode::Density,
             _ => DrawMode::None,
         }
     }
@@ -100,8 +332,13 @@ impl DrawMode {
 
 pub fn load_dbg_icon(mut images: ResMut<Assets<Image>>, mut dbg_icon: ResMut<DbgIcon>) {
     // Decode the image
-    let image = image::load_from_memory_with_format(DBG_ICON, ImageFormat::Png)
-        .expect("Failed to load digit image");
+    let image = match image::load_from_memory_with_format(DBG_ICON, ImageFormat::Png) {
+        Ok(image) => image,
+        Err(error) => {
+            error!("Failed to load debug icon: {error}");
+            return;
+        }
+    };
     let rgba_image = image.to_rgba8();
     let (width, height) = rgba_image.dimensions();
 
@@ -133,8 +370,13 @@ pub fn load_dbg_icon(mut images: ResMut<Assets<Image>>, mut dbg_icon: ResMut<Dbg
 
 fn load_digit_texture_atlas(mut images: ResMut<Assets<Image>>, mut digits: ResMut<Digits>) {
     // Decode the image
-    let image = image::load_from_memory_with_format(DIGIT_ATLAS, ImageFormat::Png)
-        .expect("Failed to load digit image");
+    let image = match image::load_from_memory_with_format(DIGIT_ATLAS, ImageFormat::Png) {
+        Ok(image) => image,
+        Err(error) => {
+            error!("Failed to load digit image: {error}");
+            return;
+        }
+    };
     let rgba_image = image.to_rgba8();
     let (width, height) = rgba_image.dimensions();
     let digit_width = width / 10;
@@ -171,3 +413,90 @@ fn load_digit_texture_atlas(mut images: ResMut<Assets<Image>>, mut digits: ResMu
         digits.0[idx as usize] = images.add(cropped_digit);
     }
 }
+
+fn load_debug_assets(
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    digits: Res<Digits>,
+    style: Res<DebugStyle>,
+    mut dbg_assets: ResMut<DebugAssets>,
+) {
+    *dbg_assets = build_debug_assets(&mut meshes, &mut materials, &digits, &style);
+}
+
+/// Rebuilds the shared assets and redraws once the style changes
+fn restyle_debug_assets(
+    mut cmds: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    digits: Res<Digits>,
+    style: Res<DebugStyle>,
+    mut dbg_assets: ResMut<DebugAssets>,
+) {
+    if !style.is_changed() || style.is_added() {
+        return;
+    }
+
+    *dbg_assets = build_debug_assets(&mut meshes, &mut materials, &digits, &style);
+    cmds.trigger(DrawDebugEv);
+}
+
+fn build_debug_assets(
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+    digits: &Digits,
+    style: &DebugStyle,
+) -> DebugAssets {
+    let arrow_length = 0.6;
+    let arrow_width = style.line_width;
+
+    // Arrowhead triangle, pointing along +X
+    let half_arrow_size = arrow_length / 2.0;
+    let d1 = half_arrow_size - 0.09;
+    let a = Vec2::new(half_arrow_size + 0.05, 0.0);
+    let b = Vec2::new(d1, arrow_width + 0.0125);
+    let c = Vec2::new(d1, -arrow_width - 0.0125);
+
+    let digit_materials = DIGIT_MODES.map(|mode| {
+        std::array::from_fn(|digit| {
+            materials.add(StandardMaterial {
+                base_color: style.digit_color(mode),
+                base_color_texture: Some(digits.0[digit].clone()),
+                alpha_mode: AlphaMode::Blend,
+                unlit: true,
+                ..default()
+            })
+        })
+    });
+
+    DebugAssets {
+        digit_mesh: meshes.add(Rectangle::new(1.0, 1.0)),
+        digit_materials,
+        arrow_mesh: style.arrow_mesh.clone().unwrap_or_else(|| {
+            meshes.add(Plane3d::default().mesh().size(arrow_length, arrow_width))
+        }),
+        arrow_head_mesh: meshes.add(Triangle2d::new(a, b, c)),
+        cross_mesh: meshes.add(Plane3d::default().mesh().size(arrow_length, arrow_width)),
+        destination_mesh: meshes.add(Circle::new(1.0 / 6.0)),
+        diff_mesh: meshes.add(Plane3d::default().mesh().size(0.9, 0.9)),
+        arrow_material: materials.add(StandardMaterial::from_color(style.arrow_color)),
+        blocked_material: materials.add(StandardMaterial::from_color(style.blocked_color)),
+        diff_material: materials.add(StandardMaterial {
+            base_color: style.diff_color,
+            alpha_mode: AlphaMode::Blend,
+            unlit: true,
+            ..default()
+        }),
+        density_materials: (0..DENSITY_LEVELS)
+            .map(|level| {
+                let heat = level as f32 / (DENSITY_LEVELS - 1) as f32;
+                materials.add(StandardMaterial {
+                    base_color: style.density_cold_color.mix(&style.density_hot_color, heat),
+                    alpha_mode: AlphaMode::Blend,
+                    unlit: true,
+                    ..default()
+                })
+            })
+            .collect(),
+    }
+}
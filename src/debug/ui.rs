@@ -113,8 +113,8 @@ fn handle_drawmode_option_interaction(
 
                 cmds.trigger(UpdateDropdownOptionEv);
             }
-            Interaction::Hovered => background.0 = CLR_BTN_HOVER.into(),
-            Interaction::None => background.0 = CLR_BACKGROUND_2.into(),
+            Interaction::Hovered => background.0 = CLR_BTN_HOVER,
+            Interaction::None => background.0 = CLR_BACKGROUND_2,
         }
     }
 }
@@ -136,8 +136,8 @@ fn handle_draw_grid_interaction(
                     txt.0 = format!("Grid: {}", dbg.draw_grid);
                 }
             }
-            Interaction::Hovered => background.0 = CLR_BTN_HOVER.into(),
-            Interaction::None => background.0 = CLR_BACKGROUND_2.into(),
+            Interaction::Hovered => background.0 = CLR_BTN_HOVER,
+            Interaction::None => background.0 = CLR_BACKGROUND_2,
         }
     }
 }
@@ -156,8 +156,8 @@ fn handle_hide_dbg_interaction(
                 cmds.trigger(ToggleDbgVisibilityEv(dbg.hide));
                 dbg.hide = !dbg.hide;
             }
-            Interaction::Hovered => background.0 = CLR_BTN_HOVER.into(),
-            Interaction::None => background.0 = CLR_BACKGROUND_1.into(),
+            Interaction::Hovered => background.0 = CLR_BTN_HOVER,
+            Interaction::None => background.0 = CLR_BACKGROUND_1,
         }
     }
 }
@@ -219,8 +219,8 @@ fn handle_dropdown_click(
     for (interaction, dropdown, mut background) in q_btn.iter_mut() {
         match interaction {
             Interaction::Pressed => cmds.trigger(ToggleModeEv(dropdown.0)),
-            Interaction::Hovered => background.0 = CLR_BTN_HOVER.into(),
-            Interaction::None => background.0 = CLR_BACKGROUND_2.into(),
+            Interaction::Hovered => background.0 = CLR_BTN_HOVER,
+            Interaction::None => background.0 = CLR_BACKGROUND_2,
         }
     }
 }
@@ -235,7 +235,11 @@ fn handle_drag(
         return;
     };
 
-    let Some(cursor_pos) = window_q.single().cursor_position() else {
+    let Some(cursor_pos) = window_q
+        .get_single()
+        .ok()
+        .and_then(|window| window.cursor_position())
+    else {
         return;
     };
 
@@ -278,13 +282,7 @@ fn toggle_dropdown_visibility(
     let option = trigger.event().0;
 
     for (mut dropdown, dropdown_options) in q_dropdown.iter_mut() {
-        if option == OptionsSet::One && dropdown_options.0.to_num() == 1 {
-            if dropdown.display == Display::Flex {
-                dropdown.display = Display::None;
-            } else if dropdown.display == Display::None {
-                dropdown.display = Display::Flex
-            }
-        } else if option == OptionsSet::Two && dropdown_options.0.to_num() == 2 {
+        if option.to_num() == dropdown_options.0.to_num() {
             if dropdown.display == Display::Flex {
                 dropdown.display = Display::None;
             } else if dropdown.display == Display::None {
@@ -373,7 +371,7 @@ fn draw_ui_box(mut cmds: Commands, dbg: Res<DebugOptions>, dbg_icon: Res<DbgIcon
         DropDownBtnBundle {
             comp: DropdownBtn(set),
             visible_node: VisibleNode,
-            btn: Button::default(),
+            btn: Button,
             background_clr: BackgroundColor::from(CLR_BACKGROUND_2),
             border_clr: BorderColor::from(CLR_BORDER),
             border_radius: radius,
@@ -517,6 +515,16 @@ fn draw_ui_box(mut cmds: Commands, dbg: Res<DebugOptions>, dbg_icon: Res<DbgIcon
                     .with_children(|btn| {
                         btn.spawn(option_txt("> CostField".to_string()));
                     });
+                options
+                    .spawn(btn_option(OptionsSet::One, "Diff".to_string(), None))
+                    .with_children(|btn| {
+                        btn.spawn(option_txt("> Diff".to_string()));
+                    });
+                options
+                    .spawn(btn_option(OptionsSet::One, "Density".to_string(), None))
+                    .with_children(|btn| {
+                        btn.spawn(option_txt("> Density".to_string()));
+                    });
                 options
                     .spawn(btn_option(OptionsSet::One, "Index".to_string(), None))
                     .with_children(|btn| {
@@ -567,6 +575,16 @@ fn draw_ui_box(mut cmds: Commands, dbg: Res<DebugOptions>, dbg_icon: Res<DbgIcon
                 .with_children(|btn| {
                     btn.spawn(option_txt("> CostField".to_string()));
                 });
+            options
+                .spawn(btn_option(OptionsSet::Two, "Diff".to_string(), None))
+                .with_children(|btn| {
+                    btn.spawn(option_txt("> Diff".to_string()));
+                });
+            options
+                .spawn(btn_option(OptionsSet::Two, "Density".to_string(), None))
+                .with_children(|btn| {
+                    btn.spawn(option_txt("> Density".to_string()));
+                });
             options
                 .spawn(btn_option(
                     OptionsSet::Two,
@@ -0,0 +1,33 @@
+//! Recoverable failures. Instead of panicking, the work that failed is skipped and a
+//! `PathfindingErrorEv` says why.
+
+use bevy::prelude::*;
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PathfindingError {
+    /// A cursor move order found no `PrimaryWindow`
+    NoWindow,
+    /// A cursor move order found no single `GameCamera`
+    NoCamera,
+    /// A cursor move order found no single `MapBase` to project the cursor onto
+    NoMapBase,
+    /// An order or `GridScene` refers to a map entity without a `Grid`
+    MissingGrid(Entity),
+    /// A `GridScene` with less than one cell along an axis
+    InvalidGridSize(IVec2),
+}
+
+impl fmt::Display for PathfindingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PathfindingError::NoWindow => write!(f, "No primary window to read the cursor from"),
+            PathfindingError::NoCamera => write!(f, "No single GameCamera to cast the cursor from"),
+            PathfindingError::NoMapBase => write!(f, "No single MapBase to cast the cursor onto"),
+            PathfindingError::MissingGrid(map) => write!(f, "Map {map} has no Grid"),
+            PathfindingError::InvalidGridSize(size) => write!(f, "Invalid grid size {size}"),
+        }
+    }
+}
+
+impl std::error::Error for PathfindingError {}
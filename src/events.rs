@@ -1,20 +1,477 @@
 use bevy::prelude::*;
 
-use crate::{cell::Cell, flowfield::FlowField};
+use crate::{
+    cell::Cell,
+    error::PathfindingError,
+    flowfield::{CostModifierFn, FlowField, FlowFieldId},
+    grid::Grid,
+    orders::AreaShape,
+    scheduler::OrderPriority,
+    utils::RayCastError,
+};
 
+/// Orders the units to the cursor position on the `MapBase`. The caller picks the units and
+/// gives them a `Destination`, see `selection::SelectionPlugin` to order the `Selected` units.
 #[derive(Event)]
 pub struct InitializeFlowFieldEv(pub Vec<Entity>);
 
+/// Like `InitializeFlowFieldEv`, but towards a known world position instead of the cursor. The
+/// flowfield is built by the `FlowFieldScheduler`.
+#[derive(Event)]
+pub struct InitializeFlowFieldAtEv {
+    pub units: Vec<Entity>,
+    pub destination: Vec3,
+    /// When set, the destination is the target's position and follows it as it moves
+    pub target: Option<Entity>,
+    /// The map entity whose `Grid` to path over, `None` for the `Grid` resource
+    pub map: Option<Entity>,
+    pub priority: OrderPriority,
+    pub cost_modifier: Option<CostModifierFn>,
+    /// Paths through parked units instead of around them, see `parking::ParkingSettings`
+    pub through_parked: bool,
+}
+
+impl InitializeFlowFieldAtEv {
+    pub fn new(units: Vec<Entity>, destination: Vec3) -> Self {
+        Self {
+            units,
+            destination,
+            target: None,
+            map: None,
+            priority: OrderPriority::Player,
+            cost_modifier: None,
+            through_parked: false,
+        }
+    }
+
+    /// Pursues `target` instead of moving to a fixed position
+    pub fn following(units: Vec<Entity>, target: Entity) -> Self {
+        Self {
+            units,
+            destination: Vec3::ZERO,
+            target: Some(target),
+            map: None,
+            priority: OrderPriority::Player,
+            cost_modifier: None,
+            through_parked: false,
+        }
+    }
+
+    /// Paths over the `Grid` of the `map` entity instead of the `Grid` resource
+    pub fn on_map(mut self, map: Entity) -> Self {
+        self.map = Some(map);
+        self
+    }
+
+    /// Queues the order behind higher priority ones, e.g. `OrderPriority::Ai` for AI orders
+    pub fn with_priority(mut self, priority: OrderPriority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Builds the flowfield over costs of the order's own, see `CostModifierFn`
+    pub fn with_cost_modifier(
+        mut self,
+        modifier: impl Fn(&Cell) -> u8 + Send + Sync + 'static,
+    ) -> Self {
+        self.cost_modifier = Some(CostModifierFn::new(modifier));
+        self
+    }
+
+    /// Ignores the soft costs of parked units, e.g. for attack-move orders
+    pub fn through_parked(mut self) -> Self {
+        self.through_parked = true;
+        self
+    }
+}
+
+/// Orders every `Selected` unit, see `selection::SelectionPlugin`
+#[derive(Event, Default)]
+pub struct MoveSelectedEv {
+    /// Where to send the units, `None` for the cursor position
+    pub destination: Option<Vec3>,
+}
+
+impl MoveSelectedEv {
+    /// Sends the units to the cursor position
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sends the units to `destination` instead of the cursor position
+    pub fn to(destination: Vec3) -> Self {
+        Self {
+            destination: Some(destination),
+        }
+    }
+}
+
+/// Spreads the units evenly over a line or rectangle, each heading to the spread destination
+/// nearest to it, see `orders::area_destinations`
+#[derive(Event)]
+pub struct AreaMoveEv {
+    pub units: Vec<Entity>,
+    pub shape: AreaShape,
+    /// The map entity whose `Grid` to path over, `None` for the `Grid` resource
+    pub map: Option<Entity>,
+}
+
+impl AreaMoveEv {
+    /// Lines the units up from `start` to `end`
+    pub fn line(units: Vec<Entity>, start: Vec3, end: Vec3) -> Self {
+        Self {
+            units,
+            shape: AreaShape::Line { start, end },
+            map: None,
+        }
+    }
+
+    /// Fills the rectangle between two opposite corners with the units
+    pub fn rect(units: Vec<Entity>, corner: Vec3, opposite: Vec3) -> Self {
+        Self {
+            units,
+            shape: AreaShape::Rect { corner, opposite },
+            map: None,
+        }
+    }
+
+    /// Paths over the `Grid` of the `map` entity instead of the `Grid` resource
+    pub fn on_map(mut self, map: Entity) -> Self {
+        self.map = Some(map);
+        self
+    }
+}
+
+/// Stops the units, for stop commands or before despawning units that died. They leave their
+/// flowfields, which are despawned once empty, and drop queued orders, A* paths, patrols, queued
+/// goals and reserved cells. Without a `Destination`, they block their cells as obstacles again.
+#[derive(Event)]
+pub struct CancelOrdersEv {
+    pub units: Vec<Entity>,
+}
+
+impl CancelOrdersEv {
+    pub fn new(units: Vec<Entity>) -> Self {
+        Self { units }
+    }
+}
+
 #[derive(Event)]
 pub struct SetActiveFlowfieldEv(pub Option<FlowField>);
 
 #[derive(Event)]
 pub struct UpdateCostEv {
     pub cell: Cell,
+    /// The map entity whose `Grid` changed, `None` for the `Grid` resource
+    pub map: Option<Entity>,
 }
 
 impl UpdateCostEv {
     pub fn new(cell: Cell) -> Self {
-        Self { cell }
+        Self { cell, map: None }
+    }
+
+    pub fn on_map(cell: Cell, map: Entity) -> Self {
+        Self {
+            cell,
+            map: Some(map),
+        }
+    }
+}
+
+/// Sent when a unit with a `Destination` hasn't moved for `StuckSettings::duration` seconds
+#[derive(Event)]
+pub struct UnitStuckEv {
+    pub unit: Entity,
+    pub position: Vec3,
+    /// The flowfield, or the id of the `AStarPath`, the unit was following
+    pub flowfield: Option<FlowFieldId>,
+}
+
+impl UnitStuckEv {
+    pub fn new(unit: Entity, position: Vec3) -> Self {
+        Self {
+            unit,
+            position,
+            flowfield: None,
+        }
+    }
+
+    pub fn with_flowfield(mut self, flowfield: FlowFieldId) -> Self {
+        self.flowfield = Some(flowfield);
+        self
+    }
+}
+
+/// Sent when a unit settled at the destination of its flowfield, see `steering::ArrivalMode`.
+/// Arrivals of one frame are sent in the order of their flowfields' ids.
+#[derive(Event)]
+pub struct DestinationReachedEv {
+    pub unit: Entity,
+    pub destination: Vec3,
+    /// The flowfield the unit followed, or the id of its `AStarPath`
+    pub flowfield: Option<FlowFieldId>,
+}
+
+impl DestinationReachedEv {
+    pub fn new(unit: Entity, destination: Vec3) -> Self {
+        Self {
+            unit,
+            destination,
+            flowfield: None,
+        }
+    }
+
+    pub fn with_flowfield(mut self, flowfield: FlowFieldId) -> Self {
+        self.flowfield = Some(flowfield);
+        self
+    }
+}
+
+/// Sent when a queued move order's flowfield was built and spawned, in build order
+#[derive(Event)]
+pub struct FlowFieldReadyEv {
+    pub id: FlowFieldId,
+    /// The flowfield entity, which differs between runs unlike `id`
+    pub entity: Entity,
+    pub units: Vec<Entity>,
+    pub destination: Vec3,
+}
+
+impl FlowFieldReadyEv {
+    pub fn new(id: FlowFieldId, entity: Entity, units: Vec<Entity>, destination: Vec3) -> Self {
+        Self {
+            id,
+            entity,
+            units,
+            destination,
+        }
+    }
+}
+
+/// Sent when a unit reached the last goal of its `OrderQueue`
+#[derive(Event)]
+pub struct OrderQueueCompletedEv {
+    pub unit: Entity,
+    pub destination: Vec3,
+    /// The flowfield, or the id of the `AStarPath`, that took the unit to the last goal
+    pub flowfield: Option<FlowFieldId>,
+}
+
+impl OrderQueueCompletedEv {
+    pub fn new(unit: Entity, destination: Vec3) -> Self {
+        Self {
+            unit,
+            destination,
+            flowfield: None,
+        }
+    }
+
+    pub fn with_flowfield(mut self, flowfield: FlowFieldId) -> Self {
+        self.flowfield = Some(flowfield);
+        self
+    }
+}
+
+/// Sent when the cell a unit stands in, or steers into next, became impassable after its
+/// flowfield was built, for example by construction completing underneath it. The unit is pushed
+/// out of a blocked cell, or held before one, until its flowfield is rebuilt.
+#[derive(Event)]
+pub struct RepathNeededEv {
+    pub unit: Entity,
+    pub position: Vec3,
+    /// The blocked cell of the grid, which may have expanded since the flowfield was built
+    pub cell: IVec2,
+    /// The flowfield that needs rebuilding
+    pub flowfield: Option<FlowFieldId>,
+}
+
+impl RepathNeededEv {
+    pub fn new(unit: Entity, position: Vec3, cell: IVec2) -> Self {
+        Self {
+            unit,
+            position,
+            cell,
+            flowfield: None,
+        }
+    }
+
+    pub fn with_flowfield(mut self, flowfield: FlowFieldId) -> Self {
+        self.flowfield = Some(flowfield);
+        self
+    }
+}
+
+/// Sent when the grid grew by `border` cells on every side, shifting every cell index by it
+#[derive(Event)]
+pub struct GridExpandedEv {
+    pub border: i32,
+}
+
+impl GridExpandedEv {
+    pub fn new(border: i32) -> Self {
+        Self { border }
+    }
+}
+
+/// Rebuilds the `Grid` resource for a new level, clearing its flowfields, cancelling the orders
+/// on it and blocking the obstacles of the new scene again. Sent when a new `MapBase` spawns or
+/// its mesh changes, or send it after swapping the terrain some other way.
+#[derive(Event, Clone, Default)]
+pub struct MapChangedEv {
+    /// The grid of the new level, `None` for a blank one of the same size
+    pub grid: Option<Grid>,
+}
+
+impl MapChangedEv {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replaces the `Grid` resource with `grid`
+    pub fn with_grid(grid: Grid) -> Self {
+        Self { grid: Some(grid) }
+    }
+}
+
+/// Sent instead of starting a move order when the destination is off the grid and
+/// `OutOfBoundsPolicy::Reject` is set
+#[derive(Event)]
+pub struct DestinationOutOfBoundsEv {
+    pub units: Vec<Entity>,
+    pub destination: Vec3,
+}
+
+impl DestinationOutOfBoundsEv {
+    pub fn new(units: Vec<Entity>, destination: Vec3) -> Self {
+        Self { units, destination }
+    }
+}
+
+/// Sent instead of starting a cursor move order when the cursor doesn't point at the map
+#[derive(Event)]
+pub struct CursorRayMissedEv {
+    pub units: Vec<Entity>,
+    pub error: RayCastError,
+}
+
+impl CursorRayMissedEv {
+    pub fn new(units: Vec<Entity>, error: RayCastError) -> Self {
+        Self { units, error }
+    }
+}
+
+/// Sent when pathfinding skips work it can't do
+#[derive(Event, Clone, Debug)]
+pub struct PathfindingErrorEv {
+    pub error: PathfindingError,
+    /// The units of the order that failed, if any
+    pub units: Vec<Entity>,
+}
+
+impl PathfindingErrorEv {
+    pub fn new(error: PathfindingError) -> Self {
+        Self {
+            error,
+            units: Vec::new(),
+        }
+    }
+
+    pub fn with_units(mut self, units: Vec<Entity>) -> Self {
+        self.units = units;
+        self
+    }
+}
+
+/// Sent when a `ToggleableObstacle` opened or closed, with the cells whose cost changed
+#[derive(Event)]
+pub struct ObstacleToggledEv {
+    pub entity: Entity,
+    /// The map entity the obstacle is on, `None` for the `Grid` resource
+    pub map: Option<Entity>,
+    pub open: bool,
+    pub cells: Vec<IVec2>,
+}
+
+impl ObstacleToggledEv {
+    pub fn new(entity: Entity, map: Option<Entity>, open: bool, cells: Vec<IVec2>) -> Self {
+        Self {
+            entity,
+            map,
+            open,
+            cells,
+        }
+    }
+}
+
+/// Sent once per frame and grid in which any of its costs changed, after
+/// `PathfindingSet::UpdateCosts`: one for the `Grid` resource and one for every map entity whose
+/// `Grid` changed, told apart by `map`. Grid expansions bump the version without it and send
+/// `GridExpandedEv` instead.
+#[derive(Event)]
+pub struct CostfieldChangedEv {
+    /// The map entity whose `Grid` changed, `None` for the `Grid` resource
+    pub map: Option<Entity>,
+    /// `Grid::version` after the change
+    pub version: u64,
+    pub cells: Vec<IVec2>,
+}
+
+impl CostfieldChangedEv {
+    pub fn new(map: Option<Entity>, version: u64, cells: Vec<IVec2>) -> Self {
+        Self {
+            map,
+            version,
+            cells,
+        }
+    }
+}
+
+/// Sets the cost of every cell whose center is within `radius` of `center` in the editor cost
+/// layer, for in-game map editors. `u8::MAX` paints impassable terrain.
+#[derive(Event)]
+pub struct PaintCostEv {
+    pub center: Vec3,
+    pub radius: f32,
+    pub cost: u8,
+    /// The map entity whose `Grid` to paint, `None` for the `Grid` resource
+    pub map: Option<Entity>,
+}
+
+impl PaintCostEv {
+    pub fn new(center: Vec3, radius: f32, cost: u8) -> Self {
+        Self {
+            center,
+            radius,
+            cost,
+            map: None,
+        }
+    }
+
+    /// Paints the `Grid` of the `map` entity instead of the `Grid` resource
+    pub fn on_map(mut self, map: Entity) -> Self {
+        self.map = Some(map);
+        self
+    }
+}
+
+/// Removes the editor paint from the cells whose center is in `region`, a world XZ rect with
+/// `Rect::min.y` being the minimum world z. `None` clears the whole grid.
+#[derive(Event)]
+pub struct ClearPaintEv {
+    pub region: Option<Rect>,
+    /// The map entity whose `Grid` to clear, `None` for the `Grid` resource
+    pub map: Option<Entity>,
+}
+
+impl ClearPaintEv {
+    pub fn new(region: Option<Rect>) -> Self {
+        Self { region, map: None }
+    }
+
+    /// Clears the `Grid` of the `map` entity instead of the `Grid` resource
+    pub fn on_map(mut self, map: Entity) -> Self {
+        self.map = Some(map);
+        self
     }
 }
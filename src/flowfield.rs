@@ -1,234 +1,2699 @@
+use crate::astar::{self, AStarPath, AStarSettings};
 use crate::components::*;
+use crate::connector::{find_route, ConnectorHop, GridConnector, GridId, GridRoute};
+use crate::error::PathfindingError;
 use crate::events::*;
-use crate::{cell::*, grid::Grid, grid_direction::GridDirection, utils};
+use crate::interior::{Door, InteriorField, InteriorGrid};
+use crate::layers::{self, GridLayers, LayerField};
+use crate::profiles::{self, CostProfile, CostProfiles, TerrainType, UnitClass};
+use crate::resources::PathfindingStats;
+use crate::scheduler::{FlowFieldRequest, FlowFieldScheduler};
+use crate::steering::Steering;
+use crate::{
+    cell::*,
+    grid::{
+        coords::{self, EdgePolicies},
+        costs,
+        edges::EdgeCosts,
+        Connectivity, Grid, Grids, OutOfBoundsPolicy,
+    },
+    grid_direction::GridDirection,
+    obstacles::ObstacleCells,
+    time, utils, PathfindingSchedule, PathfindingSet,
+};
 
-use bevy::{prelude::*, window::PrimaryWindow};
+use bevy::{ecs::system::SystemParam, prelude::*, utils::Instant, window::PrimaryWindow};
 use ops::FloatPow;
-use std::collections::VecDeque;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::sync::Arc;
 
 pub struct FlowfieldPlugin;
 
 impl Plugin for FlowfieldPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, update_flowfields)
-            .add_observer(initialize_flowfield);
+        let schedule = PathfindingSchedule::label(app);
+        app.register_type::<FlowField>()
+            .register_type::<ExcludedCell>()
+            .register_type::<Cell>()
+            .register_type::<GridDirection>()
+            .register_type::<IntegrationMethod>()
+            .init_resource::<IntegrationMethod>()
+            .register_type::<TerrainSpeed>()
+            .init_resource::<TerrainSpeed>()
+            .register_type::<LaneSettings>()
+            .init_resource::<LaneSettings>()
+            .register_type::<FlowFieldPool>()
+            .init_resource::<FlowFieldPool>()
+            .register_type::<FlowFieldId>()
+            .register_type::<FlowFieldIds>()
+            .init_resource::<FlowFieldIds>()
+            .add_event::<DestinationOutOfBoundsEv>()
+            .add_event::<FlowFieldReadyEv>()
+            .add_event::<CursorRayMissedEv>()
+            .add_systems(schedule, follow_targets.in_set(PathfindingSet::BuildFields))
+            .add_systems(schedule, update_flowfields.in_set(PathfindingSet::Steering))
+            .add_observer(initialize_flowfield)
+            .add_systems(
+                schedule,
+                build_queued_flowfields
+                    .run_if(time::pathfinding_running)
+                    .in_set(PathfindingSet::BuildFields),
+            )
+            .add_observer(queue_flowfield_at)
+            .add_observer(recycle_flowfield);
     }
 }
 
-#[derive(Component, Clone, Default, PartialEq)]
+/// How integration spreads costs from the destination. Both produce the same field.
+///
+/// On a 200x200 grid of uniform cost `Breadth` builds about three times as fast. The more cells
+/// cost between 1 and 9 the more it revisits, and from about half up to three quarters of them
+/// `Dijkstra` wins by around 1.5x. With every cell mixed the two are even. The
+/// `integration_bench` example reproduces these figures.
+#[derive(Resource, Reflect, Default, Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "config", derive(serde::Serialize, serde::Deserialize))]
+#[reflect(Resource)]
+pub enum IntegrationMethod {
+    /// A FIFO queue that revisits cells whenever a cheaper route reaches them
+    #[default]
+    Breadth,
+    /// A priority queue that settles every cell once, cheapest first
+    Dijkstra,
+}
+
+/// How terrain cost scales the speed `FlowField::sample` reports. Cells of `normal_cost` move at
+/// full speed, others in inverse proportion to their cost, so roads can be made faster than
+/// open ground by raising `normal_cost` above their cost.
+#[derive(Resource, Reflect, Clone, Copy, Debug, PartialEq)]
+#[reflect(Resource)]
+pub struct TerrainSpeed {
+    pub normal_cost: u8,
+    pub min_factor: f32,
+    pub max_factor: f32,
+}
+
+impl Default for TerrainSpeed {
+    fn default() -> Self {
+        TerrainSpeed {
+            normal_cost: 1,
+            min_factor: 0.25,
+            max_factor: 2.0,
+        }
+    }
+}
+
+impl TerrainSpeed {
+    /// The speed multiplier on a cell of `cost`
+    pub fn factor(&self, cost: u8) -> f32 {
+        let factor = self.normal_cost as f32 / cost.max(1) as f32;
+        return factor.clamp(self.min_factor, self.max_factor);
+    }
+}
+
+/// Keep-right lanes in corridors, so groups passing through one in opposite directions separate
+/// instead of deadlocking. Moving through a corridor costs `bias` more for every free cell to the
+/// right of the unit.
+#[derive(Resource, Reflect, Clone, Copy, Debug, Default, PartialEq)]
+#[reflect(Resource)]
+pub struct LaneSettings {
+    /// 0 disables lanes
+    pub bias: u8,
+    /// The widest passage, in cells, that counts as a corridor
+    pub corridor_width: i32,
+}
+
+impl LaneSettings {
+    /// The free cells in a row from `idx` towards `step`, up to `corridor_width`
+    fn free_run(
+        &self,
+        cells: &[Vec<Cell>],
+        (size, wrap): (IVec2, EdgePolicies),
+        idx: IVec2,
+        step: IVec2,
+    ) -> i32 {
+        let mut run = 0;
+        let mut next = wrap.wrap(idx + step, size);
+        while let Some(idx) =
+            next.filter(|idx| cells[idx.y as usize][idx.x as usize].cost != u8::MAX)
+        {
+            if run == self.corridor_width {
+                break;
+            }
+
+            run += 1;
+            next = wrap.wrap(idx + step, size);
+        }
+
+        return run;
+    }
+
+    /// Adds the lane costs of moving straight along corridors to `edges`
+    fn apply(
+        &self,
+        cells: &[Vec<Cell>],
+        (size, wrap): (IVec2, EdgePolicies),
+        edges: &mut EdgeCosts,
+    ) {
+        if self.bias == 0 {
+            return;
+        }
+
+        for cell in cells.iter().flatten().filter(|cell| cell.cost != u8::MAX) {
+            for direction in GridDirection::cardinal_directions() {
+                let step = direction.vector();
+                let Some(from) = wrap.wrap(cell.idx - step, size) else {
+                    continue;
+                };
+
+                let right = IVec2::new(-step.y, step.x);
+                let free_right = self.free_run(cells, (size, wrap), cell.idx, right);
+                let free_left = self.free_run(cells, (size, wrap), cell.idx, -right);
+
+                let in_corridor = free_right + free_left < self.corridor_width;
+                if in_corridor && free_right > 0 {
+                    let cost = (free_right as u32 * self.bias as u32).min(u8::MAX as u32) as u8;
+                    edges.add(from, cell.idx, cost);
+                }
+            }
+        }
+    }
+}
+
+/// Per-order costs, such as a group keeping clear of enemy artillery, without touching the
+/// shared grid. Returns the cost the order's flowfield uses for a passable cell in place of its
+/// grid cost. Impassable cells stay impassable.
+#[derive(Clone)]
+pub struct CostModifierFn(pub Arc<dyn Fn(&Cell) -> u8 + Send + Sync>);
+
+impl CostModifierFn {
+    pub fn new(modifier: impl Fn(&Cell) -> u8 + Send + Sync + 'static) -> Self {
+        Self(Arc::new(modifier))
+    }
+
+    /// The cost of `cell` for the flowfield
+    pub fn cost(&self, cell: &Cell) -> u8 {
+        match cell.cost {
+            u8::MAX => u8::MAX,
+            _ => (self.0)(cell),
+        }
+    }
+}
+
+impl fmt::Debug for CostModifierFn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("CostModifierFn")
+    }
+}
+
+/// Modifiers are equal if they're the same closure
+impl PartialEq for CostModifierFn {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// A flowfield's direction and terrain speed at a position, see `FlowField::sample`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FlowSample {
+    pub direction: Vec2,
+    pub speed_factor: f32,
+}
+
+/// A cell whose integration or flow differs between two fields, see `FlowField::diff`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CellDiff {
+    pub idx: IVec2,
+    /// The cell's best cost on the diffed field and on the other one
+    pub best_cost: (BestCost, BestCost),
+    /// The cell's direction on the diffed field and on the other one
+    pub best_direction: (GridDirection, GridDirection),
+}
+
+/// The cells two flowfields disagree on, in row order
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FieldDiff {
+    pub cells: Vec<CellDiff>,
+}
+
+impl FieldDiff {
+    pub fn contains(&self, idx: IVec2) -> bool {
+        self.cells.iter().any(|cell| cell.idx == idx)
+    }
+
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+}
+
+/// Identifies a flowfield across runs, so replays and lockstep games can correlate orders. Ids
+/// are handed out by `FlowFieldIds` in build order, the same orders get the same ids every run.
+/// Orders sent along A* paths get one too when they're given, see `AStarPath::id`.
+#[derive(Reflect, Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct FlowFieldId(pub u64);
+
+/// The counter of `FlowFieldId`s, starting at 1. Reset it along with the rest of the game state
+/// when a replay restarts.
+#[derive(Resource, Reflect, Debug, Default)]
+#[reflect(Resource)]
+pub struct FlowFieldIds {
+    last: u64,
+}
+
+impl FlowFieldIds {
+    /// Hands out the id of the next flowfield spawned, or A* order given
+    pub fn assign(&mut self) -> FlowFieldId {
+        self.last += 1;
+        FlowFieldId(self.last)
+    }
+
+    pub fn reset(&mut self) {
+        self.last = 0;
+    }
+}
+
+#[derive(Component, Clone, Default, PartialEq, Reflect)]
+#[reflect(Component)]
 pub struct FlowField {
+    /// 0 until the field is spawned
+    pub id: FlowFieldId,
     pub cell_radius: f32,
     pub cell_diameter: f32,
     pub cell_diameter_squared: f32,
     pub destination_cell: Cell,
+    /// The exact world position the field leads to, inside `destination_cell`
+    pub destination: Vec3,
     pub grid: Vec<Vec<Cell>>,
     pub size: IVec2,
     pub units: Vec<Entity>,
+    pub interiors: Vec<InteriorField>,
+    pub layers: Vec<LayerField>,
+    pub connectivity: Connectivity,
+    pub integration_method: IntegrationMethod,
+    /// The grid's edge costs the field was built with
+    pub edge_costs: EdgeCosts,
+    /// The grid's edge policies the field was built with
+    pub edge_policies: EdgePolicies,
+    /// The grid's rotation the field was built with, see `Grid::rotation`
+    pub rotation: Quat,
+    pub terrain_speed: TerrainSpeed,
+    pub lanes: LaneSettings,
+    /// The `Grid::version` the costs were copied from
+    pub costfield_version: u64,
+    /// The map entity whose `Grid` the field was built on, `None` for the `Grid` resource
+    pub map: Option<Entity>,
+    /// Cells blocked only by the field's own units, which the field paths through
+    pub exclusions: Vec<ExcludedCell>,
+    /// The order's own costs over the main grid, see `InitializeFlowFieldAtEv::with_cost_modifier`
+    #[reflect(ignore)]
+    pub cost_modifier: Option<CostModifierFn>,
+    /// The costs of the units' `UnitClass`, applied after the `cost_modifier`
+    #[reflect(ignore)]
+    pub cost_profile: Option<Arc<CostProfile>>,
+    /// Paths through parked units without their soft costs, see `parking::ParkingSettings`
+    pub through_parked: bool,
+}
+
+/// The cell buffers of despawned flowfields, handed to new ones so move orders reuse them
+/// instead of allocating a copy of the grid each. Rebuilds reuse the field's own buffer.
+#[derive(Resource, Reflect, Debug)]
+#[reflect(Resource)]
+pub struct FlowFieldPool {
+    /// Spare buffers kept at most, the rest are freed
+    pub capacity: usize,
+    buffers: Vec<Vec<Vec<Cell>>>,
+    reused: u64,
+    allocated: u64,
+}
+
+impl Default for FlowFieldPool {
+    fn default() -> Self {
+        Self {
+            capacity: 8,
+            buffers: Vec::new(),
+            reused: 0,
+            allocated: 0,
+        }
+    }
+}
+
+impl FlowFieldPool {
+    /// A spare buffer, or an empty one to be allocated when the field is built
+    pub fn take(&mut self) -> Vec<Vec<Cell>> {
+        match self.buffers.pop() {
+            Some(buffer) => {
+                self.reused += 1;
+                buffer
+            }
+            None => {
+                self.allocated += 1;
+                Vec::new()
+            }
+        }
+    }
+
+    /// Keeps `buffer` for a later field, unless the pool is full
+    pub fn put(&mut self, buffer: Vec<Vec<Cell>>) {
+        if buffer.capacity() > 0 && self.buffers.len() < self.capacity {
+            self.buffers.push(buffer);
+        }
+    }
+
+    /// Frees every spare buffer
+    pub fn clear(&mut self) {
+        self.buffers.clear();
+    }
+
+    /// The number of spare buffers
+    pub fn len(&self) -> usize {
+        self.buffers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buffers.is_empty()
+    }
+
+    /// How many fields got a spare buffer
+    pub fn reused(&self) -> u64 {
+        self.reused
+    }
+
+    /// How many fields found the pool empty and allocated their own
+    pub fn allocated(&self) -> u64 {
+        self.allocated
+    }
+}
+
+/// A cell whose cost comes from units of the flowfield itself, see `FlowField::exclude_units`
+#[derive(Clone, Copy, Debug, PartialEq, Reflect)]
+pub struct ExcludedCell {
+    pub idx: IVec2,
+    /// The cell's cost on the grid with the units
+    pub cost: u8,
+    /// The cell's cost without them
+    pub excluded_cost: u8,
 }
 
 impl FlowField {
     pub fn new(cell_radius: f32, grid_size: IVec2, units: Vec<Entity>) -> Self {
         FlowField {
+            id: FlowFieldId::default(),
             cell_radius,
             cell_diameter: cell_radius * 2.0,
             cell_diameter_squared: (cell_radius * 2.0).squared(),
             destination_cell: Cell::default(),
+            destination: Vec3::ZERO,
             grid: Vec::default(),
             size: grid_size,
             units,
+            interiors: Vec::new(),
+            layers: Vec::new(),
+            connectivity: Connectivity::default(),
+            integration_method: IntegrationMethod::default(),
+            edge_costs: EdgeCosts::default(),
+            edge_policies: EdgePolicies::default(),
+            rotation: Quat::IDENTITY,
+            terrain_speed: TerrainSpeed::default(),
+            lanes: LaneSettings::default(),
+            costfield_version: 0,
+            map: None,
+            exclusions: Vec::new(),
+            cost_modifier: None,
+            cost_profile: None,
+            through_parked: false,
+        }
+    }
+
+    /// Keeps the units at `positions` and the obstacles among them from blocking the field built
+    /// for them. Call before building the fields, later rebuilds keep the exclusions for cells
+    /// whose cost hasn't changed since.
+    pub fn exclude_units(
+        &mut self,
+        grid: &Grid,
+        obstacles: &ObstacleCells,
+        positions: &[(Entity, Vec3)],
+    ) {
+        let entities: Vec<Entity> = positions.iter().map(|(entity, _)| *entity).collect();
+        let mut skipped: HashMap<IVec2, Vec<&str>> = HashMap::new();
+
+        for (_, position) in positions.iter() {
+            let idx = grid.get_cell_from_world_position(*position).idx;
+            for layer in [costs::UNIT_LAYER, costs::PARKED_LAYER] {
+                if grid.layer_cost(layer, idx).is_some() {
+                    skipped.entry(idx).or_default().push(layer);
+                }
+            }
+        }
+        // Skips obstacle cells the grid no longer agrees on, such as right after it expanded
+        let blocked = obstacles.blocked_only_by(&entities).into_iter();
+        for idx in blocked.filter(|idx| grid.layer_cost(costs::OBSTACLE_LAYER, *idx).is_some()) {
+            skipped.entry(idx).or_default().push(costs::OBSTACLE_LAYER);
+        }
+
+        self.exclusions = skipped
+            .into_iter()
+            .filter_map(|(idx, layers)| {
+                Some(ExcludedCell {
+                    idx,
+                    cost: grid.cell(idx)?.cost,
+                    excluded_cost: grid.cost_without(idx, &layers)?,
+                })
+            })
+            .collect();
+    }
+
+    /// The cost of a cell of `grid` for this field
+    fn cost_of(&self, grid: &Grid, cell: &Cell) -> u8 {
+        let mut cell = *cell;
+        if self.through_parked {
+            cell.cost = grid
+                .cost_without(cell.idx, &[costs::PARKED_LAYER])
+                .unwrap_or(cell.cost);
+        }
+
+        let cost = match &self.cost_modifier {
+            Some(modifier) => modifier.cost(&cell),
+            None => cell.cost,
+        };
+
+        match &self.cost_profile {
+            Some(profile) => profile.cost(cost, profiles::terrain_at(grid, cell.idx)),
+            None => cost,
+        }
+    }
+
+    /// Takes the parked units out, then applies the cost modifier and cost profile to a fresh
+    /// copy of `grid`
+    fn apply_cost_modifier(&mut self, grid: &Grid) {
+        if self.through_parked {
+            for idx in grid.layer_cells(costs::PARKED_LAYER) {
+                let cell = &mut self.grid[idx.y as usize][idx.x as usize];
+                // Excluded cells already have a cost of their own
+                if cell.cost == grid.grid[idx.y as usize][idx.x as usize].cost {
+                    cell.cost = grid
+                        .cost_without(idx, &[costs::PARKED_LAYER])
+                        .unwrap_or(cell.cost);
+                }
+            }
+        }
+
+        if let Some(modifier) = self.cost_modifier.clone() {
+            for cell in self.grid.iter_mut().flatten() {
+                cell.cost = modifier.cost(cell);
+            }
+        }
+
+        if let Some(profile) = self.cost_profile.clone() {
+            let terrains = grid.layer::<TerrainType>();
+            for cell in self.grid.iter_mut().flatten() {
+                let terrain = terrains.and_then(|layer| layer.get(cell.idx));
+                cell.cost = profile.cost(cell.cost, terrain.copied().unwrap_or_default());
+            }
+        }
+    }
+
+    /// Opens the excluded cells of a fresh copy of the grid
+    fn apply_exclusions(&mut self) {
+        for excluded in self.exclusions.iter() {
+            let Some(cell) = self
+                .grid
+                .get_mut(excluded.idx.y as usize)
+                .and_then(|row| row.get_mut(excluded.idx.x as usize))
+            else {
+                continue;
+            };
+
+            if cell.cost == excluded.cost {
+                cell.cost = excluded.excluded_cost;
+            }
         }
     }
 
-    pub fn create_integration_field(&mut self, grid: ResMut<Grid>, destination_cell: Cell) {
+    pub fn create_integration_field(&mut self, grid: &Grid, destination_cell: Cell) {
+        self.create_integration_field_until(grid, destination_cell, &[]);
+    }
+
+    /// Like `create_integration_field`, but stops spreading costs once every cell in `sources`
+    /// has its final best_cost. Cells further from the destination than the sources may be left
+    /// unset, so the field only serves units starting at those cells. Empty `sources` floods the
+    /// whole grid.
+    pub fn create_integration_field_until(
+        &mut self,
+        grid: &Grid,
+        destination_cell: Cell,
+        sources: &[IVec2],
+    ) {
         // println!("Start Integration Field Create");
 
-        self.grid = grid.grid.clone();
+        self.grid.clone_from(&grid.grid);
+        self.edge_costs = grid.edge_costs().clone();
+        self.edge_policies = grid.edge_policies();
+        self.rotation = grid.rotation;
+        self.costfield_version = grid.version;
+        self.apply_exclusions();
+        self.apply_cost_modifier(grid);
+        let bounds = (self.size, self.edge_policies);
+        self.lanes.apply(&self.grid, bounds, &mut self.edge_costs);
 
         // Initialize the destination cell in the grid
         let dest_idx = destination_cell.idx;
         let dest_cell = &mut self.grid[dest_idx.y as usize][dest_idx.x as usize];
         dest_cell.cost = 0;
         dest_cell.best_cost = 0;
-        self.destination_cell = dest_cell.clone();
-
-        let mut cells_to_check: VecDeque<IVec2> = VecDeque::new();
-        cells_to_check.push_back(dest_idx);
+        self.destination_cell = *dest_cell;
+        self.destination = dest_cell.world_pos;
 
-        while let Some(cur_idx) = cells_to_check.pop_front() {
-            let cur_x = cur_idx.x as usize;
-            let cur_y = cur_idx.y as usize;
+        integrate_until(
+            &mut self.grid,
+            self.size,
+            vec![dest_idx],
+            (self.connectivity, &self.edge_costs, self.edge_policies),
+            self.integration_method,
+            sources,
+        );
 
-            let cur_cell_best_cost = self.grid[cur_y][cur_x].best_cost;
+        // println!("End Integration Field Create");
+    }
 
-            // Iterate over cardinal directions
-            for direction in GridDirection::cardinal_directions() {
-                let delta = direction.vector();
-                let neighbor_idx = cur_idx + delta;
+    pub fn create_flowfield(&mut self) {
+        derive_directions(
+            &mut self.grid,
+            self.size,
+            (self.connectivity, &self.edge_costs, self.edge_policies),
+        );
+    }
 
-                if neighbor_idx.x >= 0
-                    && neighbor_idx.x < self.size.x
-                    && neighbor_idx.y >= 0
-                    && neighbor_idx.y < self.size.y
-                {
-                    let neighbor_x = neighbor_idx.x as usize;
-                    let neighbor_y = neighbor_idx.y as usize;
+    /// Rebuilds every field towards the current destination on the field's own grid. Layers and
+    /// interiors only apply to fields on the `Grid` resource.
+    pub fn rebuild(
+        &mut self,
+        grids: &Grids,
+        layers: &GridLayers,
+        interiors: &[(Entity, &InteriorGrid)],
+    ) {
+        let Some(grid) = grids.get(self.map) else {
+            return;
+        };
 
-                    let neighbor_cell = &mut self.grid[neighbor_y][neighbor_x];
+        let destination = self.destination;
+        match self.map {
+            Some(_) => self.create_fields(grid, &GridLayers::default(), &[], destination),
+            None => self.create_fields(grid, layers, interiors, destination),
+        }
+    }
 
-                    if neighbor_cell.cost == u8::MAX {
-                        continue;
-                    }
+    /// True if any of `cells` has another cost on `grid` than the field was built with, or isn't
+    /// covered by the field since the grid expanded
+    pub fn costs_differ(&self, grid: &Grid, cells: &HashSet<IVec2>) -> bool {
+        return cells.iter().any(|idx| {
+            let built = self
+                .grid
+                .get(idx.y as usize)
+                .and_then(|row| row.get(idx.x as usize));
+            built.map(|cell| cell.cost) != grid.cell(*idx).map(|cell| self.cost_of(grid, cell))
+        });
+    }
 
-                    let tentative_best_cost = neighbor_cell.cost as u16 + cur_cell_best_cost;
-                    if tentative_best_cost < neighbor_cell.best_cost {
-                        neighbor_cell.best_cost = tentative_best_cost;
-                        cells_to_check.push_back(neighbor_idx);
-                    }
+    /// The main grid cells whose best cost or direction differ from `other`. Only the cells both
+    /// fields cover are compared, so fields from before and after a grid expansion can be diffed.
+    pub fn diff(&self, other: &FlowField) -> FieldDiff {
+        let mut diff = FieldDiff::default();
+        for (row, other_row) in self.grid.iter().zip(other.grid.iter()) {
+            for (cell, other_cell) in row.iter().zip(other_row.iter()) {
+                if cell.best_cost == other_cell.best_cost
+                    && cell.best_direction == other_cell.best_direction
+                {
+                    continue;
                 }
+
+                diff.cells.push(CellDiff {
+                    idx: cell.idx,
+                    best_cost: (cell.best_cost, other_cell.best_cost),
+                    best_direction: (cell.best_direction, other_cell.best_direction),
+                });
             }
         }
 
-        // println!("End Integration Field Create");
+        return diff;
     }
 
-    pub fn create_flowfield(&mut self) {
-        // println!("Start Flowfield Create");
-
-        let grid_size_y = self.size.y as usize;
-        let grid_size_x = self.size.x as usize;
-
-        for y in 0..grid_size_y {
-            for x in 0..grid_size_x {
-                let cell = &self.grid[y][x]; // Immutable borrow to get best_cost
-                let mut best_cost = cell.best_cost;
-                let mut best_direction = GridDirection::None;
-
-                // Get all possible directions
-                for direction in GridDirection::all_directions() {
-                    let delta = direction.vector();
-                    let nx = x as isize + delta.x as isize;
-                    let ny = y as isize + delta.y as isize;
-
-                    if nx >= 0 && nx < grid_size_x as isize && ny >= 0 && ny < grid_size_y as isize
-                    {
-                        let neighbor = &self.grid[ny as usize][nx as usize];
-                        if neighbor.best_cost < best_cost {
-                            best_cost = neighbor.best_cost;
-                            best_direction = direction;
-                        }
-                    }
-                }
+    /// True if the grid's costs changed since this field was built
+    pub fn is_stale(&self, grid: &Grid) -> bool {
+        self.costfield_version != grid.version
+    }
 
-                // Now, set the best_direction for the cell
-                self.grid[y][x].best_direction = best_direction;
-            }
+    /// Moves the destination of a main grid field to `destination_idx`, re-integrating over the
+    /// field's own costs instead of copying the grid again
+    pub fn retarget(&mut self, grid: &Grid, destination_idx: IVec2) {
+        let old_idx = self.destination_cell.idx;
+        self.grid[old_idx.y as usize][old_idx.x as usize].cost =
+            self.cost_of(grid, &grid.grid[old_idx.y as usize][old_idx.x as usize]);
+
+        for cell in self.grid.iter_mut().flatten() {
+            cell.best_cost = UNREACHABLE;
         }
-    }
 
-    pub fn get_cell_from_world_position(&self, world_pos: Vec3) -> Cell {
-        let cell = utils::get_cell_from_world_position_helper(
-            world_pos,
+        let dest_cell = &mut self.grid[destination_idx.y as usize][destination_idx.x as usize];
+        dest_cell.cost = 0;
+        dest_cell.best_cost = 0;
+        self.destination_cell = *dest_cell;
+        self.destination = dest_cell.world_pos;
+
+        integrate(
+            &mut self.grid,
+            self.size,
+            vec![destination_idx],
+            (self.connectivity, &self.edge_costs, self.edge_policies),
+            self.integration_method,
+        );
+        derive_directions(
+            &mut self.grid,
             self.size,
-            self.cell_diameter,
-            &self.grid,
+            (self.connectivity, &self.edge_costs, self.edge_policies),
         );
+    }
+
+    /// Repairs the field after the costs of `cells` of `grid` dropped, e.g. under a gate that
+    /// opened, instead of rebuilding it. The cells are integrated again from the reached cells
+    /// bordering them, and only cells whose best cost improved get new directions. Returns false,
+    /// leaving the field untouched, if it has to be rebuilt instead: when a cost rose, or the
+    /// field also covers layers or interiors or keeps lanes.
+    pub fn repair_opened(&mut self, grid: &Grid, cells: &HashSet<IVec2>) -> bool {
+        let plain = self.layers.is_empty() && self.interiors.is_empty() && self.lanes.bias == 0;
+        if !plain || grid.size != self.size {
+            return false;
+        }
+
+        let mut opened = Vec::new();
+        for idx in cells.iter() {
+            let Some(cell) = grid.cell(*idx) else {
+                return false;
+            };
 
-        return cell;
+            // The destination keeps its cost of 0
+            let built = self.grid[idx.y as usize][idx.x as usize].cost;
+            let cost = self.cost_of(grid, cell);
+            if *idx == self.destination_cell.idx || cost == built {
+                continue;
+            }
+
+            let excluded = self.exclusions.iter().any(|cell| cell.idx == *idx);
+            if excluded || cost > built {
+                return false;
+            }
+
+            opened.push((*idx, cost));
+        }
+
+        for (idx, cost) in opened.iter() {
+            let cell = &mut self.grid[idx.y as usize][idx.x as usize];
+            cell.cost = *cost;
+            cell.best_cost = UNREACHABLE;
+        }
+
+        // Re-seed the wavefront from the reached cells around the opened ones
+        let neighbors = (self.connectivity, &self.edge_costs, self.edge_policies);
+        let mut seeds = HashSet::new();
+        for (idx, _) in opened.iter() {
+            for neighbor_idx in self.neighbors_of(*idx) {
+                let neighbor = &self.grid[neighbor_idx.y as usize][neighbor_idx.x as usize];
+                if neighbor.best_cost != UNREACHABLE {
+                    seeds.insert(neighbor_idx);
+                }
+            }
+        }
+
+        let mut touched = spread_improvements(&mut self.grid, self.size, seeds, neighbors);
+        touched.extend(opened.iter().map(|(idx, _)| *idx));
+
+        // Cells next to an improved one may now point at it
+        let mut redirected = touched.clone();
+        for idx in touched.iter() {
+            redirected.extend(self.neighbors_of(*idx));
+        }
+
+        for idx in redirected {
+            self.grid[idx.y as usize][idx.x as usize].best_direction =
+                best_direction(&self.grid, self.size, idx, neighbors);
+        }
+
+        return true;
     }
 
-    pub fn remove_unit(&mut self, unit: Entity, cmds: &mut Commands) {
-        self.units.retain(|&u| u != unit);
-        cmds.entity(unit).remove::<Destination>();
+    /// The cells next to the cell at `idx` under the field's connectivity and edge policies
+    fn neighbors_of(&self, idx: IVec2) -> impl Iterator<Item = IVec2> + '_ {
+        self.connectivity
+            .flow_directions(idx)
+            .iter()
+            .filter(|direction| **direction != GridDirection::None)
+            .filter_map(move |direction| {
+                self.edge_policies.wrap(idx + direction.vector(), self.size)
+            })
     }
-}
 
-fn update_flowfields(
-    mut cmds: Commands,
-    mut q_flowfields: Query<(Entity, &mut FlowField)>,
-    q_transform: Query<&Transform>,
-) {
-    for (flowfield_entity, mut flowfield) in q_flowfields.iter_mut() {
-        let destination_pos = flowfield.destination_cell.world_pos;
-        let mut units_to_remove = Vec::new();
+    /// Builds the integration and flow fields across the main grid, every interior and every
+    /// extra layer. Costs spread over doors and layer links until nothing improves, so units can
+    /// path in and out of buildings and over bridges. The destination's layer is picked by height.
+    pub fn create_fields(
+        &mut self,
+        grid: &Grid,
+        layers: &GridLayers,
+        interiors: &[(Entity, &InteriorGrid)],
+        destination: Vec3,
+    ) {
+        let _span = info_span!("flowfield_build").entered();
+        let connectivity = self.connectivity;
+        let method = self.integration_method;
+        self.grid.clone_from(&grid.grid);
+        self.edge_costs = grid.edge_costs().clone();
+        self.edge_policies = grid.edge_policies();
+        self.rotation = grid.rotation;
+        self.costfield_version = grid.version;
+        self.apply_exclusions();
+        self.apply_cost_modifier(grid);
+        let bounds = (self.size, self.edge_policies);
+        self.lanes.apply(&self.grid, bounds, &mut self.edge_costs);
+        self.layers = layers.layers.iter().map(LayerField::new).collect();
+        self.interiors = interiors
+            .iter()
+            .map(|(building, interior)| InteriorField::new(*building, interior))
+            .collect();
+
+        // Edge costs only apply to the main grid
+        let edges = self.edge_costs.clone();
+        let no_edges = EdgeCosts::default();
+        let wrap = self.edge_policies;
+
+        // Seed the destination on whichever grid holds it
+        let goal_interior = self.interiors.iter().position(|i| i.contains(destination));
+        let (cells, size, cell_edges, cell_wrap) = match goal_interior {
+            Some(i) => {
+                let field = &mut self.interiors[i];
+                (
+                    &mut field.grid,
+                    field.size,
+                    &no_edges,
+                    EdgePolicies::default(),
+                )
+            }
+            None => match layers.layer_at(destination) {
+                0 => (&mut self.grid, self.size, &edges, wrap),
+                layer => (&mut self.layers[layer - 1].grid, self.size, &no_edges, wrap),
+            },
+        };
+
+        let dest_idx = match goal_interior {
+            Some(i) => interiors[i].1.get_cell_from_world_position(destination).idx,
+            None => grid.get_cell_from_world_position(destination).idx,
+        };
+        let dest_cell = &mut cells[dest_idx.y as usize][dest_idx.x as usize];
+        dest_cell.cost = 0;
+        dest_cell.best_cost = 0;
+        self.destination_cell = *dest_cell;
+        integrate(
+            cells,
+            size,
+            vec![dest_idx],
+            (connectivity, cell_edges, cell_wrap),
+            method,
+        );
+        self.set_destination(destination);
+
+        // Spread costs over doors and layer links until nothing improves
+        loop {
+            let mut improved = false;
+            let mut layer_seeds = vec![Vec::new(); self.layers.len() + 1];
 
-        // Identify units that need to be removed
-        for &unit_entity in flowfield.units.iter() {
-            if let Ok(transform) = q_transform.get(unit_entity) {
-                let unit_pos = transform.translation;
+            for (field, (_, interior)) in self.interiors.iter_mut().zip(interiors) {
+                let seeds = link_doors(&self.grid, &mut field.grid, &interior.doors, false);
+                improved |= !seeds.is_empty();
+                let neighbors = (connectivity, &no_edges, EdgePolicies::default());
+                integrate(&mut field.grid, field.size, seeds, neighbors, method);
 
-                // Use squared distance for efficiency
-                let distance_squared = (destination_pos - unit_pos).length_squared();
+                let seeds = link_doors(&field.grid, &mut self.grid, &interior.doors, true);
+                improved |= !seeds.is_empty();
+                layer_seeds[0].extend(seeds);
+            }
+
+            for link in layers.links.iter() {
+                for (from, to) in [(link.a, link.b), (link.b, link.a)] {
+                    let src =
+                        self.layer_cells(from.layer)[from.idx.y as usize][from.idx.x as usize];
+                    let dst =
+                        &mut self.layer_cells_mut(to.layer)[to.idx.y as usize][to.idx.x as usize];
 
-                if distance_squared < flowfield.cell_diameter_squared {
-                    units_to_remove.push(unit_entity);
+                    if let Some(cost) = linked_cost(&src, dst) {
+                        dst.best_cost = cost;
+                        layer_seeds[to.layer].push(to.idx);
+                        improved = true;
+                    }
                 }
             }
+
+            if !improved {
+                break;
+            }
+
+            for (layer, seeds) in layer_seeds.into_iter().enumerate() {
+                let size = self.size;
+                let layer_edges = match layer {
+                    0 => &edges,
+                    _ => &no_edges,
+                };
+                integrate(
+                    self.layer_cells_mut(layer),
+                    size,
+                    seeds,
+                    (connectivity, layer_edges, wrap),
+                    method,
+                );
+            }
+        }
+
+        derive_directions(&mut self.grid, self.size, (connectivity, &edges, wrap));
+        for layer in self.layers.iter_mut() {
+            derive_directions(&mut layer.grid, self.size, (connectivity, &no_edges, wrap));
+        }
+        for field in self.interiors.iter_mut() {
+            let neighbors = (connectivity, &no_edges, EdgePolicies::default());
+            derive_directions(&mut field.grid, field.size, neighbors);
         }
 
-        // Remove units from the flowfield
-        for unit in units_to_remove {
-            flowfield.remove_unit(unit, &mut cmds);
+        // Cells at a door or ramp whose cheapest way on is through the link point across it
+        for (field, (_, interior)) in self.interiors.iter_mut().zip(interiors) {
+            for door in interior.doors.iter() {
+                let (ext, int) = (door.exterior, door.interior);
+                let inside = field.grid[int.y as usize][int.x as usize];
+                point_across(&mut self.grid, ext, inside);
+                let outside = self.grid[ext.y as usize][ext.x as usize];
+                point_across(&mut field.grid, int, outside);
+            }
         }
 
-        if flowfield.units.len() == 0 {
-            cmds.entity(flowfield_entity).despawn_recursive();
+        for link in layers.links.iter() {
+            for (from, to) in [(link.a, link.b), (link.b, link.a)] {
+                let other = self.layer_cells(to.layer)[to.idx.y as usize][to.idx.x as usize];
+                point_across(self.layer_cells_mut(from.layer), from.idx, other);
+            }
         }
     }
-}
 
-fn initialize_flowfield(
-    trigger: Trigger<InitializeFlowFieldEv>,
-    mut cmds: Commands,
-    grid: ResMut<Grid>,
-    q_windows: Query<&Window, With<PrimaryWindow>>,
-    q_cam: Query<(&Camera, &GlobalTransform), With<GameCamera>>,
-    q_map_base: Query<&GlobalTransform, With<MapBase>>,
-    q_unit_info: Query<(&Transform, &UnitSize)>,
-    q_flowfields: Query<(Entity, &FlowField)>, // Query all existing flowfields
-) {
-    let Some(mouse_pos) = q_windows.single().cursor_position() else {
-        return;
-    };
+    /// Layer 0 is the main grid
+    fn layer_cells(&self, layer: usize) -> &Vec<Vec<Cell>> {
+        match layer {
+            0 => &self.grid,
+            _ => &self.layers[layer - 1].grid,
+        }
+    }
 
-    let Ok(cam) = q_cam.get_single() else {
-        return;
-    };
+    fn layer_cells_mut(&mut self, layer: usize) -> &mut Vec<Vec<Cell>> {
+        match layer {
+            0 => &mut self.grid,
+            _ => &mut self.layers[layer - 1].grid,
+        }
+    }
 
-    let Ok(map_base) = q_map_base.get_single() else {
-        return;
-    };
+    pub fn get_cell_from_world_position(&self, world_pos: Vec3) -> Cell {
+        // Units inside a building follow the interior part of the field
+        if let Some(interior) = self.interiors.iter().find(|i| i.contains(world_pos)) {
+            return interior.get_cell_from_world_position(world_pos);
+        }
 
-    let units = trigger.event().0.clone();
-    if units.is_empty() {
-        return;
+        let world_pos = self.wrap_world(world_pos);
+        let idx = coords::world_to_idx_clamped(world_pos, self.size, self.cell_diameter);
+        let cells = self.layer_cells(self.layer_at(world_pos));
+
+        // Fields not built yet have no cells, units can't go anywhere on them
+        let cell = cells
+            .get(idx.y as usize)
+            .and_then(|row| row.get(idx.x as usize));
+        return cell.copied().unwrap_or(Cell {
+            cost: u8::MAX,
+            ..Cell::new(world_pos, idx)
+        });
     }
 
-    // Remove existing flowfields that contain any of the units
-    for (flowfield_entity, flowfield) in q_flowfields.iter() {
-        if flowfield.units.iter().any(|unit| units.contains(unit)) {
-            cmds.entity(flowfield_entity).despawn_recursive();
-        }
+    /// Moves the exact destination to `world_pos`, clamped into `destination_cell`
+    fn set_destination(&mut self, world_pos: Vec3) {
+        let center = self.destination_cell.world_pos;
+        let offset = (world_pos - center).xz().clamp(
+            Vec2::splat(-self.cell_radius),
+            Vec2::splat(self.cell_radius),
+        );
+
+        self.destination = Vec3::new(center.x + offset.x, world_pos.y, center.z + offset.y);
     }
 
-    let mut unit_positions = Vec::new();
+    /// True if `world_pos` is inside the destination cell, where units seek `destination`
+    /// directly instead of following the field
+    pub fn in_destination_cell(&self, world_pos: Vec3) -> bool {
+        let offset = (world_pos - self.destination_cell.world_pos).xz().abs();
+        return offset.max_element() <= self.cell_radius
+            && self.layer_at(world_pos) == self.layer_at(self.destination);
+    }
 
-    // Gather unit positions and sizes
-    for &unit in &units {
-        if let Ok((transform, size)) = q_unit_info.get(unit) {
-            unit_positions.push((transform.translation, size.0));
+    /// Turns a direction sampled from the field into the world, see `Grid::rotation`
+    pub fn direction_to_world(&self, direction: Vec2) -> Vec2 {
+        return coords::direction_to_world(direction, self.rotation);
+    }
+
+    /// `world_pos` carried across the wrapping edges of the field's grid
+    pub fn wrap_world(&self, world_pos: Vec3) -> Vec3 {
+        self.edge_policies
+            .wrap_world(world_pos, self.size, self.cell_diameter)
+    }
+
+    /// Bilinearly interpolates the directions of the four cells surrounding `world_pos`,
+    /// skipping impassable ones. Returns a normalized XZ direction, or zero at the destination.
+    pub fn sample_direction_smooth(&self, world_pos: Vec3) -> Vec2 {
+        if let Some(interior) = self.interiors.iter().find(|i| i.contains(world_pos)) {
+            return sample_direction_smooth(
+                &interior.grid,
+                interior.size,
+                (interior.cell_diameter, EdgePolicies::default()),
+                world_pos - interior.origin,
+            );
+        }
+
+        let world_pos = self.wrap_world(world_pos);
+        sample_direction_smooth(
+            self.layer_cells(self.layer_at(world_pos)),
+            self.size,
+            (self.cell_diameter, self.edge_policies),
+            world_pos,
+        )
+    }
+
+    /// The smoothed direction at `world_pos` with the speed factor of the terrain there
+    pub fn sample(&self, world_pos: Vec3) -> FlowSample {
+        FlowSample {
+            direction: self.sample_direction_smooth(world_pos),
+            speed_factor: self.speed_factor(world_pos),
         }
     }
 
-    let world_mouse_pos = utils::get_world_pos(map_base, cam.1, cam.0, mouse_pos);
-    let destination_cell = grid.get_cell_from_world_position(world_mouse_pos);
+    /// How fast units move over the cell at `world_pos`, see `TerrainSpeed`
+    pub fn speed_factor(&self, world_pos: Vec3) -> f32 {
+        let cost = self.get_cell_from_world_position(world_pos).cost;
+        return self.terrain_speed.factor(cost);
+    }
+
+    /// The layer a unit at `world_pos` walks on, 0 being the main grid
+    pub fn layer_at(&self, world_pos: Vec3) -> usize {
+        let layers = self
+            .layers
+            .iter()
+            .map(|layer| (layer.height, layer.cell_radius, layer.grid.as_slice()));
+
+        layers::layer_at(layers, world_pos)
+    }
 
-    // Create a new flowfield
-    let mut flowfield = FlowField::new(grid.cell_radius, grid.size, units.clone());
-    flowfield.create_integration_field(grid, destination_cell);
-    flowfield.create_flowfield();
+    /// Follows best_direction across the main grid from `from` to the destination, returning
+    /// every visited cell. Returns an empty path if the destination can't be reached.
+    pub fn extract_path(&self, from: Vec3) -> Vec<Cell> {
+        let from = self.wrap_world(from);
+        let idx = coords::world_to_idx_clamped(from, self.size, self.cell_diameter);
+        let mut cell = self.grid[idx.y as usize][idx.x as usize];
+        if cell.best_cost == UNREACHABLE {
+            return Vec::new();
+        }
+
+        let mut path = vec![cell];
+        let max_steps = self.grid.iter().map(Vec::len).sum::<usize>();
 
-    // Spawn the new flowfield
-    cmds.spawn(flowfield.clone());
+        while cell.best_direction != GridDirection::None && path.len() <= max_steps {
+            let Some(next) = self
+                .edge_policies
+                .wrap(cell.idx + cell.best_direction.vector(), self.size)
+            else {
+                break;
+            };
+            cell = self.grid[next.y as usize][next.x as usize];
+            path.push(cell);
+        }
 
-    cmds.trigger(SetActiveFlowfieldEv(Some(flowfield)));
+        path
+    }
+
+    pub fn contains_unit(&self, unit: Entity) -> bool {
+        self.units.contains(&unit)
+    }
+
+    pub fn remove_unit(&mut self, unit: Entity, cmds: &mut Commands) {
+        self.units.retain(|&u| u != unit);
+        cmds.entity(unit).remove::<(Destination, Steering)>();
+    }
+}
+
+/// Moves units between flowfields, for orders issued while an earlier one is still in progress
+#[derive(SystemParam)]
+pub struct FlowFieldManager<'w, 's> {
+    cmds: Commands<'w, 's>,
+    q_flowfields: Query<'w, 's, (Entity, &'static mut FlowField)>,
+    q_following: Query<'w, 's, (), With<FollowTarget>>,
+}
+
+impl FlowFieldManager<'_, '_> {
+    /// The flowfield entity the unit follows
+    pub fn field_of(&self, unit: Entity) -> Option<Entity> {
+        return self
+            .q_flowfields
+            .iter()
+            .find(|(_, flowfield)| flowfield.contains_unit(unit))
+            .map(|(entity, _)| entity);
+    }
+
+    /// Takes the units out of their flowfields, despawning the ones left without units. The
+    /// units keep their `Destination`.
+    pub fn release(&mut self, units: &[Entity]) {
+        release_units(&mut self.cmds, self.q_flowfields.iter_mut(), units);
+    }
+
+    /// Moves the unit at `position` into a live flowfield on `map` leading to exactly
+    /// `destination`, if one reaches the unit's cell, instead of building a new one. Returns
+    /// false if there is none.
+    pub fn join(
+        &mut self,
+        unit: Entity,
+        position: Vec3,
+        destination: Vec3,
+        map: Option<Entity>,
+    ) -> bool {
+        let Some(joined) = self
+            .q_flowfields
+            .iter()
+            .find(|(entity, flowfield)| {
+                flowfield.map == map
+                    && flowfield.destination == destination
+                    && flowfield.cost_modifier.is_none()
+                    && flowfield.cost_profile.is_none()
+                    && !flowfield.through_parked
+                    && !flowfield.units.is_empty()
+                    && !self.q_following.contains(*entity)
+                    && flowfield.get_cell_from_world_position(position).best_cost != UNREACHABLE
+            })
+            .map(|(entity, _)| entity)
+        else {
+            return false;
+        };
+
+        if self.field_of(unit) != Some(joined) {
+            self.release(&[unit]);
+            if let Ok((_, mut flowfield)) = self.q_flowfields.get_mut(joined) {
+                flowfield.units.push(unit);
+            }
+        }
+
+        self.cmds.entity(unit).insert(Destination);
+        return true;
+    }
+
+    /// Moves the unit from its flowfield to a new one towards `new_goal`, on the same map
+    pub fn reassign(&mut self, unit: Entity, new_goal: Vec3) {
+        let map = self
+            .q_flowfields
+            .iter()
+            .find(|(_, flowfield)| flowfield.contains_unit(unit))
+            .and_then(|(_, flowfield)| flowfield.map);
+
+        self.release(&[unit]);
+
+        let ev = InitializeFlowFieldAtEv::new(vec![unit], new_goal);
+        self.cmds.trigger(match map {
+            Some(map) => ev.on_map(map),
+            None => ev,
+        });
+    }
+}
+
+fn release_units<'a>(
+    cmds: &mut Commands,
+    flowfields: impl Iterator<Item = (Entity, Mut<'a, FlowField>)>,
+    units: &[Entity],
+) {
+    for (flowfield_entity, mut flowfield) in flowfields {
+        if !flowfield.units.iter().any(|unit| units.contains(unit)) {
+            continue;
+        }
+
+        flowfield.units.retain(|unit| !units.contains(unit));
+        if flowfield.units.is_empty() {
+            cmds.entity(flowfield_entity).despawn_recursive();
+        }
+    }
+}
+
+/// Propagates best_cost outward from the seed cells, whose best_cost must already be set.
+/// Moving between neighbors costs the edge cost on top of the cell entered.
+pub(crate) fn integrate(
+    cells: &mut [Vec<Cell>],
+    size: IVec2,
+    seeds: Vec<IVec2>,
+    neighbors: (Connectivity, &EdgeCosts, EdgePolicies),
+    method: IntegrationMethod,
+) {
+    integrate_until(cells, size, seeds, neighbors, method, &[]);
+}
+
+/// Like `integrate`, but returns early once the best_cost of every source cell is final.
+/// Impassable sources, such as cells units stand in, wait for their walkable neighbors instead.
+pub(crate) fn integrate_until(
+    cells: &mut [Vec<Cell>],
+    size: IVec2,
+    seeds: Vec<IVec2>,
+    (connectivity, edges, wrap): (Connectivity, &EdgeCosts, EdgePolicies),
+    method: IntegrationMethod,
+    sources: &[IVec2],
+) {
+    let _span = info_span!("integration_field").entered();
+    let mut targets = HashSet::new();
+    for idx in sources.iter().filter(|idx| coords::in_bounds(**idx, size)) {
+        if cells[idx.y as usize][idx.x as usize].cost != u8::MAX {
+            targets.insert(*idx);
+            continue;
+        }
+
+        targets.extend(
+            connectivity
+                .flow_directions(*idx)
+                .iter()
+                .filter_map(|direction| wrap.wrap(*idx + direction.vector(), size))
+                .filter(|neighbor| cells[neighbor.y as usize][neighbor.x as usize].cost != u8::MAX),
+        );
+    }
+
+    match method {
+        IntegrationMethod::Breadth => {
+            integrate_breadth(cells, size, seeds, (connectivity, edges, wrap), targets)
+        }
+        IntegrationMethod::Dijkstra => {
+            integrate_dijkstra(cells, size, seeds, (connectivity, edges, wrap), targets)
+        }
+    }
+}
+
+fn integrate_breadth(
+    cells: &mut [Vec<Cell>],
+    size: IVec2,
+    seeds: Vec<IVec2>,
+    (connectivity, edges, wrap): (Connectivity, &EdgeCosts, EdgePolicies),
+    targets: HashSet<IVec2>,
+) {
+    let mut cells_to_check: VecDeque<IVec2> = VecDeque::from(seeds);
+    let mut pops_until_check = cells_to_check.len();
+
+    while let Some(cur_idx) = cells_to_check.pop_front() {
+        // Every later best_cost is at least the lowest one still queued, so targets at or below
+        // it are final. Checking once per pass over the queue keeps this cheap.
+        pops_until_check = pops_until_check.saturating_sub(1);
+        if !targets.is_empty() && pops_until_check == 0 {
+            let target_cost = targets
+                .iter()
+                .map(|idx| cells[idx.y as usize][idx.x as usize].best_cost)
+                .max()
+                .unwrap_or_default();
+            let queued_cost = cells_to_check
+                .iter()
+                .chain([&cur_idx])
+                .map(|idx| cells[idx.y as usize][idx.x as usize].best_cost)
+                .min()
+                .unwrap_or(UNREACHABLE);
+
+            if target_cost != UNREACHABLE && queued_cost >= target_cost {
+                return;
+            }
+            pops_until_check = cells_to_check.len() + 1;
+        }
+
+        let cur_x = cur_idx.x as usize;
+        let cur_y = cur_idx.y as usize;
+
+        let cur_cell_best_cost = cells[cur_y][cur_x].best_cost;
+
+        for direction in connectivity.integration_directions(cur_idx) {
+            if let Some(neighbor_idx) = wrap.wrap(cur_idx + direction.vector(), size) {
+                let neighbor_x = neighbor_idx.x as usize;
+                let neighbor_y = neighbor_idx.y as usize;
+
+                let neighbor_cell = &mut cells[neighbor_y][neighbor_x];
+
+                // Units move from the neighbor towards the destination
+                let edge_cost = edges.cost(neighbor_idx, cur_idx);
+                if neighbor_cell.cost == u8::MAX || edge_cost == u8::MAX {
+                    continue;
+                }
+
+                let tentative_best_cost =
+                    add_cost(add_cost(cur_cell_best_cost, neighbor_cell.cost), edge_cost);
+                if tentative_best_cost < neighbor_cell.best_cost {
+                    neighbor_cell.best_cost = tentative_best_cost;
+                    cells_to_check.push_back(neighbor_idx);
+                }
+            }
+        }
+    }
+}
+
+fn integrate_dijkstra(
+    cells: &mut [Vec<Cell>],
+    size: IVec2,
+    seeds: Vec<IVec2>,
+    (connectivity, edges, wrap): (Connectivity, &EdgeCosts, EdgePolicies),
+    mut targets: HashSet<IVec2>,
+) {
+    let early_exit = !targets.is_empty();
+    let mut open: BinaryHeap<Reverse<(BestCost, i32, i32)>> = seeds
+        .iter()
+        .map(|idx| {
+            Reverse((
+                cells[idx.y as usize][idx.x as usize].best_cost,
+                idx.x,
+                idx.y,
+            ))
+        })
+        .collect();
+
+    while let Some(Reverse((best_cost, x, y))) = open.pop() {
+        // Skip entries superseded by a cheaper route
+        if best_cost > cells[y as usize][x as usize].best_cost {
+            continue;
+        }
+
+        let cur_idx = IVec2::new(x, y);
+        targets.remove(&cur_idx);
+        if early_exit && targets.is_empty() {
+            return;
+        }
+
+        for direction in connectivity.integration_directions(cur_idx) {
+            let Some(neighbor_idx) = wrap.wrap(cur_idx + direction.vector(), size) else {
+                continue;
+            };
+
+            let neighbor_cell = &mut cells[neighbor_idx.y as usize][neighbor_idx.x as usize];
+            let edge_cost = edges.cost(neighbor_idx, cur_idx);
+            if neighbor_cell.cost == u8::MAX || edge_cost == u8::MAX {
+                continue;
+            }
+
+            let tentative_best_cost = add_cost(add_cost(best_cost, neighbor_cell.cost), edge_cost);
+            if tentative_best_cost < neighbor_cell.best_cost {
+                neighbor_cell.best_cost = tentative_best_cost;
+                open.push(Reverse((
+                    tentative_best_cost,
+                    neighbor_idx.x,
+                    neighbor_idx.y,
+                )));
+            }
+        }
+    }
+}
+
+/// Spreads cheaper best costs from `seeds` over cells that already hold one, cheapest first.
+/// Returns the cells whose best cost improved.
+fn spread_improvements(
+    cells: &mut [Vec<Cell>],
+    size: IVec2,
+    seeds: HashSet<IVec2>,
+    (connectivity, edges, wrap): (Connectivity, &EdgeCosts, EdgePolicies),
+) -> HashSet<IVec2> {
+    let mut improved = HashSet::new();
+    let mut open: BinaryHeap<Reverse<(BestCost, i32, i32)>> = seeds
+        .iter()
+        .map(|idx| {
+            Reverse((
+                cells[idx.y as usize][idx.x as usize].best_cost,
+                idx.x,
+                idx.y,
+            ))
+        })
+        .collect();
+
+    while let Some(Reverse((best_cost, x, y))) = open.pop() {
+        if best_cost > cells[y as usize][x as usize].best_cost {
+            continue;
+        }
+
+        let cur_idx = IVec2::new(x, y);
+        for direction in connectivity.integration_directions(cur_idx) {
+            let Some(neighbor_idx) = wrap.wrap(cur_idx + direction.vector(), size) else {
+                continue;
+            };
+
+            let neighbor_cell = &mut cells[neighbor_idx.y as usize][neighbor_idx.x as usize];
+            let edge_cost = edges.cost(neighbor_idx, cur_idx);
+            if neighbor_cell.cost == u8::MAX || edge_cost == u8::MAX {
+                continue;
+            }
+
+            let tentative_best_cost = add_cost(add_cost(best_cost, neighbor_cell.cost), edge_cost);
+            if tentative_best_cost < neighbor_cell.best_cost {
+                neighbor_cell.best_cost = tentative_best_cost;
+                improved.insert(neighbor_idx);
+                open.push(Reverse((
+                    tentative_best_cost,
+                    neighbor_idx.x,
+                    neighbor_idx.y,
+                )));
+            }
+        }
+    }
+
+    return improved;
+}
+
+/// Points every cell towards its cheapest neighbor, counting the edge cost of moving there
+pub(crate) fn derive_directions(
+    cells: &mut [Vec<Cell>],
+    size: IVec2,
+    neighbors: (Connectivity, &EdgeCosts, EdgePolicies),
+) {
+    let _span = info_span!("flow_directions").entered();
+
+    for y in 0..size.y {
+        for x in 0..size.x {
+            let idx = IVec2::new(x, y);
+            cells[y as usize][x as usize].best_direction =
+                best_direction(cells, size, idx, neighbors);
+        }
+    }
+}
+
+/// The direction from the cell at `idx` towards its cheapest neighbor, `GridDirection::None` if
+/// no neighbor is cheaper than the cell itself
+fn best_direction(
+    cells: &[Vec<Cell>],
+    size: IVec2,
+    idx: IVec2,
+    (connectivity, edges, wrap): (Connectivity, &EdgeCosts, EdgePolicies),
+) -> GridDirection {
+    let mut best_cost = cells[idx.y as usize][idx.x as usize].best_cost;
+    let mut best_direction = GridDirection::None;
+
+    for &direction in connectivity.flow_directions(idx) {
+        let Some(neighbor_idx) = wrap.wrap(idx + direction.vector(), size) else {
+            continue;
+        };
+
+        let neighbor = &cells[neighbor_idx.y as usize][neighbor_idx.x as usize];
+        let edge_cost = edges.cost(idx, neighbor.idx);
+        if edge_cost == u8::MAX {
+            continue;
+        }
+
+        let cost = neighbor.best_cost.saturating_add(edge_cost as BestCost);
+        if cost < best_cost {
+            best_cost = cost;
+            best_direction = direction;
+        }
+    }
+
+    return best_direction;
+}
+
+/// `local_pos` is relative to the grid's center
+pub(crate) fn sample_direction_smooth(
+    cells: &[Vec<Cell>],
+    size: IVec2,
+    (cell_diameter, wrap): (f32, EdgePolicies),
+    local_pos: Vec3,
+) -> Vec2 {
+    // Continuous cell coordinates, where integer values land on cell centers
+    let grid_pos = coords::to_grid_space(local_pos, size, cell_diameter) - 0.5;
+    let (fx, fy) = (grid_pos.x, grid_pos.y);
+
+    let x0 = fx.floor() as i32;
+    let y0 = fy.floor() as i32;
+    let tx = fx - x0 as f32;
+    let ty = fy - y0 as f32;
+
+    let corners = [
+        (IVec2::new(x0, y0), (1.0 - tx) * (1.0 - ty)),
+        (IVec2::new(x0 + 1, y0), tx * (1.0 - ty)),
+        (IVec2::new(x0, y0 + 1), (1.0 - tx) * ty),
+        (IVec2::new(x0 + 1, y0 + 1), tx * ty),
+    ];
+
+    let mut direction = Vec2::ZERO;
+    let mut total_weight = 0.0;
+
+    for (idx, weight) in corners {
+        let Some(idx) = wrap.wrap(idx, size) else {
+            continue;
+        };
+
+        let cell = &cells[idx.y as usize][idx.x as usize];
+        if cell.cost == u8::MAX {
+            continue;
+        }
+
+        direction += cell.best_direction.vector().as_vec2().normalize_or_zero() * weight;
+        total_weight += weight;
+    }
+
+    if total_weight <= 0.0 {
+        return Vec2::ZERO;
+    }
+
+    (direction / total_weight).normalize_or_zero()
+}
+
+/// Carries best_cost from one side of each door to the other, returning the cells that improved.
+/// `from_interior` is true when `from` is the interior grid and `to` the main grid.
+fn link_doors(
+    from: &[Vec<Cell>],
+    to: &mut [Vec<Cell>],
+    doors: &[Door],
+    from_interior: bool,
+) -> Vec<IVec2> {
+    let mut seeds = Vec::new();
+
+    for door in doors {
+        let (src, dst) = match from_interior {
+            true => (door.interior, door.exterior),
+            false => (door.exterior, door.interior),
+        };
+
+        let src_cell = from[src.y as usize][src.x as usize];
+        let dst_cell = &mut to[dst.y as usize][dst.x as usize];
+
+        if let Some(cost) = linked_cost(&src_cell, dst_cell) {
+            dst_cell.best_cost = cost;
+            seeds.push(dst);
+        }
+    }
+
+    seeds
+}
+
+/// The best_cost `dst` would get by stepping over a link from `src`, if that improves it
+fn linked_cost(src: &Cell, dst: &Cell) -> Option<BestCost> {
+    if src.best_cost == UNREACHABLE || dst.cost == u8::MAX {
+        return None;
+    }
+
+    let cost = add_cost(src.best_cost, dst.cost);
+    (cost < dst.best_cost).then_some(cost)
+}
+
+/// Linked cells have no cheaper neighbor on their own grid when the way on is through the link,
+/// so point them at their linked cell instead
+fn point_across(cells: &mut [Vec<Cell>], idx: IVec2, other: Cell) {
+    let cell = &mut cells[idx.y as usize][idx.x as usize];
+
+    if cell.best_direction != GridDirection::None || other.best_cost >= cell.best_cost {
+        return;
+    }
+
+    let delta = other.world_pos - cell.world_pos;
+    let sign = |v: f32| (v > 0.0) as i32 - (v < 0.0) as i32;
+    let step = IVec2::new(sign(delta.x), sign(delta.z));
+    cell.best_direction = GridDirection::from_vector2(step).unwrap_or_default();
+}
+
+/// Hands the cells of despawned flowfields to the `FlowFieldPool`
+fn recycle_flowfield(
+    trigger: Trigger<OnRemove, FlowField>,
+    mut pool: ResMut<FlowFieldPool>,
+    mut q_flowfields: Query<&mut FlowField>,
+) {
+    if let Ok(mut flowfield) = q_flowfields.get_mut(trigger.entity()) {
+        pool.put(std::mem::take(&mut flowfield.grid));
+    }
+}
+
+/// Despawns flowfields once every unit has arrived, see `steering::ArrivalMode`
+fn update_flowfields(mut cmds: Commands, q_flowfields: Query<(Entity, &FlowField)>) {
+    for (flowfield_entity, flowfield) in q_flowfields.iter() {
+        if flowfield.units.is_empty() {
+            cmds.entity(flowfield_entity).despawn_recursive();
+        }
+    }
+}
+
+/// Moves the destination of pursuing flowfields once their target enters another cell
+fn follow_targets(
+    mut cmds: Commands,
+    grids: Grids,
+    layers: Res<GridLayers>,
+    mut stats: ResMut<PathfindingStats>,
+    mut q_flowfields: Query<(Entity, &mut FlowField, &FollowTarget)>,
+    q_transform: Query<&Transform>,
+    q_interiors: Query<(Entity, &InteriorGrid)>,
+) {
+    let interiors: Vec<(Entity, &InteriorGrid)> = q_interiors.iter().collect();
+
+    for (flowfield_entity, mut flowfield, follow) in q_flowfields.iter_mut() {
+        // Keep heading to the last known position once the target is gone
+        let Ok(target) = q_transform.get(follow.0) else {
+            cmds.entity(flowfield_entity).remove::<FollowTarget>();
+            continue;
+        };
+
+        let cell = flowfield.get_cell_from_world_position(target.translation);
+        if cell.world_pos == flowfield.destination_cell.world_pos {
+            continue;
+        }
+
+        let Some(grid) = grids.get(flowfield.map) else {
+            continue;
+        };
+
+        let start = Instant::now();
+        if flowfield.interiors.is_empty() && flowfield.layers.is_empty() {
+            flowfield.retarget(grid, cell.idx);
+            flowfield.set_destination(target.translation);
+        } else {
+            flowfield.create_fields(grid, &layers, &interiors, target.translation);
+        }
+        stats.record_integration(start.elapsed());
+    }
+}
+
+fn initialize_flowfield(
+    trigger: Trigger<InitializeFlowFieldEv>,
+    mut cmds: Commands,
+    mut missed: EventWriter<CursorRayMissedEv>,
+    mut errors: EventWriter<PathfindingErrorEv>,
+    q_windows: Query<&Window, With<PrimaryWindow>>,
+    q_cam: Query<(&Camera, &GlobalTransform), With<GameCamera>>,
+    q_map_base: Query<&GlobalTransform, With<MapBase>>,
+    q_on_grid: Query<&OnGrid>,
+) {
+    let units = trigger.event().0.clone();
+    if units.is_empty() {
+        return;
+    }
+
+    let Ok(window) = q_windows.get_single() else {
+        errors.send(PathfindingErrorEv::new(PathfindingError::NoWindow).with_units(units));
+        return;
+    };
+
+    // A cursor outside the window is no error, the order just has nowhere to go
+    let Some(mouse_pos) = window.cursor_position() else {
+        return;
+    };
+
+    let Ok(cam) = q_cam.get_single() else {
+        errors.send(PathfindingErrorEv::new(PathfindingError::NoCamera).with_units(units));
+        return;
+    };
+
+    let Ok(map_base) = q_map_base.get_single() else {
+        errors.send(PathfindingErrorEv::new(PathfindingError::NoMapBase).with_units(units));
+        return;
+    };
+
+    // Orders go to the map of the first unit
+    let map = q_on_grid.get(units[0]).ok().map(|on_grid| on_grid.0);
+
+    match utils::get_world_pos(map_base, cam.1, cam.0, mouse_pos) {
+        Ok(world_mouse_pos) => {
+            let ev = InitializeFlowFieldAtEv::new(units, world_mouse_pos);
+            cmds.trigger(match map {
+                Some(map) => ev.on_map(map),
+                None => ev,
+            });
+        }
+        Err(error) => {
+            missed.send(CursorRayMissedEv::new(units, error));
+        }
+    }
+}
+
+/// Queues the order in the `FlowFieldScheduler`, or sends orders of at most
+/// `AStarSettings::max_units` units along A* paths
+fn queue_flowfield_at(
+    trigger: Trigger<InitializeFlowFieldAtEv>,
+    mut cmds: Commands,
+    grid: Res<Grid>,
+    mut scheduler: ResMut<FlowFieldScheduler>,
+    (astar_settings, connectivity, layers): (
+        Res<AStarSettings>,
+        Res<Connectivity>,
+        Res<GridLayers>,
+    ),
+    profiles: Res<CostProfiles>,
+    mut ids: ResMut<FlowFieldIds>,
+    q_maps: Query<&Grid>,
+    q_transform: Query<&Transform>,
+    q_classes: Query<&UnitClass>,
+    mut q_flowfields: Query<(Entity, &mut FlowField)>,
+    q_portals: Query<(), Or<(With<InteriorGrid>, With<GridConnector>)>>,
+) {
+    let ev = trigger.event();
+    if ev.units.is_empty() {
+        return;
+    }
+
+    // Units of each class get a flowfield over the costs of their class
+    let mut classes: Vec<(Option<Arc<CostProfile>>, Vec<Entity>)> = Vec::new();
+    for unit in ev.units.iter() {
+        let class = q_classes.get(*unit).ok();
+        let profile = class.and_then(|class| profiles.get(&class.0));
+        match classes.iter_mut().find(|(other, _)| *other == profile) {
+            Some((_, units)) => units.push(*unit),
+            None => classes.push((profile, vec![*unit])),
+        }
+    }
+
+    let goal_grid = match ev.map {
+        Some(map) => q_maps.get(map).ok(),
+        None => Some(grid.as_ref()),
+    };
+
+    // Only flowfields route through interiors, layers and connectors, or follow targets
+    let plain = ev.map.is_some() || (layers.layers.is_empty() && q_portals.is_empty());
+    let small = ev.units.len() <= astar_settings.max_units;
+    let unprofiled = classes.iter().all(|(profile, _)| profile.is_none());
+    if let Some(goal_grid) = goal_grid
+        .filter(|_| plain && small && unprofiled && ev.target.is_none())
+        .filter(|_| ev.cost_modifier.is_none() && !ev.through_parked)
+    {
+        let positions: Vec<(Entity, Vec3)> = ev
+            .units
+            .iter()
+            .filter_map(|unit| Some((*unit, q_transform.get(*unit).ok()?.translation)))
+            .collect();
+
+        let in_bounds = goal_grid.contains_world_pos(ev.destination);
+        if let Some(paths) = astar::plan_order(goal_grid, &positions, ev.destination, *connectivity)
+            .filter(|_| in_bounds)
+        {
+            scheduler.cancel(&ev.units);
+            release_units(&mut cmds, q_flowfields.iter_mut(), &ev.units);
+            let id = ids.assign();
+            for (unit, waypoints) in paths {
+                let path = AStarPath::new(waypoints).with_id(id);
+                cmds.entity(unit).insert(match ev.map {
+                    Some(map) => path.on_map(map),
+                    None => path,
+                });
+            }
+            return;
+        }
+    }
+    let goal_cell = goal_grid.map_or(IVec2::ZERO, |goal_grid| {
+        coords::world_to_idx_unclamped(ev.destination, goal_grid.size, goal_grid.cell_diameter)
+    });
+
+    for (cost_profile, units) in classes {
+        scheduler.push(FlowFieldRequest {
+            units,
+            destination: ev.destination,
+            target: ev.target,
+            map: ev.map,
+            priority: ev.priority,
+            goal_cell,
+            cost_modifier: ev.cost_modifier.clone(),
+            cost_profile,
+            through_parked: ev.through_parked,
+        });
+    }
+}
+
+/// Builds the queued flowfields, within the budget of the `FlowFieldScheduler`
+fn build_queued_flowfields(
+    mut cmds: Commands,
+    mut scheduler: ResMut<FlowFieldScheduler>,
+    mut grid: ResMut<Grid>,
+    mut layers: ResMut<GridLayers>,
+    mut stats: ResMut<PathfindingStats>,
+    (mut pool, mut ids): (ResMut<FlowFieldPool>, ResMut<FlowFieldIds>),
+    (policy, connectivity, method, terrain_speed, lanes): (
+        Res<OutOfBoundsPolicy>,
+        Res<Connectivity>,
+        Res<IntegrationMethod>,
+        Res<TerrainSpeed>,
+        Res<LaneSettings>,
+    ),
+    (mut out_of_bounds, mut expanded, mut errors, mut ready): (
+        EventWriter<DestinationOutOfBoundsEv>,
+        EventWriter<GridExpandedEv>,
+        EventWriter<PathfindingErrorEv>,
+        EventWriter<FlowFieldReadyEv>,
+    ),
+    obstacles: Res<ObstacleCells>,
+    q_transform: Query<&Transform>,
+    mut q_flowfields: Query<(Entity, &mut FlowField)>,
+    q_interiors: Query<(Entity, &InteriorGrid)>,
+    q_connectors: Query<(Entity, &GridConnector)>,
+    q_maps: Query<(&Grid, &ObstacleCells)>,
+) {
+    let frame_start = Instant::now();
+    let mut builds = 0;
+
+    while builds == 0
+        || (builds < scheduler.max_builds && frame_start.elapsed() < scheduler.time_budget)
+    {
+        let Some(request) = scheduler.pop() else {
+            break;
+        };
+
+        build_flowfield(
+            request,
+            &mut cmds,
+            &mut grid,
+            &mut layers,
+            &mut stats,
+            (&mut pool, &mut ids),
+            (&policy, &connectivity, &method, &terrain_speed, &lanes),
+            &mut out_of_bounds,
+            &mut expanded,
+            &mut errors,
+            &mut ready,
+            &obstacles,
+            &q_transform,
+            &mut q_flowfields,
+            &q_interiors,
+            &q_connectors,
+            &q_maps,
+        );
+        builds += 1;
+    }
+}
+
+/// Spawns the flowfields of a move order, one per leg when units first head to a connector
+fn build_flowfield(
+    request: FlowFieldRequest,
+    cmds: &mut Commands,
+    grid: &mut Grid,
+    layers: &mut GridLayers,
+    stats: &mut PathfindingStats,
+    (pool, ids): (&mut FlowFieldPool, &mut FlowFieldIds),
+    (policy, connectivity, method, terrain_speed, lanes): (
+        &OutOfBoundsPolicy,
+        &Connectivity,
+        &IntegrationMethod,
+        &TerrainSpeed,
+        &LaneSettings,
+    ),
+    out_of_bounds: &mut EventWriter<DestinationOutOfBoundsEv>,
+    expanded: &mut EventWriter<GridExpandedEv>,
+    errors: &mut EventWriter<PathfindingErrorEv>,
+    ready: &mut EventWriter<FlowFieldReadyEv>,
+    obstacles: &ObstacleCells,
+    q_transform: &Query<&Transform>,
+    q_flowfields: &mut Query<(Entity, &mut FlowField)>,
+    q_interiors: &Query<(Entity, &InteriorGrid)>,
+    q_connectors: &Query<(Entity, &GridConnector)>,
+    q_maps: &Query<(&Grid, &ObstacleCells)>,
+) {
+    let FlowFieldRequest {
+        units,
+        destination,
+        target,
+        map,
+        cost_modifier,
+        cost_profile,
+        through_parked,
+        ..
+    } = request;
+
+    // Units may have despawned while the request was queued
+    let units: Vec<Entity> = units
+        .into_iter()
+        .filter(|unit| q_transform.contains(*unit))
+        .collect();
+    let destination = match target {
+        Some(target) => match q_transform.get(target) {
+            Ok(transform) => transform.translation,
+            Err(_) => return,
+        },
+        None => destination,
+    };
+    if units.is_empty() {
+        return;
+    }
+
+    // The units leave their current flowfields or paths, the rest of those groups carry on
+    release_units(cmds, q_flowfields.iter_mut(), &units);
+    for unit in units.iter() {
+        cmds.entity(*unit).remove::<AStarPath>();
+    }
+
+    let positions = |units: &[Entity]| -> Vec<(Entity, Vec3)> {
+        units
+            .iter()
+            .filter_map(|unit| Some((*unit, q_transform.get(*unit).ok()?.translation)))
+            .collect()
+    };
+
+    // Map grids have no interiors, layers or connectors to route through
+    if let Some(map) = map {
+        let Ok((map_grid, map_obstacles)) = q_maps.get(map) else {
+            errors.send(
+                PathfindingErrorEv::new(PathfindingError::MissingGrid(map)).with_units(units),
+            );
+            return;
+        };
+
+        let policy = policy.on_map_grid();
+        if !map_grid.contains_world_pos(destination) && policy == OutOfBoundsPolicy::Reject {
+            out_of_bounds.send(DestinationOutOfBoundsEv::new(units, destination));
+            return;
+        }
+
+        let start = Instant::now();
+        let unit_positions = positions(&units);
+        let mut flowfield = FlowField::new(map_grid.cell_radius, map_grid.size, units);
+        flowfield.id = ids.assign();
+        flowfield.connectivity = *connectivity;
+        flowfield.integration_method = *method;
+        flowfield.terrain_speed = *terrain_speed;
+        flowfield.lanes = *lanes;
+        flowfield.map = Some(map);
+        flowfield.cost_modifier = cost_modifier;
+        flowfield.cost_profile = cost_profile;
+        flowfield.through_parked = through_parked;
+        flowfield.grid = pool.take();
+        flowfield.exclude_units(map_grid, map_obstacles, &unit_positions);
+        flowfield.create_fields(map_grid, &GridLayers::default(), &[], destination);
+        stats.record_integration(start.elapsed());
+
+        cmds.trigger(SetActiveFlowfieldEv(Some(flowfield.clone())));
+        let (id, units, destination) =
+            (flowfield.id, flowfield.units.clone(), flowfield.destination);
+        let mut flowfield_entity = cmds.spawn(flowfield);
+        if let Some(target) = target {
+            flowfield_entity.insert(FollowTarget(target));
+        }
+        let entity = flowfield_entity.id();
+        ready.send(FlowFieldReadyEv::new(id, entity, units, destination));
+        return;
+    }
+
+    let interiors: Vec<(Entity, &InteriorGrid)> = q_interiors.iter().collect();
+    let connectors: Vec<(Entity, &GridConnector)> = q_connectors.iter().collect();
+    let goal_grid = GridId::at(destination, &interiors);
+
+    if goal_grid == GridId::Main && !grid.contains_world_pos(destination) {
+        match *policy {
+            OutOfBoundsPolicy::Clamp => (),
+            OutOfBoundsPolicy::Reject => {
+                out_of_bounds.send(DestinationOutOfBoundsEv::new(units, destination));
+                return;
+            }
+            OutOfBoundsPolicy::Expand => {
+                let border = grid.border_to_contain(destination);
+                grid.expand(border, 1);
+                layers.expand(border);
+                expanded.send(GridExpandedEv::new(border));
+            }
+        }
+    }
+
+    // Units on a grid that doesn't reach the destination through doors first head to a connector
+    let mut direct_units = Vec::new();
+    let mut hop_groups: Vec<(ConnectorHop, Vec<Entity>)> = Vec::new();
+
+    for &unit in &units {
+        let first_hop = q_transform.get(unit).ok().and_then(|transform| {
+            let start = GridId::at(transform.translation, &interiors);
+            find_route(start, goal_grid, &interiors, &connectors)?
+                .first()
+                .copied()
+        });
+
+        let Some(hop) = first_hop else {
+            cmds.entity(unit).remove::<GridRoute>();
+            direct_units.push(unit);
+            continue;
+        };
+
+        cmds.entity(unit).insert(GridRoute {
+            destination,
+            next_hop: hop,
+            target,
+        });
+
+        match hop_groups.iter_mut().find(|(h, _)| *h == hop) {
+            Some((_, group)) => group.push(unit),
+            None => hop_groups.push((hop, vec![unit])),
+        }
+    }
+
+    // Only the leg that ends at the destination follows the target
+    let mut legs = Vec::new();
+    if !direct_units.is_empty() {
+        legs.push((direct_units, destination, target));
+    }
+    legs.extend(
+        hop_groups
+            .into_iter()
+            .map(|(hop, group)| (group, hop.from, None)),
+    );
+
+    let start = Instant::now();
+    let mut active = None;
+    for (leg_units, goal, follow) in legs {
+        // Create a new flowfield
+        let unit_positions = positions(&leg_units);
+        let mut flowfield = FlowField::new(grid.cell_radius, grid.size, leg_units);
+        flowfield.id = ids.assign();
+        flowfield.connectivity = *connectivity;
+        flowfield.integration_method = *method;
+        flowfield.terrain_speed = *terrain_speed;
+        flowfield.lanes = *lanes;
+        flowfield.cost_modifier = cost_modifier.clone();
+        flowfield.cost_profile = cost_profile.clone();
+        flowfield.through_parked = through_parked;
+        flowfield.grid = pool.take();
+        flowfield.exclude_units(grid, obstacles, &unit_positions);
+        flowfield.create_fields(grid, layers, &interiors, goal);
+
+        if active.is_none() {
+            active = Some(flowfield.clone());
+        }
+
+        // Spawn the new flowfield
+        let (id, units, destination) =
+            (flowfield.id, flowfield.units.clone(), flowfield.destination);
+        let mut flowfield_entity = cmds.spawn(flowfield);
+        if let Some(target) = follow {
+            flowfield_entity.insert(FollowTarget(target));
+        }
+        let entity = flowfield_entity.id();
+        ready.send(FlowFieldReadyEv::new(id, entity, units, destination));
+    }
+    stats.record_integration(start.elapsed());
+
+    if let Some(flowfield) = active {
+        cmds.trigger(SetActiveFlowfieldEv(Some(flowfield)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::coords::EdgePolicy;
+    use crate::layers::LayerCell;
+
+    /// Builds a grid from rows of `.` (cost 1), `#` (impassable) and digits (that cost),
+    /// returning it along with the position of the `D` destination cell
+    fn grid_from_map(rows: &[&str]) -> (Grid, IVec2) {
+        let size = IVec2::new(rows[0].len() as i32, rows.len() as i32);
+        let mut grid = Grid::new(size, 1.0, |_| false);
+        let mut destination = IVec2::ZERO;
+
+        for (y, row) in rows.iter().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                let cell = &mut grid.grid[y][x];
+                match c {
+                    '.' => cell.cost = 1,
+                    '#' => cell.cost = u8::MAX,
+                    'D' => destination = IVec2::new(x as i32, y as i32),
+                    _ => cell.cost = c.to_digit(10).unwrap() as u8,
+                }
+            }
+        }
+
+        (grid, destination)
+    }
+
+    fn build(rows: &[&str]) -> FlowField {
+        let (grid, destination) = grid_from_map(rows);
+        let mut flowfield = FlowField::new(grid.cell_radius, grid.size, Vec::new());
+        let destination_cell = grid.grid[destination.y as usize][destination.x as usize];
+        flowfield.create_integration_field(&grid, destination_cell);
+        flowfield.create_flowfield();
+        flowfield
+    }
+
+    fn best_costs(flowfield: &FlowField) -> Vec<Vec<BestCost>> {
+        flowfield
+            .grid
+            .iter()
+            .map(|row| row.iter().map(|cell| cell.best_cost).collect())
+            .collect()
+    }
+
+    fn direction_at(flowfield: &FlowField, x: usize, y: usize) -> GridDirection {
+        flowfield.grid[y][x].best_direction
+    }
+
+    fn build_with(rows: &[&str], connectivity: Connectivity) -> FlowField {
+        let (grid, destination) = grid_from_map(rows);
+        let mut flowfield = FlowField::new(grid.cell_radius, grid.size, Vec::new());
+        flowfield.connectivity = connectivity;
+        let destination_cell = grid.grid[destination.y as usize][destination.x as usize];
+        flowfield.create_integration_field(&grid, destination_cell);
+        flowfield.create_flowfield();
+        flowfield
+    }
+
+    #[test]
+    fn expensive_long_routes_saturate_instead_of_overflowing() {
+        let row = format!("D{}", ".".repeat(299));
+        let (mut grid, destination) = grid_from_map(&[row.as_str()]);
+        for cell in grid.grid[0].iter_mut().skip(1) {
+            cell.cost = u8::MAX - 1;
+        }
+
+        for method in [IntegrationMethod::Breadth, IntegrationMethod::Dijkstra] {
+            let mut flowfield = FlowField::new(grid.cell_radius, grid.size, Vec::new());
+            flowfield.integration_method = method;
+            let destination_cell = grid.grid[destination.y as usize][destination.x as usize];
+            flowfield.create_integration_field(&grid, destination_cell);
+            flowfield.create_flowfield();
+
+            for (x, cell) in flowfield.grid[0].iter().enumerate() {
+                let exact = x as u64 * (u8::MAX - 1) as u64;
+                let expected = exact.min(MAX_BEST_COST as u64) as BestCost;
+                assert_eq!(cell.best_cost, expected, "{method:?} at {x}");
+            }
+            assert_eq!(direction_at(&flowfield, 100, 0), GridDirection::West);
+        }
+    }
+
+    #[test]
+    fn fields_path_through_their_own_units() {
+        let (mut grid, _) = grid_from_map(&["D...."]);
+        let mut obstacles = ObstacleCells::default();
+        let (unit, building, other) = (
+            Entity::from_raw(0),
+            Entity::from_raw(1),
+            Entity::from_raw(2),
+        );
+
+        // A moving unit of the order, a building of the order and a building that isn't
+        grid.set_layer_cost(costs::UNIT_LAYER, IVec2::new(1, 0), u8::MAX);
+        obstacles.insert(&mut grid, building, vec![IVec2::new(3, 0)]);
+        obstacles.insert(&mut grid, other, vec![IVec2::new(2, 0)]);
+
+        let build = |exclusions: &[(Entity, Vec3)]| {
+            let mut flowfield = FlowField::new(grid.cell_radius, grid.size, Vec::new());
+            flowfield.exclude_units(&grid, &obstacles, exclusions);
+            flowfield.create_fields(
+                &grid,
+                &GridLayers::default(),
+                &[],
+                grid.grid[0][0].world_pos,
+            );
+            flowfield
+        };
+
+        let plain = build(&[]);
+        assert_eq!(plain.grid[0][1].best_cost, UNREACHABLE);
+        assert_eq!(
+            grid.grid[0][1].cost,
+            u8::MAX,
+            "the grid itself is untouched"
+        );
+
+        let excluded = build(&[
+            (unit, grid.grid[0][1].world_pos),
+            (building, grid.grid[0][3].world_pos),
+        ]);
+        assert_eq!(excluded.grid[0][1].best_cost, 1);
+        assert_eq!(excluded.grid[0][2].best_cost, UNREACHABLE);
+        assert_eq!(excluded.grid[0][3].best_cost, UNREACHABLE);
+        assert_eq!(excluded.grid[0][3].cost, 1);
+    }
+
+    #[test]
+    fn integration_stops_once_sources_are_final() {
+        let rows = ["D.......", "..9.....", "........", "........"];
+        let full = build(&rows);
+
+        let (grid, destination) = grid_from_map(&rows);
+        let mut flowfield = FlowField::new(grid.cell_radius, grid.size, Vec::new());
+        let destination_cell = grid.grid[destination.y as usize][destination.x as usize];
+        let source = IVec2::new(3, 1);
+        flowfield.create_integration_field_until(&grid, destination_cell, &[source]);
+
+        assert_eq!(
+            flowfield.grid[1][3].best_cost, full.grid[1][3].best_cost,
+            "the source gets its exact cost"
+        );
+        assert_eq!(flowfield.grid[3][7].best_cost, UNREACHABLE);
+    }
+
+    #[test]
+    fn dijkstra_matches_breadth_first() {
+        let rows = ["D..9....", ".#.9.##.", ".#...3..", "...#.9.1", "7...#..."];
+        let breadth = build(&rows);
+
+        let (grid, destination) = grid_from_map(&rows);
+        let mut dijkstra = FlowField::new(grid.cell_radius, grid.size, Vec::new());
+        dijkstra.integration_method = IntegrationMethod::Dijkstra;
+        let destination_cell = grid.grid[destination.y as usize][destination.x as usize];
+        dijkstra.create_integration_field(&grid, destination_cell);
+        dijkstra.create_flowfield();
+
+        assert_eq!(best_costs(&dijkstra), best_costs(&breadth));
+    }
+
+    #[test]
+    fn cardinal_connectivity_never_points_diagonally() {
+        let flowfield = build_with(&["D...", "....", "...."], Connectivity::Cardinal4);
+
+        assert_eq!(best_costs(&flowfield)[2], vec![2, 3, 4, 5]);
+        for cell in flowfield.grid.iter().flatten() {
+            let vector = cell.best_direction.vector();
+            assert!(vector.x == 0 || vector.y == 0);
+        }
+    }
+
+    #[test]
+    fn hex_connectivity_follows_row_offsets() {
+        let flowfield = build_with(&["D...", "....", "...."], Connectivity::Hex);
+
+        // Odd rows sit half a cell east, so (0, 1) is adjacent to the destination
+        // while (1, 1) is one step further away along its north-west neighbor
+        assert_eq!(best_costs(&flowfield)[1], vec![1, 2, 3, 4]);
+        assert_eq!(direction_at(&flowfield, 0, 1), GridDirection::North);
+        assert_eq!(direction_at(&flowfield, 1, 1), GridDirection::West);
+        assert_eq!(best_costs(&flowfield)[2], vec![2, 2, 3, 4]);
+        assert_eq!(direction_at(&flowfield, 1, 2), GridDirection::NorthWest);
+    }
+
+    #[test]
+    fn corridor_costs_increase_with_distance() {
+        let flowfield = build(&["#####", "D....", "#####"]);
+
+        assert_eq!(best_costs(&flowfield)[1], vec![0, 1, 2, 3, 4]);
+        for x in 1..5 {
+            assert_eq!(direction_at(&flowfield, x, 1), GridDirection::West);
+        }
+    }
+
+    #[test]
+    fn retarget_matches_a_fresh_build() {
+        let rows = [".#...", ".#.#.", "D..#."];
+        let (grid, _) = grid_from_map(&rows);
+        let mut flowfield = build(&rows);
+
+        flowfield.retarget(&grid, IVec2::new(4, 0));
+        let fresh = build(&[".#..D", ".#.#.", "...#."]);
+
+        assert_eq!(best_costs(&flowfield), best_costs(&fresh));
+        assert_eq!(flowfield.destination_cell.idx, IVec2::new(4, 0));
+        assert_eq!(flowfield.grid[2][0].cost, 1);
+    }
+
+    #[test]
+    fn repairing_an_opened_gate_matches_a_full_rebuild() {
+        let rows = ["D.#....", "..#.##.", "..#.##.", "..#...#", "......."];
+        let (mut grid, destination) = grid_from_map(&rows);
+        let mut flowfield = build(&rows);
+        let detour = flowfield.grid[0][6].best_cost;
+
+        let gate: HashSet<IVec2> = [IVec2::new(2, 1), IVec2::new(2, 2)].into();
+        for idx in gate.iter() {
+            grid.set_base_cost(*idx, 1);
+        }
+        assert!(flowfield.repair_opened(&grid, &gate));
+
+        let mut fresh = FlowField::new(grid.cell_radius, grid.size, Vec::new());
+        let destination_cell = grid.grid[destination.y as usize][destination.x as usize];
+        fresh.create_integration_field(&grid, destination_cell);
+        fresh.create_flowfield();
+
+        assert!(flowfield.diff(&fresh).is_empty());
+        assert!(flowfield.grid[0][6].best_cost < detour);
+
+        // Closing it again takes a full rebuild
+        grid.set_base_cost(IVec2::new(2, 1), u8::MAX);
+        assert!(!flowfield.repair_opened(&grid, &gate));
+        assert!(flowfield.diff(&fresh).is_empty());
+    }
+
+    #[test]
+    fn diff_lists_only_the_cells_that_changed() {
+        let before = build(&["D....", ".....", "....."]);
+        let after = build(&["D....", "..#..", "....."]);
+
+        assert!(before.diff(&before).is_empty());
+
+        let diff = before.diff(&after);
+        assert!(diff.contains(IVec2::new(2, 1)));
+        assert!(!diff.contains(IVec2::new(1, 0)));
+        for cell in diff.cells.iter() {
+            let (x, y) = (cell.idx.x as usize, cell.idx.y as usize);
+            assert_eq!(
+                cell.best_cost,
+                (before.grid[y][x].best_cost, after.grid[y][x].best_cost)
+            );
+            assert_ne!(
+                (
+                    before.grid[y][x].best_cost,
+                    before.grid[y][x].best_direction
+                ),
+                (after.grid[y][x].best_cost, after.grid[y][x].best_direction)
+            );
+        }
+    }
+
+    #[test]
+    fn destination_has_zero_cost_and_no_direction() {
+        let flowfield = build(&["...", ".D.", "..."]);
+
+        assert_eq!(flowfield.destination_cell.idx, IVec2::new(1, 1));
+        assert_eq!(flowfield.grid[1][1].best_cost, 0);
+        assert_eq!(direction_at(&flowfield, 1, 1), GridDirection::None);
+    }
+
+    #[test]
+    fn open_field_uses_manhattan_costs_and_diagonal_directions() {
+        let flowfield = build(&["...", ".D.", "..."]);
+
+        assert_eq!(
+            best_costs(&flowfield),
+            vec![vec![2, 1, 2], vec![1, 0, 1], vec![2, 1, 2]]
+        );
+        assert_eq!(direction_at(&flowfield, 0, 0), GridDirection::SouthEast);
+        assert_eq!(direction_at(&flowfield, 2, 0), GridDirection::SouthWest);
+        assert_eq!(direction_at(&flowfield, 0, 2), GridDirection::NorthEast);
+        assert_eq!(direction_at(&flowfield, 2, 2), GridDirection::NorthWest);
+        assert_eq!(direction_at(&flowfield, 1, 0), GridDirection::South);
+        assert_eq!(direction_at(&flowfield, 0, 1), GridDirection::East);
+    }
+
+    #[test]
+    fn u_shaped_obstacle_is_routed_around() {
+        let flowfield = build(&[
+            ".......", //
+            ".#...#.", ".#.D.#.", ".#####.", ".......",
+        ]);
+        let costs = best_costs(&flowfield);
+
+        // Inside the U the costs are plain distances
+        assert_eq!(costs[2][2], 1);
+        assert_eq!(costs[1][3], 1);
+
+        // Below the U the only way in is over the top
+        assert_eq!(costs[0][3], 2);
+        assert_eq!(costs[0][0], 5);
+        assert_eq!(costs[4][0], 9);
+        assert_eq!(costs[4][3], 12);
+
+        // The cell right below the U must head out to the side, not into the wall.
+        // Both sides tie, so the first direction in scan order wins.
+        assert_eq!(direction_at(&flowfield, 3, 4), GridDirection::East);
+        assert_eq!(direction_at(&flowfield, 0, 4), GridDirection::North);
+        assert_eq!(direction_at(&flowfield, 0, 3), GridDirection::North);
+    }
+
+    #[test]
+    fn unreachable_pocket_keeps_max_cost() {
+        let flowfield = build(&[
+            "D.#...", //
+            "..#.#.", "..#...",
+        ]);
+        let costs = best_costs(&flowfield);
+
+        for (y, row) in flowfield.grid.iter().enumerate() {
+            for cell in row.iter().skip(3) {
+                assert_eq!(cell.best_cost, UNREACHABLE, "cell ({}, {y})", cell.idx.x);
+                assert_eq!(cell.best_direction, GridDirection::None);
+            }
+        }
+        assert_eq!(costs[2][1], 3);
+    }
+
+    #[test]
+    fn impassable_cells_are_never_integrated() {
+        let flowfield = build(&["D#.", ".#.", "..."]);
+
+        assert_eq!(flowfield.grid[0][1].best_cost, UNREACHABLE);
+        assert_eq!(flowfield.grid[1][1].best_cost, UNREACHABLE);
+        assert_eq!(flowfield.grid[0][2].best_cost, 6);
+    }
+
+    #[test]
+    fn smooth_sampling_matches_cell_direction_at_cell_center() {
+        let flowfield = build(&["#####", "D....", "#####"]);
+        let center = flowfield.grid[1][3].world_pos;
+
+        let direction = flowfield.sample_direction_smooth(center);
+        assert!(direction.abs_diff_eq(Vec2::NEG_X, 1e-5), "{direction}");
+    }
+
+    #[test]
+    fn smooth_sampling_blends_neighboring_directions() {
+        let flowfield = build(&["...", ".D.", "..."]);
+
+        // Halfway between the cell north-west of the goal and the one north of it
+        let a = flowfield.grid[0][0].world_pos;
+        let b = flowfield.grid[0][1].world_pos;
+        let direction = flowfield.sample_direction_smooth((a + b) / 2.0);
+
+        let expected = (Vec2::new(1.0, 1.0).normalize() + Vec2::Y).normalize();
+        assert!(direction.abs_diff_eq(expected, 1e-5), "{direction}");
+    }
+
+    #[test]
+    fn smooth_sampling_skips_impassable_cells() {
+        let flowfield = build(&["D#.", "...", "..."]);
+
+        // Between a walkable cell pointing north-west and the wall, only the walkable one counts
+        let a = flowfield.grid[1][1].world_pos;
+        let b = flowfield.grid[0][1].world_pos;
+        let direction = flowfield.sample_direction_smooth((a + b) / 2.0);
+
+        let expected = Vec2::new(-1.0, -1.0).normalize();
+        assert!(direction.abs_diff_eq(expected, 1e-5), "{direction}");
+    }
+
+    #[test]
+    fn extracted_path_walks_around_walls_to_destination() {
+        let flowfield = build(&[
+            "D#.", //
+            ".#.", "...",
+        ]);
+        let path = flowfield.extract_path(flowfield.grid[0][2].world_pos);
+        let idxs: Vec<IVec2> = path.iter().map(|cell| cell.idx).collect();
+
+        assert_eq!(idxs.first(), Some(&IVec2::new(2, 0)));
+        assert_eq!(idxs.last(), Some(&IVec2::new(0, 0)));
+        assert!(path.iter().all(|cell| cell.cost < u8::MAX));
+    }
+
+    #[test]
+    fn extracted_path_is_empty_when_unreachable() {
+        let flowfield = build(&["D#."]);
+
+        assert!(flowfield
+            .extract_path(flowfield.grid[0][2].world_pos)
+            .is_empty());
+    }
+
+    #[test]
+    fn bridge_layer_crosses_impassable_river() {
+        let (grid, destination) = grid_from_map(&[
+            "D#.", //
+            ".#.", ".#.",
+        ]);
+
+        // A one cell bridge deck above the river, with ramps on both banks
+        let mut layers = GridLayers::default();
+        let bridge = layers.add_layer(&grid, 2.0, |pos| pos.x != 0.0 || pos.z != 0.0);
+        let deck = IVec2::new(1, 1);
+        layers.link(
+            LayerCell::new(0, IVec2::new(0, 1)),
+            LayerCell::new(bridge, deck),
+        );
+        layers.link(
+            LayerCell::new(bridge, deck),
+            LayerCell::new(0, IVec2::new(2, 1)),
+        );
+
+        let mut flowfield = FlowField::new(grid.cell_radius, grid.size, Vec::new());
+        let goal = grid.grid[destination.y as usize][destination.x as usize].world_pos;
+        flowfield.create_fields(&grid, &layers, &[], goal);
+
+        assert_eq!(flowfield.layers[0].grid[1][1].best_cost, 2);
+        assert_eq!(flowfield.grid[1][2].best_cost, 3);
+        assert_eq!(flowfield.grid[0][2].best_cost, 4);
+        assert_eq!(direction_at(&flowfield, 2, 1), GridDirection::West);
+
+        // Units are sampled on the bridge only when standing on top of it
+        let on_deck = flowfield.layers[0].grid[1][1].world_pos;
+        assert_eq!(flowfield.layer_at(on_deck), bridge);
+        assert_eq!(flowfield.layer_at(on_deck.with_y(0.0)), 0);
+    }
+
+    #[test]
+    fn exact_destination_is_kept_inside_the_destination_cell() {
+        let (grid, _) = grid_from_map(&[
+            "...", //
+            "...", "...",
+        ]);
+        let mut flowfield = FlowField::new(grid.cell_radius, grid.size, Vec::new());
+
+        let clicked = Vec3::new(0.2, 0.0, -0.1);
+        flowfield.create_fields(&grid, &GridLayers::default(), &[], clicked);
+        assert_eq!(flowfield.destination_cell.idx, IVec2::new(1, 1));
+        assert_eq!(flowfield.destination, clicked);
+        assert!(flowfield.in_destination_cell(Vec3::new(-0.4, 0.0, 0.4)));
+        assert!(!flowfield.in_destination_cell(Vec3::new(0.6, 0.0, 0.0)));
+
+        // Off the grid the destination is clamped to the edge of the nearest cell
+        flowfield.create_fields(&grid, &GridLayers::default(), &[], Vec3::new(5.0, 0.0, 0.0));
+        assert_eq!(flowfield.destination_cell.idx, IVec2::new(2, 1));
+        assert_eq!(flowfield.destination, Vec3::new(1.5, 0.0, 0.0));
+    }
+
+    #[test]
+    fn expensive_terrain_is_avoided_when_cheaper_route_exists() {
+        let flowfield = build(&[
+            "D9.", //
+            "...",
+        ]);
+        let costs = best_costs(&flowfield);
+
+        // Going around through the bottom row is cheaper than crossing the 9
+        assert_eq!(costs[0][2], 4);
+        assert_eq!(costs[0][1], 9);
+        assert_eq!(direction_at(&flowfield, 2, 0), GridDirection::SouthWest);
+    }
+
+    #[test]
+    fn cost_modifiers_only_change_their_own_field() {
+        let (grid, destination) = grid_from_map(&["D..", "...", "..."]);
+        let mut flowfield = FlowField::new(grid.cell_radius, grid.size, Vec::new());
+        flowfield.cost_modifier = Some(CostModifierFn::new(|cell| match cell.idx {
+            IVec2 { x: 1, y: 1 } => 50,
+            _ => cell.cost,
+        }));
+        let destination_cell = grid.grid[destination.y as usize][destination.x as usize];
+        flowfield.create_integration_field(&grid, destination_cell);
+        flowfield.create_flowfield();
+
+        // The corner steers around the penalized center instead of cutting through it
+        assert_eq!(flowfield.grid[1][1].cost, 50);
+        assert_ne!(direction_at(&flowfield, 2, 2), GridDirection::NorthWest);
+        assert_eq!(grid.grid[1][1].cost, 1);
+        assert!(!flowfield.costs_differ(&grid, &HashSet::from([IVec2::new(1, 1)])));
+    }
+
+    #[test]
+    fn cost_profiles_scale_terrain_for_their_field() {
+        let (mut grid, destination) = grid_from_map(&["D..", "...", "..."]);
+        let forest = TerrainType(1);
+        grid.layer_mut::<TerrainType>()
+            .set(IVec2::new(1, 1), forest);
+
+        let mut flowfield = FlowField::new(grid.cell_radius, grid.size, Vec::new());
+        flowfield.cost_profile = Some(Arc::new(CostProfile::new().with_multiplier(forest, 20.0)));
+        let destination_cell = grid.grid[destination.y as usize][destination.x as usize];
+        flowfield.create_integration_field(&grid, destination_cell);
+        flowfield.create_flowfield();
+
+        // The vehicle steers around the forest the grid itself costs nothing extra for
+        assert_eq!(flowfield.grid[1][1].cost, 20);
+        assert_ne!(direction_at(&flowfield, 2, 2), GridDirection::NorthWest);
+        assert_eq!(grid.grid[1][1].cost, 1);
+        assert!(!flowfield.costs_differ(&grid, &HashSet::from([IVec2::new(1, 1)])));
+    }
+
+    #[test]
+    fn parked_units_are_avoided_unless_the_order_paths_through_them() {
+        let (mut grid, destination) = grid_from_map(&["D..", "...", "..."]);
+        grid.set_layer_cost(costs::PARKED_LAYER, IVec2::new(1, 1), 20);
+        let destination_cell = grid.grid[destination.y as usize][destination.x as usize];
+
+        let mut around = FlowField::new(grid.cell_radius, grid.size, Vec::new());
+        around.create_integration_field(&grid, destination_cell);
+        around.create_flowfield();
+        assert_ne!(direction_at(&around, 2, 2), GridDirection::NorthWest);
+
+        let mut through = FlowField::new(grid.cell_radius, grid.size, Vec::new());
+        through.through_parked = true;
+        through.create_integration_field(&grid, destination_cell);
+        through.create_flowfield();
+        assert_eq!(through.grid[1][1].cost, 1);
+        assert!(!through.costs_differ(&grid, &HashSet::from([IVec2::new(1, 1)])));
+    }
+
+    #[test]
+    fn flowfield_ids_count_up_from_one() {
+        let mut ids = FlowFieldIds::default();
+        assert_eq!(
+            FlowField::new(0.5, IVec2::ONE, Vec::new()).id,
+            FlowFieldId(0)
+        );
+        assert_eq!(ids.assign(), FlowFieldId(1));
+        assert_eq!(ids.assign(), FlowFieldId(2));
+
+        ids.reset();
+        assert_eq!(ids.assign(), FlowFieldId(1));
+    }
+
+    #[test]
+    fn pooled_buffers_are_reused_by_new_fields() {
+        let grid = Grid::new(IVec2::new(6, 4), 1.0, |_| false);
+        let mut pool = FlowFieldPool::default();
+        let mut flowfield = FlowField::new(grid.cell_radius, grid.size, Vec::new());
+        flowfield.grid = pool.take();
+        flowfield.create_fields(&grid, &GridLayers::default(), &[], Vec3::ZERO);
+        assert_eq!(pool.allocated(), 1);
+
+        let rows: Vec<*const Cell> = flowfield.grid.iter().map(|row| row.as_ptr()).collect();
+        pool.put(std::mem::take(&mut flowfield.grid));
+
+        let mut reused = FlowField::new(grid.cell_radius, grid.size, Vec::new());
+        reused.grid = pool.take();
+        reused.create_fields(&grid, &GridLayers::default(), &[], Vec3::new(2.5, 0.0, 1.5));
+        assert_eq!(pool.reused(), 1);
+        assert!(pool.is_empty());
+
+        let reused_rows: Vec<*const Cell> = reused.grid.iter().map(|row| row.as_ptr()).collect();
+        assert_eq!(reused_rows, rows);
+
+        // The reused buffer holds nothing of the old field
+        let mut fresh = FlowField::new(grid.cell_radius, grid.size, Vec::new());
+        fresh.create_fields(&grid, &GridLayers::default(), &[], Vec3::new(2.5, 0.0, 1.5));
+        assert_eq!(reused.grid, fresh.grid);
+    }
+
+    #[test]
+    fn opposing_flows_keep_right_in_corridors() {
+        let corridor = [
+            "#########", //
+            ".........",
+            ".........",
+            ".........",
+            "#########",
+        ];
+        let lanes = LaneSettings {
+            bias: 2,
+            corridor_width: 3,
+        };
+
+        // East is towards +x, so its right hand side is the last row of the corridor
+        for (start, end, lane) in [(0, 8, 3), (8, 0, 1)] {
+            let (grid, _) = grid_from_map(&corridor);
+            let mut flowfield = FlowField::new(grid.cell_radius, grid.size, Vec::new());
+            flowfield.lanes = lanes;
+            flowfield.create_integration_field(&grid, grid.grid[2][end]);
+            flowfield.create_flowfield();
+
+            let path = flowfield.extract_path(grid.grid[2][start].world_pos);
+            let middle = path.iter().find(|cell| cell.idx.x == 4).unwrap();
+            assert_eq!(middle.idx.y, lane);
+        }
+
+        // Without a bias both directions share the middle row
+        let (grid, _) = grid_from_map(&corridor);
+        let mut flowfield = FlowField::new(grid.cell_radius, grid.size, Vec::new());
+        flowfield.create_integration_field(&grid, grid.grid[2][8]);
+        flowfield.create_flowfield();
+        let path = flowfield.extract_path(grid.grid[2][0].world_pos);
+        assert!(path.iter().all(|cell| cell.idx.y == 2));
+    }
+
+    #[test]
+    fn wrapping_maps_path_across_the_seam() {
+        let (mut grid, destination) = grid_from_map(&[
+            ".#....", //
+            "D#....", ".#....",
+        ]);
+        grid.set_edge_policies(EdgePolicies::new(EdgePolicy::Wrap, EdgePolicy::Clamp));
+        let destination_cell = grid.grid[destination.y as usize][destination.x as usize];
+
+        for method in [IntegrationMethod::Breadth, IntegrationMethod::Dijkstra] {
+            let mut flowfield = FlowField::new(grid.cell_radius, grid.size, Vec::new());
+            flowfield.integration_method = method;
+            flowfield.create_integration_field(&grid, destination_cell);
+            flowfield.create_flowfield();
+
+            // The wall cuts the map in two, but the east edge joins the west one
+            assert_eq!(best_costs(&flowfield)[1][5], 1);
+            assert_eq!(best_costs(&flowfield)[1][2], 4);
+            assert_eq!(direction_at(&flowfield, 5, 1), GridDirection::East);
+
+            let from = grid.grid[1][2].world_pos;
+            let path: Vec<IVec2> = flowfield
+                .extract_path(from)
+                .iter()
+                .map(|cell| cell.idx)
+                .collect();
+            assert_eq!(path.first(), Some(&IVec2::new(2, 1)));
+            assert_eq!(path.last(), Some(&destination));
+            assert_eq!(path.len(), 5);
+
+            // Units stepping off the east edge follow the west edge's cells
+            let past_edge = grid.grid[1][0].world_pos + Vec3::X * grid.size.x as f32;
+            assert_eq!(
+                flowfield.get_cell_from_world_position(past_edge).idx,
+                destination
+            );
+        }
+
+        assert!(grid.is_reachable(grid.grid[0][0].world_pos, grid.grid[2][4].world_pos));
+        grid.set_edge_policies(EdgePolicies::default());
+        assert!(!grid.is_reachable(grid.grid[0][0].world_pos, grid.grid[2][4].world_pos));
+    }
+
+    #[test]
+    fn one_way_edges_are_never_taken_backwards() {
+        let (mut grid, destination) = grid_from_map(&[
+            "D..", //
+            "...",
+        ]);
+        // Units may move east along the top row, but not back west into the middle
+        grid.set_one_way(IVec2::new(1, 0), IVec2::new(2, 0));
+        let destination_cell = grid.grid[destination.y as usize][destination.x as usize];
+
+        for method in [IntegrationMethod::Breadth, IntegrationMethod::Dijkstra] {
+            let mut flowfield = FlowField::new(grid.cell_radius, grid.size, Vec::new());
+            flowfield.integration_method = method;
+            flowfield.create_integration_field(&grid, destination_cell);
+            flowfield.create_flowfield();
+
+            // The top right cell goes around through the bottom row
+            assert_eq!(best_costs(&flowfield)[0][2], 4);
+            assert_eq!(direction_at(&flowfield, 2, 0), GridDirection::SouthWest);
+            assert_eq!(direction_at(&flowfield, 1, 0), GridDirection::West);
+        }
+    }
+
+    #[test]
+    fn sampled_speed_follows_terrain_cost() {
+        let mut flowfield = build(&[
+            "D3.", //
+            "...",
+        ]);
+        let mud = flowfield.grid[0][1].world_pos;
+        let ground = flowfield.grid[1][1].world_pos;
+
+        assert_eq!(flowfield.sample(ground).speed_factor, 1.0);
+        assert_eq!(flowfield.sample(mud).speed_factor, 1.0 / 3.0);
+        assert_eq!(
+            flowfield.sample(mud).direction,
+            GridDirection::West.vector().as_vec2()
+        );
+
+        // Raising the normal cost makes cheaper cells faster, up to the maximum
+        flowfield.terrain_speed.normal_cost = 6;
+        assert_eq!(flowfield.sample(mud).speed_factor, 2.0);
+        assert_eq!(flowfield.sample(ground).speed_factor, 2.0);
+    }
+
+    #[test]
+    fn map_grid_orders_outside_the_map_clamp_instead_of_expanding() {
+        let policy = OutOfBoundsPolicy::Expand.on_map_grid();
+        assert_eq!(policy, OutOfBoundsPolicy::Clamp);
+        assert_eq!(
+            OutOfBoundsPolicy::Reject.on_map_grid(),
+            OutOfBoundsPolicy::Reject
+        );
+
+        let map_grid = Grid::new(IVec2::new(4, 4), 1.0, |_| false);
+        let destination = Vec3::new(10.0, 0.0, -10.0);
+        assert!(!map_grid.contains_world_pos(destination));
+
+        let mut flowfield = FlowField::new(map_grid.cell_radius, map_grid.size, Vec::new());
+        flowfield.create_fields(&map_grid, &GridLayers::default(), &[], destination);
+        assert_eq!(flowfield.size, IVec2::new(4, 4));
+        assert_eq!(flowfield.destination_cell.idx, IVec2::new(3, 0));
+    }
 }
@@ -0,0 +1,309 @@
+//! Pre-baked flowfields, like a mission's escape routes or scripted attack waves, saved as
+//! `.flowfield.ron` assets and loaded back through the asset server instead of being integrated
+//! at runtime.
+//!
+//! ```ignore
+//! // Baking, e.g. from an editor tool
+//! FlowFieldAsset::from_flowfield(&flowfield).save("assets/routes/escape.flowfield.ron")?;
+//!
+//! // In the mission
+//! let asset = asset_server.load("routes/escape.flowfield.ron");
+//! cmds.spawn(FlowFieldFromAsset::new(asset, units));
+//! ```
+
+use crate::{
+    cell::{BestCost, Cell},
+    flowfield::{FlowField, FlowFieldIds, IntegrationMethod, LaneSettings, TerrainSpeed},
+    grid::{coords, Connectivity, Grids},
+    grid_direction::GridDirection,
+    PathfindingSchedule, PathfindingSet,
+};
+
+use bevy::{
+    asset::{io::Reader, AssetLoader, LoadContext},
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+use std::{fmt, path::Path};
+
+pub struct FlowFieldAssetPlugin;
+
+impl Plugin for FlowFieldAssetPlugin {
+    fn build(&self, app: &mut App) {
+        // Headless apps without an asset server build their flowfields at runtime
+        if !app.world().contains_resource::<AssetServer>() {
+            return;
+        }
+
+        let schedule = PathfindingSchedule::label(app);
+        app.init_asset::<FlowFieldAsset>()
+            .init_asset_loader::<FlowFieldAssetLoader>()
+            .add_systems(
+                schedule,
+                instantiate_flowfield_assets.in_set(PathfindingSet::BuildFields),
+            );
+    }
+}
+
+/// The integrated costs and directions of a flowfield on its grid. Interior and layer fields
+/// aren't baked, units follow the main grid's field only.
+#[derive(Asset, TypePath, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct FlowFieldAsset {
+    /// Cells along x and z
+    pub size: (i32, i32),
+    pub cell_diameter: f32,
+    pub destination: (f32, f32, f32),
+    /// The column and row of the destination cell
+    pub destination_idx: (i32, i32),
+    #[serde(default)]
+    pub connectivity: Connectivity,
+    #[serde(default)]
+    pub integration_method: IntegrationMethod,
+    /// Row by row
+    pub cells: Vec<BakedCell>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct BakedCell {
+    pub cost: u8,
+    pub best_cost: BestCost,
+    pub best_direction: GridDirection,
+}
+
+impl FlowFieldAsset {
+    pub fn from_flowfield(flowfield: &FlowField) -> Self {
+        let cells = flowfield
+            .grid
+            .iter()
+            .flatten()
+            .map(|cell| BakedCell {
+                cost: cell.cost,
+                best_cost: cell.best_cost,
+                best_direction: cell.best_direction,
+            })
+            .collect();
+
+        let destination = flowfield.destination;
+        let destination_idx = flowfield.destination_cell.idx;
+        return Self {
+            size: (flowfield.size.x, flowfield.size.y),
+            cell_diameter: flowfield.cell_diameter,
+            destination: (destination.x, destination.y, destination.z),
+            destination_idx: (destination_idx.x, destination_idx.y),
+            connectivity: flowfield.connectivity,
+            integration_method: flowfield.integration_method,
+            cells,
+        };
+    }
+
+    pub fn size(&self) -> IVec2 {
+        IVec2::new(self.size.0, self.size.1)
+    }
+
+    /// A `FlowField` for `units` with the baked costs and directions
+    pub fn instantiate(&self, units: Vec<Entity>) -> FlowField {
+        let size = self.size();
+        let mut flowfield = FlowField::new(self.cell_diameter / 2.0, size, units);
+        flowfield.connectivity = self.connectivity;
+        flowfield.integration_method = self.integration_method;
+        flowfield.destination =
+            Vec3::new(self.destination.0, self.destination.1, self.destination.2);
+
+        flowfield.grid = self
+            .cells
+            .chunks(size.x.max(1) as usize)
+            .enumerate()
+            .map(|(y, row)| {
+                row.iter()
+                    .enumerate()
+                    .map(|(x, baked)| {
+                        let idx = IVec2::new(x as i32, y as i32);
+                        Cell {
+                            best_cost: baked.best_cost,
+                            best_direction: baked.best_direction,
+                            cost: baked.cost,
+                            idx,
+                            world_pos: coords::idx_to_world(idx, size, self.cell_diameter),
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let destination_idx = IVec2::new(self.destination_idx.0, self.destination_idx.1);
+        if let Some(cell) = flowfield.grid.get(destination_idx.y as usize) {
+            flowfield.destination_cell = cell
+                .get(destination_idx.x as usize)
+                .copied()
+                .unwrap_or_default();
+        }
+
+        return flowfield;
+    }
+
+    pub fn to_ron(&self) -> Result<String, ron::Error> {
+        return ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default());
+    }
+
+    /// Writes the asset to `path`, which should end in `.flowfield.ron` for the loader to find it
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), FlowFieldAssetError> {
+        let ron = self.to_ron().map_err(FlowFieldAssetError::Serialize)?;
+        return std::fs::write(path, ron).map_err(FlowFieldAssetError::Io);
+    }
+}
+
+/// Spawn to turn the entity into a `FlowField` for `units` once the asset has loaded. Assets
+/// baked on a grid of another size than the current one are discarded with a warning.
+#[derive(Component, Clone, Debug)]
+pub struct FlowFieldFromAsset {
+    pub asset: Handle<FlowFieldAsset>,
+    pub units: Vec<Entity>,
+    /// The map entity whose `Grid` the field was baked on, `None` for the `Grid` resource
+    pub map: Option<Entity>,
+}
+
+impl FlowFieldFromAsset {
+    pub fn new(asset: Handle<FlowFieldAsset>, units: Vec<Entity>) -> Self {
+        Self {
+            asset,
+            units,
+            map: None,
+        }
+    }
+
+    /// Instantiates the field on the `Grid` of the `map` entity instead of the `Grid` resource
+    pub fn on_map(mut self, map: Entity) -> Self {
+        self.map = Some(map);
+        self
+    }
+}
+
+#[derive(Default)]
+pub struct FlowFieldAssetLoader;
+
+#[derive(Debug)]
+pub enum FlowFieldAssetError {
+    Io(std::io::Error),
+    Ron(ron::error::SpannedError),
+    Serialize(ron::Error),
+}
+
+impl fmt::Display for FlowFieldAssetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FlowFieldAssetError::Io(error) => {
+                write!(f, "Could not access flowfield asset: {error}")
+            }
+            FlowFieldAssetError::Ron(error) => {
+                write!(f, "Could not parse flowfield asset: {error}")
+            }
+            FlowFieldAssetError::Serialize(error) => {
+                write!(f, "Could not serialize flowfield asset: {error}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for FlowFieldAssetError {}
+
+impl AssetLoader for FlowFieldAssetLoader {
+    type Asset = FlowFieldAsset;
+    type Settings = ();
+    type Error = FlowFieldAssetError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader
+            .read_to_end(&mut bytes)
+            .await
+            .map_err(FlowFieldAssetError::Io)?;
+
+        return ron::de::from_bytes(&bytes).map_err(FlowFieldAssetError::Ron);
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["flowfield.ron"]
+    }
+}
+
+/// Replaces `FlowFieldFromAsset`s whose asset has loaded with their `FlowField`
+fn instantiate_flowfield_assets(
+    mut cmds: Commands,
+    grids: Grids,
+    assets: Res<Assets<FlowFieldAsset>>,
+    terrain_speed: Res<TerrainSpeed>,
+    lanes: Res<LaneSettings>,
+    mut ids: ResMut<FlowFieldIds>,
+    q_pending: Query<(Entity, &FlowFieldFromAsset)>,
+) {
+    for (entity, pending) in q_pending.iter() {
+        let Some(asset) = assets.get(&pending.asset) else {
+            continue;
+        };
+        let Some(grid) = grids.get(pending.map) else {
+            continue;
+        };
+
+        if asset.size() != grid.size || asset.cell_diameter != grid.cell_diameter {
+            warn!(
+                "Discarding flowfield asset baked on a {} grid, the current one is {}",
+                asset.size(),
+                grid.size
+            );
+            cmds.entity(entity).despawn_recursive();
+            continue;
+        }
+
+        // The baked field stays in use until the grid's costs next change
+        let mut flowfield = asset.instantiate(pending.units.clone());
+        flowfield.id = ids.assign();
+        flowfield.map = pending.map;
+        flowfield.terrain_speed = *terrain_speed;
+        flowfield.lanes = *lanes;
+        flowfield.edge_costs = grid.edge_costs().clone();
+        flowfield.edge_policies = grid.edge_policies();
+        flowfield.rotation = grid.rotation;
+        flowfield.costfield_version = grid.version;
+
+        cmds.entity(entity)
+            .remove::<FlowFieldFromAsset>()
+            .insert(flowfield);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{grid::Grid, layers::GridLayers};
+
+    #[test]
+    fn baked_fields_survive_a_round_trip_through_ron() {
+        let grid = Grid::new(IVec2::new(6, 4), 1.0, |pos| pos.x == 0.5 && pos.z < 1.0);
+        let mut flowfield = FlowField::new(grid.cell_radius, grid.size, Vec::new());
+        flowfield.create_fields(
+            &grid,
+            &GridLayers::default(),
+            &[],
+            Vec3::new(2.5, 0.0, -1.5),
+        );
+
+        let asset = FlowFieldAsset::from_flowfield(&flowfield);
+        let loaded: FlowFieldAsset = ron::de::from_str(&asset.to_ron().unwrap()).unwrap();
+        assert_eq!(loaded, asset);
+
+        let units = vec![Entity::from_raw(7)];
+        let instance = loaded.instantiate(units.clone());
+        assert_eq!(instance.grid, flowfield.grid);
+        assert_eq!(instance.destination_cell, flowfield.destination_cell);
+        assert_eq!(instance.destination, flowfield.destination);
+        assert_eq!(instance.units, units);
+
+        let from = Vec3::new(-2.5, 0.0, -1.5);
+        assert_eq!(instance.extract_path(from), flowfield.extract_path(from));
+    }
+}
@@ -0,0 +1,101 @@
+//! Costly or impassable rings of cells along the edges of a grid, keeping flowfields from
+//! funneling units along the world boundary. Configured for every grid by `GridSettings`.
+
+use super::{coords::EdgePolicy, costs, Grid};
+use crate::events::UpdateCostEv;
+
+use bevy::prelude::*;
+use std::collections::HashSet;
+
+/// Settings applied to the `Grid` resource and the grid of every map entity
+#[derive(Resource, Reflect, Clone, Copy, Debug, PartialEq)]
+#[reflect(Resource)]
+pub struct GridSettings {
+    /// Rings of cells along the edges raised to `border_cost`, 0 for none. Edges joined to the
+    /// opposite one by `EdgePolicy::Wrap` have no border.
+    pub border_rings: u32,
+    /// The least cost of border cells, `u8::MAX` to make them impassable
+    pub border_cost: u8,
+}
+
+impl Default for GridSettings {
+    fn default() -> Self {
+        Self {
+            border_rings: 0,
+            border_cost: u8::MAX,
+        }
+    }
+}
+
+impl Grid {
+    /// The rings and cost of the border, see `Grid::set_border`
+    pub fn border(&self) -> (u32, u8) {
+        self.border
+    }
+
+    /// Raises the outer `rings` rings of cells to at least `cost` in the border layer, replacing
+    /// the previous border. The border follows the edges when the grid expands or its edge
+    /// policies change. Returns the cells whose cost changed.
+    pub fn set_border(&mut self, rings: u32, cost: u8) -> Vec<IVec2> {
+        self.border = (rings, cost);
+
+        let rings = rings as i32;
+        let wrap = self.edge_policies;
+        let on_border = |idx: IVec2| {
+            let near_x = idx.x < rings || idx.x >= self.size.x - rings;
+            let near_y = idx.y < rings || idx.y >= self.size.y - rings;
+            (near_x && wrap.x == EdgePolicy::Clamp) || (near_y && wrap.y == EdgePolicy::Clamp)
+        };
+
+        let border: HashSet<IVec2> = self
+            .grid
+            .iter()
+            .flatten()
+            .map(|cell| cell.idx)
+            .filter(|idx| on_border(*idx))
+            .collect();
+
+        let mut changed = Vec::new();
+        for idx in self.layer_cells(costs::BORDER_LAYER) {
+            if !border.contains(&idx) && self.clear_layer_cost(costs::BORDER_LAYER, idx) {
+                changed.push(idx);
+            }
+        }
+
+        for idx in border {
+            if self.set_layer_cost(costs::BORDER_LAYER, idx, cost) {
+                changed.push(idx);
+            }
+        }
+
+        return changed;
+    }
+}
+
+/// Paints the border of `GridSettings` on grids whose border differs, like new or replaced
+/// grids and after the settings change
+pub(super) fn apply_grid_settings(
+    settings: Res<GridSettings>,
+    mut grid: ResMut<Grid>,
+    mut q_maps: Query<(Entity, &mut Grid)>,
+    mut events: EventWriter<UpdateCostEv>,
+) {
+    let border = (settings.border_rings, settings.border_cost);
+
+    if grid.border() != border {
+        for idx in grid.set_border(border.0, border.1) {
+            events.send(UpdateCostEv::new(grid.grid[idx.y as usize][idx.x as usize]));
+        }
+    }
+
+    for (map, mut grid) in q_maps.iter_mut() {
+        if grid.border() == border {
+            continue;
+        }
+
+        for idx in grid.set_border(border.0, border.1) {
+            let cell = grid.grid[idx.y as usize][idx.x as usize];
+            events.send(UpdateCostEv::on_map(cell, map));
+        }
+    }
+}
@@ -0,0 +1,168 @@
+//! Per-cell data games attach to a `Grid`, like ownership, creep or buildability. Every type
+//! gets its own layer with a value for each cell, which stays with its cell when the grid
+//! expands.
+
+use super::{coords, Grid};
+
+use bevy::prelude::*;
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+
+/// Data that can be kept for every cell, see `Grid::layer`
+pub trait CellData: Default + Clone + Send + Sync + 'static {}
+
+impl<T: Default + Clone + Send + Sync + 'static> CellData for T {}
+
+/// A value of `T` for every cell of a `Grid`
+#[derive(Clone, Debug, PartialEq)]
+pub struct CellLayer<T> {
+    size: IVec2,
+    // row by row
+    values: Vec<T>,
+}
+
+impl<T: CellData> CellLayer<T> {
+    /// A layer of `T::default()` for a grid of `size`
+    pub fn new(size: IVec2) -> Self {
+        Self {
+            size,
+            values: vec![T::default(); (size.x * size.y).max(0) as usize],
+        }
+    }
+
+    pub fn size(&self) -> IVec2 {
+        self.size
+    }
+
+    /// The value of the cell at `idx`, `None` off the grid
+    pub fn get(&self, idx: IVec2) -> Option<&T> {
+        return self.values.get(self.index(idx)?);
+    }
+
+    pub fn get_mut(&mut self, idx: IVec2) -> Option<&mut T> {
+        let i = self.index(idx)?;
+        return self.values.get_mut(i);
+    }
+
+    /// Sets the value of the cell at `idx`. Returns false if it's off the grid.
+    pub fn set(&mut self, idx: IVec2, value: T) -> bool {
+        let Some(slot) = self.get_mut(idx) else {
+            return false;
+        };
+
+        *slot = value;
+        return true;
+    }
+
+    /// Every cell index with its value, row by row
+    pub fn iter(&self) -> impl Iterator<Item = (IVec2, &T)> {
+        let width = self.size.x.max(1);
+        self.values
+            .iter()
+            .enumerate()
+            .map(move |(i, value)| (IVec2::new(i as i32 % width, i as i32 / width), value))
+    }
+
+    /// The values row by row
+    pub fn values(&self) -> &[T] {
+        &self.values
+    }
+
+    fn index(&self, idx: IVec2) -> Option<usize> {
+        if !coords::in_bounds(idx, self.size) {
+            return None;
+        }
+
+        return Some((idx.y * self.size.x + idx.x) as usize);
+    }
+
+    /// Follows the cells after the grid grew by `border` on every side, filling the new ones
+    /// with `T::default()`
+    fn expand(&mut self, border: i32) {
+        let mut expanded = CellLayer::new(self.size + 2 * border);
+        for (idx, value) in self.iter() {
+            expanded.set(idx + border, value.clone());
+        }
+
+        *self = expanded;
+    }
+}
+
+trait AnyCellLayer: Send + Sync {
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+    fn clone_box(&self) -> Box<dyn AnyCellLayer>;
+    fn expand(&mut self, border: i32);
+}
+
+impl<T: CellData> AnyCellLayer for CellLayer<T> {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn clone_box(&self) -> Box<dyn AnyCellLayer> {
+        Box::new(self.clone())
+    }
+
+    fn expand(&mut self, border: i32) {
+        CellLayer::expand(self, border);
+    }
+}
+
+/// The cell data layers of a `Grid`, one per type
+#[derive(Default)]
+pub struct CellLayers(HashMap<TypeId, Box<dyn AnyCellLayer>>);
+
+impl Clone for CellLayers {
+    fn clone(&self) -> Self {
+        let layers = self
+            .0
+            .iter()
+            .map(|(type_id, layer)| (*type_id, layer.clone_box()))
+            .collect();
+
+        return Self(layers);
+    }
+}
+
+impl CellLayers {
+    /// Follows the cell indices after the grid was expanded
+    pub(super) fn shift(&mut self, border: i32) {
+        for layer in self.0.values_mut() {
+            layer.expand(border);
+        }
+    }
+}
+
+impl Grid {
+    /// The cell data of type `T`, `None` until `layer_mut` first creates it
+    pub fn layer<T: CellData>(&self) -> Option<&CellLayer<T>> {
+        let layer = self.cell_layers.0.get(&TypeId::of::<T>())?;
+        return layer.as_any().downcast_ref();
+    }
+
+    /// The cell data of type `T`, with every cell set to `T::default()` on first use
+    pub fn layer_mut<T: CellData>(&mut self) -> &mut CellLayer<T> {
+        let size = self.size;
+        let layer = self
+            .cell_layers
+            .0
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(CellLayer::<T>::new(size)));
+
+        return layer
+            .as_any_mut()
+            .downcast_mut()
+            .expect("cell layers are stored by their type");
+    }
+
+    /// Takes the cell data of type `T` off the grid
+    pub fn remove_layer<T: CellData>(&mut self) -> Option<CellLayer<T>> {
+        let layer = self.cell_layers.0.remove(&TypeId::of::<T>())?;
+        return layer.as_any().downcast_ref().cloned();
+    }
+}
@@ -0,0 +1,188 @@
+//! Conversions between world positions and cell indices. Positions are relative to the grid's
+//! center and indices are `[y][x]`, with y running along world z.
+
+use bevy::prelude::*;
+
+/// Continuous cell coordinates of `world_pos`, where integer values land on cell edges
+pub fn to_grid_space(world_pos: Vec3, size: IVec2, cell_diameter: f32) -> Vec2 {
+    world_pos.xz() / cell_diameter + size.as_vec2() / 2.0
+}
+
+/// The index of the cell containing `world_pos`, which may lie outside the grid
+pub fn world_to_idx_unclamped(world_pos: Vec3, size: IVec2, cell_diameter: f32) -> IVec2 {
+    to_grid_space(world_pos, size, cell_diameter)
+        .floor()
+        .as_ivec2()
+}
+
+/// The index of the cell containing `world_pos`, or `None` if it's off the grid
+pub fn world_to_idx(world_pos: Vec3, size: IVec2, cell_diameter: f32) -> Option<IVec2> {
+    let idx = world_to_idx_unclamped(world_pos, size, cell_diameter);
+    in_bounds(idx, size).then_some(idx)
+}
+
+/// The index of the cell containing `world_pos`, or of the nearest edge cell if it's off the grid
+pub fn world_to_idx_clamped(world_pos: Vec3, size: IVec2, cell_diameter: f32) -> IVec2 {
+    let last = (size - 1).max(IVec2::ZERO);
+    world_to_idx_unclamped(world_pos, size, cell_diameter).clamp(IVec2::ZERO, last)
+}
+
+/// The world position of the center of the cell at `idx`, at a height of 0
+pub fn idx_to_world(idx: IVec2, size: IVec2, cell_diameter: f32) -> Vec3 {
+    let pos = (idx.as_vec2() + 0.5 - size.as_vec2() / 2.0) * cell_diameter;
+    Vec3::new(pos.x, 0.0, pos.y)
+}
+
+/// An XZ direction in the space of a grid turned by `rotation` turned into the world, see
+/// `Grid::rotation`
+pub fn direction_to_world(direction: Vec2, rotation: Quat) -> Vec2 {
+    return (rotation * Vec3::new(direction.x, 0.0, direction.y)).xz();
+}
+
+pub fn in_bounds(idx: IVec2, size: IVec2) -> bool {
+    idx.x >= 0 && idx.y >= 0 && idx.x < size.x && idx.y < size.y
+}
+
+/// What lies past the edges of a grid along one axis
+#[derive(Reflect, Default, Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "config", derive(serde::Serialize, serde::Deserialize))]
+pub enum EdgePolicy {
+    /// The edges are walls
+    #[default]
+    Clamp,
+    /// Each edge joins the opposite one, for maps that wrap around
+    Wrap,
+}
+
+impl EdgePolicy {
+    fn wrap(self, i: i32, len: i32) -> i32 {
+        match self {
+            EdgePolicy::Clamp => i,
+            EdgePolicy::Wrap => i.rem_euclid(len),
+        }
+    }
+
+    fn wrap_world(self, pos: f32, width: f32) -> f32 {
+        match self {
+            EdgePolicy::Clamp => pos,
+            EdgePolicy::Wrap => (pos + width / 2.0).rem_euclid(width) - width / 2.0,
+        }
+    }
+}
+
+/// The `EdgePolicy` of each axis of a grid. `y` is the axis of the rows, along world z.
+/// `Connectivity::Hex` grids wrapping along `y` need an even number of rows.
+#[derive(Reflect, Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EdgePolicies {
+    pub x: EdgePolicy,
+    pub y: EdgePolicy,
+}
+
+impl EdgePolicies {
+    pub fn new(x: EdgePolicy, y: EdgePolicy) -> Self {
+        Self { x, y }
+    }
+
+    /// `idx` carried across wrapping edges, or `None` if it's off the grid past a clamped one
+    pub fn wrap(self, idx: IVec2, size: IVec2) -> Option<IVec2> {
+        let idx = IVec2::new(self.x.wrap(idx.x, size.x), self.y.wrap(idx.y, size.y));
+        in_bounds(idx, size).then_some(idx)
+    }
+
+    /// `world_pos` carried across wrapping edges back onto the grid
+    pub fn wrap_world(self, world_pos: Vec3, size: IVec2, cell_diameter: f32) -> Vec3 {
+        let width = size.as_vec2() * cell_diameter;
+        Vec3::new(
+            self.x.wrap_world(world_pos.x, width.x),
+            world_pos.y,
+            self.y.wrap_world(world_pos.z, width.y),
+        )
+    }
+}
+
+/// Converts an index of a `Connectivity::Hex` grid to axial hex coordinates
+pub fn offset_to_axial(idx: IVec2) -> IVec2 {
+    IVec2::new(idx.x - (idx.y - (idx.y & 1)) / 2, idx.y)
+}
+
+/// Converts axial hex coordinates back to an index of a `Connectivity::Hex` grid
+pub fn axial_to_offset(axial: IVec2) -> IVec2 {
+    IVec2::new(axial.x + (axial.y - (axial.y & 1)) / 2, axial.y)
+}
+
+/// The number of steps between two cells of a `Connectivity::Hex` grid
+pub fn hex_distance(a: IVec2, b: IVec2) -> i32 {
+    let d = offset_to_axial(a) - offset_to_axial(b);
+    (d.x.abs() + d.y.abs() + (d.x + d.y).abs()) / 2
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZE: IVec2 = IVec2::new(4, 2);
+
+    #[test]
+    fn cell_centers_round_trip() {
+        for y in 0..SIZE.y {
+            for x in 0..SIZE.x {
+                let idx = IVec2::new(x, y);
+                let world_pos = idx_to_world(idx, SIZE, 2.0);
+                assert_eq!(world_to_idx(world_pos, SIZE, 2.0), Some(idx));
+            }
+        }
+    }
+
+    #[test]
+    fn edges_belong_to_the_cell_after_them() {
+        // Cells span -4..4 on x and -2..2 on z
+        assert_eq!(
+            world_to_idx(Vec3::new(-4.0, 0.0, -2.0), SIZE, 2.0),
+            Some(IVec2::ZERO)
+        );
+        assert_eq!(
+            world_to_idx(Vec3::new(0.0, 0.0, 0.0), SIZE, 2.0),
+            Some(IVec2::new(2, 1))
+        );
+        assert_eq!(world_to_idx(Vec3::new(4.0, 0.0, 0.0), SIZE, 2.0), None);
+    }
+
+    #[test]
+    fn axial_coordinates_round_trip() {
+        for y in -3..3 {
+            for x in -3..3 {
+                let idx = IVec2::new(x, y);
+                assert_eq!(axial_to_offset(offset_to_axial(idx)), idx);
+            }
+        }
+
+        // Odd rows are shifted east, so (1, 1) touches both (1, 0) and (2, 0)
+        assert_eq!(hex_distance(IVec2::new(1, 1), IVec2::new(1, 0)), 1);
+        assert_eq!(hex_distance(IVec2::new(1, 1), IVec2::new(2, 0)), 1);
+        assert_eq!(hex_distance(IVec2::new(1, 1), IVec2::new(0, 0)), 2);
+        assert_eq!(hex_distance(IVec2::new(0, 0), IVec2::new(3, 0)), 3);
+    }
+
+    #[test]
+    fn wrapping_axes_carry_positions_to_the_opposite_edge() {
+        let wrap_x = EdgePolicies::new(EdgePolicy::Wrap, EdgePolicy::Clamp);
+
+        assert_eq!(wrap_x.wrap(IVec2::new(-1, 1), SIZE), Some(IVec2::new(3, 1)));
+        assert_eq!(wrap_x.wrap(IVec2::new(4, 0), SIZE), Some(IVec2::new(0, 0)));
+        assert_eq!(wrap_x.wrap(IVec2::new(0, 2), SIZE), None);
+
+        // Cells span -4..4 on x, so 5 is one unit into the west edge cell
+        let world_pos = wrap_x.wrap_world(Vec3::new(5.0, 1.0, 3.0), SIZE, 2.0);
+        assert_eq!(world_pos, Vec3::new(-3.0, 1.0, 3.0));
+        assert_eq!(world_to_idx(world_pos, SIZE, 2.0), None);
+    }
+
+    #[test]
+    fn off_grid_positions_clamp_to_the_nearest_edge_cell() {
+        let far = Vec3::new(-100.0, 0.0, 100.0);
+
+        assert_eq!(world_to_idx(far, SIZE, 2.0), None);
+        assert_eq!(world_to_idx_unclamped(far, SIZE, 2.0), IVec2::new(-48, 51));
+        assert_eq!(world_to_idx_clamped(far, SIZE, 2.0), IVec2::new(0, 1));
+    }
+}
@@ -0,0 +1,270 @@
+//! Cost layers composited into `Cell::cost`. Every cost source writes its own sparse layer, and
+//! a cell's cost is baked from its terrain cost and the layers covering it whenever one changes.
+
+use super::Grid;
+
+use bevy::prelude::*;
+use std::collections::HashMap;
+
+/// Blocked by units standing in the cell
+pub const UNIT_LAYER: &str = "units";
+/// Blocked by stationary obstacles, see `ObstacleCells`
+pub const OBSTACLE_LAYER: &str = "obstacles";
+/// Raised by `PendingObstacle`s
+pub const RESERVATION_LAYER: &str = "reservations";
+/// Painted by `PaintCostEv`, replacing the terrain cost below obstacles and units
+pub const EDITOR_LAYER: &str = "editor";
+/// Added to by `Grid::add_temporary_cost` until the costs expire
+pub const TEMPORARY_LAYER: &str = "temporary";
+/// Raised around blocked cells, see `ObstacleSettings::inflation_radius`
+pub const INFLATION_LAYER: &str = "inflation";
+/// Raised along the edges of the grid, see `GridSettings`
+pub const BORDER_LAYER: &str = "border";
+/// Added to under units that stand still, see `ParkingSettings`
+pub const PARKED_LAYER: &str = "parked";
+
+/// How a layer's cost combines with the cost below it
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Reflect)]
+#[cfg_attr(feature = "config", derive(serde::Serialize, serde::Deserialize))]
+pub enum BlendOp {
+    /// Adds to the cost, without making a walkable cell impassable
+    Add,
+    /// Raises the cost to at least the layer's
+    Max,
+    /// Replaces the cost, e.g. with `u8::MAX` to make the cell impassable
+    Override,
+}
+
+impl BlendOp {
+    fn blend(self, below: u8, cost: u8) -> u8 {
+        match self {
+            BlendOp::Add if below == u8::MAX => u8::MAX,
+            BlendOp::Add => below.saturating_add(cost).min(u8::MAX - 1),
+            BlendOp::Max => below.max(cost),
+            BlendOp::Override => cost,
+        }
+    }
+}
+
+/// A named source of costs. Layers are applied from the lowest priority to the highest.
+#[derive(Clone, Debug, Reflect)]
+pub struct CostLayer {
+    pub name: String,
+    pub priority: i32,
+    pub op: BlendOp,
+    costs: HashMap<IVec2, u8>,
+}
+
+impl CostLayer {
+    /// The cells the layer covers with their costs
+    pub fn costs(&self) -> impl Iterator<Item = (IVec2, u8)> + '_ {
+        self.costs.iter().map(|(idx, cost)| (*idx, *cost))
+    }
+
+    /// Raises the layer's cost of the cell at `idx` to at least `cost`
+    pub(super) fn put_max(&mut self, idx: IVec2, cost: u8) {
+        let entry = self.costs.entry(idx).or_insert(cost);
+        *entry = (*entry).max(cost);
+    }
+}
+
+/// The cost layers of a `Grid`, with the terrain cost of every cell a layer covers
+#[derive(Clone, Debug, Reflect)]
+pub struct CostLayers {
+    layers: Vec<CostLayer>,
+    base: HashMap<IVec2, u8>,
+}
+
+impl Default for CostLayers {
+    fn default() -> Self {
+        let mut layers = CostLayers {
+            layers: Vec::new(),
+            base: HashMap::new(),
+        };
+
+        layers.add(RESERVATION_LAYER, 50, BlendOp::Max);
+        layers.add(EDITOR_LAYER, 0, BlendOp::Override);
+        layers.add(TEMPORARY_LAYER, 25, BlendOp::Add);
+        layers.add(PARKED_LAYER, 30, BlendOp::Add);
+        layers.add(INFLATION_LAYER, 40, BlendOp::Max);
+        layers.add(BORDER_LAYER, 90, BlendOp::Max);
+        layers.add(OBSTACLE_LAYER, 100, BlendOp::Override);
+        layers.add(UNIT_LAYER, 100, BlendOp::Override);
+        layers
+    }
+}
+
+impl CostLayers {
+    fn add(&mut self, name: &str, priority: i32, op: BlendOp) {
+        match self.layers.iter_mut().find(|layer| layer.name == name) {
+            Some(layer) => {
+                layer.priority = priority;
+                layer.op = op;
+            }
+            None => self.layers.push(CostLayer {
+                name: name.to_string(),
+                priority,
+                op,
+                costs: HashMap::new(),
+            }),
+        }
+
+        // Stable, so layers of equal priority keep the order they were added in
+        self.layers.sort_by_key(|layer| layer.priority);
+    }
+
+    fn layer(&self, name: &str) -> Option<&CostLayer> {
+        self.layers.iter().find(|layer| layer.name == name)
+    }
+
+    fn layer_mut(&mut self, name: &str) -> Option<&mut CostLayer> {
+        self.layers.iter_mut().find(|layer| layer.name == name)
+    }
+
+    /// The cost of the cell at `idx` with every layer but the `skipped` ones applied, `None` if
+    /// no layer covers it
+    fn composite(&self, idx: IVec2, skipped: &[&str]) -> Option<u8> {
+        let base = *self.base.get(&idx)?;
+
+        return Some(
+            self.layers
+                .iter()
+                .filter(|layer| !skipped.contains(&layer.name.as_str()))
+                .fold(base, |cost, layer| match layer.costs.get(&idx) {
+                    Some(layer_cost) => layer.op.blend(cost, *layer_cost),
+                    None => cost,
+                }),
+        );
+    }
+
+    fn is_covered(&self, idx: IVec2) -> bool {
+        self.layers
+            .iter()
+            .any(|layer| layer.costs.contains_key(&idx))
+    }
+
+    /// Follows the cell indices after the grid was expanded
+    pub(super) fn shift(&mut self, border: i32) {
+        let shift = |costs: &mut HashMap<IVec2, u8>| {
+            *costs = costs
+                .drain()
+                .map(|(idx, cost)| (idx + border, cost))
+                .collect();
+        };
+
+        shift(&mut self.base);
+        for layer in self.layers.iter_mut() {
+            shift(&mut layer.costs);
+        }
+    }
+}
+
+impl Grid {
+    /// Adds a cost layer, or changes the priority and blend op of an existing one
+    pub fn add_cost_layer(&mut self, name: &str, priority: i32, op: BlendOp) {
+        self.cost_layers.add(name, priority, op);
+
+        let covered: Vec<IVec2> = self.cost_layers.base.keys().copied().collect();
+        for idx in covered {
+            self.bake_cost(idx);
+        }
+    }
+
+    /// The layers of the grid, from the lowest priority to the highest
+    pub fn cost_layers(&self) -> &[CostLayer] {
+        &self.cost_layers.layers
+    }
+
+    /// The cost the `name` layer puts on the cell at `idx`
+    pub fn layer_cost(&self, name: &str, idx: IVec2) -> Option<u8> {
+        return self.cost_layers.layer(name)?.costs.get(&idx).copied();
+    }
+
+    /// The cells the `name` layer puts a cost on
+    pub fn layer_cells(&self, name: &str) -> Vec<IVec2> {
+        let Some(layer) = self.cost_layers.layer(name) else {
+            return Vec::new();
+        };
+
+        return layer.costs.keys().copied().collect();
+    }
+
+    /// The cost of the cell at `idx` without any layers, `None` if it's off the grid
+    pub fn base_cost(&self, idx: IVec2) -> Option<u8> {
+        let cost = self.cell(idx)?.cost;
+        return Some(self.cost_layers.base.get(&idx).copied().unwrap_or(cost));
+    }
+
+    /// The cost the cell at `idx` would have without the `skipped` layers, `None` if it's off
+    /// the grid
+    pub fn cost_without(&self, idx: IVec2, skipped: &[&str]) -> Option<u8> {
+        let cost = self.cell(idx)?.cost;
+        return Some(self.cost_layers.composite(idx, skipped).unwrap_or(cost));
+    }
+
+    /// Sets the cost of the cell at `idx` below every layer. Returns true if `Cell::cost` changed.
+    pub fn set_base_cost(&mut self, idx: IVec2, cost: u8) -> bool {
+        if self.cell(idx).is_none() {
+            return false;
+        }
+
+        if self.cost_layers.is_covered(idx) {
+            self.cost_layers.base.insert(idx, cost);
+            return self.bake_cost(idx);
+        }
+
+        let cell = &mut self.grid[idx.y as usize][idx.x as usize];
+        if cell.cost == cost {
+            return false;
+        }
+
+        cell.cost = cost;
+        return true;
+    }
+
+    /// Puts `cost` on the cell at `idx` in the `name` layer. Returns true if `Cell::cost` changed.
+    pub fn set_layer_cost(&mut self, name: &str, idx: IVec2, cost: u8) -> bool {
+        let Some(cell_cost) = self.cell(idx).map(|cell| cell.cost) else {
+            return false;
+        };
+        let Some(layer) = self.cost_layers.layer_mut(name) else {
+            warn!("No cost layer named {name}");
+            return false;
+        };
+
+        layer.costs.insert(idx, cost);
+        self.cost_layers.base.entry(idx).or_insert(cell_cost);
+        return self.bake_cost(idx);
+    }
+
+    /// Takes the cell at `idx` out of the `name` layer. Returns true if `Cell::cost` changed.
+    pub fn clear_layer_cost(&mut self, name: &str, idx: IVec2) -> bool {
+        let Some(layer) = self.cost_layers.layer_mut(name) else {
+            return false;
+        };
+        if layer.costs.remove(&idx).is_none() {
+            return false;
+        }
+
+        return self.bake_cost(idx);
+    }
+
+    /// Writes the composited cost of the cell at `idx`, forgetting its terrain cost once no layer
+    /// covers it. Returns true if `Cell::cost` changed.
+    fn bake_cost(&mut self, idx: IVec2) -> bool {
+        let Some(cost) = self.cost_layers.composite(idx, &[]) else {
+            return false;
+        };
+        if !self.cost_layers.is_covered(idx) {
+            self.cost_layers.base.remove(&idx);
+        }
+
+        let cell = &mut self.grid[idx.y as usize][idx.x as usize];
+        if cell.cost == cost {
+            return false;
+        }
+
+        cell.cost = cost;
+        return true;
+    }
+}
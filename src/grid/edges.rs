@@ -0,0 +1,74 @@
+//! Costs on the edges between neighboring cells, for one-way ramps and conveyor belts. Unlike
+//! cell costs they depend on the direction a unit moves in.
+
+use super::Grid;
+
+use bevy::prelude::*;
+use std::collections::HashMap;
+
+/// Extra costs of moving from a cell to a neighbor, on top of the cost of the cell entered.
+/// `u8::MAX` forbids the move.
+#[derive(Clone, Debug, Default, PartialEq, Reflect)]
+pub struct EdgeCosts(HashMap<(IVec2, IVec2), u8>);
+
+impl EdgeCosts {
+    /// The extra cost of moving from `from` to `to`, 0 for edges without one
+    pub fn cost(&self, from: IVec2, to: IVec2) -> u8 {
+        if self.0.is_empty() {
+            return 0;
+        }
+
+        return self.0.get(&(from, to)).copied().unwrap_or(0);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Every edge with an extra cost, as the cell moved from, the cell moved to and the cost
+    pub fn iter(&self) -> impl Iterator<Item = (IVec2, IVec2, u8)> + '_ {
+        self.0.iter().map(|((from, to), cost)| (*from, *to, *cost))
+    }
+
+    /// Adds to the cost of moving from `from` to `to`, short of forbidding the move
+    pub(crate) fn add(&mut self, from: IVec2, to: IVec2, cost: u8) {
+        let edge = self.0.entry((from, to)).or_default();
+        if *edge != u8::MAX {
+            *edge = edge.saturating_add(cost).min(u8::MAX - 1);
+        }
+    }
+
+    /// Follows the cell indices after the grid was expanded
+    pub(super) fn shift(&mut self, border: i32) {
+        self.0 = self
+            .0
+            .drain()
+            .map(|((from, to), cost)| ((from + border, to + border), cost))
+            .collect();
+    }
+}
+
+impl Grid {
+    /// Puts an extra `cost` on moving from the cell at `from` to its neighbor at `to`. 0 removes
+    /// it and `u8::MAX` forbids the move. Flowfields built before pick it up when rebuilt.
+    pub fn set_edge_cost(&mut self, from: IVec2, to: IVec2, cost: u8) {
+        match cost {
+            0 => self.edge_costs.0.remove(&(from, to)),
+            cost => self.edge_costs.0.insert((from, to), cost),
+        };
+    }
+
+    /// Lets units move from the cell at `from` to its neighbor at `to`, but never back
+    pub fn set_one_way(&mut self, from: IVec2, to: IVec2) {
+        self.set_edge_cost(from, to, 0);
+        self.set_edge_cost(to, from, u8::MAX);
+    }
+
+    pub fn edge_cost(&self, from: IVec2, to: IVec2) -> u8 {
+        self.edge_costs.cost(from, to)
+    }
+
+    pub fn edge_costs(&self) -> &EdgeCosts {
+        &self.edge_costs
+    }
+}
@@ -0,0 +1,90 @@
+//! Islands are the connected parts of the grid a movement domain, like ground, naval or air
+//! units, can cross. Unlike regions they're partitioned once, typically right after building the
+//! grid, and don't follow later cost changes.
+
+use super::{coords, Grid};
+use crate::cell::Cell;
+
+use bevy::prelude::*;
+use std::collections::VecDeque;
+
+/// Label of cells a domain can't cross
+const NO_ISLAND: u32 = 0;
+
+/// The island labels of one movement domain, see `Grid::partition_islands`
+#[derive(Clone, Debug, Default, Reflect)]
+pub struct Islands {
+    labels: Vec<Vec<u32>>,
+    count: u32,
+}
+
+impl Islands {
+    /// Follows the cell indices after the grid was expanded. The new cells belong to no island.
+    pub(super) fn shift(&mut self, border: i32, size: IVec2) {
+        let mut labels = vec![vec![NO_ISLAND; size.x as usize]; size.y as usize];
+        for (y, row) in self.labels.iter().enumerate() {
+            for (x, label) in row.iter().enumerate() {
+                labels[y + border as usize][x + border as usize] = *label;
+            }
+        }
+
+        self.labels = labels;
+    }
+}
+
+impl Grid {
+    /// Labels every cell `passable` accepts with the island of `domain` it belongs to, replacing
+    /// the domain's earlier islands. Returns the number of islands.
+    pub fn partition_islands(&mut self, domain: &str, passable: impl Fn(&Cell) -> bool) -> u32 {
+        let mut islands = Islands {
+            labels: vec![vec![NO_ISLAND; self.size.x as usize]; self.size.y as usize],
+            count: 0,
+        };
+
+        for start in self.grid.iter().flatten() {
+            let start_idx = start.idx;
+            let label = &mut islands.labels[start_idx.y as usize][start_idx.x as usize];
+            if *label != NO_ISLAND || !passable(start) {
+                continue;
+            }
+
+            islands.count += 1;
+            *label = islands.count;
+
+            let mut queue = VecDeque::from([start_idx]);
+            while let Some(idx) = queue.pop_front() {
+                for direction in self.region_connectivity.integration_directions(idx) {
+                    let neighbor = idx + direction.vector();
+                    let Some(cell) = self.cell(neighbor) else {
+                        continue;
+                    };
+
+                    let label = &mut islands.labels[neighbor.y as usize][neighbor.x as usize];
+                    if *label == NO_ISLAND && passable(cell) {
+                        *label = islands.count;
+                        queue.push_back(neighbor);
+                    }
+                }
+            }
+        }
+
+        let count = islands.count;
+        self.islands.insert(domain.to_string(), islands);
+        return count;
+    }
+
+    /// The island of `domain` at `world_pos`, `None` if the domain can't cross the cell, the
+    /// position is off the grid or the domain was never partitioned
+    pub fn island_of(&self, world_pos: Vec3, domain: &str) -> Option<u32> {
+        let idx = coords::world_to_idx(world_pos, self.size, self.cell_diameter)?;
+        let label = self.islands.get(domain)?.labels[idx.y as usize][idx.x as usize];
+
+        return Some(label).filter(|label| *label != NO_ISLAND);
+    }
+
+    /// True if a unit of `domain` at `from` could ever travel to `to`
+    pub fn same_island(&self, from: Vec3, to: Vec3, domain: &str) -> bool {
+        let from = self.island_of(from, domain);
+        return from.is_some() && from == self.island_of(to, domain);
+    }
+}
@@ -0,0 +1,1075 @@
+use crate::{
+    cell::Cell,
+    components::*,
+    events::{ClearPaintEv, CostfieldChangedEv, GridExpandedEv, MapChangedEv, PaintCostEv},
+    grid_direction::{GridDirection, GridDirection as D},
+    obstacles::ObstacleCells,
+    PathfindingSchedule, PathfindingSet, UpdateCostEv,
+};
+
+use bevy::{ecs::system::SystemParam, prelude::*, render::mesh::MeshAabb};
+use std::collections::{HashMap, HashSet};
+
+pub mod border;
+pub mod cell_data;
+pub mod coords;
+pub mod costs;
+pub mod edges;
+pub mod islands;
+mod paint;
+mod raycast;
+mod rebuild;
+mod regions;
+pub mod scene;
+pub mod symmetry;
+pub mod temporary;
+
+use border::GridSettings;
+use cell_data::CellLayers;
+use coords::EdgePolicies;
+use costs::CostLayers;
+use edges::EdgeCosts;
+use islands::Islands;
+use temporary::{TemporaryCostChanges, TemporaryCosts};
+
+pub struct GridPlugin;
+
+impl Plugin for GridPlugin {
+    fn build(&self, app: &mut App) {
+        let schedule = PathfindingSchedule::label(app);
+        app.register_type::<Grid>()
+            .register_type::<OccupiedCells>()
+            .init_resource::<OccupiedCells>()
+            .init_resource::<TemporaryCostChanges>()
+            .register_type::<OutOfBoundsPolicy>()
+            .register_type::<Connectivity>()
+            .register_type::<coords::EdgePolicies>()
+            .register_type::<symmetry::Symmetry>()
+            .register_type::<scene::GridScene>()
+            .register_type::<GridSettings>()
+            .init_resource::<GridSettings>()
+            .init_resource::<OutOfBoundsPolicy>()
+            .init_resource::<Connectivity>()
+            .add_event::<UpdateCostEv>()
+            .add_event::<CostfieldChangedEv>()
+            .add_event::<GridExpandedEv>()
+            .add_event::<MapChangedEv>()
+            .add_event::<PaintCostEv>()
+            .add_event::<ClearPaintEv>()
+            .add_systems(
+                schedule,
+                (
+                    scene::build_scene_grids,
+                    rebuild::detect_map_change,
+                    rebuild::rebuild_grid,
+                    border::apply_grid_settings,
+                    shift_occupied_cells,
+                    (
+                        update_costs,
+                        update_map_costs,
+                        paint::paint_costs,
+                        temporary::tick_temporary_costs,
+                        // Headless apps have no meshes to size from
+                        size_rts_objs_from_mesh.run_if(resource_exists::<Assets<Mesh>>),
+                    )
+                        .in_set(PathfindingSet::UpdateCosts),
+                )
+                    .chain(),
+            )
+            .add_systems(
+                schedule,
+                (publish_costfield_changes, regions::update_regions)
+                    .chain()
+                    .after(PathfindingSet::UpdateCosts)
+                    .before(PathfindingSet::BuildFields),
+            )
+            .add_systems(
+                schedule,
+                temporary::repair_temporary_flowfields.in_set(PathfindingSet::BuildFields),
+            );
+    }
+}
+
+/// Which neighbors `Grid::neighbors` yields
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DirectionSet {
+    /// North, east, south and west
+    Cardinal,
+    /// Cardinal and diagonal
+    All,
+}
+
+const CARDINAL: [GridDirection; 4] = [D::North, D::East, D::South, D::West];
+const CARDINAL_FLOW: [GridDirection; 5] = [D::None, D::North, D::East, D::South, D::West];
+const OCTILE_FLOW: [GridDirection; 9] = [
+    D::None,
+    D::North,
+    D::NorthEast,
+    D::East,
+    D::SouthEast,
+    D::South,
+    D::SouthWest,
+    D::West,
+    D::NorthWest,
+];
+// Odd rows are shifted half a cell east, so the diagonal neighbors depend on the row
+const HEX_EVEN: [GridDirection; 6] = [
+    D::North,
+    D::East,
+    D::South,
+    D::SouthWest,
+    D::West,
+    D::NorthWest,
+];
+const HEX_ODD: [GridDirection; 6] = [
+    D::NorthEast,
+    D::East,
+    D::SouthEast,
+    D::South,
+    D::West,
+    D::North,
+];
+const HEX_EVEN_FLOW: [GridDirection; 7] = [
+    D::None,
+    D::North,
+    D::East,
+    D::South,
+    D::SouthWest,
+    D::West,
+    D::NorthWest,
+];
+const HEX_ODD_FLOW: [GridDirection; 7] = [
+    D::None,
+    D::NorthEast,
+    D::East,
+    D::SouthEast,
+    D::South,
+    D::West,
+    D::North,
+];
+
+/// Which neighbors costs spread to and units move between
+#[derive(Resource, Reflect, Default, Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "config", derive(serde::Serialize, serde::Deserialize))]
+#[reflect(Resource)]
+pub enum Connectivity {
+    /// Only north, east, south and west
+    Cardinal4,
+    /// Costs spread over cardinal neighbors and units head to any of the 8 neighbors
+    #[default]
+    Octile8,
+    /// Six neighbors, with odd rows shifted half a cell east ("odd-r" offset coordinates).
+    /// See `coords::offset_to_axial` for axial coordinates.
+    Hex,
+}
+
+impl Connectivity {
+    /// The neighbors integration spreads costs to from the cell at `idx`
+    pub(crate) fn integration_directions(self, idx: IVec2) -> &'static [GridDirection] {
+        match self {
+            Connectivity::Cardinal4 | Connectivity::Octile8 => &CARDINAL,
+            Connectivity::Hex if idx.y % 2 == 0 => &HEX_EVEN,
+            Connectivity::Hex => &HEX_ODD,
+        }
+    }
+
+    /// The directions a cell at `idx` can point in, starting with `None`
+    pub(crate) fn flow_directions(self, idx: IVec2) -> &'static [GridDirection] {
+        match self {
+            Connectivity::Cardinal4 => &CARDINAL_FLOW,
+            Connectivity::Octile8 => &OCTILE_FLOW,
+            Connectivity::Hex if idx.y % 2 == 0 => &HEX_EVEN_FLOW,
+            Connectivity::Hex => &HEX_ODD_FLOW,
+        }
+    }
+}
+
+/// What a move order to a destination outside the grid does
+#[derive(Resource, Reflect, Default, Clone, Copy, Debug, PartialEq, Eq)]
+#[reflect(Resource)]
+pub enum OutOfBoundsPolicy {
+    /// Head to the nearest edge cell
+    #[default]
+    Clamp,
+    /// Drop the order and send a `DestinationOutOfBoundsEv`
+    Reject,
+    /// Grow the grid, and its layers, until it contains the destination. Map grids don't grow,
+    /// orders on them clamp instead, see `OutOfBoundsPolicy::on_map_grid`.
+    Expand,
+}
+
+impl OutOfBoundsPolicy {
+    /// The policy orders on map grids follow, `Expand` clamps since only the `Grid` resource
+    /// grows
+    pub fn on_map_grid(self) -> Self {
+        match self {
+            OutOfBoundsPolicy::Expand => OutOfBoundsPolicy::Clamp,
+            policy => policy,
+        }
+    }
+}
+
+#[derive(Resource, Component, Default, Reflect)]
+#[reflect(Resource, Component)]
+pub struct OccupiedCells(HashSet<IVec2>);
+
+/// The costfield units path over. The `Grid` resource is the default map. Further independent
+/// maps are `Grid` components on map entities, whose units and obstacles carry `OnGrid`.
+/// Interiors, layers, connectors and grid expansion only apply to the resource.
+#[derive(Resource, Component, Reflect, Clone)]
+#[reflect(Resource, Component)]
+#[require(OccupiedCells, ObstacleCells)]
+pub struct Grid {
+    pub size: IVec2,
+    pub cell_radius: f32,
+    pub cell_diameter: f32,
+    pub grid: Vec<Vec<Cell>>,
+    /// Bumped every frame the costs change, see `CostfieldChangedEv`
+    pub version: u64,
+    /// The grid's orientation about the Y axis, for maps under a turned parent whose units path
+    /// in the grid's own space. Cells stay axis aligned, flow directions are turned into the
+    /// world with it, see `GridDirection::to_world`.
+    pub rotation: Quat,
+    // sources of cost composited into `Cell::cost`, see `Grid::set_layer_cost`
+    cost_layers: CostLayers,
+    // extra costs of moving between neighbors in one direction, see `Grid::set_edge_cost`
+    edge_costs: EdgeCosts,
+    // whether each axis wraps around, see `Grid::set_edge_policies`
+    edge_policies: EdgePolicies,
+    // connected region of every walkable cell, see `Grid::is_reachable`
+    regions: Vec<Vec<u32>>,
+    next_region: u32,
+    region_connectivity: Connectivity,
+    // islands of every movement domain, see `Grid::partition_islands`
+    islands: HashMap<String, Islands>,
+    // costs that expire on their own, see `Grid::add_temporary_cost`
+    temporary_costs: TemporaryCosts,
+    // rings and cost of the border along the edges, see `Grid::set_border`
+    border: (u32, u8),
+    // gameplay data games keep for every cell, see `Grid::layer`
+    #[reflect(ignore)]
+    cell_layers: CellLayers,
+}
+
+impl Grid {
+    // creates the grid and the costfield
+    // all flowfields will share the same costfield
+    // grids have at least one cell along each axis, smaller sizes are raised to 1
+    pub fn new<F>(size: IVec2, cell_diameter: f32, mut collision_checker: F) -> Self
+    where
+        F: FnMut(Vec3) -> bool,
+    {
+        let _span = info_span!("grid_rasterize").entered();
+        if size.min_element() < 1 {
+            warn!("Grid size {size} raised to at least one cell along each axis");
+        }
+
+        let size = size.max(IVec2::ONE);
+        let mut grid = Grid {
+            size,
+            cell_diameter,
+            cell_radius: cell_diameter / 2.0,
+            grid: Vec::default(),
+            version: 0,
+            rotation: Quat::IDENTITY,
+            cost_layers: CostLayers::default(),
+            edge_costs: EdgeCosts::default(),
+            edge_policies: EdgePolicies::default(),
+            regions: Vec::new(),
+            next_region: 0,
+            region_connectivity: Connectivity::default(),
+            islands: HashMap::new(),
+            temporary_costs: TemporaryCosts::default(),
+            border: (0, u8::MAX),
+            cell_layers: CellLayers::default(),
+        };
+
+        // Initialize Grid
+        grid.grid = (0..grid.size.y)
+            .map(|y| {
+                (0..grid.size.x)
+                    .map(|x| {
+                        let idx = IVec2::new(x, y);
+                        let world_pos = coords::idx_to_world(idx, grid.size, grid.cell_diameter);
+                        Cell::new(world_pos, idx)
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        // Create Costfield
+        for y in 0..grid.size.y {
+            for x in 0..grid.size.x {
+                let world_pos = grid.grid[y as usize][x as usize].world_pos;
+
+                if collision_checker(world_pos) {
+                    grid.grid[y as usize][x as usize].increase_cost(255);
+                }
+            }
+        }
+
+        grid.label_regions(Connectivity::default());
+        grid
+    }
+
+    /// The cell containing `world_pos`, or the nearest edge cell if it's off the grid. Positions
+    /// past a wrapping edge land on the cell across it.
+    pub fn get_cell_from_world_position(&self, world_pos: Vec3) -> Cell {
+        let world_pos = self.wrap_world(world_pos);
+        let idx = coords::world_to_idx_clamped(world_pos, self.size, self.cell_diameter);
+        return self.grid[idx.y as usize][idx.x as usize];
+    }
+
+    /// The cell containing `world_pos`, or `None` if it's off the grid past a clamped edge
+    pub fn try_get_cell_from_world_position(&self, world_pos: Vec3) -> Option<Cell> {
+        let world_pos = self.wrap_world(world_pos);
+        let idx = coords::world_to_idx(world_pos, self.size, self.cell_diameter)?;
+        return Some(self.grid[idx.y as usize][idx.x as usize]);
+    }
+
+    pub fn edge_policies(&self) -> EdgePolicies {
+        self.edge_policies
+    }
+
+    /// Makes either axis wrap around, joining its edges for maps like a globe's east and west.
+    /// Flowfields and regions then spread across the joined edges.
+    pub fn set_edge_policies(&mut self, edge_policies: EdgePolicies) {
+        if self.edge_policies == edge_policies {
+            return;
+        }
+
+        self.edge_policies = edge_policies;
+        self.set_border(self.border.0, self.border.1);
+        self.version += 1;
+        self.label_regions(self.region_connectivity);
+    }
+
+    /// `world_pos` carried across wrapping edges back onto the grid
+    pub fn wrap_world(&self, world_pos: Vec3) -> Vec3 {
+        self.edge_policies
+            .wrap_world(world_pos, self.size, self.cell_diameter)
+    }
+
+    /// The neighbors of the cell at `idx`, across wrapping edges
+    pub fn neighbors(&self, idx: IVec2, directions: DirectionSet) -> impl Iterator<Item = &Cell> {
+        let directions = match directions {
+            DirectionSet::Cardinal => GridDirection::cardinal_directions(),
+            DirectionSet::All => GridDirection::cardinal_and_intercardinal_directions(),
+        };
+
+        directions.into_iter().filter_map(move |direction| {
+            let neighbor = self
+                .edge_policies
+                .wrap(idx + direction.vector(), self.size)?;
+            self.cell(neighbor)
+        })
+    }
+
+    /// The cells whose center is within `radius` of `world_pos` on the XZ plane
+    pub fn cells_in_radius(&self, world_pos: Vec3, radius: f32) -> impl Iterator<Item = &Cell> {
+        let extent = Vec3::new(radius, 0.0, radius);
+        let radius_squared = radius * radius;
+
+        self.cells_in_rect(world_pos - extent, world_pos + extent)
+            .filter(move |cell| {
+                cell.world_pos.xz().distance_squared(world_pos.xz()) <= radius_squared
+            })
+    }
+
+    /// The cells overlapping the XZ rectangle between the world positions `min` and `max`
+    pub fn cells_in_rect(&self, min: Vec3, max: Vec3) -> impl Iterator<Item = &Cell> {
+        let min_idx = coords::world_to_idx_unclamped(min, self.size, self.cell_diameter);
+        let max_idx = coords::world_to_idx_unclamped(max, self.size, self.cell_diameter);
+        let min_idx = min_idx.max(IVec2::ZERO);
+        let max_idx = max_idx.min(self.size - 1);
+
+        (min_idx.y..=max_idx.y).flat_map(move |y| {
+            (min_idx.x..=max_idx.x).map(move |x| &self.grid[y as usize][x as usize])
+        })
+    }
+
+    /// The cells overlapped by a convex polygon of world XZ points, clipped to the grid. Each row
+    /// of cells covers the polygon's extent within that row, so a concave polygon is filled in.
+    pub fn cells_in_polygon(&self, polygon: &[Vec2]) -> Vec<IVec2> {
+        let points: Vec<Vec2> = polygon
+            .iter()
+            .map(|p| coords::to_grid_space(Vec3::new(p.x, 0.0, p.y), self.size, self.cell_diameter))
+            .collect();
+        let Some(first) = points.first() else {
+            return Vec::new();
+        };
+
+        let (min_y, max_y) = points.iter().fold((first.y, first.y), |(min, max), p| {
+            (min.min(p.y), max.max(p.y))
+        });
+        let rows = (min_y.floor() as i32).max(0)..=(max_y.floor() as i32).min(self.size.y - 1);
+        let mut cells = Vec::new();
+
+        for y in rows {
+            let Some((min_x, max_x)) = row_extent(&points, y as f32, y as f32 + 1.0) else {
+                continue;
+            };
+
+            let min_x = (min_x.floor() as i32).max(0);
+            let max_x = (max_x.floor() as i32).min(self.size.x - 1);
+            cells.extend((min_x..=max_x).map(|x| IVec2::new(x, y)));
+        }
+
+        return cells;
+    }
+
+    /// The cell at `idx`, or `None` if it's outside the grid
+    pub fn cell(&self, idx: IVec2) -> Option<&Cell> {
+        if !coords::in_bounds(idx, self.size) {
+            return None;
+        }
+
+        return Some(&self.grid[idx.y as usize][idx.x as usize]);
+    }
+
+    /// The XZ area covered by the grid, with `Rect::min.y` being the minimum world z
+    pub fn world_bounds(&self) -> Rect {
+        let half_size = self.size.as_vec2() * self.cell_diameter / 2.0;
+        return Rect::from_corners(-half_size, half_size);
+    }
+
+    pub fn contains_world_pos(&self, world_pos: Vec3) -> bool {
+        self.world_bounds().contains(world_pos.xz())
+    }
+
+    /// The number of cells to add on every side for `world_pos` to be on the grid
+    pub fn border_to_contain(&self, world_pos: Vec3) -> i32 {
+        let half_size = self.size.as_vec2() * self.cell_diameter / 2.0;
+        let outside = world_pos.xz().abs() - half_size;
+        return (outside.max_element() / self.cell_diameter).ceil().max(0.0) as i32;
+    }
+
+    /// Grows the grid by `border` cells on every side. Existing cells keep their world position
+    /// and cost, their index shifts by `border`. New cells get `cost`.
+    pub fn expand(&mut self, border: i32, cost: u8) {
+        if border <= 0 {
+            return;
+        }
+
+        let height = self
+            .grid
+            .iter()
+            .flatten()
+            .next()
+            .map_or(0.0, |cell| cell.world_pos.y);
+        let mut expanded = Grid::new(self.size + 2 * border, self.cell_diameter, |_| false);
+
+        for cell in expanded.grid.iter_mut().flatten() {
+            cell.cost = cost;
+            cell.world_pos.y = height;
+        }
+
+        for cell in std::mem::take(&mut self.grid).into_iter().flatten() {
+            let idx = cell.idx + border;
+            expanded.grid[idx.y as usize][idx.x as usize] = Cell { idx, ..cell };
+        }
+
+        expanded.cost_layers = std::mem::take(&mut self.cost_layers);
+        expanded.cost_layers.shift(border);
+        expanded.edge_costs = std::mem::take(&mut self.edge_costs);
+        expanded.edge_costs.shift(border);
+        expanded.edge_policies = self.edge_policies;
+        expanded.rotation = self.rotation;
+        expanded.islands = std::mem::take(&mut self.islands);
+        for islands in expanded.islands.values_mut() {
+            islands.shift(border, expanded.size);
+        }
+        expanded.temporary_costs = std::mem::take(&mut self.temporary_costs);
+        expanded.temporary_costs.shift(border);
+        expanded.cell_layers = std::mem::take(&mut self.cell_layers);
+        expanded.cell_layers.shift(border);
+        expanded.set_border(self.border.0, self.border.1);
+        expanded.version = self.version + 1;
+        expanded.label_regions(self.region_connectivity);
+        *self = expanded;
+    }
+
+    pub fn reset_costs(&mut self, units: Vec<(Vec3, Vec2)>) {
+        for (unit_pos, unit_size) in units.iter() {
+            let half_extents = Vec3::new(unit_size.x, 0.0, unit_size.y);
+            let idxs: Vec<IVec2> = self
+                .cells_in_rect(*unit_pos - half_extents, *unit_pos + half_extents)
+                .map(|cell| cell.idx)
+                .collect();
+
+            for idx in idxs {
+                self.clear_layer_cost(costs::UNIT_LAYER, idx);
+            }
+        }
+    }
+
+    pub fn update_unit_cell_costs(&mut self, position: Vec3) -> Cell {
+        // Determine which cell the unit occupies, always on the grid since it's clamped
+        let idx = self.get_cell_from_world_position(position).idx;
+        self.set_layer_cost(costs::UNIT_LAYER, idx, u8::MAX);
+
+        return self.grid[idx.y as usize][idx.x as usize];
+    }
+}
+
+/// The `Grid` resource and the grids of map entities, looked up by the map a unit, event or
+/// flowfield belongs to
+#[derive(SystemParam)]
+pub struct Grids<'w, 's> {
+    main: Res<'w, Grid>,
+    maps: Query<'w, 's, &'static Grid>,
+}
+
+impl Grids<'_, '_> {
+    /// The grid of `map`, or the `Grid` resource for `None`
+    pub fn get(&self, map: Option<Entity>) -> Option<&Grid> {
+        match map {
+            Some(map) => self.maps.get(map).ok(),
+            None => Some(&self.main),
+        }
+    }
+}
+
+/// Blocks the cells units stand in and frees the ones they left, returning the changed cells
+fn occupy_unit_cells(
+    grid: &mut Grid,
+    occupied_cells: &mut OccupiedCells,
+    positions: &[Vec3],
+) -> Vec<Cell> {
+    if positions.is_empty() && occupied_cells.0.is_empty() {
+        return Vec::new();
+    }
+
+    let mut changed = Vec::new();
+    let mut current_occupied = HashSet::new();
+
+    // Mark cells occupied by units
+    for position in positions.iter() {
+        let old_cost = grid.get_cell_from_world_position(*position).cost;
+        let cell = grid.update_unit_cell_costs(*position);
+        current_occupied.insert(cell.idx);
+
+        // Report newly occupied cells
+        if cell.cost != old_cost {
+            changed.push(cell);
+        }
+    }
+
+    // Free previously occupied cells that are no longer occupied
+    for idx in occupied_cells.0.difference(&current_occupied) {
+        if grid.clear_layer_cost(costs::UNIT_LAYER, *idx) {
+            changed.push(grid.grid[idx.y as usize][idx.x as usize]);
+        }
+    }
+
+    // Update the occupied cells set
+    occupied_cells.0 = current_occupied;
+    changed
+}
+
+pub fn update_costs(
+    mut grid: ResMut<Grid>,
+    mut events: EventWriter<UpdateCostEv>,
+    mut occupied_cells: ResMut<OccupiedCells>,
+    q_units: Query<&Transform, (With<Destination>, Without<OnGrid>)>,
+) {
+    // Cells stay occupied until the last moving unit stopped or despawned is freed
+    if q_units.is_empty() && occupied_cells.0.is_empty() {
+        return;
+    }
+
+    println!("updating costs");
+    let positions: Vec<Vec3> = q_units.iter().map(|t| t.translation).collect();
+    for cell in occupy_unit_cells(&mut grid, &mut occupied_cells, &positions) {
+        events.send(UpdateCostEv::new(cell));
+    }
+}
+
+/// `update_costs` for the grids of map entities
+fn update_map_costs(
+    mut events: EventWriter<UpdateCostEv>,
+    mut q_maps: Query<(Entity, &mut Grid, &mut OccupiedCells)>,
+    q_units: Query<(&Transform, &OnGrid), With<Destination>>,
+) {
+    let mut positions: HashMap<Entity, Vec<Vec3>> = HashMap::new();
+    for (transform, on_grid) in q_units.iter() {
+        positions
+            .entry(on_grid.0)
+            .or_default()
+            .push(transform.translation);
+    }
+
+    for (map, mut grid, mut occupied_cells) in q_maps.iter_mut() {
+        let positions = positions.remove(&map).unwrap_or_default();
+        for cell in occupy_unit_cells(&mut grid, &mut occupied_cells, &positions) {
+            events.send(UpdateCostEv::on_map(cell, map));
+        }
+    }
+}
+
+/// Bumps `Grid::version` and reports the changed cells once per frame with cost changes,
+/// separately for every map
+fn publish_costfield_changes(
+    mut grid: ResMut<Grid>,
+    mut q_maps: Query<&mut Grid>,
+    mut events: EventReader<UpdateCostEv>,
+    mut changed: EventWriter<CostfieldChangedEv>,
+) {
+    let mut cells: HashMap<Option<Entity>, HashSet<IVec2>> = HashMap::new();
+    for ev in events.read() {
+        cells.entry(ev.map).or_default().insert(ev.cell.idx);
+    }
+
+    for (map, cells) in cells {
+        let grid = match map {
+            Some(map) => match q_maps.get_mut(map) {
+                Ok(grid) => grid.into_inner(),
+                Err(_) => continue,
+            },
+            None => grid.as_mut(),
+        };
+
+        grid.version += 1;
+        changed.send(CostfieldChangedEv::new(
+            map,
+            grid.version,
+            cells.into_iter().collect(),
+        ));
+    }
+}
+
+fn shift_occupied_cells(
+    mut events: EventReader<GridExpandedEv>,
+    mut occupied_cells: ResMut<OccupiedCells>,
+) {
+    for ev in events.read() {
+        occupied_cells.0 = occupied_cells
+            .0
+            .iter()
+            .map(|idx| *idx + ev.border)
+            .collect();
+    }
+}
+
+/// The x extent of a convex polygon between the horizontal lines `top` and `bottom`, made up of
+/// its vertices between them and the points where its edges cross them
+fn row_extent(points: &[Vec2], top: f32, bottom: f32) -> Option<(f32, f32)> {
+    let mut extent: Option<(f32, f32)> = None;
+    let mut include = |x: f32| {
+        extent = Some(match extent {
+            Some((min, max)) => (min.min(x), max.max(x)),
+            None => (x, x),
+        });
+    };
+
+    for (i, a) in points.iter().enumerate() {
+        let b = points[(i + 1) % points.len()];
+        if a.y >= top && a.y <= bottom {
+            include(a.x);
+        }
+
+        for line in [top, bottom] {
+            let crosses = (a.y < line && b.y > line) || (a.y > line && b.y < line);
+            if crosses {
+                let t = (line - a.y) / (b.y - a.y);
+                include(a.x + (b.x - a.x) * t);
+            }
+        }
+    }
+
+    return extent;
+}
+
+/// Gives `RtsObj`s spawned without an `RtsObjSize` a footprint from their mesh bounds, and keeps
+/// auto sized footprints in sync when the mesh is swapped or modified
+fn size_rts_objs_from_mesh(
+    mut cmds: Commands,
+    meshes: Res<Assets<Mesh>>,
+    mut mesh_events: EventReader<AssetEvent<Mesh>>,
+    q_unsized: Query<(Entity, &Mesh3d, &GlobalTransform), (With<RtsObj>, Without<RtsObjSize>)>,
+    q_auto_sized: Query<(Entity, Ref<Mesh3d>, &GlobalTransform), With<AutoSized>>,
+) {
+    let modified: HashSet<AssetId<Mesh>> = mesh_events
+        .read()
+        .filter_map(|ev| match ev {
+            AssetEvent::Modified { id } => Some(*id),
+            _ => None,
+        })
+        .collect();
+
+    let footprint = |mesh: &Mesh3d, transform: &GlobalTransform| -> Option<Vec2> {
+        let aabb = meshes.get(mesh)?.compute_aabb()?;
+
+        // Rotated and scaled half extents, projected onto the XZ plane
+        let half_extents = transform.affine().matrix3.abs() * aabb.half_extents;
+        Some(Vec2::new(half_extents.x, half_extents.z))
+    };
+
+    // The mesh may still be loading, in which case this is retried next frame
+    for (entity, mesh, transform) in q_unsized.iter() {
+        if let Some(size) = footprint(mesh, transform) {
+            cmds.entity(entity).insert((RtsObjSize(size), AutoSized));
+        }
+    }
+
+    for (entity, mesh, transform) in q_auto_sized.iter() {
+        if !mesh.is_changed() && !modified.contains(&mesh.id()) {
+            continue;
+        }
+
+        if let Some(size) = footprint(&mesh, transform) {
+            cmds.entity(entity).insert(RtsObjSize(size));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn idxs<'a>(cells: impl Iterator<Item = &'a Cell>) -> Vec<IVec2> {
+        cells.map(|cell| cell.idx).collect()
+    }
+
+    #[test]
+    fn neighbors_stay_in_bounds() {
+        let grid = Grid::new(IVec2::new(3, 3), 1.0, |_| false);
+
+        assert_eq!(
+            grid.neighbors(IVec2::new(1, 1), DirectionSet::All).count(),
+            8
+        );
+        assert_eq!(
+            idxs(grid.neighbors(IVec2::ZERO, DirectionSet::Cardinal)),
+            vec![IVec2::new(1, 0), IVec2::new(0, 1)]
+        );
+    }
+
+    #[test]
+    fn mirrored_maps_are_fair_to_both_players() {
+        use crate::flowfield::IntegrationMethod;
+        use symmetry::Symmetry;
+
+        // Terrain is generated on the west half only
+        let mut grid = Grid::new(IVec2::new(6, 4), 1.0, |_| false);
+        grid.set_base_cost(IVec2::new(1, 1), u8::MAX);
+        grid.set_base_cost(IVec2::new(2, 3), 5);
+        grid.set_layer_cost(costs::EDITOR_LAYER, IVec2::new(0, 2), 9);
+        assert!(!grid.asymmetric_cells(Symmetry::MirrorX).is_empty());
+
+        let mut changed = grid.mirror_base_costs(Symmetry::MirrorX);
+        changed.extend(grid.mirror_layer(costs::EDITOR_LAYER, Symmetry::MirrorX));
+        assert_eq!(changed.len(), 3);
+        assert_eq!(grid.cell(IVec2::new(4, 1)).unwrap().cost, u8::MAX);
+        assert_eq!(grid.cell(IVec2::new(3, 3)).unwrap().cost, 5);
+        assert_eq!(grid.cell(IVec2::new(5, 2)).unwrap().cost, 9);
+        assert!(grid.asymmetric_cells(Symmetry::MirrorX).is_empty());
+
+        let start = IVec2::new(0, 0);
+        let fair = |grid: &Grid| {
+            grid.integration_asymmetries(
+                Symmetry::MirrorX,
+                start,
+                Connectivity::Octile8,
+                IntegrationMethod::Dijkstra,
+            )
+        };
+        assert!(fair(&grid).is_empty());
+
+        // A wall on one side only makes the other player's side faster to cross
+        grid.set_base_cost(IVec2::new(1, 0), u8::MAX);
+        assert!(fair(&grid).contains(&IVec2::new(2, 0)));
+
+        // Quarter turns visit all four corners
+        let corners = Symmetry::Rotate90.orbit(IVec2::ZERO, IVec2::splat(4));
+        assert_eq!(
+            corners,
+            vec![
+                IVec2::ZERO,
+                IVec2::new(3, 0),
+                IVec2::new(3, 3),
+                IVec2::new(0, 3)
+            ]
+        );
+    }
+
+    #[test]
+    fn cells_in_rect_is_clipped_to_the_grid() {
+        let grid = Grid::new(IVec2::new(4, 4), 1.0, |_| false);
+
+        // Cells span -2..2 on both axes
+        let cells = idxs(grid.cells_in_rect(Vec3::new(-5.0, 0.0, -0.5), Vec3::new(-0.5, 0.0, 0.5)));
+        assert_eq!(
+            cells,
+            vec![
+                IVec2::new(0, 1),
+                IVec2::new(1, 1),
+                IVec2::new(0, 2),
+                IVec2::new(1, 2)
+            ]
+        );
+
+        let outside = grid.cells_in_rect(Vec3::new(3.0, 0.0, 3.0), Vec3::new(4.0, 0.0, 4.0));
+        assert_eq!(outside.count(), 0);
+    }
+
+    #[test]
+    fn cost_layers_blend_by_priority_and_restore_the_base_cost() {
+        let mut grid = Grid::new(IVec2::new(3, 3), 1.0, |_| false);
+        let idx = IVec2::new(1, 1);
+        let cost = |grid: &Grid| grid.cell(idx).unwrap().cost;
+
+        grid.set_base_cost(idx, 5);
+        grid.add_cost_layer("danger", 10, costs::BlendOp::Add);
+        assert!(grid.set_layer_cost("danger", idx, 20));
+        assert!(grid.set_layer_cost(costs::RESERVATION_LAYER, idx, 200));
+        assert_eq!(cost(&grid), 200);
+
+        // Reservations apply after the danger layer once they have a lower priority
+        grid.add_cost_layer(costs::RESERVATION_LAYER, 0, costs::BlendOp::Max);
+        assert_eq!(cost(&grid), 220);
+
+        assert!(grid.set_layer_cost(costs::UNIT_LAYER, idx, u8::MAX));
+        assert_eq!(cost(&grid), u8::MAX);
+
+        grid.clear_layer_cost(costs::UNIT_LAYER, idx);
+        grid.clear_layer_cost(costs::RESERVATION_LAYER, idx);
+        grid.clear_layer_cost("danger", idx);
+        assert_eq!(cost(&grid), 5);
+        assert_eq!(grid.base_cost(idx), Some(5));
+    }
+
+    #[test]
+    fn polygon_footprint_matches_rect_and_trims_diagonals() {
+        let grid = Grid::new(IVec2::new(10, 10), 1.0, |_| false);
+
+        // An axis aligned rectangle covers the same cells as its AABB
+        let (min, max) = (Vec2::new(-2.3, -1.4), Vec2::new(1.6, 0.7));
+        let rect = [min, Vec2::new(max.x, min.y), max, Vec2::new(min.x, max.y)];
+        let aabb = idxs(grid.cells_in_rect(min.extend(0.0).xzy(), max.extend(0.0).xzy()));
+        assert_eq!(grid.cells_in_polygon(&rect), aabb);
+
+        // A diamond leaves out the corners of its AABB, but keeps every cell it touches
+        let diamond = [
+            Vec2::new(0.0, -3.0),
+            Vec2::new(3.0, 0.0),
+            Vec2::new(0.0, 3.0),
+            Vec2::new(-3.0, 0.0),
+        ];
+        let cells = grid.cells_in_polygon(&diamond);
+        let aabb = idxs(grid.cells_in_rect(Vec3::new(-3.0, 0.0, -3.0), Vec3::new(3.0, 0.0, 3.0)));
+
+        assert!(cells.iter().all(|idx| aabb.contains(idx)));
+        assert!(cells.len() < aabb.len());
+        assert!(!cells.contains(&IVec2::new(2, 2)));
+        assert!(cells.contains(&IVec2::new(5, 2)));
+        assert!(cells.contains(&IVec2::new(4, 3)));
+        assert!(cells.contains(&IVec2::new(7, 5)));
+    }
+
+    #[test]
+    fn expand_keeps_cells_in_place() {
+        let mut grid = Grid::new(IVec2::new(2, 2), 1.0, |_| false);
+        grid.grid[0][1].cost = 7;
+        let far = Vec3::new(3.5, 0.0, 0.0);
+
+        assert!(!grid.contains_world_pos(far));
+        grid.expand(grid.border_to_contain(far), 1);
+
+        assert!(grid.contains_world_pos(far));
+        assert_eq!(grid.size, IVec2::new(8, 8));
+        let moved = grid.get_cell_from_world_position(Vec3::new(0.5, 0.0, -0.5));
+        assert_eq!(moved.idx, IVec2::new(4, 3));
+        assert_eq!(moved.cost, 7);
+    }
+
+    #[test]
+    fn regions_follow_walls_opening_and_closing() {
+        // A wall down the middle column splits the grid in two
+        let mut grid = Grid::new(IVec2::new(5, 3), 1.0, |pos| pos.x.abs() < 0.5);
+        let left = Vec3::new(-2.0, 0.0, 0.0);
+        let right = Vec3::new(2.0, 0.0, 0.0);
+        assert!(!grid.is_reachable(left, right));
+        assert!(grid.is_reachable(left, Vec3::new(-1.0, 0.0, 1.0)));
+
+        let gap = IVec2::new(2, 1);
+        grid.grid[1][2].cost = 1;
+        grid.update_regions(&[gap]);
+        assert!(grid.is_reachable(left, right));
+
+        grid.grid[1][2].cost = u8::MAX;
+        grid.update_regions(&[gap]);
+        assert!(!grid.is_reachable(left, right));
+        assert!(grid.region(gap).is_none());
+
+        // A blocked cell belongs to the regions around it
+        assert!(grid.is_reachable(Vec3::new(0.0, 0.0, 0.0), right));
+    }
+
+    #[test]
+    fn islands_are_partitioned_per_domain() {
+        // A wall down the middle column splits the ground in two, aircraft fly over it
+        let mut grid = Grid::new(IVec2::new(5, 3), 1.0, |pos| pos.x.abs() < 0.5);
+        let left = Vec3::new(-2.0, 0.0, 0.0);
+        let right = Vec3::new(2.0, 0.0, 0.0);
+
+        assert_eq!(
+            grid.partition_islands("ground", |cell| cell.cost != u8::MAX),
+            2
+        );
+        assert_eq!(grid.partition_islands("air", |_| true), 1);
+        assert!(!grid.same_island(left, right, "ground"));
+        assert!(grid.same_island(left, right, "air"));
+        assert_eq!(grid.island_of(Vec3::ZERO, "ground"), None);
+        assert_eq!(grid.island_of(left, "naval"), None);
+
+        // Expanding keeps the islands, the new cells belong to none
+        grid.expand(1, 1);
+        assert!(grid.same_island(left, right, "air"));
+        assert_eq!(grid.island_of(Vec3::new(3.0, 0.0, 0.0), "air"), None);
+    }
+
+    #[test]
+    fn cells_in_radius_uses_cell_centers() {
+        let grid = Grid::new(IVec2::new(5, 5), 1.0, |_| false);
+
+        // The center cell and its four cardinal neighbors are within 1.0, the diagonals aren't
+        assert_eq!(grid.cells_in_radius(Vec3::ZERO, 1.0).count(), 5);
+        assert_eq!(grid.cells_in_radius(Vec3::ZERO, 0.4).count(), 1);
+    }
+
+    #[test]
+    fn walls_block_line_of_sight() {
+        // A wall at x = 0 with a gap in the last row
+        let grid = Grid::new(IVec2::new(5, 3), 1.0, |pos| pos.x == 0.0 && pos.z < 1.0);
+        let left = Vec3::new(-2.0, 0.0, -1.0);
+
+        assert!(!grid.line_of_sight(left, Vec3::new(2.0, 0.0, -1.0)));
+        assert!(grid.line_of_sight(Vec3::new(-2.0, 0.0, 1.0), Vec3::new(2.0, 0.0, 1.0)));
+        assert!(!grid.line_of_sight(left, Vec3::new(-9.0, 0.0, -1.0)));
+
+        let cells: Vec<IVec2> = grid
+            .raycast_cells(left, Vec3::new(9.0, 0.0, -1.0))
+            .collect();
+        assert_eq!(cells, (0..5).map(|x| IVec2::new(x, 0)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn temporary_costs_add_up_and_expire() {
+        let mut grid = Grid::new(IVec2::new(3, 3), 1.0, |pos| pos.x > 0.5);
+        let cost = |grid: &Grid, x: i32| grid.cell(IVec2::new(x, 1)).unwrap().cost;
+        let second = std::time::Duration::from_secs(1);
+
+        // The first covers the left and middle columns, the second the middle and right ones
+        grid.add_temporary_cost(Rect::new(-1.0, -1.0, 0.0, 1.0), 10, second);
+        grid.add_temporary_cost(Rect::new(0.0, -1.0, 1.0, 1.0), 20, second * 2);
+        assert_eq!((cost(&grid, 0), cost(&grid, 1)), (11, 31));
+        assert_eq!(cost(&grid, 2), u8::MAX);
+
+        grid.tick_temporary_costs(second);
+        assert_eq!((cost(&grid, 0), cost(&grid, 1)), (1, 21));
+        assert_eq!(grid.temporary_costs().len(), 1);
+
+        grid.tick_temporary_costs(second);
+        assert_eq!((cost(&grid, 0), cost(&grid, 1)), (1, 1));
+        assert!(grid.temporary_costs().is_empty());
+    }
+
+    #[test]
+    fn cell_data_follows_its_cells() {
+        #[derive(Clone, Default, PartialEq, Debug)]
+        struct Owner(Option<u8>);
+
+        let mut grid = Grid::new(IVec2::new(3, 2), 1.0, |_| false);
+        assert!(grid.layer::<Owner>().is_none());
+        assert!(grid
+            .layer_mut::<Owner>()
+            .set(IVec2::new(2, 1), Owner(Some(1))));
+        assert!(!grid
+            .layer_mut::<Owner>()
+            .set(IVec2::new(3, 1), Owner(Some(1))));
+        *grid.layer_mut::<u32>().get_mut(IVec2::new(0, 0)).unwrap() = 5;
+
+        let copy = grid.clone();
+        grid.expand(1, 1);
+        let owners = grid.layer::<Owner>().unwrap();
+        assert_eq!(owners.size(), grid.size);
+        assert_eq!(owners.get(IVec2::new(3, 2)), Some(&Owner(Some(1))));
+        assert_eq!(
+            owners.iter().filter(|(_, owner)| owner.0.is_some()).count(),
+            1
+        );
+        assert_eq!(grid.layer::<u32>().unwrap().get(IVec2::new(1, 1)), Some(&5));
+
+        // Clones keep their own copy
+        assert_eq!(copy.layer::<Owner>().unwrap().size(), IVec2::new(3, 2));
+        assert_eq!(grid.remove_layer::<u32>().unwrap().values().len(), 20);
+        assert!(grid.layer::<u32>().is_none());
+    }
+
+    #[test]
+    fn empty_grids_have_one_cell() {
+        let mut grid = Grid::new(IVec2::ZERO, 1.0, |_| false);
+        assert_eq!(grid.size, IVec2::ONE);
+        assert_eq!(
+            grid.get_cell_from_world_position(Vec3::splat(5.0)).idx,
+            IVec2::ZERO
+        );
+
+        grid.expand(1, 1);
+        assert_eq!(grid.size, IVec2::splat(3));
+
+        // Fields without cells yet answer with an impassable cell
+        let flowfield = crate::flowfield::FlowField::new(0.5, IVec2::ZERO, Vec::new());
+        let cell = flowfield.get_cell_from_world_position(Vec3::ZERO);
+        assert_eq!(cell.best_cost, crate::cell::UNREACHABLE);
+        assert_eq!(cell.cost, u8::MAX);
+    }
+
+    #[test]
+    fn stopped_units_free_their_cells() {
+        let mut grid = Grid::new(IVec2::new(4, 4), 1.0, |_| false);
+        let mut occupied = OccupiedCells::default();
+        let pos = Vec3::new(0.5, 0.0, 0.5);
+
+        let changed = occupy_unit_cells(&mut grid, &mut occupied, &[pos]);
+        assert_eq!(changed.len(), 1);
+        assert_eq!(grid.get_cell_from_world_position(pos).cost, u8::MAX);
+
+        // The last moving unit stopping frees its cell too
+        let changed = occupy_unit_cells(&mut grid, &mut occupied, &[]);
+        assert_eq!(changed.len(), 1);
+        assert_eq!(grid.get_cell_from_world_position(pos).cost, 1);
+        assert!(occupied.0.is_empty());
+    }
+
+    #[test]
+    fn borders_follow_the_edges() {
+        let mut grid = Grid::new(IVec2::new(5, 4), 1.0, |_| false);
+        let blocked = |grid: &Grid| {
+            grid.grid
+                .iter()
+                .flatten()
+                .filter(|cell| cell.cost == u8::MAX)
+                .count()
+        };
+
+        assert_eq!(grid.set_border(1, u8::MAX).len(), 14);
+        assert_eq!(grid.cell(IVec2::new(1, 1)).unwrap().cost, 1);
+        assert_eq!(grid.cell(IVec2::new(4, 2)).unwrap().cost, u8::MAX);
+
+        // The old edges become walkable and the new ones blocked
+        grid.expand(1, 1);
+        assert_eq!(grid.cell(IVec2::new(1, 1)).unwrap().cost, 1);
+        assert_eq!(blocked(&grid), 22);
+
+        // Wrapping edges have no border
+        grid.set_edge_policies(EdgePolicies::new(
+            coords::EdgePolicy::Wrap,
+            coords::EdgePolicy::Clamp,
+        ));
+        assert_eq!(blocked(&grid), 14);
+        assert_eq!(grid.set_border(0, u8::MAX).len(), 14);
+        assert_eq!(blocked(&grid), 0);
+    }
+}
@@ -0,0 +1,71 @@
+use super::{costs, Grid};
+use crate::{
+    cell::Cell,
+    events::{ClearPaintEv, PaintCostEv, UpdateCostEv},
+};
+
+use bevy::prelude::*;
+
+/// Applies editor brush strokes to the editor cost layer of the painted grids
+pub(super) fn paint_costs(
+    mut grid: ResMut<Grid>,
+    mut q_maps: Query<&mut Grid>,
+    mut painted: EventReader<PaintCostEv>,
+    mut cleared: EventReader<ClearPaintEv>,
+    mut events: EventWriter<UpdateCostEv>,
+) {
+    let mut changed: Vec<(Option<Entity>, Cell)> = Vec::new();
+
+    for ev in painted.read() {
+        let Some(mut grid) = map_grid(&mut grid, &mut q_maps, ev.map) else {
+            continue;
+        };
+
+        let brushed: Vec<IVec2> = grid
+            .cells_in_radius(ev.center, ev.radius)
+            .map(|cell| cell.idx)
+            .collect();
+
+        for idx in brushed {
+            if grid.set_layer_cost(costs::EDITOR_LAYER, idx, ev.cost) {
+                changed.push((ev.map, grid.grid[idx.y as usize][idx.x as usize]));
+            }
+        }
+    }
+
+    for ev in cleared.read() {
+        let Some(mut grid) = map_grid(&mut grid, &mut q_maps, ev.map) else {
+            continue;
+        };
+
+        for idx in grid.layer_cells(costs::EDITOR_LAYER) {
+            let inside = ev.region.is_none_or(|region| {
+                let cell = &grid.grid[idx.y as usize][idx.x as usize];
+                region.contains(cell.world_pos.xz())
+            });
+
+            if inside && grid.clear_layer_cost(costs::EDITOR_LAYER, idx) {
+                changed.push((ev.map, grid.grid[idx.y as usize][idx.x as usize]));
+            }
+        }
+    }
+
+    for (map, cell) in changed {
+        events.send(match map {
+            Some(map) => UpdateCostEv::on_map(cell, map),
+            None => UpdateCostEv::new(cell),
+        });
+    }
+}
+
+/// The grid a brush event targets: the `map` entity's, or the `Grid` resource
+fn map_grid<'a>(
+    grid: &'a mut ResMut<Grid>,
+    q_maps: &'a mut Query<&mut Grid>,
+    map: Option<Entity>,
+) -> Option<Mut<'a, Grid>> {
+    match map {
+        Some(map) => q_maps.get_mut(map).ok(),
+        None => Some(grid.reborrow()),
+    }
+}
@@ -0,0 +1,24 @@
+//! Straight line queries over the costfield, for line of sight checks like turrets targeting
+//! over walls
+
+use super::{coords, Grid};
+use crate::path::crossed_cells;
+
+use bevy::prelude::*;
+
+impl Grid {
+    /// The cells the straight line from `from` to `to` crosses, in order, up to where it leaves
+    /// the grid. A line through a cell corner crosses both cells beside it.
+    pub fn raycast_cells(&self, from: Vec3, to: Vec3) -> impl Iterator<Item = IVec2> + '_ {
+        crossed_cells(from, to, self.size, self.cell_diameter)
+            .into_iter()
+            .take_while(|idx| coords::in_bounds(*idx, self.size))
+    }
+
+    /// True if the straight line from `from` to `to` stays on the grid and crosses no
+    /// impassable cell
+    pub fn line_of_sight(&self, from: Vec3, to: Vec3) -> bool {
+        let mut cells = crossed_cells(from, to, self.size, self.cell_diameter).into_iter();
+        return cells.all(|idx| self.cell(idx).is_some_and(|cell| cell.cost != u8::MAX));
+    }
+}
@@ -0,0 +1,76 @@
+//! Rebuilding the `Grid` resource when the level changes at runtime, so it doesn't keep pointing
+//! at the old world. See `MapChangedEv`.
+
+use super::{Grid, OccupiedCells};
+use crate::{
+    components::{Destination, MapBase, OnGrid},
+    events::{CancelOrdersEv, MapChangedEv, SetActiveFlowfieldEv},
+    flowfield::{FlowField, FlowFieldPool},
+    obstacles::ObstacleCells,
+};
+
+use bevy::prelude::*;
+
+/// Sends `MapChangedEv` when a new `MapBase` spawns or the mesh of the current one changes, but
+/// not for the first `MapBase`
+pub(super) fn detect_map_change(
+    mut known: Local<Option<Entity>>,
+    mut events: EventWriter<MapChangedEv>,
+    q_changed: Query<Entity, (With<MapBase>, Or<(Added<MapBase>, Changed<Mesh3d>)>)>,
+) {
+    let Some(map_base) = q_changed.iter().last() else {
+        return;
+    };
+
+    if known.replace(map_base).is_some() {
+        events.send(MapChangedEv::new());
+    }
+}
+
+/// Replaces the `Grid` resource for the new level, forgetting the cells units and obstacles
+/// blocked on the old one. Orders on the old grid are cancelled and its flowfields despawned.
+pub(super) fn rebuild_grid(
+    mut cmds: Commands,
+    mut events: EventReader<MapChangedEv>,
+    mut grid: ResMut<Grid>,
+    mut occupied_cells: ResMut<OccupiedCells>,
+    mut obstacles: ResMut<ObstacleCells>,
+    mut pool: ResMut<FlowFieldPool>,
+    q_units: Query<Entity, (With<Destination>, Without<OnGrid>)>,
+    q_flowfields: Query<(Entity, &FlowField)>,
+) {
+    let Some(ev) = events.read().last() else {
+        return;
+    };
+
+    let mut rebuilt = match &ev.grid {
+        Some(new_grid) => new_grid.clone(),
+        None => {
+            let mut blank = Grid::new(grid.size, grid.cell_diameter, |_| false);
+            blank.set_edge_policies(grid.edge_policies());
+            blank.rotation = grid.rotation;
+            blank
+        }
+    };
+
+    // Continue the current version so nothing mistakes the new grid for the old one
+    rebuilt.version = grid.version + 1;
+    *grid = rebuilt;
+    *occupied_cells = OccupiedCells::default();
+    *obstacles = ObstacleCells::default();
+    pool.clear();
+
+    for (entity, flowfield) in q_flowfields.iter() {
+        if flowfield.map.is_none() {
+            cmds.entity(entity).despawn_recursive();
+        }
+    }
+
+    let units: Vec<Entity> = q_units.iter().collect();
+    if !units.is_empty() {
+        cmds.trigger(CancelOrdersEv::new(units));
+    }
+
+    cmds.trigger(SetActiveFlowfieldEv(None));
+    info!("Rebuilt the grid for the new map");
+}
@@ -0,0 +1,182 @@
+use super::{Connectivity, Grid};
+use crate::events::{CostfieldChangedEv, GridExpandedEv};
+use crate::grid_direction::GridDirection;
+
+use bevy::prelude::*;
+use std::collections::{HashMap, VecDeque};
+
+/// Label of cells that belong to no region
+const NO_REGION: u32 = 0;
+
+impl Grid {
+    /// Labels every walkable cell with the connected region it belongs to
+    pub fn label_regions(&mut self, connectivity: Connectivity) {
+        self.region_connectivity = connectivity;
+        self.regions = vec![vec![NO_REGION; self.size.x as usize]; self.size.y as usize];
+        self.next_region = NO_REGION + 1;
+
+        for y in 0..self.size.y {
+            for x in 0..self.size.x {
+                let idx = IVec2::new(x, y);
+                if self.is_walkable(idx) && self.regions[y as usize][x as usize] == NO_REGION {
+                    self.flood_region(idx);
+                }
+            }
+        }
+    }
+
+    /// Relabels only the regions the changed cells could have split or merged
+    pub fn update_regions(&mut self, cells: &[IVec2]) {
+        for idx in cells.iter() {
+            let Some(label) = self.region_label(*idx) else {
+                continue;
+            };
+
+            match (self.is_walkable(*idx), label != NO_REGION) {
+                // A new walkable cell may join every region around it
+                (true, false) => self.flood_region(*idx),
+                // A new obstacle may split its region into one part per neighbor
+                (false, true) => {
+                    self.regions[idx.y as usize][idx.x as usize] = NO_REGION;
+
+                    let directions = self.region_connectivity.integration_directions(*idx);
+                    for direction in directions {
+                        let neighbor = self.wrap_neighbor(*idx, *direction);
+                        if self.is_walkable(neighbor) {
+                            self.flood_region(neighbor);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// The region of the walkable cell at `idx`, `None` for blocked or off-grid cells
+    pub fn region(&self, idx: IVec2) -> Option<u32> {
+        return self.region_label(idx).filter(|label| *label != NO_REGION);
+    }
+
+    /// True if a unit at `from` can walk to `to`. Blocked cells, such as the ones moving units
+    /// stand in, count as part of the regions around them. Off-grid positions are unreachable.
+    pub fn is_reachable(&self, from: Vec3, to: Vec3) -> bool {
+        let (Some(from), Some(to)) = (
+            self.try_get_cell_from_world_position(from)
+                .map(|cell| cell.idx),
+            self.try_get_cell_from_world_position(to)
+                .map(|cell| cell.idx),
+        ) else {
+            return false;
+        };
+
+        let from_regions = self.regions_around(from);
+        let to_regions = self.regions_around(to);
+
+        return from_regions
+            .iter()
+            .any(|region| to_regions.contains(region));
+    }
+
+    fn regions_around(&self, idx: IVec2) -> Vec<u32> {
+        if let Some(region) = self.region(idx) {
+            return vec![region];
+        }
+
+        let directions = self.region_connectivity.integration_directions(idx);
+        return directions
+            .iter()
+            .filter_map(|direction| self.region(self.wrap_neighbor(idx, *direction)))
+            .collect();
+    }
+
+    fn region_label(&self, idx: IVec2) -> Option<u32> {
+        return self
+            .regions
+            .get(idx.y as usize)?
+            .get(idx.x as usize)
+            .copied();
+    }
+
+    /// The neighbor of `idx` towards `direction`, carried across wrapping edges. Neighbors past
+    /// clamped edges stay off the grid.
+    fn wrap_neighbor(&self, idx: IVec2, direction: GridDirection) -> IVec2 {
+        let neighbor = idx + direction.vector();
+        return self
+            .edge_policies()
+            .wrap(neighbor, self.size)
+            .unwrap_or(neighbor);
+    }
+
+    fn is_walkable(&self, idx: IVec2) -> bool {
+        return self.cell(idx).is_some_and(|cell| cell.cost != u8::MAX);
+    }
+
+    /// Gives the walkable cells connected to `start` a fresh label
+    fn flood_region(&mut self, start: IVec2) {
+        let label = self.next_region;
+        self.next_region += 1;
+
+        let mut queue = VecDeque::from([start]);
+        self.regions[start.y as usize][start.x as usize] = label;
+
+        while let Some(idx) = queue.pop_front() {
+            for direction in self.region_connectivity.integration_directions(idx) {
+                let neighbor = self.wrap_neighbor(idx, *direction);
+                if !self.is_walkable(neighbor) {
+                    continue;
+                }
+
+                let region = &mut self.regions[neighbor.y as usize][neighbor.x as usize];
+                if *region != label {
+                    *region = label;
+                    queue.push_back(neighbor);
+                }
+            }
+        }
+    }
+}
+
+/// Keeps the region labels of every grid in sync with cost changes, relabeling everything when
+/// a grid grows or the connectivity changes
+pub(super) fn update_regions(
+    mut grid: ResMut<Grid>,
+    mut q_maps: Query<(Entity, &mut Grid)>,
+    connectivity: Res<Connectivity>,
+    mut changes: EventReader<CostfieldChangedEv>,
+    mut expanded: EventReader<GridExpandedEv>,
+) {
+    let mut cells: HashMap<Option<Entity>, Vec<IVec2>> = HashMap::new();
+    for ev in changes.read() {
+        cells.entry(ev.map).or_default().extend(ev.cells.iter());
+    }
+
+    // Labels are derived from the costs, so keeping them current isn't a change to the grid
+    let expanded = expanded.read().count() > 0;
+    let main_cells = cells.remove(&None).unwrap_or_default();
+    sync_regions(
+        grid.bypass_change_detection(),
+        expanded,
+        *connectivity,
+        &main_cells,
+    );
+
+    for (map, mut grid) in q_maps.iter_mut() {
+        let map_cells = cells.remove(&Some(map)).unwrap_or_default();
+        sync_regions(
+            grid.bypass_change_detection(),
+            false,
+            *connectivity,
+            &map_cells,
+        );
+    }
+}
+
+fn sync_regions(grid: &mut Grid, expanded: bool, connectivity: Connectivity, cells: &[IVec2]) {
+    let resized = expanded || grid.regions.len() != grid.size.y as usize;
+    if resized || grid.region_connectivity != connectivity {
+        grid.label_regions(connectivity);
+        return;
+    }
+
+    grid.update_regions(cells);
+}
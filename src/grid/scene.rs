@@ -0,0 +1,214 @@
+//! Grids described by Bevy scenes. A map scene holds an entity with a `GridScene`, and spawning
+//! the scene builds the `Grid` from it, so the map file describes its navigable space without
+//! any setup code.
+//!
+//! ```ignore
+//! // Exporting, e.g. from an editor tool
+//! let scene = GridScene::from_grid(&grid).into_dynamic_scene();
+//! std::fs::write("assets/maps/canyon.scn.ron", scene.serialize(&registry.read())?)?;
+//!
+//! // In the game
+//! cmds.spawn(DynamicSceneRoot(asset_server.load("maps/canyon.scn.ron")));
+//! ```
+
+use super::{
+    costs::{self, CostLayer},
+    Connectivity, EdgePolicies, Grid,
+};
+use crate::{
+    error::PathfindingError,
+    events::{MapChangedEv, PathfindingErrorEv},
+};
+
+use bevy::{prelude::*, scene::DynamicEntity};
+
+/// Layers whose costs come from entities or timers the scene spawns or runs on its own
+const RUNTIME_LAYERS: [&str; 6] = [
+    costs::UNIT_LAYER,
+    costs::OBSTACLE_LAYER,
+    costs::RESERVATION_LAYER,
+    costs::TEMPORARY_LAYER,
+    costs::INFLATION_LAYER,
+    costs::BORDER_LAYER,
+];
+
+/// The settings and costs of a `Grid`, serializable through reflection. Replaces the `Grid`
+/// resource when spawned, or gives its entity a `Grid` of its own with `map` set. Changing the
+/// component rebuilds the grid.
+#[derive(Component, Reflect, Clone, Debug, Default)]
+#[reflect(Component)]
+pub struct GridScene {
+    pub size: IVec2,
+    pub cell_diameter: f32,
+    pub edge_policies: EdgePolicies,
+    /// See `Grid::rotation`, scenes saved without one aren't turned
+    #[reflect(default)]
+    pub rotation: Quat,
+    /// The terrain cost of every cell, row by row. Cells past its end cost 1.
+    pub terrain: Vec<u8>,
+    /// The layers painted over the terrain, like the editor layer. Layers from units, obstacles,
+    /// reservations, temporary costs, inflation and the border are rebuilt at runtime and left
+    /// out.
+    pub layers: Vec<CostLayer>,
+    /// The extra costs of moving from a cell to a neighbor, see `Grid::set_edge_cost`. A list
+    /// rather than `EdgeCosts`, whose tuple keys can't be deserialized through reflection.
+    pub edge_costs: Vec<(IVec2, IVec2, u8)>,
+    /// Makes the entity a map with its own `Grid` instead of replacing the `Grid` resource
+    pub map: bool,
+}
+
+impl GridScene {
+    pub fn from_grid(grid: &Grid) -> Self {
+        let terrain = grid
+            .grid
+            .iter()
+            .flatten()
+            .map(|cell| grid.base_cost(cell.idx).unwrap_or(cell.cost))
+            .collect();
+
+        let layers = grid
+            .cost_layers()
+            .iter()
+            .filter(|layer| !RUNTIME_LAYERS.contains(&layer.name.as_str()))
+            .cloned()
+            .collect();
+
+        // Sorted so saving an unchanged grid writes the same file
+        let mut edge_costs: Vec<(IVec2, IVec2, u8)> = grid.edge_costs().iter().collect();
+        edge_costs.sort_by_key(|(from, to, _)| (from.y, from.x, to.y, to.x));
+
+        return Self {
+            size: grid.size,
+            cell_diameter: grid.cell_diameter,
+            edge_policies: grid.edge_policies(),
+            rotation: grid.rotation,
+            terrain,
+            layers,
+            edge_costs,
+            map: false,
+        };
+    }
+
+    /// Exports the grid as a map entity instead of the `Grid` resource
+    pub fn as_map(mut self) -> Self {
+        self.map = true;
+        self
+    }
+
+    pub fn to_grid(&self) -> Grid {
+        let mut grid = Grid::new(self.size, self.cell_diameter, |_| false);
+        grid.edge_policies = self.edge_policies;
+        grid.rotation = self.rotation;
+        for (from, to, cost) in self.edge_costs.iter() {
+            grid.set_edge_cost(*from, *to, *cost);
+        }
+
+        for (cell, cost) in grid
+            .grid
+            .iter_mut()
+            .flatten()
+            .zip(self.terrain.iter().copied())
+        {
+            cell.cost = cost;
+        }
+
+        for layer in self.layers.iter() {
+            grid.add_cost_layer(&layer.name, layer.priority, layer.op);
+            for (idx, cost) in layer.costs() {
+                grid.set_layer_cost(&layer.name, idx, cost);
+            }
+        }
+
+        grid.label_regions(Connectivity::default());
+        return grid;
+    }
+
+    /// A scene with a single entity holding the grid, ready to serialize
+    pub fn into_dynamic_scene(self) -> DynamicScene {
+        return DynamicScene {
+            resources: Vec::new(),
+            entities: vec![DynamicEntity {
+                entity: Entity::from_raw(0),
+                components: vec![Box::new(self)],
+            }],
+        };
+    }
+}
+
+/// Builds the grids of spawned or changed `GridScene`s. Scenes replacing the `Grid` resource
+/// change the map, see `MapChangedEv`. Map grids continue the version of the grid they replace
+/// so flowfields on it rebuild.
+pub(super) fn build_scene_grids(
+    mut cmds: Commands,
+    mut map_changed: EventWriter<MapChangedEv>,
+    mut errors: EventWriter<PathfindingErrorEv>,
+    q_scenes: Query<(Entity, &GridScene, Option<&Grid>), Changed<GridScene>>,
+) {
+    for (entity, scene, map_grid) in q_scenes.iter() {
+        if scene.size.min_element() < 1 {
+            errors.send(PathfindingErrorEv::new(PathfindingError::InvalidGridSize(
+                scene.size,
+            )));
+            continue;
+        }
+
+        let mut built = scene.to_grid();
+        if !scene.map {
+            map_changed.send(MapChangedEv::with_grid(built));
+            continue;
+        }
+
+        built.version = map_grid.map_or(0, |grid| grid.version + 1);
+        cmds.entity(entity).insert(built);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grid::coords::EdgePolicy;
+    use bevy::{
+        reflect::TypeRegistry,
+        scene::{ron, serde::SceneDeserializer},
+    };
+    use serde::de::DeserializeSeed;
+
+    #[test]
+    fn grids_survive_a_round_trip_through_a_scene_file() {
+        let mut grid = Grid::new(IVec2::new(5, 3), 2.0, |pos| pos.x > 2.0);
+        grid.set_base_cost(IVec2::new(0, 1), 7);
+        grid.set_layer_cost(costs::EDITOR_LAYER, IVec2::new(1, 1), 3);
+        grid.set_layer_cost(costs::UNIT_LAYER, IVec2::new(2, 2), u8::MAX);
+        grid.set_edge_cost(IVec2::new(0, 0), IVec2::new(1, 0), 4);
+        grid.set_edge_policies(EdgePolicies::new(EdgePolicy::Wrap, EdgePolicy::Clamp));
+
+        let mut registry = TypeRegistry::default();
+        registry.register::<GridScene>();
+
+        let scene = GridScene::from_grid(&grid).into_dynamic_scene();
+        let ron = scene.serialize(&registry).unwrap();
+        let mut deserializer = ron::de::Deserializer::from_str(&ron).unwrap();
+        let loaded = SceneDeserializer {
+            type_registry: &registry,
+        }
+        .deserialize(&mut deserializer)
+        .unwrap();
+
+        let component = &loaded.entities[0].components[0];
+        let scene = GridScene::from_reflect(component.as_partial_reflect()).unwrap();
+        assert!(!scene.map);
+
+        let loaded = scene.to_grid();
+        assert_eq!(loaded.size, grid.size);
+        assert_eq!(loaded.edge_policies(), grid.edge_policies());
+        assert_eq!(loaded.edge_cost(IVec2::new(0, 0), IVec2::new(1, 0)), 4);
+        assert_eq!(loaded.cell(IVec2::new(0, 1)).unwrap().cost, 7);
+        assert_eq!(loaded.cell(IVec2::new(1, 1)).unwrap().cost, 3);
+        assert_eq!(loaded.base_cost(IVec2::new(1, 1)), Some(1));
+        assert_eq!(loaded.cell(IVec2::new(4, 0)).unwrap().cost, u8::MAX);
+
+        // Units stand in their cells again once the game runs
+        assert_eq!(loaded.cell(IVec2::new(2, 2)).unwrap().cost, 1);
+        assert!(loaded.layer_cells(costs::UNIT_LAYER).is_empty());
+    }
+}
@@ -0,0 +1,185 @@
+//! Symmetric costfields for competitive maps. Generate the costs of one part of the map, mirror
+//! or rotate them onto the rest, then check that every player's start gets the same integration
+//! field as the others.
+
+use super::{coords, costs::CostLayer, edges::EdgeCosts, Connectivity, Grid};
+use crate::{
+    cell::{Cell, UNREACHABLE},
+    flowfield::{integrate, IntegrationMethod},
+};
+
+use bevy::prelude::*;
+use std::collections::HashSet;
+
+/// How the cells of a symmetric map map onto each other
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Reflect)]
+pub enum Symmetry {
+    /// Mirrored across the center line along z, swapping the west and east halves
+    MirrorX,
+    /// Mirrored across the center line along x, swapping the first and last rows
+    MirrorY,
+    /// Half a turn around the center, for two players in opposite corners
+    Rotate180,
+    /// Quarter turns around the center, for four players. Needs a square grid.
+    Rotate90,
+}
+
+impl Symmetry {
+    /// The counterpart of the cell at `idx` on a grid of `size`
+    pub fn map(self, idx: IVec2, size: IVec2) -> IVec2 {
+        let last = size - 1;
+        match self {
+            Symmetry::MirrorX => IVec2::new(last.x - idx.x, idx.y),
+            Symmetry::MirrorY => IVec2::new(idx.x, last.y - idx.y),
+            Symmetry::Rotate180 => last - idx,
+            Symmetry::Rotate90 => IVec2::new(last.y - idx.y, idx.x),
+        }
+    }
+
+    /// The cell at `idx` and each of its distinct counterparts
+    pub fn orbit(self, idx: IVec2, size: IVec2) -> Vec<IVec2> {
+        let mut orbit = vec![idx];
+        let mut next = self.map(idx, size);
+        while next != idx && orbit.len() < 4 {
+            orbit.push(next);
+            next = self.map(next, size);
+        }
+
+        return orbit;
+    }
+
+    /// The cell of `idx`'s orbit first in row order, which the others are copied from
+    fn source(self, idx: IVec2, size: IVec2) -> IVec2 {
+        let orbit = self.orbit(idx, size);
+        return orbit
+            .into_iter()
+            .min_by_key(|idx| (idx.y, idx.x))
+            .unwrap_or(idx);
+    }
+}
+
+impl CostLayer {
+    /// A copy of the layer with every cost repeated on the counterparts of its cell, keeping
+    /// the highest cost where costs meet
+    pub fn mirrored(&self, symmetry: Symmetry, size: IVec2) -> CostLayer {
+        let mut mirrored = self.clone();
+        for (idx, cost) in self.costs() {
+            for image in symmetry.orbit(idx, size) {
+                mirrored.put_max(image, cost);
+            }
+        }
+
+        return mirrored;
+    }
+}
+
+impl Grid {
+    /// Copies the terrain cost of the first cell of every orbit, in row order, onto its
+    /// counterparts. That's the west half for `MirrorX` and the first rows for `MirrorY` and
+    /// `Rotate180`. Returns the cells whose cost changed.
+    pub fn mirror_base_costs(&mut self, symmetry: Symmetry) -> Vec<IVec2> {
+        let mut changed = Vec::new();
+        for idx in self
+            .grid
+            .iter()
+            .flatten()
+            .map(|cell| cell.idx)
+            .collect::<Vec<_>>()
+        {
+            let source = symmetry.source(idx, self.size);
+            let Some(cost) = self.base_cost(source).filter(|_| source != idx) else {
+                continue;
+            };
+
+            if self.set_base_cost(idx, cost) {
+                changed.push(idx);
+            }
+        }
+
+        return changed;
+    }
+
+    /// Makes the `name` layer symmetric, see `CostLayer::mirrored`. Returns the cells whose cost
+    /// changed.
+    pub fn mirror_layer(&mut self, name: &str, symmetry: Symmetry) -> Vec<IVec2> {
+        let Some(layer) = self.cost_layers().iter().find(|layer| layer.name == name) else {
+            return Vec::new();
+        };
+
+        let mirrored = layer.mirrored(symmetry, self.size);
+        let mut changed = Vec::new();
+        for (idx, cost) in mirrored.costs() {
+            if self.layer_cost(name, idx) != Some(cost) && self.set_layer_cost(name, idx, cost) {
+                changed.push(idx);
+            }
+        }
+
+        return changed;
+    }
+
+    /// The cells whose cost differs from one of their counterparts
+    pub fn asymmetric_cells(&self, symmetry: Symmetry) -> Vec<IVec2> {
+        return self
+            .grid
+            .iter()
+            .flatten()
+            .filter(|cell| {
+                let image = symmetry.map(cell.idx, self.size);
+                self.cell(image).map(|image| image.cost) != Some(cell.cost)
+            })
+            .map(|cell| cell.idx)
+            .collect();
+    }
+
+    /// Integrates the costs from `start` and from each of its counterparts, returning the cells
+    /// where a counterpart's field disagrees with the field from `start`. Empty means every
+    /// player at a counterpart of `start` is as far from everything as the player at `start`.
+    /// Edge costs are left out, as one-way edges are rarely mirrored along with the cells.
+    pub fn integration_asymmetries(
+        &self,
+        symmetry: Symmetry,
+        start: IVec2,
+        connectivity: Connectivity,
+        method: IntegrationMethod,
+    ) -> Vec<IVec2> {
+        if !coords::in_bounds(start, self.size) {
+            return Vec::new();
+        }
+
+        let field_from = |start: IVec2| {
+            let mut cells: Vec<Vec<Cell>> = self.grid.clone();
+            for cell in cells.iter_mut().flatten() {
+                cell.best_cost = UNREACHABLE;
+            }
+
+            cells[start.y as usize][start.x as usize].best_cost = 0;
+            let neighbors = (connectivity, &EdgeCosts::default(), self.edge_policies());
+            integrate(&mut cells, self.size, vec![start], neighbors, method);
+            cells
+        };
+
+        let field = field_from(start);
+        let mut asymmetries = HashSet::new();
+        let mut image = start;
+        let mut counterparts: Vec<IVec2> = field.iter().flatten().map(|cell| cell.idx).collect();
+
+        for _ in 1..symmetry.orbit(start, self.size).len() {
+            image = symmetry.map(image, self.size);
+            for idx in counterparts.iter_mut() {
+                *idx = symmetry.map(*idx, self.size);
+            }
+
+            let image_field = field_from(image);
+            for (cell, counterpart) in field.iter().flatten().zip(counterparts.iter()) {
+                let image_cell = &image_field[counterpart.y as usize][counterpart.x as usize];
+                if image_cell.best_cost != cell.best_cost {
+                    asymmetries.insert(cell.idx);
+                }
+            }
+        }
+
+        let mut asymmetries: Vec<IVec2> = asymmetries.into_iter().collect();
+        asymmetries.sort_by_key(|idx| (idx.y, idx.x));
+        return asymmetries;
+    }
+}
@@ -0,0 +1,181 @@
+//! Temporary costs, like wreckage, fires or spell zones, expire on their own. They add up in
+//! the temporary cost layer, and flowfields crossing their cells are repaired whenever one is
+//! added or expires.
+
+use super::{coords, costs, Grid};
+use crate::{
+    events::UpdateCostEv, flowfield::FlowField, grid::Grids, interior::InteriorGrid,
+    layers::GridLayers, resources::PathfindingStats, time::PathfindingTime,
+};
+
+use bevy::{prelude::*, utils::Instant};
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+/// A cost `Grid::add_temporary_cost` puts on a region until it expires
+#[derive(Clone, Debug, Reflect)]
+pub struct TemporaryCost {
+    /// A world XZ rect, with `Rect::min.y` being the minimum world z
+    pub region: Rect,
+    pub amount: u8,
+    /// Time left until it expires
+    pub remaining: Duration,
+}
+
+/// The temporary costs of a `Grid`, with the cells they changed since last reported
+#[derive(Clone, Debug, Default, Reflect)]
+pub struct TemporaryCosts {
+    costs: Vec<TemporaryCost>,
+    changed: Vec<IVec2>,
+}
+
+impl TemporaryCosts {
+    /// Follows the cell indices after the grid was expanded
+    pub(super) fn shift(&mut self, border: i32) {
+        for idx in self.changed.iter_mut() {
+            *idx += border;
+        }
+    }
+}
+
+/// Cells whose cost temporary costs changed this frame, per map
+#[derive(Resource, Default)]
+pub(super) struct TemporaryCostChanges(HashMap<Option<Entity>, HashSet<IVec2>>);
+
+impl Grid {
+    /// Adds `amount` to the cost of every cell overlapping `region` until `duration` has passed.
+    /// `region` is a world XZ rect with `Rect::min.y` being the minimum world z. Overlapping
+    /// temporary costs add up, but never make a walkable cell impassable.
+    pub fn add_temporary_cost(&mut self, region: Rect, amount: u8, duration: Duration) {
+        self.temporary_costs.costs.push(TemporaryCost {
+            region,
+            amount,
+            remaining: duration,
+        });
+        self.bake_temporary_costs(region);
+    }
+
+    /// The temporary costs that haven't expired yet
+    pub fn temporary_costs(&self) -> &[TemporaryCost] {
+        &self.temporary_costs.costs
+    }
+
+    /// Counts `delta` off every temporary cost, removing the expired ones
+    pub fn tick_temporary_costs(&mut self, delta: Duration) {
+        let mut expired = Vec::new();
+        self.temporary_costs.costs.retain_mut(|cost| {
+            cost.remaining = cost.remaining.saturating_sub(delta);
+            if cost.remaining.is_zero() {
+                expired.push(cost.region);
+            }
+
+            !cost.remaining.is_zero()
+        });
+
+        for region in expired {
+            self.bake_temporary_costs(region);
+        }
+    }
+
+    /// Rewrites the temporary cost layer for the cells overlapping `region`
+    fn bake_temporary_costs(&mut self, region: Rect) {
+        let (min, max) = self.rect_extent(region);
+        let cells: Vec<IVec2> = self.cells_in_rect(min, max).map(|cell| cell.idx).collect();
+
+        for idx in cells {
+            let total = self
+                .temporary_costs
+                .costs
+                .iter()
+                .filter(|cost| self.rect_covers(cost.region, idx))
+                .fold(0u8, |total, cost| total.saturating_add(cost.amount));
+
+            let changed = match total {
+                0 => self.clear_layer_cost(costs::TEMPORARY_LAYER, idx),
+                total => self.set_layer_cost(costs::TEMPORARY_LAYER, idx, total),
+            };
+            if changed {
+                self.temporary_costs.changed.push(idx);
+            }
+        }
+    }
+
+    fn rect_extent(&self, region: Rect) -> (Vec3, Vec3) {
+        return (
+            Vec3::new(region.min.x, 0.0, region.min.y),
+            Vec3::new(region.max.x, 0.0, region.max.y),
+        );
+    }
+
+    /// True if the cell at `idx` overlaps `region`, the way `Grid::cells_in_rect` finds them
+    fn rect_covers(&self, region: Rect, idx: IVec2) -> bool {
+        let (min, max) = self.rect_extent(region);
+        let min_idx = coords::world_to_idx_unclamped(min, self.size, self.cell_diameter);
+        let max_idx = coords::world_to_idx_unclamped(max, self.size, self.cell_diameter);
+
+        return idx.cmpge(min_idx).all() && idx.cmple(max_idx).all();
+    }
+}
+
+/// Expires temporary costs and reports the cells they changed
+pub(super) fn tick_temporary_costs(
+    time: PathfindingTime,
+    mut grid: ResMut<Grid>,
+    mut changes: ResMut<TemporaryCostChanges>,
+    mut events: EventWriter<UpdateCostEv>,
+    mut q_maps: Query<(Entity, &mut Grid)>,
+) {
+    let grids = std::iter::once((None, grid.reborrow()))
+        .chain(q_maps.iter_mut().map(|(map, grid)| (Some(map), grid)));
+
+    for (map, mut grid) in grids {
+        let temporary = &grid.temporary_costs;
+        if temporary.costs.is_empty() && temporary.changed.is_empty() {
+            continue;
+        }
+
+        grid.tick_temporary_costs(time.delta());
+
+        for idx in std::mem::take(&mut grid.temporary_costs.changed) {
+            let Some(cell) = grid.cell(idx).copied() else {
+                continue;
+            };
+
+            changes.0.entry(map).or_default().insert(idx);
+            events.send(match map {
+                Some(map) => UpdateCostEv::on_map(cell, map),
+                None => UpdateCostEv::new(cell),
+            });
+        }
+    }
+}
+
+/// Rebuilds the flowfields whose costs temporary costs changed
+pub(super) fn repair_temporary_flowfields(
+    grids: Grids,
+    layers: Res<GridLayers>,
+    mut stats: ResMut<PathfindingStats>,
+    mut changes: ResMut<TemporaryCostChanges>,
+    mut q_flowfields: Query<&mut FlowField>,
+    q_interiors: Query<(Entity, &InteriorGrid)>,
+) {
+    if changes.0.is_empty() {
+        return;
+    }
+
+    let changed = std::mem::take(&mut changes.0);
+    let start = Instant::now();
+    let interiors: Vec<(Entity, &InteriorGrid)> = q_interiors.iter().collect();
+
+    for mut flowfield in q_flowfields.iter_mut() {
+        let (Some(cells), Some(grid)) = (changed.get(&flowfield.map), grids.get(flowfield.map))
+        else {
+            continue;
+        };
+
+        if flowfield.costs_differ(grid, cells) {
+            flowfield.rebuild(&grids, &layers, &interiors);
+        }
+    }
+    stats.record_integration(start.elapsed());
+}
@@ -15,6 +15,7 @@ const DIRECTIONS: [GridDirection; 9] = [
 ];
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy, Default, Reflect)]
+#[cfg_attr(feature = "config", derive(serde::Serialize, serde::Deserialize))]
 pub enum GridDirection {
     #[default]
     None,
@@ -87,7 +88,21 @@ impl GridDirection {
         ]
     }
 
-    pub fn to_angle(&self) -> f32 {
+    /// The unit vector of the direction in the world, on a grid turned by `grid_rotation`, see
+    /// `Grid::rotation`. Zero for `GridDirection::None`.
+    pub fn to_world(self, grid_rotation: Quat) -> Vec3 {
+        let local = self.vector().as_vec2().normalize_or_zero();
+        return grid_rotation * Vec3::new(local.x, 0.0, local.y);
+    }
+
+    /// The rotation turning the world's +X axis along the direction, on a grid turned by
+    /// `grid_rotation`
+    pub fn to_world_rotation(self, grid_rotation: Quat) -> Quat {
+        return grid_rotation * Quat::from_rotation_y(self.to_angle());
+    }
+
+    /// The angle about the Y axis from +X to the direction in the grid's own space
+    pub fn to_angle(self) -> f32 {
         match self {
             GridDirection::None => 0.0,
             GridDirection::North => FRAC_PI_2,
@@ -101,3 +116,22 @@ impl GridDirection {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn directions_turn_with_the_grid() {
+        let rotation = Quat::from_rotation_y(FRAC_PI_2);
+        assert!(GridDirection::East
+            .to_world(rotation)
+            .abs_diff_eq(Vec3::NEG_Z, 1e-5));
+        assert_eq!(GridDirection::None.to_world(rotation), Vec3::ZERO);
+
+        for direction in DIRECTIONS.into_iter().skip(1) {
+            let arrow = direction.to_world_rotation(rotation) * Vec3::X;
+            assert!(arrow.abs_diff_eq(direction.to_world(rotation), 1e-5));
+        }
+    }
+}
@@ -0,0 +1,152 @@
+//! Heatmaps are integration fields without flow directions, for strategic AI asking questions
+//! like "how far is my base from everywhere" rather than moving units
+
+use crate::{
+    cell::{BestCost, Cell, UNREACHABLE},
+    flowfield::{integrate, IntegrationMethod},
+    grid::{
+        coords::{self, EdgePolicies},
+        Connectivity, Grid, Grids,
+    },
+    PathfindingSchedule, PathfindingSet,
+};
+
+use bevy::prelude::*;
+
+pub struct HeatMapPlugin;
+
+impl Plugin for HeatMapPlugin {
+    fn build(&self, app: &mut App) {
+        let schedule = PathfindingSchedule::label(app);
+        app.register_type::<HeatMap>().add_systems(
+            schedule,
+            refresh_heatmaps.in_set(PathfindingSet::BuildFields),
+        );
+    }
+}
+
+/// The cheapest cost from every cell to the nearest of `seeds`. Built once spawned and rebuilt
+/// only after the costs of its grid changed, so it's cheap to keep around for AI evaluation.
+#[derive(Component, Reflect, Clone, Debug, Default)]
+#[reflect(Component)]
+pub struct HeatMap {
+    pub seeds: Vec<Vec3>,
+    /// The map entity whose `Grid` the heatmap covers, `None` for the `Grid` resource
+    pub map: Option<Entity>,
+    costs: Vec<Vec<BestCost>>,
+    size: IVec2,
+    cell_diameter: f32,
+    edge_policies: EdgePolicies,
+    // the `Grid::version` and seeds the costs were integrated from, `None` until first built
+    costfield_version: Option<u64>,
+    built_seeds: Vec<Vec3>,
+}
+
+impl HeatMap {
+    pub fn new(seeds: Vec<Vec3>) -> Self {
+        Self { seeds, ..default() }
+    }
+
+    /// Covers the `Grid` of the `map` entity instead of the `Grid` resource
+    pub fn on_map(mut self, map: Entity) -> Self {
+        self.map = Some(map);
+        self
+    }
+
+    /// The cost of getting from `world_pos` to the nearest seed, `None` off the grid or where no
+    /// seed can be reached
+    pub fn cost_at(&self, world_pos: Vec3) -> Option<BestCost> {
+        let world_pos = self
+            .edge_policies
+            .wrap_world(world_pos, self.size, self.cell_diameter);
+        let idx = coords::world_to_idx(world_pos, self.size, self.cell_diameter)?;
+        let cost = self.costs[idx.y as usize][idx.x as usize];
+
+        return Some(cost).filter(|cost| *cost != UNREACHABLE);
+    }
+
+    /// True if the heatmap was never built, or its seeds or `grid`'s costs changed since
+    pub fn is_stale(&self, grid: &Grid) -> bool {
+        self.costfield_version != Some(grid.version)
+            || self.size != grid.size
+            || self.built_seeds != self.seeds
+    }
+
+    /// Integrates the costs of `grid` outwards from every seed on it
+    pub fn build(&mut self, grid: &Grid, connectivity: Connectivity, method: IntegrationMethod) {
+        let mut cells: Vec<Vec<Cell>> = grid.grid.clone();
+        for cell in cells.iter_mut().flatten() {
+            cell.best_cost = UNREACHABLE;
+        }
+
+        let mut seeds = Vec::new();
+        for seed in self.seeds.iter() {
+            let Some(idx) = coords::world_to_idx(*seed, grid.size, grid.cell_diameter) else {
+                continue;
+            };
+
+            let cell = &mut cells[idx.y as usize][idx.x as usize];
+            cell.cost = 0;
+            cell.best_cost = 0;
+            seeds.push(idx);
+        }
+
+        integrate(
+            &mut cells,
+            grid.size,
+            seeds,
+            (connectivity, grid.edge_costs(), grid.edge_policies()),
+            method,
+        );
+
+        self.costs = cells
+            .iter()
+            .map(|row| row.iter().map(|cell| cell.best_cost).collect())
+            .collect();
+        self.size = grid.size;
+        self.cell_diameter = grid.cell_diameter;
+        self.edge_policies = grid.edge_policies();
+        self.costfield_version = Some(grid.version);
+        self.built_seeds = self.seeds.clone();
+    }
+}
+
+/// Rebuilds stale heatmaps
+fn refresh_heatmaps(
+    grids: Grids,
+    connectivity: Res<Connectivity>,
+    method: Res<IntegrationMethod>,
+    mut q_heatmaps: Query<&mut HeatMap>,
+) {
+    for mut heatmap in q_heatmaps.iter_mut() {
+        let Some(grid) = grids.get(heatmap.map) else {
+            continue;
+        };
+
+        if heatmap.is_stale(grid) {
+            heatmap.build(grid, *connectivity, *method);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn costs_grow_with_distance_from_the_nearest_seed() {
+        // A wall at x = 0 with a gap in the last row
+        let grid = Grid::new(IVec2::new(5, 3), 1.0, |pos| pos.x == 0.0 && pos.z < 1.0);
+        let mut heatmap = HeatMap::new(vec![Vec3::new(-2.0, 0.0, -1.0)]);
+        assert!(heatmap.is_stale(&grid));
+
+        heatmap.build(&grid, Connectivity::Cardinal4, IntegrationMethod::Breadth);
+        assert!(!heatmap.is_stale(&grid));
+
+        assert_eq!(heatmap.cost_at(Vec3::new(-2.0, 0.0, -1.0)), Some(0));
+        assert_eq!(heatmap.cost_at(Vec3::new(-1.0, 0.0, -1.0)), Some(1));
+        assert_eq!(heatmap.cost_at(Vec3::new(0.0, 0.0, -1.0)), None);
+        assert_eq!(heatmap.cost_at(Vec3::new(2.0, 0.0, -1.0)), Some(8));
+        assert_eq!(heatmap.cost_at(Vec3::new(9.0, 0.0, 0.0)), None);
+    }
+}
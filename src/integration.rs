@@ -0,0 +1,125 @@
+//! Distance maps over a grid, the integration half of a `FlowField` without its directions.
+//! Gameplay can ask one how far a unit is from a point along walkable terrain, such as from the
+//! rally point, without building a flowfield for it.
+
+use crate::{
+    cell::{BestCost, Cell, UNREACHABLE},
+    flowfield::{self, FlowField, IntegrationMethod},
+    grid::{coords, coords::EdgePolicies, Connectivity, Grid},
+};
+
+use bevy::prelude::*;
+
+/// The cost of the cheapest route from every cell of a grid to a destination cell. Costs are
+/// in the grid's cost units, which on terrain of cost 1 is the number of cells to walk.
+#[derive(Clone, Debug, Default, PartialEq, Reflect)]
+pub struct IntegrationField {
+    pub size: IVec2,
+    pub cell_diameter: f32,
+    /// The cell the costs lead to
+    pub destination: IVec2,
+    edge_policies: EdgePolicies,
+    best_costs: Vec<Vec<BestCost>>,
+}
+
+impl IntegrationField {
+    /// Integrates the costs of `grid` from the cell containing `destination`
+    pub fn new(
+        grid: &Grid,
+        destination: Vec3,
+        connectivity: Connectivity,
+        method: IntegrationMethod,
+    ) -> Self {
+        let mut cells = grid.grid.clone();
+        let destination = grid.get_cell_from_world_position(destination).idx;
+        let dest_cell = &mut cells[destination.y as usize][destination.x as usize];
+        dest_cell.cost = 0;
+        dest_cell.best_cost = 0;
+
+        flowfield::integrate(
+            &mut cells,
+            grid.size,
+            vec![destination],
+            (connectivity, grid.edge_costs(), grid.edge_policies()),
+            method,
+        );
+
+        return Self {
+            size: grid.size,
+            cell_diameter: grid.cell_diameter,
+            destination,
+            edge_policies: grid.edge_policies(),
+            best_costs: best_costs(&cells),
+        };
+    }
+
+    /// The cost of the route from the cell at `idx`, `UNREACHABLE` off the grid or where no
+    /// route leads to the destination
+    pub fn cost_at(&self, idx: IVec2) -> BestCost {
+        let Some(idx) = self.edge_policies.wrap(idx, self.size) else {
+            return UNREACHABLE;
+        };
+
+        return self
+            .best_costs
+            .get(idx.y as usize)
+            .and_then(|row| row.get(idx.x as usize))
+            .copied()
+            .unwrap_or(UNREACHABLE);
+    }
+
+    /// The cost of the route from `world_pos` to the destination, `None` off the grid or where
+    /// no route leads there
+    pub fn distance_at(&self, world_pos: Vec3) -> Option<BestCost> {
+        let idx = coords::world_to_idx_unclamped(world_pos, self.size, self.cell_diameter);
+        let cost = self.cost_at(idx);
+        return (cost != UNREACHABLE).then_some(cost);
+    }
+}
+
+impl FlowField {
+    /// The distances of the field's main grid, which stay valid after the field is despawned.
+    /// Layers and interiors are left out.
+    pub fn integration_field(&self) -> IntegrationField {
+        IntegrationField {
+            size: self.size,
+            cell_diameter: self.cell_diameter,
+            destination: self.destination_cell.idx,
+            edge_policies: self.edge_policies,
+            best_costs: best_costs(&self.grid),
+        }
+    }
+}
+
+fn best_costs(cells: &[Vec<Cell>]) -> Vec<Vec<BestCost>> {
+    cells
+        .iter()
+        .map(|row| row.iter().map(|cell| cell.best_cost).collect())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layers::GridLayers;
+
+    #[test]
+    fn distances_follow_walkable_terrain() {
+        // A wall across x = 0 with a gap at the far end
+        let grid = Grid::new(IVec2::new(5, 5), 1.0, |pos| pos.x == 0.0 && pos.z < 2.0);
+        let rally_point = Vec3::new(-2.0, 0.0, -2.0);
+        let method = IntegrationMethod::default();
+        let field = IntegrationField::new(&grid, rally_point, Connectivity::default(), method);
+
+        assert_eq!(field.distance_at(rally_point), Some(0));
+        assert_eq!(field.distance_at(Vec3::new(-1.0, 0.0, -2.0)), Some(1));
+        // Around the wall instead of the 2 cells straight through it
+        assert_eq!(field.distance_at(Vec3::new(1.0, 0.0, -2.0)), Some(11));
+        assert_eq!(field.distance_at(Vec3::new(0.0, 0.0, -2.0)), None);
+        assert_eq!(field.distance_at(Vec3::new(9.0, 0.0, 0.0)), None);
+
+        let mut flowfield = FlowField::new(grid.cell_radius, grid.size, Vec::new());
+        flowfield.create_fields(&grid, &GridLayers::default(), &[], rally_point);
+        assert_eq!(flowfield.integration_field(), field);
+    }
+}
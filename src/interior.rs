@@ -0,0 +1,114 @@
+use crate::{
+    cell::Cell,
+    grid::{coords, Grid},
+};
+
+use bevy::prelude::*;
+
+pub struct InteriorPlugin;
+
+impl Plugin for InteriorPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<InteriorGrid>();
+    }
+}
+
+/// Links a walkable cell on the main grid to a walkable cell inside a building
+#[derive(Clone, Copy, Debug, PartialEq, Reflect)]
+pub struct Door {
+    pub exterior: IVec2,
+    pub interior: IVec2,
+}
+
+impl Door {
+    pub fn new(exterior: IVec2, interior: IVec2) -> Self {
+        Self { exterior, interior }
+    }
+}
+
+/// A small navigation grid for an enterable structure. The main grid only needs the building's
+/// footprint blocked and its door cells walkable, the indoor detail lives here.
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct InteriorGrid {
+    pub origin: Vec3,
+    pub grid: Grid,
+    pub doors: Vec<Door>,
+}
+
+impl InteriorGrid {
+    // creates an interior grid centered on `origin`
+    // the collision checker receives world positions
+    pub fn new<F>(
+        origin: Vec3,
+        size: IVec2,
+        cell_diameter: f32,
+        doors: Vec<Door>,
+        mut collision_checker: F,
+    ) -> Self
+    where
+        F: FnMut(Vec3) -> bool,
+    {
+        let mut grid = Grid::new(size, cell_diameter, |pos| collision_checker(pos + origin));
+
+        for cell in grid.grid.iter_mut().flatten() {
+            cell.world_pos += origin;
+        }
+
+        InteriorGrid {
+            origin,
+            grid,
+            doors,
+        }
+    }
+
+    pub fn contains(&self, world_pos: Vec3) -> bool {
+        contains(
+            self.origin,
+            self.grid.size,
+            self.grid.cell_diameter,
+            world_pos,
+        )
+    }
+
+    pub fn get_cell_from_world_position(&self, world_pos: Vec3) -> Cell {
+        self.grid
+            .get_cell_from_world_position(world_pos - self.origin)
+    }
+}
+
+/// The part of a flowfield that lies inside an interior grid
+#[derive(Clone, PartialEq, Reflect)]
+pub struct InteriorField {
+    pub building: Entity,
+    pub origin: Vec3,
+    pub cell_diameter: f32,
+    pub grid: Vec<Vec<Cell>>,
+    pub size: IVec2,
+}
+
+impl InteriorField {
+    pub fn new(building: Entity, interior: &InteriorGrid) -> Self {
+        InteriorField {
+            building,
+            origin: interior.origin,
+            cell_diameter: interior.grid.cell_diameter,
+            grid: interior.grid.grid.clone(),
+            size: interior.grid.size,
+        }
+    }
+
+    pub fn contains(&self, world_pos: Vec3) -> bool {
+        contains(self.origin, self.size, self.cell_diameter, world_pos)
+    }
+
+    pub fn get_cell_from_world_position(&self, world_pos: Vec3) -> Cell {
+        let idx =
+            coords::world_to_idx_clamped(world_pos - self.origin, self.size, self.cell_diameter);
+        self.grid[idx.y as usize][idx.x as usize]
+    }
+}
+
+fn contains(origin: Vec3, size: IVec2, cell_diameter: f32, world_pos: Vec3) -> bool {
+    coords::world_to_idx(world_pos - origin, size, cell_diameter).is_some()
+}
@@ -0,0 +1,147 @@
+use crate::{
+    cell::Cell,
+    grid::{coords, Grid},
+};
+
+use bevy::prelude::*;
+
+pub struct LayersPlugin;
+
+impl Plugin for LayersPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GridLayers>()
+            .register_type::<GridLayers>();
+    }
+}
+
+/// A cell on a specific layer. Layer 0 is the main grid, layer 1 is `GridLayers::layers[0]`.
+#[derive(Clone, Copy, Debug, PartialEq, Reflect)]
+pub struct LayerCell {
+    pub layer: usize,
+    pub idx: IVec2,
+}
+
+impl LayerCell {
+    pub fn new(layer: usize, idx: IVec2) -> Self {
+        Self { layer, idx }
+    }
+}
+
+/// A ramp or staircase that can be walked both ways between two layers
+#[derive(Clone, Copy, Debug, PartialEq, Reflect)]
+pub struct LayerLink {
+    pub a: LayerCell,
+    pub b: LayerCell,
+}
+
+/// A walkable level above the main grid, sharing its size and cell layout
+#[derive(Clone, Reflect)]
+pub struct GridLayer {
+    pub height: f32,
+    pub grid: Grid,
+}
+
+/// Extra walkable levels stacked over the main grid, e.g. bridges over walkable ground
+#[derive(Resource, Default, Reflect)]
+#[reflect(Resource)]
+pub struct GridLayers {
+    pub layers: Vec<GridLayer>,
+    pub links: Vec<LayerLink>,
+}
+
+impl GridLayers {
+    /// Adds a layer at `height` with the main grid's layout and returns its layer number.
+    /// Most of a layer is usually empty space, so the collision checker should report every
+    /// cell that isn't part of the walkable deck.
+    pub fn add_layer<F>(&mut self, main: &Grid, height: f32, mut collision_checker: F) -> usize
+    where
+        F: FnMut(Vec3) -> bool,
+    {
+        let mut grid = Grid::new(main.size, main.cell_diameter, |pos| {
+            collision_checker(pos.with_y(height))
+        });
+
+        for cell in grid.grid.iter_mut().flatten() {
+            cell.world_pos.y = height;
+        }
+
+        self.layers.push(GridLayer { height, grid });
+        self.layers.len()
+    }
+
+    /// Grows every layer along with the main grid. See `Grid::expand`.
+    pub fn expand(&mut self, border: i32) {
+        for layer in self.layers.iter_mut() {
+            layer.grid.expand(border, u8::MAX);
+        }
+
+        for link in self.links.iter_mut() {
+            link.a.idx += border;
+            link.b.idx += border;
+        }
+    }
+
+    pub fn link(&mut self, a: LayerCell, b: LayerCell) {
+        self.links.push(LayerLink { a, b });
+    }
+
+    /// The highest layer with a walkable cell at or below `world_pos`
+    pub fn layer_at(&self, world_pos: Vec3) -> usize {
+        let layers = self.layers.iter().map(|layer| {
+            let grid = &layer.grid;
+            (layer.height, grid.cell_radius, grid.grid.as_slice())
+        });
+
+        layer_at(layers, world_pos)
+    }
+}
+
+/// The part of a flowfield on one extra layer
+#[derive(Clone, PartialEq, Reflect)]
+pub struct LayerField {
+    pub height: f32,
+    pub cell_radius: f32,
+    pub grid: Vec<Vec<Cell>>,
+}
+
+impl LayerField {
+    pub fn new(layer: &GridLayer) -> Self {
+        LayerField {
+            height: layer.height,
+            cell_radius: layer.grid.cell_radius,
+            grid: layer.grid.grid.clone(),
+        }
+    }
+}
+
+/// Picks the highest layer whose height is at or below `world_pos` (within half a cell)
+/// and that is walkable there. Layers are given as (height, cell radius, cells).
+/// Returns 0 for the main grid.
+pub(crate) fn layer_at<'a>(
+    layers: impl Iterator<Item = (f32, f32, &'a [Vec<Cell>])>,
+    world_pos: Vec3,
+) -> usize {
+    let mut best = 0;
+    let mut best_height = f32::MIN;
+
+    for (i, (height, cell_radius, cells)) in layers.enumerate() {
+        if height > world_pos.y + cell_radius || height <= best_height {
+            continue;
+        }
+
+        let Some(row) = cells.first().filter(|row| !row.is_empty()) else {
+            continue;
+        };
+
+        let size = IVec2::new(row.len() as i32, cells.len() as i32);
+        let idx = coords::world_to_idx_clamped(world_pos, size, cell_radius * 2.0);
+        let cell = cells[idx.y as usize][idx.x as usize];
+
+        if cell.cost < u8::MAX {
+            best = i + 1;
+            best_height = height;
+        }
+    }
+
+    best
+}
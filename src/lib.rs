@@ -1,26 +1,185 @@
+#![allow(
+    clippy::needless_return,
+    clippy::too_many_arguments,
+    clippy::type_complexity
+)]
+
+use crate::components::*;
 use crate::events::*;
-use crate::resources::*;
-use bevy::color::palettes::css::*;
-use bevy::prelude::*;
+use bevy::{
+    ecs::schedule::{InternedScheduleLabel, ScheduleLabel},
+    prelude::*,
+};
 
-mod cell;
+pub mod astar;
+pub mod cell;
 pub mod components;
+#[cfg(feature = "config")]
+pub mod config;
+pub mod congestion;
+pub mod connector;
+#[cfg(feature = "console")]
+pub mod console;
+#[cfg(feature = "debug-draw")]
 pub mod debug;
+pub mod error;
 pub mod events;
 pub mod flowfield;
+#[cfg(feature = "config")]
+pub mod flowfield_asset;
 pub mod grid;
 mod grid_direction;
+pub mod heatmap;
+pub mod integration;
+pub mod interior;
+pub mod layers;
+pub mod minimap;
+#[cfg(feature = "nav-state")]
+pub mod nav_state;
+pub mod obstacles;
+pub mod orders;
+pub mod parking;
+pub mod path;
+pub mod physics;
+pub mod placement;
+pub mod prelude;
+pub mod profiles;
+pub mod reservations;
 pub mod resources;
+pub mod retreat;
+pub mod scheduler;
+pub mod selection;
+pub mod spatial;
+pub mod steering;
+pub mod streaming;
+pub mod stuck;
+pub mod time;
 pub mod utils;
+pub mod visibility;
 
+use astar::AStarPlugin;
+use congestion::CongestionPlugin;
+use connector::ConnectorPlugin;
 use flowfield::FlowfieldPlugin;
 use grid::GridPlugin;
+use heatmap::HeatMapPlugin;
+use interior::InteriorPlugin;
+use layers::LayersPlugin;
+use minimap::MinimapPlugin;
+use obstacles::ObstaclesPlugin;
+use orders::OrdersPlugin;
+use parking::ParkingPlugin;
+use physics::PhysicsPlugin;
+use profiles::ProfilesPlugin;
+use reservations::ReservationsPlugin;
 use resources::ResourcesPlugin;
+use retreat::RetreatPlugin;
+use scheduler::SchedulerPlugin;
+use spatial::SpatialPlugin;
+use steering::SteeringPlugin;
+use streaming::StreamingPlugin;
+use stuck::StuckPlugin;
+use visibility::VisibilityPlugin;
 
 pub struct BevyRtsPathFindingPlugin;
 
 impl Plugin for BevyRtsPathFindingPlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugins((FlowfieldPlugin, ResourcesPlugin, GridPlugin));
+        let schedule = PathfindingSchedule::label(app);
+        app.init_resource::<PathfindingSchedule>()
+            .register_type::<PathfindingSchedule>()
+            .init_resource::<time::PathfindingTimeSource>()
+            .register_type::<time::PathfindingTimeSource>()
+            .add_event::<PathfindingErrorEv>()
+            .configure_sets(
+                schedule,
+                (
+                    PathfindingSet::UpdateCosts,
+                    PathfindingSet::BuildFields,
+                    PathfindingSet::Steering.run_if(time::pathfinding_running),
+                )
+                    .chain(),
+            )
+            .register_type::<MapBase>()
+            .register_type::<GameCamera>()
+            .register_type::<Destination>()
+            .register_type::<RtsObj>()
+            .register_type::<RtsObjSize>()
+            .register_type::<RtsObjFootprint>()
+            .register_type::<AutoSized>()
+            .register_type::<FollowTarget>()
+            .register_type::<OnGrid>()
+            .register_type::<placement::PlacementPreview>()
+            .add_plugins((
+                FlowfieldPlugin,
+                ResourcesPlugin,
+                GridPlugin,
+                InteriorPlugin,
+                ConnectorPlugin,
+                CongestionPlugin,
+                LayersPlugin,
+                ObstaclesPlugin,
+                StuckPlugin,
+                VisibilityPlugin,
+                MinimapPlugin,
+                SteeringPlugin,
+                SpatialPlugin,
+                SchedulerPlugin,
+                OrdersPlugin,
+            ))
+            .add_plugins((
+                ReservationsPlugin,
+                HeatMapPlugin,
+                PhysicsPlugin,
+                StreamingPlugin,
+                RetreatPlugin,
+                AStarPlugin,
+                ProfilesPlugin,
+                ParkingPlugin,
+            ));
+
+        #[cfg(feature = "config")]
+        app.add_plugins((config::ConfigPlugin, flowfield_asset::FlowFieldAssetPlugin));
+        #[cfg(feature = "nav-state")]
+        app.add_plugins(nav_state::NavStatePlugin);
+        #[cfg(feature = "console")]
+        app.add_plugins(console::ConsolePlugin);
     }
 }
+
+/// The schedule the crate's systems run in, read once when the plugins are added. Insert it
+/// before adding `BevyRtsPathFindingPlugin` to change it.
+#[derive(Resource, Reflect, Default, Clone, Copy, Debug, PartialEq, Eq)]
+#[reflect(Resource)]
+pub enum PathfindingSchedule {
+    #[default]
+    Update,
+    /// For fixed-tick simulations. Costs, flowfields and `Steering` are updated once per tick
+    /// from the simulated `Transform`s, so units moved in `FixedUpdate` after
+    /// `PathfindingSet::Steering` can be interpolated like any other fixed-tick movement.
+    FixedUpdate,
+}
+
+impl PathfindingSchedule {
+    /// The schedule set by the app's `PathfindingSchedule`, `Update` without one
+    pub fn label(app: &App) -> InternedScheduleLabel {
+        let schedule = app.world().get_resource::<PathfindingSchedule>();
+        match schedule.copied().unwrap_or_default() {
+            PathfindingSchedule::Update => Update.intern(),
+            PathfindingSchedule::FixedUpdate => FixedUpdate.intern(),
+        }
+    }
+}
+
+/// Ordered stages of the crate's systems in the `PathfindingSchedule`. Order movement after
+/// `Steering` to see the current flowfields.
+#[derive(SystemSet, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PathfindingSet {
+    /// Obstacle, unit and congestion costs are written into the grid
+    UpdateCosts,
+    /// Flowfields are (re)built from the grid
+    BuildFields,
+    /// Units sample the flowfields and arrivals are resolved. Skipped while the game is paused,
+    /// see `time::PathfindingTimeSource`.
+    Steering,
+}
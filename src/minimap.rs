@@ -0,0 +1,104 @@
+use crate::{cell::UNREACHABLE, flowfield::FlowField, grid::Grid};
+
+use bevy::{
+    prelude::*,
+    render::{
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+    },
+};
+
+pub struct MinimapPlugin;
+
+impl Plugin for MinimapPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CostfieldTextureSettings>()
+            .register_type::<CostfieldTextureSettings>()
+            .add_systems(
+                PostUpdate,
+                bake_costfield_texture.run_if(resource_exists::<Assets<Image>>),
+            );
+    }
+}
+
+/// The costfield baked into an image with one pixel per cell, for minimaps or custom shaders.
+/// Red holds the cell cost, green the normalized best cost of the selected flowfield.
+#[derive(Resource, Clone)]
+pub struct CostfieldTexture(pub Handle<Image>);
+
+#[derive(Resource, Reflect, Default)]
+#[reflect(Resource)]
+pub struct CostfieldTextureSettings {
+    /// Flowfield entity whose integration field is baked into the green channel
+    pub flowfield: Option<Entity>,
+}
+
+fn bake_costfield_texture(
+    mut cmds: Commands,
+    mut images: ResMut<Assets<Image>>,
+    texture: Option<Res<CostfieldTexture>>,
+    settings: Res<CostfieldTextureSettings>,
+    grid: Res<Grid>,
+    q_flowfields: Query<Ref<FlowField>>,
+) {
+    let flowfield = settings.flowfield.and_then(|e| q_flowfields.get(e).ok());
+    let flowfield_changed = flowfield.as_ref().is_some_and(|f| f.is_changed());
+
+    let size = Extent3d {
+        width: grid.size.x as u32,
+        height: grid.size.y as u32,
+        depth_or_array_layers: 1,
+    };
+
+    let existing = texture.as_ref().and_then(|t| images.get(&t.0));
+    let stale = existing.is_none_or(|image| image.texture_descriptor.size != size);
+    if !stale && !grid.is_changed() && !settings.is_changed() && !flowfield_changed {
+        return;
+    }
+
+    let mut data = Vec::with_capacity(grid.grid.len() * grid.size.x as usize * 4);
+    let max_best_cost = flowfield
+        .as_ref()
+        .and_then(|f| {
+            f.grid
+                .iter()
+                .flatten()
+                .map(|cell| cell.best_cost)
+                .filter(|cost| *cost != UNREACHABLE)
+                .max()
+        })
+        .unwrap_or(0)
+        .max(1);
+
+    for (y, row) in grid.grid.iter().enumerate() {
+        for (x, cell) in row.iter().enumerate() {
+            let best_cost = match &flowfield {
+                Some(f) => match f.grid[y][x].best_cost {
+                    UNREACHABLE => u8::MAX,
+                    cost => (cost as u64 * 254 / max_best_cost as u64) as u8,
+                },
+                None => 0,
+            };
+
+            data.extend_from_slice(&[cell.cost, best_cost, 0, u8::MAX]);
+        }
+    }
+
+    if let (false, Some(image)) = (stale, texture.as_ref().and_then(|t| images.get_mut(&t.0))) {
+        image.data = data;
+        return;
+    }
+
+    // Created or resized. Resized images keep their handle so existing users stay in sync.
+    let image = Image::new(
+        size,
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8Unorm,
+        RenderAssetUsages::default(),
+    );
+    match texture {
+        Some(texture) => images.insert(&texture.0, image),
+        None => cmds.insert_resource(CostfieldTexture(images.add(image))),
+    }
+}
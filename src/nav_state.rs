@@ -0,0 +1,409 @@
+//! Snapshots of the pathfinding state for bug reports. `DumpNavStateEv` writes the `Grid`
+//! resource, its cost layers and the flowfields on it to a RON file, next to a PNG of the costs
+//! and of every field. `LoadNavStateEv` puts them back, so a report's state can be reproduced.
+//!
+//! ```ignore
+//! // Wherever the bug shows up
+//! cmds.send_event(DumpNavStateEv::new("bug-1234.nav.ron"));
+//!
+//! // Reproducing it
+//! cmds.send_event(LoadNavStateEv::new("bug-1234.nav.ron"));
+//! ```
+
+use crate::{
+    cell::UNREACHABLE,
+    events::SetActiveFlowfieldEv,
+    flowfield::{FlowField, FlowFieldIds, LaneSettings, TerrainSpeed},
+    flowfield_asset::FlowFieldAsset,
+    grid::{
+        coords::{EdgePolicies, EdgePolicy},
+        costs::BlendOp,
+        Connectivity, Grid,
+    },
+    PathfindingSchedule, PathfindingSet,
+};
+
+use bevy::prelude::*;
+use image::{ImageFormat, Rgb, RgbImage};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+pub struct NavStatePlugin;
+
+impl Plugin for NavStatePlugin {
+    fn build(&self, app: &mut App) {
+        let schedule = PathfindingSchedule::label(app);
+        app.add_event::<DumpNavStateEv>()
+            .add_event::<LoadNavStateEv>()
+            .add_systems(
+                schedule,
+                (
+                    load_nav_state.before(PathfindingSet::UpdateCosts),
+                    dump_nav_state.after(PathfindingSet::BuildFields),
+                ),
+            );
+    }
+}
+
+/// Writes the current `NavState` to `path`, and its `NavState::render` next to it as a PNG
+#[derive(Event, Clone, Debug)]
+pub struct DumpNavStateEv {
+    pub path: PathBuf,
+}
+
+impl DumpNavStateEv {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+/// Replaces the `Grid` resource and its flowfields with the `NavState` at `path`
+#[derive(Event, Clone, Debug)]
+pub struct LoadNavStateEv {
+    pub path: PathBuf,
+}
+
+impl LoadNavStateEv {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+/// The `Grid` resource with every cost layer and the flowfields on it. Fields of map entities,
+/// and the interior and layer fields of a flowfield, are left out.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct NavState {
+    /// Cells along x and z
+    pub size: (i32, i32),
+    pub cell_diameter: f32,
+    pub edge_policies: (EdgePolicy, EdgePolicy),
+    pub version: u64,
+    /// The terrain cost of every cell below the layers, row by row
+    pub terrain: Vec<u8>,
+    pub layers: Vec<DumpedLayer>,
+    /// The extra costs of moving from a cell to a neighbor, see `Grid::set_edge_cost`
+    pub edge_costs: Vec<((i32, i32), (i32, i32), u8)>,
+    pub flowfields: Vec<DumpedFlowField>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct DumpedLayer {
+    pub name: String,
+    pub priority: i32,
+    pub op: BlendOp,
+    /// The cells the layer covers with their costs, row by row
+    pub costs: Vec<((i32, i32), u8)>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct DumpedFlowField {
+    pub field: FlowFieldAsset,
+    /// The `Entity::to_bits` of the field's units
+    pub units: Vec<u64>,
+}
+
+impl NavState {
+    pub fn capture<'a>(grid: &Grid, flowfields: impl IntoIterator<Item = &'a FlowField>) -> Self {
+        let terrain = grid
+            .grid
+            .iter()
+            .flatten()
+            .map(|cell| grid.base_cost(cell.idx).unwrap_or(cell.cost))
+            .collect();
+
+        // Sorted so dumps of the same state are the same file
+        let layers = grid
+            .cost_layers()
+            .iter()
+            .map(|layer| {
+                let mut costs: Vec<((i32, i32), u8)> = layer
+                    .costs()
+                    .map(|(idx, cost)| ((idx.x, idx.y), cost))
+                    .collect();
+                costs.sort_by_key(|((x, y), _)| (*y, *x));
+
+                DumpedLayer {
+                    name: layer.name.clone(),
+                    priority: layer.priority,
+                    op: layer.op,
+                    costs,
+                }
+            })
+            .collect();
+
+        let mut edge_costs: Vec<((i32, i32), (i32, i32), u8)> = grid
+            .edge_costs()
+            .iter()
+            .map(|(from, to, cost)| ((from.x, from.y), (to.x, to.y), cost))
+            .collect();
+        edge_costs.sort_by_key(|((x, y), (to_x, to_y), _)| (*y, *x, *to_y, *to_x));
+
+        let flowfields = flowfields
+            .into_iter()
+            .filter(|flowfield| flowfield.map.is_none())
+            .map(|flowfield| DumpedFlowField {
+                field: FlowFieldAsset::from_flowfield(flowfield),
+                units: flowfield.units.iter().map(|unit| unit.to_bits()).collect(),
+            })
+            .collect();
+
+        let edge_policies = grid.edge_policies();
+        return Self {
+            size: (grid.size.x, grid.size.y),
+            cell_diameter: grid.cell_diameter,
+            edge_policies: (edge_policies.x, edge_policies.y),
+            version: grid.version,
+            terrain,
+            layers,
+            edge_costs,
+            flowfields,
+        };
+    }
+
+    pub fn size(&self) -> IVec2 {
+        IVec2::new(self.size.0, self.size.1)
+    }
+
+    pub fn to_grid(&self) -> Grid {
+        let mut grid = Grid::new(self.size(), self.cell_diameter, |_| false);
+        let (x, y) = self.edge_policies;
+        grid.set_edge_policies(EdgePolicies::new(x, y));
+        for (from, to, cost) in self.edge_costs.iter() {
+            grid.set_edge_cost(IVec2::new(from.0, from.1), IVec2::new(to.0, to.1), *cost);
+        }
+
+        for (cell, cost) in grid
+            .grid
+            .iter_mut()
+            .flatten()
+            .zip(self.terrain.iter().copied())
+        {
+            cell.cost = cost;
+        }
+
+        for layer in self.layers.iter() {
+            grid.add_cost_layer(&layer.name, layer.priority, layer.op);
+            for ((x, y), cost) in layer.costs.iter() {
+                grid.set_layer_cost(&layer.name, IVec2::new(*x, *y), *cost);
+            }
+        }
+
+        grid.label_regions(Connectivity::default());
+        grid.version = self.version;
+        return grid;
+    }
+
+    pub fn to_ron(&self) -> Result<String, ron::Error> {
+        return ron::ser::to_string(self);
+    }
+
+    /// Writes the state to `path` and `NavState::render` to `path` with a `png` extension
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), NavStateError> {
+        let path = path.as_ref();
+        let ron = self.to_ron().map_err(NavStateError::Serialize)?;
+        std::fs::write(path, ron).map_err(NavStateError::Io)?;
+
+        return self
+            .render()
+            .save_with_format(path.with_extension("png"), ImageFormat::Png)
+            .map_err(NavStateError::Image);
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, NavStateError> {
+        let bytes = std::fs::read(path).map_err(NavStateError::Io)?;
+        return ron::de::from_bytes(&bytes).map_err(NavStateError::Ron);
+    }
+
+    /// The costs, then the integrated costs of every flowfield, side by side. Costs are white
+    /// for 1, darker the higher they are and black when impassable. Integrated costs go from
+    /// blue near the green destination to red far from it, with unreachable cells black.
+    pub fn render(&self) -> RgbImage {
+        let size = self.size().max(IVec2::ONE);
+        let scale = (512 / size.max_element()).clamp(1, 8) as u32;
+        let panel = UVec2::new(size.x as u32, size.y as u32) * scale;
+        let panels = 1 + self.flowfields.len() as u32;
+        let costs = self.to_grid();
+        let max_costs: Vec<_> = self
+            .flowfields
+            .iter()
+            .map(|dumped| {
+                let reachable = dumped.field.cells.iter().map(|cell| cell.best_cost);
+                let max = reachable
+                    .filter(|best_cost| *best_cost != UNREACHABLE)
+                    .max();
+                max.unwrap_or(1).max(1)
+            })
+            .collect();
+
+        return RgbImage::from_fn(panel.x * panels, panel.y, |x, y| {
+            let idx = IVec2::new(((x % panel.x) / scale) as i32, (y / scale) as i32);
+            let Some(dumped) = (x / panel.x).checked_sub(1) else {
+                let cost = costs.cell(idx).map_or(u8::MAX, |cell| cell.cost);
+                return cost_color(cost);
+            };
+
+            let field = &self.flowfields[dumped as usize].field;
+            if idx == IVec2::new(field.destination_idx.0, field.destination_idx.1) {
+                return Rgb([0, 200, 0]);
+            }
+
+            let best_cost = field
+                .cells
+                .get((idx.y * size.x + idx.x) as usize)
+                .map_or(UNREACHABLE, |cell| cell.best_cost);
+            if best_cost == UNREACHABLE {
+                return Rgb([0, 0, 0]);
+            }
+
+            let t = best_cost as f32 / max_costs[dumped as usize] as f32;
+            return Rgb([(255.0 * t) as u8, 64, (255.0 * (1.0 - t)) as u8]);
+        });
+    }
+}
+
+fn cost_color(cost: u8) -> Rgb<u8> {
+    if cost == u8::MAX {
+        return Rgb([0, 0, 0]);
+    }
+
+    let shade = 255 - (cost.saturating_sub(1) as u32 * 200 / 253) as u8;
+    return Rgb([shade, shade, shade]);
+}
+
+#[derive(Debug)]
+pub enum NavStateError {
+    Io(std::io::Error),
+    Ron(ron::error::SpannedError),
+    Serialize(ron::Error),
+    Image(image::ImageError),
+}
+
+impl fmt::Display for NavStateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NavStateError::Io(error) => write!(f, "Could not access nav state: {error}"),
+            NavStateError::Ron(error) => write!(f, "Could not parse nav state: {error}"),
+            NavStateError::Serialize(error) => {
+                write!(f, "Could not serialize nav state: {error}")
+            }
+            NavStateError::Image(error) => write!(f, "Could not render nav state: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for NavStateError {}
+
+fn dump_nav_state(
+    mut events: EventReader<DumpNavStateEv>,
+    grid: Res<Grid>,
+    q_flowfields: Query<&FlowField>,
+) {
+    for ev in events.read() {
+        match NavState::capture(&grid, q_flowfields.iter()).save(&ev.path) {
+            Ok(()) => info!("Dumped the nav state to {}", ev.path.display()),
+            Err(error) => warn!("{error}"),
+        }
+    }
+}
+
+/// Replaces the `Grid` resource and its flowfields. Fields keep the units that still exist and
+/// are despawned without any, the first one is shown by the debug draw. Units, obstacles and
+/// timers in the world keep writing their costs into the loaded grid.
+fn load_nav_state(
+    mut cmds: Commands,
+    mut events: EventReader<LoadNavStateEv>,
+    mut grid: ResMut<Grid>,
+    terrain_speed: Res<TerrainSpeed>,
+    lanes: Res<LaneSettings>,
+    mut ids: ResMut<FlowFieldIds>,
+    q_flowfields: Query<(Entity, &FlowField)>,
+) {
+    for ev in events.read() {
+        let state = match NavState::load(&ev.path) {
+            Ok(state) => state,
+            Err(error) => {
+                warn!("{error}");
+                continue;
+            }
+        };
+
+        // Continue the current version so fields built on the replaced grid rebuild
+        let mut loaded = state.to_grid();
+        loaded.version = grid.version + 1;
+        *grid = loaded;
+
+        for (entity, flowfield) in q_flowfields.iter() {
+            if flowfield.map.is_none() {
+                cmds.entity(entity).despawn_recursive();
+            }
+        }
+
+        for (dumped, i) in state.flowfields.iter().zip(0..) {
+            let units = dumped
+                .units
+                .iter()
+                .filter_map(|bits| Entity::try_from_bits(*bits).ok())
+                .filter(|unit| cmds.get_entity(*unit).is_some())
+                .collect();
+
+            let mut flowfield = dumped.field.instantiate(units);
+            flowfield.id = ids.assign();
+            flowfield.terrain_speed = *terrain_speed;
+            flowfield.lanes = *lanes;
+            flowfield.edge_costs = grid.edge_costs().clone();
+            flowfield.edge_policies = grid.edge_policies();
+            flowfield.rotation = grid.rotation;
+            flowfield.costfield_version = grid.version;
+
+            if i == 0 {
+                cmds.trigger(SetActiveFlowfieldEv(Some(flowfield.clone())));
+            }
+            cmds.spawn(flowfield);
+        }
+
+        info!("Loaded the nav state from {}", ev.path.display());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{grid::costs, layers::GridLayers};
+
+    #[test]
+    fn states_survive_a_round_trip_through_ron() {
+        let mut grid = Grid::new(IVec2::new(6, 4), 1.0, |pos| pos.x == 0.5 && pos.z < 1.0);
+        grid.set_base_cost(IVec2::new(0, 3), 9);
+        grid.set_layer_cost(costs::UNIT_LAYER, IVec2::new(1, 1), u8::MAX);
+        grid.set_edge_cost(IVec2::new(4, 0), IVec2::new(5, 0), 6);
+        let mut flowfield = FlowField::new(grid.cell_radius, grid.size, vec![Entity::from_raw(3)]);
+        flowfield.create_fields(
+            &grid,
+            &GridLayers::default(),
+            &[],
+            Vec3::new(2.5, 0.0, -1.5),
+        );
+
+        let state = NavState::capture(&grid, [&flowfield]);
+        let loaded: NavState = ron::de::from_str(&state.to_ron().unwrap()).unwrap();
+        assert_eq!(loaded, state);
+        assert_eq!(
+            loaded.flowfields[0].units,
+            vec![Entity::from_raw(3).to_bits()]
+        );
+
+        let restored = loaded.to_grid();
+        assert_eq!(restored.grid, grid.grid);
+        assert_eq!(restored.version, grid.version);
+        assert_eq!(restored.base_cost(IVec2::new(1, 1)), Some(1));
+        assert_eq!(restored.edge_cost(IVec2::new(4, 0), IVec2::new(5, 0)), 6);
+
+        let image = loaded.render();
+        assert_eq!((image.width(), image.height()), (6 * 8 * 2, 4 * 8));
+        assert_eq!(image.get_pixel(8, 8), &Rgb([0, 0, 0]));
+        assert_eq!(image.get_pixel(0, 0), &Rgb([255, 255, 255]));
+    }
+}
@@ -0,0 +1,750 @@
+use crate::{
+    components::*,
+    events::{GridExpandedEv, MapChangedEv, ObstacleToggledEv, UpdateCostEv},
+    flowfield::FlowField,
+    grid::{costs, Grid, Grids},
+    interior::InteriorGrid,
+    layers::GridLayers,
+    resources::PathfindingStats,
+    time::PathfindingTime,
+    PathfindingSchedule, PathfindingSet,
+};
+
+use bevy::{
+    prelude::*,
+    render::{mesh::MeshAabb, primitives::Aabb},
+    utils::Instant,
+};
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+pub struct ObstaclesPlugin;
+
+impl Plugin for ObstaclesPlugin {
+    fn build(&self, app: &mut App) {
+        let schedule = PathfindingSchedule::label(app);
+        app.init_resource::<ObstacleSettings>()
+            .init_resource::<ObstacleCells>()
+            .register_type::<ObstacleSettings>()
+            .register_type::<ObstacleCells>()
+            .register_type::<InflationRadius>()
+            .register_type::<PendingObstacle>()
+            .register_type::<ToggleableObstacle>()
+            .register_type::<NavBlocker>()
+            .add_event::<ObstacleToggledEv>()
+            .add_systems(
+                schedule,
+                (
+                    (
+                        // Headless apps have no meshes to scan
+                        scan_nav_blockers.run_if(resource_exists::<Assets<Mesh>>),
+                        inflate_obstacles,
+                        track_obstacles,
+                    )
+                        .chain()
+                        .in_set(PathfindingSet::UpdateCosts),
+                    repair_toggled_flowfields.in_set(PathfindingSet::BuildFields),
+                ),
+            );
+    }
+}
+
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
+pub struct ObstacleSettings {
+    /// Seconds between obstacle re-rasterizations. Moves in between are batched.
+    pub update_interval: f32,
+    /// Cost of cells reserved by a `PendingObstacle`. Units avoid them but can still pass.
+    pub reserved_cost: u8,
+    /// How far around blocked cells costs are raised to `inflation_cost`, so flowfields keep
+    /// units off walls
+    pub inflation_radius: InflationRadius,
+    pub inflation_cost: u8,
+}
+
+impl Default for ObstacleSettings {
+    fn default() -> Self {
+        ObstacleSettings {
+            update_interval: 0.1,
+            reserved_cost: 200,
+            inflation_radius: InflationRadius::Cells(0),
+            inflation_cost: 20,
+        }
+    }
+}
+
+/// The safety margin around blocked cells, see `ObstacleSettings::inflation_radius`
+#[derive(Reflect, Clone, Copy, Debug, PartialEq)]
+pub enum InflationRadius {
+    Cells(u32),
+    /// Rounded up to whole cells of each grid
+    World(f32),
+}
+
+impl InflationRadius {
+    /// The radius in cells on a grid of `cell_diameter`
+    pub fn cells(self, cell_diameter: f32) -> i32 {
+        match self {
+            InflationRadius::Cells(cells) => cells as i32,
+            InflationRadius::World(radius) => (radius / cell_diameter).ceil().max(0.0) as i32,
+        }
+    }
+}
+
+/// The inflation ring an `ObstacleCells` currently writes
+#[derive(Reflect, Clone, Copy, Default, PartialEq)]
+struct Inflation {
+    radius: i32,
+    cost: u8,
+}
+
+/// Marks an `RtsObj` that is planned or under construction. Its cells are reserved with
+/// `ObstacleSettings::reserved_cost` instead of blocked, and become impassable once the component
+/// is removed. Despawning the entity cancels the reservation.
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+pub struct PendingObstacle;
+
+/// A gate or door. Open, it leaves its cells as they are; closed, it blocks them like any other
+/// obstacle. Toggling rebuilds the flowfields built against the old costs.
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+pub struct ToggleableObstacle {
+    pub open: bool,
+}
+
+#[derive(Reflect, Clone, Copy)]
+struct Occupancy {
+    blocking: u32,
+    reserving: u32,
+    reserved_cost: u8,
+}
+
+impl Occupancy {
+    /// Writes the occupancy into the grid's obstacle and reservation layers, returning true if
+    /// the cell's cost changed
+    fn write(&self, grid: &mut Grid, idx: IVec2) -> bool {
+        let before = grid.grid[idx.y as usize][idx.x as usize].cost;
+
+        match self.blocking > 0 {
+            true => grid.set_layer_cost(costs::OBSTACLE_LAYER, idx, u8::MAX),
+            false => grid.clear_layer_cost(costs::OBSTACLE_LAYER, idx),
+        };
+        match self.reserving > 0 {
+            true => grid.set_layer_cost(costs::RESERVATION_LAYER, idx, self.reserved_cost),
+            false => grid.clear_layer_cost(costs::RESERVATION_LAYER, idx),
+        };
+
+        return grid.grid[idx.y as usize][idx.x as usize].cost != before;
+    }
+}
+
+/// The cells blocked by each stationary `RtsObj`. Units with a `Destination` are moving and
+/// don't block cells. The resource tracks the `Grid` resource, map entities carry their own.
+#[derive(Resource, Component, Default, Reflect)]
+#[reflect(Resource, Component)]
+pub struct ObstacleCells {
+    by_entity: HashMap<Entity, Vec<IVec2>>,
+    reserved: HashSet<Entity>,
+    // obstacles covering a cell
+    occupancy: HashMap<IVec2, Occupancy>,
+    // blocked cells within the inflation radius of a cell
+    inflated: HashMap<IVec2, u32>,
+    inflation: Inflation,
+}
+
+impl ObstacleCells {
+    pub fn cells_of(&self, entity: Entity) -> &[IVec2] {
+        self.by_entity
+            .get(&entity)
+            .map(Vec::as_slice)
+            .unwrap_or(&[])
+    }
+
+    pub fn is_occupied(&self, idx: IVec2) -> bool {
+        self.occupancy.contains_key(&idx)
+    }
+
+    /// The cells every blocking obstacle of which is one of `entities`
+    pub fn blocked_only_by(&self, entities: &[Entity]) -> Vec<IVec2> {
+        let mut covering: HashMap<IVec2, u32> = HashMap::new();
+        for entity in entities.iter().filter(|entity| !self.is_reserved(**entity)) {
+            for idx in self.cells_of(*entity) {
+                *covering.entry(*idx).or_default() += 1;
+            }
+        }
+
+        return covering
+            .into_iter()
+            .filter(|(idx, count)| {
+                self.occupancy
+                    .get(idx)
+                    .is_some_and(|occupancy| occupancy.blocking == *count)
+            })
+            .map(|(idx, _)| idx)
+            .collect();
+    }
+
+    /// True if the entity's cells are only reserved by a `PendingObstacle`
+    pub fn is_reserved(&self, entity: Entity) -> bool {
+        self.reserved.contains(&entity)
+    }
+
+    /// Follows the cell indices after the grid was expanded
+    pub fn shift(&mut self, border: i32) {
+        for cells in self.by_entity.values_mut() {
+            for idx in cells.iter_mut() {
+                *idx += border;
+            }
+        }
+
+        self.occupancy = self
+            .occupancy
+            .drain()
+            .map(|(idx, occupancy)| (idx + border, occupancy))
+            .collect();
+        self.inflated = self
+            .inflated
+            .drain()
+            .map(|(idx, count)| (idx + border, count))
+            .collect();
+    }
+
+    /// Raises the cells within `radius` of blocked cells to at least `cost`, replacing the
+    /// previous ring. Returns the cells whose cost changed.
+    pub fn set_inflation(&mut self, grid: &mut Grid, radius: i32, cost: u8) -> Vec<IVec2> {
+        let inflation = Inflation { radius, cost };
+        if inflation == self.inflation {
+            return Vec::new();
+        }
+
+        let blocked: Vec<IVec2> = self
+            .occupancy
+            .iter()
+            .filter(|(_, occupancy)| occupancy.blocking > 0)
+            .map(|(idx, _)| *idx)
+            .collect();
+
+        let mut changed = Vec::new();
+        for idx in blocked.iter() {
+            self.inflate(grid, *idx, false, &mut changed);
+        }
+
+        self.inflation = inflation;
+        for idx in blocked.iter() {
+            self.inflate(grid, *idx, true, &mut changed);
+        }
+
+        changed.sort_by_key(|idx| (idx.y, idx.x));
+        changed.dedup();
+        return changed;
+    }
+
+    /// Adds or takes the blocked cell at `center` from the counts of the cells around it
+    fn inflate(&mut self, grid: &mut Grid, center: IVec2, add: bool, changed: &mut Vec<IVec2>) {
+        let Inflation { radius, cost } = self.inflation;
+        if radius <= 0 {
+            return;
+        }
+
+        for y in -radius..=radius {
+            for x in -radius..=radius {
+                let idx = center + IVec2::new(x, y);
+                if grid.cell(idx).is_none() {
+                    continue;
+                }
+
+                let count = self.inflated.entry(idx).or_default();
+                match add {
+                    true => *count += 1,
+                    false => *count = count.saturating_sub(1),
+                }
+
+                let cost_changed = match *count {
+                    0 => {
+                        self.inflated.remove(&idx);
+                        grid.clear_layer_cost(costs::INFLATION_LAYER, idx)
+                    }
+                    _ => grid.set_layer_cost(costs::INFLATION_LAYER, idx, cost),
+                };
+                if cost_changed {
+                    changed.push(idx);
+                }
+            }
+        }
+    }
+
+    /// Blocks the cells and returns the ones whose cost changed
+    pub fn insert(&mut self, grid: &mut Grid, entity: Entity, cells: Vec<IVec2>) -> Vec<IVec2> {
+        return self.occupy(grid, entity, cells, None);
+    }
+
+    /// Raises the cells to at least `cost` without blocking them, returning the ones whose cost
+    /// changed
+    pub fn reserve(
+        &mut self,
+        grid: &mut Grid,
+        entity: Entity,
+        cells: Vec<IVec2>,
+        cost: u8,
+    ) -> Vec<IVec2> {
+        return self.occupy(grid, entity, cells, Some(cost));
+    }
+
+    fn occupy(
+        &mut self,
+        grid: &mut Grid,
+        entity: Entity,
+        cells: Vec<IVec2>,
+        reserved_cost: Option<u8>,
+    ) -> Vec<IVec2> {
+        let mut changed = Vec::new();
+
+        for idx in cells.iter() {
+            let occupancy = self.occupancy.entry(*idx).or_insert(Occupancy {
+                blocking: 0,
+                reserving: 0,
+                reserved_cost: 0,
+            });
+            let was_blocking = occupancy.blocking > 0;
+
+            match reserved_cost {
+                Some(cost) => {
+                    occupancy.reserving += 1;
+                    occupancy.reserved_cost = occupancy.reserved_cost.max(cost);
+                }
+                None => occupancy.blocking += 1,
+            }
+
+            let blocking = occupancy.blocking > 0;
+            if occupancy.write(grid, *idx) {
+                changed.push(*idx);
+            }
+            if blocking && !was_blocking {
+                self.inflate(grid, *idx, true, &mut changed);
+            }
+        }
+
+        if reserved_cost.is_some() {
+            self.reserved.insert(entity);
+        }
+        self.by_entity.insert(entity, cells);
+        changed
+    }
+
+    /// Frees the entity's cells, restoring their cost once no other obstacle covers them.
+    /// Returns the cells whose cost changed.
+    pub fn remove(&mut self, grid: &mut Grid, entity: Entity) -> Vec<IVec2> {
+        let mut changed = Vec::new();
+        let reserved = self.reserved.remove(&entity);
+
+        for idx in self.by_entity.remove(&entity).unwrap_or_default() {
+            let Some(occupancy) = self.occupancy.get_mut(&idx) else {
+                continue;
+            };
+
+            let was_blocking = occupancy.blocking > 0;
+            match reserved {
+                true => occupancy.reserving -= 1,
+                false => occupancy.blocking -= 1,
+            }
+            if occupancy.reserving == 0 {
+                occupancy.reserved_cost = 0;
+            }
+
+            if occupancy.write(grid, idx) {
+                changed.push(idx);
+            }
+
+            let blocking = occupancy.blocking > 0;
+            if occupancy.blocking == 0 && occupancy.reserving == 0 {
+                self.occupancy.remove(&idx);
+            }
+            if was_blocking && !blocking {
+                self.inflate(grid, idx, false, &mut changed);
+            }
+        }
+
+        changed
+    }
+}
+
+/// The cells covered by an obstacle's polygon footprint, or else by its `RtsObjSize`
+fn obstacle_cells(
+    grid: &Grid,
+    transform: &Transform,
+    size: Option<&RtsObjSize>,
+    footprint: Option<&RtsObjFootprint>,
+) -> Vec<IVec2> {
+    match (footprint, size) {
+        (Some(RtsObjFootprint::Polygon(points)), _) => {
+            let points: Vec<Vec2> = points
+                .iter()
+                .map(|p| transform.transform_point(Vec3::new(p.x, 0.0, p.y)).xz())
+                .collect();
+            grid.cells_in_polygon(&points)
+        }
+        (_, Some(size)) => footprint_cells(grid, transform.translation, size.0),
+        _ => Vec::new(),
+    }
+}
+
+/// The cells covered by a footprint centered at `position` with the given XZ half extents
+pub fn footprint_cells(grid: &Grid, position: Vec3, half_extents: Vec2) -> Vec<IVec2> {
+    let half_extents = Vec3::new(half_extents.x, 0.0, half_extents.y);
+
+    grid.cells_in_rect(position - half_extents, position + half_extents)
+        .map(|cell| cell.idx)
+        .collect()
+}
+
+/// A mesh of a `NavBlocker` whose cells are blocked
+#[derive(Component)]
+struct ScannedMesh;
+
+/// The cells under the world space bounds of a mesh, and the height of those bounds
+fn mesh_cells(grid: &Grid, aabb: &Aabb, transform: &GlobalTransform) -> (Vec<IVec2>, f32) {
+    let center = transform.transform_point(aabb.center.into());
+    let half_extents = transform.affine().matrix3.abs() * aabb.half_extents;
+
+    let cells = footprint_cells(grid, center, half_extents.xz());
+    return (cells, half_extents.y * 2.0);
+}
+
+/// Blocks the cells under the meshes of `NavBlocker`s as they spawn, such as when a GLTF scene
+/// finishes loading, and frees them when the meshes despawn. Every mesh is scanned again on the
+/// rebuilt grid after a `MapChangedEv`.
+fn scan_nav_blockers(
+    mut cmds: Commands,
+    mut pending: Local<HashSet<Entity>>,
+    mut map_changed: EventReader<MapChangedEv>,
+    meshes: Res<Assets<Mesh>>,
+    mut grid: ResMut<Grid>,
+    mut obstacles: ResMut<ObstacleCells>,
+    mut events: EventWriter<UpdateCostEv>,
+    mut removed: RemovedComponents<ScannedMesh>,
+    q_added_meshes: Query<Entity, Added<Mesh3d>>,
+    q_added_blockers: Query<Entity, Added<NavBlocker>>,
+    q_meshes: Query<(&Mesh3d, &GlobalTransform, Has<ScannedMesh>), Without<RtsObj>>,
+    q_blockers: Query<&NavBlocker>,
+    q_all_blockers: Query<Entity, With<NavBlocker>>,
+    q_parents: Query<&Parent>,
+    q_children: Query<&Children>,
+) {
+    for entity in removed.read() {
+        for idx in obstacles.remove(&mut grid, entity) {
+            events.send(UpdateCostEv::new(grid.grid[idx.y as usize][idx.x as usize]));
+        }
+    }
+
+    let rescan = map_changed.read().count() > 0;
+    let blockers = match rescan {
+        true => q_all_blockers.iter().collect::<Vec<_>>(),
+        false => q_added_blockers.iter().collect(),
+    };
+
+    pending.extend(q_added_meshes.iter());
+    for blocker in blockers {
+        pending.insert(blocker);
+        pending.extend(q_children.iter_descendants(blocker));
+    }
+
+    for entity in pending.drain().collect::<Vec<_>>() {
+        let Ok((mesh, transform, scanned)) = q_meshes.get(entity) else {
+            continue;
+        };
+        if scanned && !rescan {
+            continue;
+        }
+        let blocker = std::iter::once(entity)
+            .chain(q_parents.iter_ancestors(entity))
+            .find_map(|ancestor| q_blockers.get(ancestor).ok());
+        let Some(blocker) = blocker else {
+            continue;
+        };
+
+        // The mesh may still be loading, in which case this is retried next frame
+        let Some(aabb) = meshes.get(mesh).and_then(|mesh| mesh.compute_aabb()) else {
+            pending.insert(entity);
+            continue;
+        };
+
+        let (cells, height) = mesh_cells(&grid, &aabb, transform);
+        if height < blocker.min_height || cells.is_empty() {
+            continue;
+        }
+
+        for idx in obstacles.insert(&mut grid, entity, cells) {
+            events.send(UpdateCostEv::new(grid.grid[idx.y as usize][idx.x as usize]));
+        }
+        cmds.entity(entity).insert(ScannedMesh);
+    }
+}
+
+/// Keeps the inflation ring of every grid in line with `ObstacleSettings`
+fn inflate_obstacles(
+    settings: Res<ObstacleSettings>,
+    mut grid: ResMut<Grid>,
+    mut obstacles: ResMut<ObstacleCells>,
+    mut q_maps: Query<(Entity, &mut Grid, &mut ObstacleCells)>,
+    mut events: EventWriter<UpdateCostEv>,
+) {
+    let inflation = |grid: &Grid| Inflation {
+        radius: settings.inflation_radius.cells(grid.cell_diameter),
+        cost: settings.inflation_cost,
+    };
+
+    // Only borrow mutably on a change, so grids aren't flagged as changed every frame
+    if obstacles.inflation != inflation(&grid) {
+        let Inflation { radius, cost } = inflation(&grid);
+        for idx in obstacles.set_inflation(&mut grid, radius, cost) {
+            events.send(UpdateCostEv::new(grid.grid[idx.y as usize][idx.x as usize]));
+        }
+    }
+
+    for (map, mut grid, mut obstacles) in q_maps.iter_mut() {
+        if obstacles.inflation == inflation(&grid) {
+            continue;
+        }
+
+        let Inflation { radius, cost } = inflation(&grid);
+        for idx in obstacles.set_inflation(&mut grid, radius, cost) {
+            let cell = grid.grid[idx.y as usize][idx.x as usize];
+            events.send(UpdateCostEv::on_map(cell, map));
+        }
+    }
+}
+
+fn track_obstacles(
+    mut pending: Local<HashSet<Entity>>,
+    mut timer: Local<Timer>,
+    time: PathfindingTime,
+    settings: Res<ObstacleSettings>,
+    grid: ResMut<Grid>,
+    mut obstacles: ResMut<ObstacleCells>,
+    mut events: EventWriter<UpdateCostEv>,
+    mut toggled: EventWriter<ObstacleToggledEv>,
+    (mut expanded, mut map_changed): (EventReader<GridExpandedEv>, EventReader<MapChangedEv>),
+    q_objs: Query<Entity, With<RtsObj>>,
+    q_changed: Query<
+        Entity,
+        (
+            With<RtsObj>,
+            Or<(
+                Changed<Transform>,
+                Changed<RtsObjSize>,
+                Changed<RtsObjFootprint>,
+                Added<Destination>,
+                Added<PendingObstacle>,
+                Changed<ToggleableObstacle>,
+            )>,
+        ),
+    >,
+    mut removed_objs: RemovedComponents<RtsObj>,
+    mut removed_destinations: RemovedComponents<Destination>,
+    mut removed_pending: RemovedComponents<PendingObstacle>,
+    mut q_maps: Query<(Entity, &mut Grid, &mut ObstacleCells)>,
+    q_obstacles: Query<
+        (
+            &Transform,
+            Option<&RtsObjSize>,
+            Option<&RtsObjFootprint>,
+            Has<PendingObstacle>,
+            Option<&ToggleableObstacle>,
+            Option<&OnGrid>,
+        ),
+        (With<RtsObj>, Without<Destination>),
+    >,
+) {
+    for ev in expanded.read() {
+        obstacles.shift(ev.border);
+    }
+
+    // The rebuilt grid has none of the obstacles blocked yet
+    let rebuilt = map_changed.read().count() > 0;
+    if rebuilt {
+        pending.extend(q_objs.iter());
+    }
+
+    pending.extend(q_changed.iter());
+    pending.extend(removed_objs.read());
+    pending.extend(removed_destinations.read());
+    pending.extend(removed_pending.read());
+
+    timer.set_duration(Duration::from_secs_f32(settings.update_interval));
+    timer.set_mode(TimerMode::Repeating);
+    if !(timer.tick(time.delta()).just_finished() || rebuilt) || pending.is_empty() {
+        return;
+    }
+
+    // The `Grid` resource, then the grid of every map entity
+    let mut grids: Vec<(Option<Entity>, Mut<Grid>, Mut<ObstacleCells>)> =
+        vec![(None, grid.into(), obstacles.into())];
+    grids.extend(
+        q_maps
+            .iter_mut()
+            .map(|(map, grid, obstacles)| (Some(map), grid, obstacles)),
+    );
+
+    for entity in pending.drain() {
+        let obstacle = q_obstacles.get(entity).ok();
+        let map = obstacle.and_then(|(.., on_grid)| on_grid.map(|on_grid| on_grid.0));
+        let gate = obstacle.and_then(|(.., gate, _)| gate);
+        let reserved = obstacle.is_some_and(|(_, _, _, reserved, ..)| reserved);
+
+        let cells = match obstacle {
+            Some((.., Some(gate), _)) if gate.open => Vec::new(),
+            Some((transform, size, footprint, ..)) => grids
+                .iter()
+                .find(|(grid_map, ..)| *grid_map == map)
+                .map(|(_, grid, _)| obstacle_cells(grid, transform, size, footprint))
+                .unwrap_or_default(),
+            None => Vec::new(),
+        };
+
+        // Frees the cells on every other grid, in case the obstacle moved between maps
+        for (grid_map, grid, obstacles) in grids.iter_mut() {
+            let cells = match *grid_map == map {
+                true => cells.clone(),
+                false => Vec::new(),
+            };
+
+            let unchanged = cells == obstacles.cells_of(entity)
+                && (cells.is_empty() || reserved == obstacles.is_reserved(entity));
+            if unchanged {
+                continue;
+            }
+
+            let mut changed = obstacles.remove(grid, entity);
+            if !cells.is_empty() {
+                changed.extend(match reserved {
+                    true => obstacles.reserve(grid, entity, cells, settings.reserved_cost),
+                    false => obstacles.insert(grid, entity, cells),
+                });
+            }
+
+            for idx in changed.iter() {
+                let cell = grid.grid[idx.y as usize][idx.x as usize];
+                events.send(match grid_map {
+                    Some(map) => UpdateCostEv::on_map(cell, *map),
+                    None => UpdateCostEv::new(cell),
+                });
+            }
+
+            if let Some(gate) = gate.filter(|_| !changed.is_empty()) {
+                toggled.send(ObstacleToggledEv::new(
+                    entity, *grid_map, gate.open, changed,
+                ));
+            }
+        }
+    }
+}
+
+/// Repairs the flowfields whose costs under a toggled gate no longer match the grid around the
+/// gate, or rebuilds them when the gate closed
+fn repair_toggled_flowfields(
+    grids: Grids,
+    layers: Res<GridLayers>,
+    mut stats: ResMut<PathfindingStats>,
+    mut events: EventReader<ObstacleToggledEv>,
+    mut q_flowfields: Query<&mut FlowField>,
+    q_interiors: Query<(Entity, &InteriorGrid)>,
+) {
+    let mut toggled: HashMap<Option<Entity>, HashSet<IVec2>> = HashMap::new();
+    for ev in events.read() {
+        toggled.entry(ev.map).or_default().extend(ev.cells.iter());
+    }
+    if toggled.is_empty() {
+        return;
+    }
+
+    let start = Instant::now();
+    let interiors: Vec<(Entity, &InteriorGrid)> = q_interiors.iter().collect();
+
+    for mut flowfield in q_flowfields.iter_mut() {
+        let (Some(cells), Some(grid)) = (toggled.get(&flowfield.map), grids.get(flowfield.map))
+        else {
+            continue;
+        };
+
+        if !flowfield.costs_differ(grid, cells) || flowfield.repair_opened(grid, cells) {
+            continue;
+        }
+
+        flowfield.rebuild(&grids, &layers, &interiors);
+    }
+    stats.record_integration(start.elapsed());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::FRAC_PI_2;
+
+    #[test]
+    fn inflation_rings_blocked_cells_until_they_are_freed() {
+        let mut grid = Grid::new(IVec2::new(7, 7), 1.0, |_| false);
+        let mut obstacles = ObstacleCells::default();
+        let wall = Entity::from_raw(0);
+        let cost = |grid: &Grid, x: usize, y: usize| grid.grid[y][x].cost;
+
+        obstacles.insert(&mut grid, wall, vec![IVec2::new(3, 3)]);
+        let changed = obstacles.set_inflation(&mut grid, 1, 20);
+        assert_eq!(changed.len(), 8);
+        assert_eq!(cost(&grid, 3, 3), u8::MAX);
+        assert_eq!(cost(&grid, 2, 2), 20);
+        assert_eq!(cost(&grid, 1, 3), 1);
+
+        // A wider ring replaces the old one
+        obstacles.set_inflation(&mut grid, 2, 20);
+        assert_eq!(cost(&grid, 1, 3), 20);
+
+        obstacles.remove(&mut grid, wall);
+        assert!(grid.grid.iter().flatten().all(|cell| cell.cost == 1));
+    }
+
+    #[test]
+    fn scanned_meshes_block_the_cells_under_their_bounds() {
+        let grid = Grid::new(IVec2::new(8, 8), 1.0, |_| false);
+        let aabb = Mesh::from(Cuboid::new(2.0, 1.0, 1.0))
+            .compute_aabb()
+            .unwrap();
+
+        // Turned a quarter around y, the 2 x 1 crate lies along z
+        let transform = GlobalTransform::from(
+            Transform::from_xyz(0.5, 0.5, 0.0).with_rotation(Quat::from_rotation_y(FRAC_PI_2)),
+        );
+        let (cells, height) = mesh_cells(&grid, &aabb, &transform);
+        assert!((height - 1.0).abs() < 1e-5);
+        assert!(cells.contains(&IVec2::new(4, 3)) && cells.contains(&IVec2::new(4, 4)));
+        assert!(!cells.iter().any(|idx| idx.x == 3 || idx.y < 2 || idx.y > 5));
+    }
+
+    #[test]
+    fn reused_entity_indices_keep_their_own_cells() {
+        let mut world = World::new();
+        let mut grid = Grid::new(IVec2::new(5, 5), 1.0, |_| false);
+        let mut obstacles = ObstacleCells::default();
+
+        // Despawn/respawn cycles hand out the same index with a new generation
+        let mut previous = world.spawn_empty().id();
+        obstacles.insert(&mut grid, previous, vec![IVec2::new(0, 0)]);
+        for cycle in 1..4 {
+            obstacles.remove(&mut grid, previous);
+            world.despawn(previous);
+
+            let current = world.spawn_empty().id();
+            assert_eq!(current.index(), previous.index());
+            assert_ne!(current, previous);
+
+            obstacles.insert(&mut grid, current, vec![IVec2::new(cycle, cycle)]);
+
+            // Stale handles of despawned obstacles free nothing
+            assert!(obstacles.remove(&mut grid, previous).is_empty());
+            assert!(obstacles.cells_of(previous).is_empty());
+            assert_eq!(obstacles.cells_of(current), &[IVec2::new(cycle, cycle)]);
+            assert_eq!(grid.grid[cycle as usize][cycle as usize].cost, u8::MAX);
+            previous = current;
+        }
+    }
+}
@@ -0,0 +1,480 @@
+//! Order types built on top of single move orders
+
+use crate::{
+    astar::AStarPath,
+    components::{Destination, OnGrid},
+    connector::GridRoute,
+    error::PathfindingError,
+    events::{
+        AreaMoveEv, CancelOrdersEv, DestinationReachedEv, InitializeFlowFieldAtEv,
+        OrderQueueCompletedEv, PathfindingErrorEv,
+    },
+    flowfield::FlowFieldManager,
+    grid::{coords, costs, Grid, Grids},
+    reservations::CellReservations,
+    scheduler::FlowFieldScheduler,
+    steering::{ArrivalSlot, Steering},
+    PathfindingSchedule, PathfindingSet,
+};
+
+use bevy::prelude::*;
+use std::collections::{HashSet, VecDeque};
+
+pub struct OrdersPlugin;
+
+impl Plugin for OrdersPlugin {
+    fn build(&self, app: &mut App) {
+        let schedule = PathfindingSchedule::label(app);
+        app.register_type::<Patrol>()
+            .register_type::<PatrolLeg>()
+            .register_type::<OrderQueue>()
+            .add_event::<OrderQueueCompletedEv>()
+            .add_systems(
+                schedule,
+                (
+                    start_patrols,
+                    advance_patrols,
+                    start_order_queues,
+                    advance_order_queues,
+                )
+                    .chain()
+                    .after(PathfindingSet::Steering),
+            )
+            .add_observer(order_area_move)
+            .add_observer(cancel_orders);
+    }
+}
+
+/// Sends the unit to each waypoint in turn, starting over after the last one. Units patrolling
+/// towards the same waypoint share its flowfield. Remove it to stop patrolling once the current
+/// leg is done.
+#[derive(Component, Reflect, Clone, Debug, Default)]
+#[reflect(Component)]
+pub struct Patrol(pub Vec<Vec3>);
+
+/// The index of the `Patrol` waypoint the unit is heading to
+#[derive(Component, Reflect, Clone, Copy, Debug, Default)]
+#[reflect(Component)]
+pub struct PatrolLeg(pub usize);
+
+/// Shift-queued move orders. The unit heads to the first goal and on to the next whenever it
+/// arrives, the reached goal is taken off the queue. The component is removed with an
+/// `OrderQueueCompletedEv` after the last goal. Remove it to drop the remaining goals once the
+/// current one is reached.
+#[derive(Component, Reflect, Clone, Debug, Default)]
+#[reflect(Component)]
+pub struct OrderQueue {
+    goals: VecDeque<Vec3>,
+}
+
+impl OrderQueue {
+    pub fn new(goals: impl IntoIterator<Item = Vec3>) -> Self {
+        Self {
+            goals: goals.into_iter().collect(),
+        }
+    }
+
+    /// Queues `goal` after the others. An idle unit sets off right away.
+    pub fn push(&mut self, goal: Vec3) {
+        self.goals.push_back(goal);
+    }
+
+    /// The goal the unit is heading to
+    pub fn current(&self) -> Option<Vec3> {
+        self.goals.front().copied()
+    }
+
+    pub fn goals(&self) -> impl Iterator<Item = &Vec3> {
+        self.goals.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.goals.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.goals.is_empty()
+    }
+}
+
+/// The shape of an `AreaMoveEv`, in world space
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AreaShape {
+    /// Units line up evenly along the segment, from one end to the other
+    Line { start: Vec3, end: Vec3 },
+    /// Units fill the rectangle between two opposite corners in evenly spaced rows
+    Rect { corner: Vec3, opposite: Vec3 },
+}
+
+impl AreaShape {
+    /// `count` evenly spread points on the shape
+    fn points(self, count: usize) -> Vec<Vec3> {
+        match self {
+            AreaShape::Line { start, end } => match count {
+                1 => vec![start.lerp(end, 0.5)],
+                _ => (0..count)
+                    .map(|i| start.lerp(end, i as f32 / (count - 1) as f32))
+                    .collect(),
+            },
+            AreaShape::Rect { corner, opposite } => {
+                let min = corner.min(opposite);
+                let extent = (corner.max(opposite) - min)
+                    .xz()
+                    .max(Vec2::splat(f32::EPSILON));
+
+                // Columns and rows in proportion to the rectangle's sides
+                let columns = ((count as f32 * extent.x / extent.y).sqrt().ceil() as usize)
+                    .clamp(1, count.max(1));
+                let rows = count.div_ceil(columns);
+
+                (0..count)
+                    .map(|i| {
+                        let (column, row) = (i % columns, i / columns);
+                        let x = (column as f32 + 0.5) / columns as f32 * extent.x;
+                        let z = (row as f32 + 0.5) / rows as f32 * extent.y;
+                        min + Vec3::new(x, 0.0, z)
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+/// `count` destinations spread evenly over `shape`, each the center of a different passable
+/// cell. Points on blocked or already taken cells move to the nearest free cell. Cells blocked
+/// only by standing units count as passable, as those units may be the ones ordered.
+pub fn area_destinations(grid: &Grid, shape: AreaShape, count: usize) -> Vec<Vec3> {
+    let mut taken = HashSet::new();
+    let mut destinations = Vec::with_capacity(count);
+
+    for point in shape.points(count) {
+        let start = coords::world_to_idx_clamped(point, grid.size, grid.cell_diameter);
+        let free = |idx: IVec2| {
+            !taken.contains(&idx)
+                && grid
+                    .cost_without(idx, &[costs::UNIT_LAYER])
+                    .is_some_and(|cost| cost != u8::MAX)
+        };
+
+        // Search rings of growing distance around the point
+        let max_ring = grid.size.max_element();
+        let Some(idx) = (0..=max_ring).find_map(|ring| {
+            (-ring..=ring)
+                .flat_map(|y| (-ring..=ring).map(move |x| IVec2::new(x, y)))
+                .filter(|offset| offset.abs().max_element() == ring)
+                .map(|offset| start + offset)
+                .filter(|idx| free(*idx))
+                .min_by(|a, b| {
+                    let a = coords::idx_to_world(*a, grid.size, grid.cell_diameter).xz();
+                    let b = coords::idx_to_world(*b, grid.size, grid.cell_diameter).xz();
+                    let point = point.xz();
+                    a.distance_squared(point)
+                        .total_cmp(&b.distance_squared(point))
+                })
+        }) else {
+            break;
+        };
+
+        taken.insert(idx);
+        destinations.push(grid.grid[idx.y as usize][idx.x as usize].world_pos);
+    }
+
+    return destinations;
+}
+
+/// Pairs units with destinations, closest pairs first, so units don't cross each other's paths
+fn assign_nearest(units: &[(Entity, Vec3)], destinations: &[Vec3]) -> Vec<(Entity, Vec3)> {
+    let mut pairs: Vec<(f32, usize, usize)> = Vec::with_capacity(units.len() * destinations.len());
+    for (u, (_, position)) in units.iter().enumerate() {
+        for (d, destination) in destinations.iter().enumerate() {
+            pairs.push((position.xz().distance_squared(destination.xz()), u, d));
+        }
+    }
+    pairs.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let mut assigned_units = vec![false; units.len()];
+    let mut assigned_destinations = vec![false; destinations.len()];
+    let mut assignments = Vec::new();
+    for (_, u, d) in pairs {
+        if assigned_units[u] || assigned_destinations[d] {
+            continue;
+        }
+
+        assigned_units[u] = true;
+        assigned_destinations[d] = true;
+        assignments.push((units[u].0, destinations[d]));
+    }
+
+    return assignments;
+}
+
+fn order_area_move(
+    trigger: Trigger<AreaMoveEv>,
+    mut cmds: Commands,
+    mut flowfields: FlowFieldManager,
+    grids: Grids,
+    mut errors: EventWriter<PathfindingErrorEv>,
+    q_transforms: Query<&Transform>,
+) {
+    let ev = trigger.event();
+    let Some(grid) = grids.get(ev.map) else {
+        if let Some(map) = ev.map {
+            let error = PathfindingErrorEv::new(PathfindingError::MissingGrid(map));
+            errors.send(error.with_units(ev.units.clone()));
+        }
+        return;
+    };
+
+    let units: Vec<(Entity, Vec3)> = ev
+        .units
+        .iter()
+        .filter_map(|unit| Some((*unit, q_transforms.get(*unit).ok()?.translation)))
+        .collect();
+
+    let destinations = area_destinations(grid, ev.shape, units.len());
+    for (unit, destination) in assign_nearest(&units, &destinations) {
+        let Ok(transform) = q_transforms.get(unit) else {
+            continue;
+        };
+
+        send_to_waypoint(
+            &mut cmds,
+            &mut flowfields,
+            unit,
+            transform.translation,
+            destination,
+            ev.map,
+        );
+    }
+}
+
+fn cancel_orders(
+    trigger: Trigger<CancelOrdersEv>,
+    mut cmds: Commands,
+    mut flowfields: FlowFieldManager,
+    mut scheduler: ResMut<FlowFieldScheduler>,
+    mut reservations: ResMut<CellReservations>,
+) {
+    let units = &trigger.event().units;
+    scheduler.cancel(units);
+    flowfields.release(units);
+
+    for unit in units.iter() {
+        reservations.release(*unit);
+
+        // Dead units may be gone already
+        if let Some(mut unit) = cmds.get_entity(*unit) {
+            unit.remove::<(
+                Destination,
+                Steering,
+                AStarPath,
+                ArrivalSlot,
+                GridRoute,
+                Patrol,
+                PatrolLeg,
+                OrderQueue,
+            )>();
+        }
+    }
+}
+
+/// (Re)starts patrols at their first waypoint whenever the waypoints are set
+fn start_patrols(
+    mut cmds: Commands,
+    mut flowfields: FlowFieldManager,
+    q_patrols: Query<(Entity, &Transform, &Patrol, Option<&OnGrid>), Changed<Patrol>>,
+) {
+    for (unit, transform, patrol, on_grid) in q_patrols.iter() {
+        cmds.entity(unit).insert(PatrolLeg(0));
+
+        if let Some(waypoint) = patrol.0.first() {
+            let map = on_grid.map(|on_grid| on_grid.0);
+            send_to_waypoint(
+                &mut cmds,
+                &mut flowfields,
+                unit,
+                transform.translation,
+                *waypoint,
+                map,
+            );
+        }
+    }
+}
+
+/// Heads for the next waypoint once a unit arrives, units still crossing connectors have only
+/// reached a connector
+fn advance_patrols(
+    mut cmds: Commands,
+    mut flowfields: FlowFieldManager,
+    mut reached: EventReader<DestinationReachedEv>,
+    mut q_patrols: Query<
+        (&Transform, &Patrol, &mut PatrolLeg, Option<&OnGrid>),
+        Without<GridRoute>,
+    >,
+) {
+    for ev in reached.read() {
+        let Ok((transform, patrol, mut leg, on_grid)) = q_patrols.get_mut(ev.unit) else {
+            continue;
+        };
+
+        if patrol.0.is_empty() {
+            continue;
+        }
+
+        leg.0 = (leg.0 + 1) % patrol.0.len();
+        let map = on_grid.map(|on_grid| on_grid.0);
+        let waypoint = patrol.0[leg.0];
+        send_to_waypoint(
+            &mut cmds,
+            &mut flowfields,
+            ev.unit,
+            transform.translation,
+            waypoint,
+            map,
+        );
+    }
+}
+
+/// Sends units to the first goal of a new queue, or of a queue pushed to after they went idle
+fn start_order_queues(
+    mut cmds: Commands,
+    mut flowfields: FlowFieldManager,
+    q_queues: Query<
+        (
+            Entity,
+            &Transform,
+            Ref<OrderQueue>,
+            Option<&OnGrid>,
+            Has<Destination>,
+        ),
+        Changed<OrderQueue>,
+    >,
+) {
+    for (unit, transform, queue, on_grid, moving) in q_queues.iter() {
+        if moving && !queue.is_added() {
+            continue;
+        }
+
+        let Some(goal) = queue.current() else {
+            continue;
+        };
+
+        let map = on_grid.map(|on_grid| on_grid.0);
+        send_to_waypoint(
+            &mut cmds,
+            &mut flowfields,
+            unit,
+            transform.translation,
+            goal,
+            map,
+        );
+    }
+}
+
+/// Takes reached goals off the queues and heads for the next one
+fn advance_order_queues(
+    mut cmds: Commands,
+    mut flowfields: FlowFieldManager,
+    mut reached: EventReader<DestinationReachedEv>,
+    mut completed: EventWriter<OrderQueueCompletedEv>,
+    mut q_queues: Query<(&Transform, &mut OrderQueue, Option<&OnGrid>), Without<GridRoute>>,
+) {
+    for ev in reached.read() {
+        let Ok((transform, mut queue, on_grid)) = q_queues.get_mut(ev.unit) else {
+            continue;
+        };
+
+        queue.goals.pop_front();
+        let Some(goal) = queue.current() else {
+            cmds.entity(ev.unit).remove::<OrderQueue>();
+            let mut completion = OrderQueueCompletedEv::new(ev.unit, ev.destination);
+            completion.flowfield = ev.flowfield;
+            completed.send(completion);
+            continue;
+        };
+
+        let map = on_grid.map(|on_grid| on_grid.0);
+        send_to_waypoint(
+            &mut cmds,
+            &mut flowfields,
+            ev.unit,
+            transform.translation,
+            goal,
+            map,
+        );
+    }
+}
+
+/// Joins a flowfield already leading to the waypoint, or orders a new one
+fn send_to_waypoint(
+    cmds: &mut Commands,
+    flowfields: &mut FlowFieldManager,
+    unit: Entity,
+    position: Vec3,
+    waypoint: Vec3,
+    map: Option<Entity>,
+) {
+    if flowfields.join(unit, position, waypoint, map) {
+        return;
+    }
+
+    cmds.entity(unit).insert(Destination);
+
+    let ev = InitializeFlowFieldAtEv::new(vec![unit], waypoint);
+    cmds.trigger(match map {
+        Some(map) => ev.on_map(map),
+        None => ev,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn area_destinations_spread_over_free_cells() {
+        // A wall across the middle column
+        let grid = Grid::new(IVec2::new(9, 9), 1.0, |pos| pos.x == 0.0);
+        let line = AreaShape::Line {
+            start: Vec3::new(-4.0, 0.0, 0.0),
+            end: Vec3::new(4.0, 0.0, 0.0),
+        };
+
+        let destinations = area_destinations(&grid, line, 5);
+        assert_eq!(destinations.len(), 5);
+        assert_eq!(destinations[0], Vec3::new(-4.0, 0.0, 0.0));
+        assert_eq!(destinations[4], Vec3::new(4.0, 0.0, 0.0));
+
+        // The point on the wall moved to a free neighbor, and no cell is used twice
+        let cells: HashSet<IVec2> = destinations
+            .iter()
+            .map(|pos| grid.get_cell_from_world_position(*pos).idx)
+            .collect();
+        assert_eq!(cells.len(), 5);
+        assert!(destinations.iter().all(|pos| pos.x != 0.0));
+
+        let rect = AreaShape::Rect {
+            corner: Vec3::new(-4.0, 0.0, -4.0),
+            opposite: Vec3::new(-1.0, 0.0, -1.0),
+        };
+        assert_eq!(area_destinations(&grid, rect, 4).len(), 4);
+    }
+
+    #[test]
+    fn units_are_assigned_their_nearest_destinations() {
+        let left = Entity::from_raw(0);
+        let right = Entity::from_raw(1);
+        let units = [
+            (right, Vec3::new(5.0, 0.0, 0.0)),
+            (left, Vec3::new(-5.0, 0.0, 0.0)),
+        ];
+        let destinations = [Vec3::new(-1.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0)];
+
+        let mut assignments = assign_nearest(&units, &destinations);
+        assignments.sort_by_key(|(unit, _)| unit.index());
+        assert_eq!(
+            assignments,
+            vec![(left, destinations[0]), (right, destinations[1])]
+        );
+    }
+}
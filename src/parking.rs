@@ -0,0 +1,230 @@
+//! Soft costs under units that stand still, so flowfields route moving groups around parked
+//! armies instead of pushing through them. There are no teams, every unit counts as friendly.
+//! Orders opt out with `InitializeFlowFieldAtEv::through_parked`, e.g. for attack-move.
+
+use crate::{
+    components::{Destination, OnGrid, RtsObjSize},
+    events::UpdateCostEv,
+    grid::{costs, Grid},
+    time::PathfindingTime,
+    PathfindingSchedule, PathfindingSet,
+};
+
+use bevy::prelude::*;
+use std::collections::{HashMap, HashSet};
+
+pub struct ParkingPlugin;
+
+impl Plugin for ParkingPlugin {
+    fn build(&self, app: &mut App) {
+        let schedule = PathfindingSchedule::label(app);
+        app.init_resource::<ParkingSettings>()
+            .register_type::<ParkingSettings>()
+            .register_type::<ParkedTimer>()
+            .register_type::<Parked>()
+            .add_systems(
+                schedule,
+                (
+                    detect_parked_units.run_if(parking_enabled),
+                    paint_parked_costs,
+                )
+                    .chain()
+                    .in_set(PathfindingSet::UpdateCosts),
+            );
+    }
+}
+
+/// Opt-in soft costs under parked units
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
+pub struct ParkingSettings {
+    pub enabled: bool,
+    /// Cost added to the cells under a parked unit's footprint
+    pub cost: u8,
+    /// A unit that stays within this distance of where it was is considered not moving
+    pub threshold: f32,
+    /// Seconds a unit has to stand still before it's parked
+    pub duration: f32,
+}
+
+impl Default for ParkingSettings {
+    fn default() -> Self {
+        ParkingSettings {
+            enabled: false,
+            cost: 20,
+            threshold: 0.1,
+            duration: 2.0,
+        }
+    }
+}
+
+/// Tracks how long a unit with a `Destination` hasn't moved
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct ParkedTimer {
+    anchor: Vec3,
+    still_for: f32,
+}
+
+/// Marks a unit that stood still for `ParkingSettings::duration` seconds. Removed once it moves.
+#[derive(Component, Reflect, Default, Clone, Copy, Debug)]
+#[reflect(Component)]
+pub struct Parked;
+
+fn parking_enabled(settings: Res<ParkingSettings>) -> bool {
+    settings.enabled
+}
+
+fn detect_parked_units(
+    mut cmds: Commands,
+    time: PathfindingTime,
+    settings: Res<ParkingSettings>,
+    mut q_units: Query<
+        (Entity, &Transform, Option<&mut ParkedTimer>, Has<Parked>),
+        With<Destination>,
+    >,
+    q_idle: Query<Entity, (With<ParkedTimer>, Without<Destination>)>,
+) {
+    for unit in q_idle.iter() {
+        cmds.entity(unit).remove::<(ParkedTimer, Parked)>();
+    }
+
+    for (unit, transform, timer, parked) in q_units.iter_mut() {
+        let pos = transform.translation;
+        let Some(mut timer) = timer else {
+            cmds.entity(unit).insert(ParkedTimer {
+                anchor: pos,
+                still_for: 0.0,
+            });
+            continue;
+        };
+
+        if pos.distance_squared(timer.anchor) > settings.threshold * settings.threshold {
+            timer.anchor = pos;
+            timer.still_for = 0.0;
+            if parked {
+                cmds.entity(unit).remove::<Parked>();
+            }
+            continue;
+        }
+
+        timer.still_for += time.delta_secs();
+        if timer.still_for >= settings.duration && !parked {
+            cmds.entity(unit).insert(Parked);
+        }
+    }
+}
+
+/// Keeps the parked layer of every grid on the cells under the footprints of `Parked` units,
+/// clearing it while parking is disabled
+fn paint_parked_costs(
+    settings: Res<ParkingSettings>,
+    mut grid: ResMut<Grid>,
+    mut q_maps: Query<(Entity, &mut Grid)>,
+    mut events: EventWriter<UpdateCostEv>,
+    q_parked: Query<
+        (&Transform, Option<&RtsObjSize>, Option<&OnGrid>),
+        (With<Parked>, With<Destination>),
+    >,
+) {
+    let mut parked: HashMap<Option<Entity>, Vec<(Vec3, Vec2)>> = HashMap::new();
+    if settings.enabled {
+        for (transform, size, on_grid) in q_parked.iter() {
+            let half_extents = size.map_or(Vec2::ZERO, |size| size.0);
+            parked
+                .entry(on_grid.map(|on_grid| on_grid.0))
+                .or_default()
+                .push((transform.translation, half_extents));
+        }
+    }
+
+    let units = parked.remove(&None).unwrap_or_default();
+    let cells = footprint_cells(&grid, &units);
+    if !cells.is_empty() || !grid.layer_cells(costs::PARKED_LAYER).is_empty() {
+        for idx in set_parked_cells(&mut grid, &cells, settings.cost) {
+            events.send(UpdateCostEv::new(grid.grid[idx.y as usize][idx.x as usize]));
+        }
+    }
+
+    for (map, mut grid) in q_maps.iter_mut() {
+        let units = parked.remove(&Some(map)).unwrap_or_default();
+        let cells = footprint_cells(&grid, &units);
+        if cells.is_empty() && grid.layer_cells(costs::PARKED_LAYER).is_empty() {
+            continue;
+        }
+
+        for idx in set_parked_cells(&mut grid, &cells, settings.cost) {
+            let cell = grid.grid[idx.y as usize][idx.x as usize];
+            events.send(UpdateCostEv::on_map(cell, map));
+        }
+    }
+}
+
+/// The cells under the footprints of units at the given positions with the given half extents
+fn footprint_cells(grid: &Grid, units: &[(Vec3, Vec2)]) -> HashSet<IVec2> {
+    let mut cells = HashSet::new();
+    for (pos, half_extents) in units.iter() {
+        let half_extents = Vec3::new(half_extents.x, 0.0, half_extents.y);
+        cells.insert(grid.get_cell_from_world_position(*pos).idx);
+        cells.extend(
+            grid.cells_in_rect(*pos - half_extents, *pos + half_extents)
+                .map(|cell| cell.idx),
+        );
+    }
+
+    return cells;
+}
+
+/// Puts `cost` on exactly `cells` in the parked layer of `grid`. Returns the cells whose cost
+/// changed.
+pub fn set_parked_cells(grid: &mut Grid, cells: &HashSet<IVec2>, cost: u8) -> Vec<IVec2> {
+    let mut changed = Vec::new();
+    for idx in grid.layer_cells(costs::PARKED_LAYER) {
+        if !cells.contains(&idx) && grid.clear_layer_cost(costs::PARKED_LAYER, idx) {
+            changed.push(idx);
+        }
+    }
+
+    for idx in cells.iter() {
+        if grid.layer_cost(costs::PARKED_LAYER, *idx) == Some(cost) {
+            continue;
+        }
+
+        if grid.set_layer_cost(costs::PARKED_LAYER, *idx, cost) {
+            changed.push(*idx);
+        }
+    }
+
+    return changed;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parked_cells_follow_the_parked_units() {
+        let mut grid = Grid::new(IVec2::new(5, 5), 1.0, |_| false);
+        let first: HashSet<IVec2> = [IVec2::new(1, 1), IVec2::new(2, 1)].into();
+        let second: HashSet<IVec2> = [IVec2::new(2, 1)].into();
+
+        assert_eq!(set_parked_cells(&mut grid, &first, 20).len(), 2);
+        assert_eq!(grid.grid[1][1].cost, 21);
+        assert!(set_parked_cells(&mut grid, &first, 20).is_empty());
+
+        assert_eq!(
+            set_parked_cells(&mut grid, &second, 20),
+            vec![IVec2::new(1, 1)]
+        );
+        assert_eq!(grid.grid[1][1].cost, 1);
+        assert_eq!(grid.grid[1][2].cost, 21);
+
+        // Parked costs stay soft and never block a cell, nor open a blocked one
+        let third: HashSet<IVec2> = [IVec2::new(0, 0), IVec2::new(3, 3)].into();
+        grid.set_base_cost(IVec2::new(3, 3), u8::MAX);
+        set_parked_cells(&mut grid, &third, u8::MAX);
+        assert_eq!(grid.grid[0][0].cost, u8::MAX - 1);
+        assert_eq!(grid.grid[3][3].cost, u8::MAX);
+        assert_eq!(grid.grid[1][2].cost, 1);
+    }
+}
@@ -0,0 +1,210 @@
+//! Waypoint paths for movement that doesn't follow a flowfield cell by cell, like projectiles or
+//! scripted moves
+
+use crate::{
+    cell::Cell,
+    flowfield::FlowField,
+    grid::{coords, edges::EdgeCosts, Grid},
+};
+
+use bevy::prelude::*;
+
+/// Shortens a path from `FlowField::extract_path` by string pulling. Each waypoint skips ahead
+/// to the furthest later one in a straight line of sight, which only crosses passable cells no
+/// more expensive than the stretch of path it replaces. Returns the world positions of the
+/// remaining waypoints, the first and last cell included.
+pub fn smooth(flowfield: &FlowField, path: &[Cell]) -> Vec<Vec3> {
+    let field = (
+        flowfield.grid.as_slice(),
+        flowfield.size,
+        flowfield.cell_diameter,
+        &flowfield.edge_costs,
+    );
+    return pull(path, |from, to, max_cost| {
+        in_sight(field, from, to, max_cost)
+    });
+}
+
+/// Like `smooth`, for a path over the current costs of `grid`, such as one from `astar::find_path`
+pub fn smooth_on_grid(grid: &Grid, path: &[Cell]) -> Vec<Vec3> {
+    let field = (
+        grid.grid.as_slice(),
+        grid.size,
+        grid.cell_diameter,
+        grid.edge_costs(),
+    );
+    return pull(path, |from, to, max_cost| {
+        in_sight(field, from, to, max_cost)
+    });
+}
+
+fn pull(path: &[Cell], in_sight: impl Fn(Vec3, Vec3, u8) -> bool) -> Vec<Vec3> {
+    let Some(first) = path.first() else {
+        return Vec::new();
+    };
+
+    let mut waypoints = vec![first.world_pos];
+    let mut anchor = 0;
+
+    while anchor + 1 < path.len() {
+        // Consecutive cells are always in sight of each other
+        let mut next = anchor + 1;
+        let mut max_cost = path[anchor].cost.max(path[next].cost);
+
+        for candidate in anchor + 2..path.len() {
+            max_cost = max_cost.max(path[candidate].cost);
+            let from = path[anchor].world_pos;
+            if !in_sight(from, path[candidate].world_pos, max_cost) {
+                break;
+            }
+
+            next = candidate;
+        }
+
+        waypoints.push(path[next].world_pos);
+        anchor = next;
+    }
+
+    return waypoints;
+}
+
+fn in_sight(
+    (cells, size, cell_diameter, edges): (&[Vec<Cell>], IVec2, f32, &EdgeCosts),
+    from: Vec3,
+    to: Vec3,
+    max_cost: u8,
+) -> bool {
+    let crossed = crossed_cells(from, to, size, cell_diameter);
+    let passable = crossed.iter().all(|idx| {
+        coords::in_bounds(*idx, size) && {
+            let cost = cells[idx.y as usize][idx.x as usize].cost;
+            cost != u8::MAX && cost <= max_cost
+        }
+    });
+
+    // Shortcuts can't cross one-way edges the wrong way either
+    return passable
+        && crossed
+            .windows(2)
+            .all(|pair| edges.cost(pair[0], pair[1]) != u8::MAX);
+}
+
+/// Every cell the straight line from `from` to `to` touches, in order. A line through a cell
+/// corner touches both cells beside it, so lines can't squeeze between diagonal walls.
+pub(crate) fn crossed_cells(from: Vec3, to: Vec3, size: IVec2, cell_diameter: f32) -> Vec<IVec2> {
+    let start = coords::to_grid_space(from, size, cell_diameter);
+    let end = coords::to_grid_space(to, size, cell_diameter);
+    let delta = end - start;
+
+    let mut idx = start.floor().as_ivec2();
+    let last = end.floor().as_ivec2();
+    let step = IVec2::new(step_of(delta.x), step_of(delta.y));
+
+    // How far along the line, from 0 to 1, the next vertical and horizontal cell edges are
+    let t_delta = Vec2::new(1.0 / delta.x.abs(), 1.0 / delta.y.abs());
+    let mut t_max = Vec2::new(
+        first_edge(start.x, idx.x, step.x, delta.x),
+        first_edge(start.y, idx.y, step.y, delta.y),
+    );
+
+    let mut cells = vec![idx];
+    let steps = (last - idx).abs();
+    for _ in 0..steps.x + steps.y {
+        if idx == last {
+            break;
+        }
+
+        if t_max.x < t_max.y {
+            idx.x += step.x;
+            t_max.x += t_delta.x;
+        } else if t_max.y < t_max.x {
+            idx.y += step.y;
+            t_max.y += t_delta.y;
+        } else {
+            cells.push(idx + IVec2::new(step.x, 0));
+            cells.push(idx + IVec2::new(0, step.y));
+            idx += step;
+            t_max += t_delta;
+        }
+
+        cells.push(idx);
+    }
+
+    return cells;
+}
+
+fn step_of(delta: f32) -> i32 {
+    if delta > 0.0 {
+        return 1;
+    } else if delta < 0.0 {
+        return -1;
+    }
+
+    return 0;
+}
+
+fn first_edge(start: f32, idx: i32, step: i32, delta: f32) -> f32 {
+    match step {
+        1 => (idx as f32 + 1.0 - start) / delta,
+        -1 => (start - idx as f32) / -delta,
+        _ => f32::INFINITY,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{grid::Grid, layers::GridLayers};
+
+    #[test]
+    fn smoothed_path_keeps_only_the_corners_around_a_wall() {
+        // A wall at x = 0 from the top edge down to the last row, which stays open
+        let grid = Grid::new(IVec2::new(7, 5), 1.0, |pos| pos.x == 0.0 && pos.z < 2.0);
+        let from = Vec3::new(-3.0, 0.0, -2.0);
+        let to = Vec3::new(3.0, 0.0, -2.0);
+
+        let mut flowfield = FlowField::new(grid.cell_radius, grid.size, Vec::new());
+        flowfield.create_fields(&grid, &GridLayers::default(), &[], to);
+        let path = flowfield.extract_path(from);
+        let waypoints = smooth(&flowfield, &path);
+
+        assert!(waypoints.len() < path.len());
+        assert_eq!(waypoints.first(), Some(&path[0].world_pos));
+        assert_eq!(
+            waypoints.last(),
+            Some(&flowfield.destination_cell.world_pos)
+        );
+
+        // Every shortcut stays clear of the wall, neighboring cells are kept as the field has them
+        for pair in waypoints.windows(2) {
+            let neighbors = pair[0].distance(pair[1]) < 1.5;
+            let field = (
+                flowfield.grid.as_slice(),
+                flowfield.size,
+                flowfield.cell_diameter,
+                &flowfield.edge_costs,
+            );
+            assert!(neighbors || in_sight(field, pair[0], pair[1], 1));
+        }
+    }
+
+    #[test]
+    fn lines_through_corners_touch_both_cells_beside_them() {
+        let cells = crossed_cells(
+            Vec3::new(-0.5, 0.0, -0.5),
+            Vec3::new(0.5, 0.0, 0.5),
+            IVec2::splat(2),
+            1.0,
+        );
+
+        assert_eq!(
+            cells,
+            vec![
+                IVec2::new(0, 0),
+                IVec2::new(1, 0),
+                IVec2::new(0, 1),
+                IVec2::new(1, 1)
+            ]
+        );
+    }
+}
@@ -0,0 +1,105 @@
+//! Steering for physics-driven units. The crate doesn't depend on a physics engine, instead
+//! `PhysicsVelocity` holds what a rigid body needs to follow its flowfield, ready to copy into
+//! e.g. Rapier's `Velocity::linvel` or, scaled by the body's mass, `ExternalImpulse::impulse`.
+
+use crate::{steering::Steering, time::PathfindingTime, PathfindingSchedule, PathfindingSet};
+
+use bevy::prelude::*;
+
+pub struct PhysicsPlugin;
+
+impl Plugin for PhysicsPlugin {
+    fn build(&self, app: &mut App) {
+        let schedule = PathfindingSchedule::label(app);
+        app.register_type::<PhysicsSteering>()
+            .register_type::<PhysicsVelocity>()
+            .add_systems(
+                schedule,
+                drive_physics_units.after(PathfindingSet::Steering),
+            );
+    }
+}
+
+/// Limits of a physics-driven unit. Its `Steering` is turned into a `PhysicsVelocity` every
+/// frame, and units without one slow down to a halt.
+#[derive(Component, Reflect, Clone, Copy, Debug)]
+#[reflect(Component)]
+#[require(PhysicsVelocity)]
+pub struct PhysicsSteering {
+    /// Speed on terrain of `TerrainSpeed::normal_cost`, in world units per second
+    pub max_speed: f32,
+    /// In world units per second squared
+    pub max_acceleration: f32,
+}
+
+impl Default for PhysicsSteering {
+    fn default() -> Self {
+        PhysicsSteering {
+            max_speed: 5.0,
+            max_acceleration: 20.0,
+        }
+    }
+}
+
+/// The velocity a `PhysicsSteering` unit should move at. Write the body's actual velocity back
+/// into `linvel` before `PathfindingSet::Steering` when driving it with impulses, so collisions
+/// are accelerated out of rather than ignored.
+#[derive(Component, Reflect, Clone, Copy, Debug, Default)]
+#[reflect(Component)]
+pub struct PhysicsVelocity {
+    pub linvel: Vec3,
+    /// How much `linvel` changed this frame
+    pub delta: Vec3,
+}
+
+impl PhysicsVelocity {
+    /// The impulse that applies this frame's change to a body of `mass`
+    pub fn impulse(&self, mass: f32) -> Vec3 {
+        self.delta * mass
+    }
+}
+
+fn drive_physics_units(
+    time: PathfindingTime,
+    mut q_units: Query<(&PhysicsSteering, &mut PhysicsVelocity, Option<&Steering>)>,
+) {
+    let dt = time.delta_secs();
+
+    for (limits, mut velocity, steering) in q_units.iter_mut() {
+        let desired = match steering {
+            Some(steering) => {
+                let speed = limits.max_speed * steering.speed_factor;
+                Vec3::new(steering.direction.x, 0.0, steering.direction.y) * speed
+            }
+            None => Vec3::ZERO,
+        };
+
+        let linvel = accelerate(velocity.linvel, desired, limits.max_acceleration * dt);
+        velocity.delta = linvel - velocity.linvel;
+        velocity.linvel = linvel;
+    }
+}
+
+/// Moves the horizontal velocity `current` towards `desired` by at most `max_change`, keeping
+/// the vertical velocity to the physics engine
+fn accelerate(current: Vec3, desired: Vec3, max_change: f32) -> Vec3 {
+    let change = (desired.xz() - current.xz()).clamp_length_max(max_change);
+    return current + Vec3::new(change.x, 0.0, change.y);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn acceleration_is_capped_and_keeps_vertical_velocity() {
+        let current = Vec3::new(0.0, -2.0, 0.0);
+        let desired = Vec3::new(10.0, 0.0, 0.0);
+
+        let next = accelerate(current, desired, 4.0);
+        assert_eq!(next, Vec3::new(4.0, -2.0, 0.0));
+
+        let settled = accelerate(Vec3::new(9.0, 0.0, 0.0), desired, 4.0);
+        assert_eq!(settled, desired);
+    }
+}
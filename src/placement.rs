@@ -0,0 +1,75 @@
+use crate::{
+    cell::{BestCost, Cell},
+    flowfield::FlowField,
+    grid::Grid,
+};
+
+use bevy::prelude::*;
+
+/// How a proposed building footprint would change the best route between two points.
+/// Insert it as a resource to have the debug plugin draw both routes.
+#[derive(Resource, Clone, Default, Debug, Reflect)]
+#[reflect(Resource)]
+pub struct PlacementPreview {
+    /// Route cost without the building, `None` if unreachable
+    pub cost_before: Option<BestCost>,
+    /// Route cost with the building placed, `None` if the building cuts the route off
+    pub cost_after: Option<BestCost>,
+    pub path_before: Vec<Cell>,
+    pub path_after: Vec<Cell>,
+}
+
+impl PlacementPreview {
+    /// `footprint_half_size` is the half extent of the building on the XZ plane
+    pub fn new(
+        grid: &Grid,
+        footprint_center: Vec3,
+        footprint_half_size: Vec2,
+        from: Vec3,
+        to: Vec3,
+    ) -> Self {
+        let (cost_before, path_before) = route(grid, from, to);
+
+        let mut placed = grid.clone();
+        let min = footprint_center - Vec3::new(footprint_half_size.x, 0.0, footprint_half_size.y);
+        let max = footprint_center + Vec3::new(footprint_half_size.x, 0.0, footprint_half_size.y);
+        let min_idx = placed.get_cell_from_world_position(min).idx;
+        let max_idx = placed.get_cell_from_world_position(max).idx;
+
+        for y in min_idx.y..=max_idx.y {
+            for x in min_idx.x..=max_idx.x {
+                placed.grid[y as usize][x as usize].cost = u8::MAX;
+            }
+        }
+
+        let (cost_after, path_after) = route(&placed, from, to);
+
+        PlacementPreview {
+            cost_before,
+            cost_after,
+            path_before,
+            path_after,
+        }
+    }
+
+    /// Extra route cost caused by the building, `None` if either route is unreachable
+    pub fn delta(&self) -> Option<i32> {
+        Some(self.cost_after? as i32 - self.cost_before? as i32)
+    }
+
+    /// True if the route existed before and the building cuts it off entirely
+    pub fn blocks_route(&self) -> bool {
+        self.cost_before.is_some() && self.cost_after.is_none()
+    }
+}
+
+fn route(grid: &Grid, from: Vec3, to: Vec3) -> (Option<BestCost>, Vec<Cell>) {
+    let mut flowfield = FlowField::new(grid.cell_radius, grid.size, Vec::new());
+    flowfield.create_integration_field(grid, grid.get_cell_from_world_position(to));
+    flowfield.create_flowfield();
+
+    let path = flowfield.extract_path(from);
+    let cost = path.first().map(|cell| cell.best_cost);
+
+    (cost, path)
+}
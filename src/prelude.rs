@@ -0,0 +1,20 @@
+//! The types most games need, `use bevy_rts_pathfinding::prelude::*;` imports them all
+
+#[cfg(feature = "debug-draw")]
+pub use crate::debug::BevyRtsPathFindingDebugPlugin;
+pub use crate::{
+    components::{Destination, GameCamera, MapBase, NavBlocker, OnGrid, RtsObj, RtsObjSize},
+    error::PathfindingError,
+    events::{
+        AreaMoveEv, CancelOrdersEv, DestinationReachedEv, FlowFieldReadyEv,
+        InitializeFlowFieldAtEv, InitializeFlowFieldEv, MapChangedEv, MoveSelectedEv,
+        PathfindingErrorEv, UpdateCostEv,
+    },
+    flowfield::{FlowField, FlowFieldId},
+    grid::Grid,
+    grid_direction::GridDirection,
+    integration::IntegrationField,
+    selection::{Selected, SelectionPlugin},
+    steering::Steering,
+    BevyRtsPathFindingPlugin, PathfindingSchedule, PathfindingSet,
+};
@@ -0,0 +1,128 @@
+//! Movement costs per unit class, so infantry cross forests cheaply while vehicles go around.
+//! Cells are tagged with a `TerrainType` in the grid's cell data, and the `CostProfile` of a
+//! unit's `UnitClass` scales the cost of every terrain type for the flowfields it's ordered along.
+
+use crate::grid::Grid;
+
+use bevy::prelude::*;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+pub struct ProfilesPlugin;
+
+impl Plugin for ProfilesPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CostProfiles>()
+            .register_type::<UnitClass>()
+            .register_type::<TerrainType>();
+    }
+}
+
+/// The kind of ground of a cell, kept in the cell data of a `Grid`, see `Grid::layer_mut`.
+/// Cells are `TerrainType(0)` until set. Flowfields see changed types once they're rebuilt.
+#[derive(Reflect, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct TerrainType(pub u8);
+
+/// Cost multipliers of a unit class per `TerrainType`, 1 for types without one
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CostProfile {
+    multipliers: HashMap<TerrainType, f32>,
+}
+
+impl CostProfile {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Scales the cost of `terrain` by `multiplier`, `f32::INFINITY` to make it impassable
+    pub fn with_multiplier(mut self, terrain: TerrainType, multiplier: f32) -> Self {
+        self.multipliers.insert(terrain, multiplier);
+        self
+    }
+
+    pub fn multiplier(&self, terrain: TerrainType) -> f32 {
+        return self.multipliers.get(&terrain).copied().unwrap_or(1.0);
+    }
+
+    /// The cost of a cell of `cost` on `terrain` for the class. Impassable cells stay
+    /// impassable, and passable ones stay passable unless the multiplier is infinite.
+    pub fn cost(&self, cost: u8, terrain: TerrainType) -> u8 {
+        if cost == u8::MAX {
+            return u8::MAX;
+        }
+
+        let scaled = (cost as f32 * self.multiplier(terrain)).round();
+        if !scaled.is_finite() {
+            return u8::MAX;
+        }
+
+        return scaled.clamp(cost.min(1) as f32, (u8::MAX - 1) as f32) as u8;
+    }
+}
+
+/// The `CostProfile` of every unit class by name. Insert profiles before ordering units of the
+/// class, units of a class without one move over the plain grid costs.
+#[derive(Resource, Clone, Debug, Default)]
+pub struct CostProfiles {
+    profiles: HashMap<String, Arc<CostProfile>>,
+}
+
+impl CostProfiles {
+    /// Adds or replaces the profile of the `class` unit class
+    pub fn insert(&mut self, class: impl Into<String>, profile: CostProfile) {
+        self.profiles.insert(class.into(), Arc::new(profile));
+    }
+
+    pub fn with(mut self, class: impl Into<String>, profile: CostProfile) -> Self {
+        self.insert(class, profile);
+        self
+    }
+
+    /// The profile of the `class` unit class, shared by every flowfield built for it
+    pub fn get(&self, class: &str) -> Option<Arc<CostProfile>> {
+        return self.profiles.get(class).cloned();
+    }
+}
+
+/// The unit class whose `CostProfile` the unit's flowfields are built with. Units of different
+/// classes ordered together each get a flowfield of their own class.
+#[derive(Component, Reflect, Clone, Debug, PartialEq, Eq, Hash)]
+#[reflect(Component)]
+pub struct UnitClass(pub String);
+
+impl UnitClass {
+    pub fn new(class: impl Into<String>) -> Self {
+        Self(class.into())
+    }
+}
+
+/// The `TerrainType` of the cell at `idx` of `grid`
+pub fn terrain_at(grid: &Grid, idx: IVec2) -> TerrainType {
+    let layer = grid.layer::<TerrainType>();
+    return layer
+        .and_then(|layer| layer.get(idx))
+        .copied()
+        .unwrap_or_default();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profiles_scale_costs_by_terrain() {
+        let forest = TerrainType(1);
+        let infantry = CostProfile::new().with_multiplier(forest, 0.5);
+        let vehicle = CostProfile::new()
+            .with_multiplier(forest, 4.0)
+            .with_multiplier(TerrainType(2), f32::INFINITY);
+
+        assert_eq!(infantry.cost(6, forest), 3);
+        assert_eq!(infantry.cost(1, forest), 1);
+        assert_eq!(infantry.cost(6, TerrainType(0)), 6);
+        assert_eq!(vehicle.cost(6, forest), 24);
+        assert_eq!(vehicle.cost(100, forest), u8::MAX - 1);
+        assert_eq!(vehicle.cost(1, TerrainType(2)), u8::MAX);
+        assert_eq!(infantry.cost(u8::MAX, forest), u8::MAX);
+    }
+}
@@ -0,0 +1,145 @@
+//! Cells claimed by the units settling in them with `ArrivalMode::Reserve`
+
+use crate::{events::GridExpandedEv, grid::Grid, PathfindingSchedule, PathfindingSet};
+
+use bevy::prelude::*;
+use std::collections::HashMap;
+
+pub struct ReservationsPlugin;
+
+impl Plugin for ReservationsPlugin {
+    fn build(&self, app: &mut App) {
+        let schedule = PathfindingSchedule::label(app);
+        app.init_resource::<CellReservations>()
+            .register_type::<CellReservations>()
+            .add_systems(
+                schedule,
+                release_reservations.before(PathfindingSet::Steering),
+            );
+    }
+}
+
+/// One unit per cell, per grid. Units claim the cell they settle in when they're given an order,
+/// and keep it until their next order or until they despawn. Building placement can use it to
+/// keep from placing on top of arriving units.
+#[derive(Resource, Reflect, Default, Debug)]
+#[reflect(Resource)]
+pub struct CellReservations {
+    cells: HashMap<(Option<Entity>, IVec2), Entity>,
+    units: HashMap<Entity, (Option<Entity>, IVec2)>,
+}
+
+impl CellReservations {
+    /// Claims the cell at `idx` of `map`'s grid for `unit`, releasing its earlier claim. Returns
+    /// false if another unit holds the cell.
+    pub fn reserve(&mut self, map: Option<Entity>, idx: IVec2, unit: Entity) -> bool {
+        if self
+            .reserved_by(map, idx)
+            .is_some_and(|holder| holder != unit)
+        {
+            return false;
+        }
+
+        self.release(unit);
+        self.cells.insert((map, idx), unit);
+        self.units.insert(unit, (map, idx));
+        return true;
+    }
+
+    /// Gives up the cell `unit` holds, if any
+    pub fn release(&mut self, unit: Entity) {
+        if let Some(cell) = self.units.remove(&unit) {
+            self.cells.remove(&cell);
+        }
+    }
+
+    /// The unit holding the cell at `idx` of `map`'s grid
+    pub fn reserved_by(&self, map: Option<Entity>, idx: IVec2) -> Option<Entity> {
+        self.cells.get(&(map, idx)).copied()
+    }
+
+    pub fn is_reserved(&self, map: Option<Entity>, idx: IVec2) -> bool {
+        self.cells.contains_key(&(map, idx))
+    }
+
+    /// The map and cell `unit` holds
+    pub fn cell_of(&self, unit: Entity) -> Option<(Option<Entity>, IVec2)> {
+        self.units.get(&unit).copied()
+    }
+
+    /// The units holding cells overlapped by a footprint centered on `center`, with
+    /// `half_size` the half extent on the XZ plane
+    pub fn reserved_in_footprint(
+        &self,
+        grid: &Grid,
+        map: Option<Entity>,
+        center: Vec3,
+        half_size: Vec2,
+    ) -> Vec<Entity> {
+        let extent = Vec3::new(half_size.x, 0.0, half_size.y);
+
+        return grid
+            .cells_in_rect(center - extent, center + extent)
+            .filter_map(|cell| self.reserved_by(map, cell.idx))
+            .collect();
+    }
+
+    pub fn len(&self) -> usize {
+        self.units.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.units.is_empty()
+    }
+}
+
+/// Frees the cells of despawned units and follows the main grid's cell indices as it expands
+fn release_reservations(
+    mut reservations: ResMut<CellReservations>,
+    mut expanded: EventReader<GridExpandedEv>,
+    mut despawned: RemovedComponents<Transform>,
+) {
+    for unit in despawned.read() {
+        if reservations.units.contains_key(&unit) {
+            reservations.release(unit);
+        }
+    }
+
+    for ev in expanded.read() {
+        let claims: Vec<(Entity, (Option<Entity>, IVec2))> = reservations.units.drain().collect();
+        reservations.cells.clear();
+
+        for (unit, (map, idx)) in claims {
+            let idx = match map {
+                Some(_) => idx,
+                None => idx + ev.border,
+            };
+            reservations.reserve(map, idx, unit);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn units_hold_one_cell_each() {
+        let mut reservations = CellReservations::default();
+        let (a, b) = (Entity::from_raw(0), Entity::from_raw(1));
+        let idx = IVec2::new(2, 2);
+
+        assert!(reservations.reserve(None, idx, a));
+        assert!(!reservations.reserve(None, idx, b));
+        assert!(reservations.reserve(Some(b), idx, b));
+
+        // A new claim gives up the old one
+        assert!(reservations.reserve(None, IVec2::new(3, 2), a));
+        assert!(!reservations.is_reserved(None, idx));
+        assert_eq!(reservations.len(), 2);
+
+        let grid = Grid::new(IVec2::new(5, 5), 1.0, |_| false);
+        let in_footprint = reservations.reserved_in_footprint(&grid, None, Vec3::X, Vec2::ZERO);
+        assert_eq!(in_footprint, vec![a]);
+    }
+}
@@ -1,14 +1,108 @@
-use bevy::prelude::*;
+use bevy::{
+    diagnostic::{Diagnostic, DiagnosticPath, Diagnostics, RegisterDiagnostic},
+    prelude::*,
+};
+use std::collections::HashSet;
+use std::time::Duration;
 
-use crate::flowfield::FlowField;
+use crate::{events::UpdateCostEv, flowfield::FlowField, PathfindingSchedule, PathfindingSet};
 
 pub struct ResourcesPlugin;
 
 impl Plugin for ResourcesPlugin {
     fn build(&self, app: &mut App) {
-        app.init_resource::<ActiveDebugFlowfield>();
+        let schedule = PathfindingSchedule::label(app);
+        app.init_resource::<ActiveDebugFlowfield>()
+            .init_resource::<PathfindingStats>()
+            .register_type::<ActiveDebugFlowfield>()
+            .register_type::<PathfindingStats>()
+            .register_diagnostic(
+                Diagnostic::new(PathfindingStats::FIELD_BUILD_TIME).with_suffix("ms"),
+            )
+            .register_diagnostic(Diagnostic::new(PathfindingStats::FLOWFIELD_COUNT))
+            .add_systems(
+                schedule,
+                (
+                    update_pathfinding_stats.in_set(PathfindingSet::UpdateCosts),
+                    measure_pathfinding_stats.after(PathfindingSet::Steering),
+                ),
+            );
     }
 }
 
-#[derive(Resource, Default)]
+#[derive(Resource, Default, Reflect)]
+#[reflect(Resource)]
 pub struct ActiveDebugFlowfield(pub Option<FlowField>);
+
+/// Performance counters for the field-generation systems
+#[derive(Resource, Default, Debug, Reflect)]
+#[reflect(Resource)]
+pub struct PathfindingStats {
+    /// Time spent building the fields for the last move order or rebuild
+    pub last_integration: Duration,
+    /// Number of recorded integrations
+    pub integrations: u32,
+    /// Number of live flowfields
+    pub flowfield_count: usize,
+    total_integration: Duration,
+    dirty: HashSet<IVec2>,
+}
+
+impl PathfindingStats {
+    /// Milliseconds spent building fields, measured for every integration
+    pub const FIELD_BUILD_TIME: DiagnosticPath =
+        DiagnosticPath::const_new("pathfinding/field_build_time");
+    /// Number of live flowfields
+    pub const FLOWFIELD_COUNT: DiagnosticPath =
+        DiagnosticPath::const_new("pathfinding/flowfield_count");
+
+    pub fn record_integration(&mut self, elapsed: Duration) {
+        self.integrations += 1;
+        self.last_integration = elapsed;
+        self.total_integration += elapsed;
+        self.dirty.clear();
+    }
+
+    /// Mean of every recorded integration time
+    pub fn average_integration(&self) -> Duration {
+        if self.integrations == 0 {
+            return Duration::ZERO;
+        }
+
+        return self.total_integration / self.integrations;
+    }
+
+    /// Cells whose cost changed since the last integration
+    pub fn dirty_cells(&self) -> usize {
+        self.dirty.len()
+    }
+}
+
+fn update_pathfinding_stats(
+    mut stats: ResMut<PathfindingStats>,
+    mut events: EventReader<UpdateCostEv>,
+    q_flowfields: Query<(), With<FlowField>>,
+) {
+    stats.flowfield_count = q_flowfields.iter().count();
+    for ev in events.read() {
+        stats.dirty.insert(ev.cell.idx);
+    }
+}
+
+/// Feeds `PathfindingStats` into Bevy's diagnostics, for overlays and the diagnostic logger
+fn measure_pathfinding_stats(
+    mut diagnostics: Diagnostics,
+    mut last_measured: Local<u32>,
+    stats: Res<PathfindingStats>,
+) {
+    if stats.integrations != *last_measured {
+        *last_measured = stats.integrations;
+        diagnostics.add_measurement(&PathfindingStats::FIELD_BUILD_TIME, || {
+            stats.last_integration.as_secs_f64() * 1000.0
+        });
+    }
+
+    diagnostics.add_measurement(&PathfindingStats::FLOWFIELD_COUNT, || {
+        stats.flowfield_count as f64
+    });
+}
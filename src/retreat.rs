@@ -0,0 +1,224 @@
+//! Retreat fields point away from threats, for workers fleeing raids or AI pulling back from a
+//! losing fight. Costs are integrated outwards from the threats like a `HeatMap`, and every cell
+//! points to its neighbor furthest from them until `max_distance` is reached.
+
+use crate::{
+    cell::{BestCost, Cell, UNREACHABLE},
+    flowfield::{integrate, sample_direction_smooth, IntegrationMethod},
+    grid::{
+        coords::{self, EdgePolicies},
+        edges::EdgeCosts,
+        Connectivity, Grid, Grids,
+    },
+    grid_direction::GridDirection,
+    PathfindingSchedule, PathfindingSet,
+};
+
+use bevy::prelude::*;
+
+pub struct RetreatPlugin;
+
+impl Plugin for RetreatPlugin {
+    fn build(&self, app: &mut App) {
+        let schedule = PathfindingSchedule::label(app);
+        app.register_type::<RetreatField>().add_systems(
+            schedule,
+            refresh_retreat_fields.in_set(PathfindingSet::BuildFields),
+        );
+    }
+}
+
+/// Directions away from `threats`, up to `max_distance` from the nearest one. Rebuilt once
+/// spawned and whenever the threats or the costs of its grid change.
+#[derive(Component, Reflect, Clone, Debug, Default)]
+#[reflect(Component)]
+pub struct RetreatField {
+    pub threats: Vec<Vec3>,
+    /// The integrated cost from the nearest threat at which cells count as safe
+    pub max_distance: BestCost,
+    /// The map entity whose `Grid` the field covers, `None` for the `Grid` resource
+    pub map: Option<Entity>,
+    cells: Vec<Vec<Cell>>,
+    size: IVec2,
+    cell_diameter: f32,
+    edge_policies: EdgePolicies,
+    // the `Grid::version`, threats and distance the field was built from, `None` until built
+    costfield_version: Option<u64>,
+    built_threats: Vec<Vec3>,
+    built_max_distance: BestCost,
+}
+
+impl RetreatField {
+    pub fn new(threats: Vec<Vec3>, max_distance: BestCost) -> Self {
+        Self {
+            threats,
+            max_distance,
+            ..default()
+        }
+    }
+
+    /// Covers the `Grid` of the `map` entity instead of the `Grid` resource
+    pub fn on_map(mut self, map: Entity) -> Self {
+        self.map = Some(map);
+        self
+    }
+
+    /// The smoothed XZ direction away from the threats at `world_pos`, zero where it's safe or
+    /// there's no way further away
+    pub fn direction_at(&self, world_pos: Vec3) -> Vec2 {
+        let world_pos = self
+            .edge_policies
+            .wrap_world(world_pos, self.size, self.cell_diameter);
+
+        return sample_direction_smooth(
+            &self.cells,
+            self.size,
+            (self.cell_diameter, self.edge_policies),
+            world_pos,
+        );
+    }
+
+    /// The cost of getting from the nearest threat to `world_pos`, `None` off the grid or where
+    /// no threat can reach
+    pub fn threat_distance(&self, world_pos: Vec3) -> Option<BestCost> {
+        let world_pos = self
+            .edge_policies
+            .wrap_world(world_pos, self.size, self.cell_diameter);
+        let idx = coords::world_to_idx(world_pos, self.size, self.cell_diameter)?;
+        let cost = self.cells[idx.y as usize][idx.x as usize].best_cost;
+
+        return Some(cost).filter(|cost| *cost != UNREACHABLE);
+    }
+
+    /// True if `world_pos` is at least `max_distance` from every threat
+    pub fn is_safe(&self, world_pos: Vec3) -> bool {
+        self.threat_distance(world_pos)
+            .is_none_or(|distance| distance >= self.max_distance)
+    }
+
+    /// True if the field was never built, or its threats, distance or `grid`'s costs changed
+    pub fn is_stale(&self, grid: &Grid) -> bool {
+        self.costfield_version != Some(grid.version)
+            || self.size != grid.size
+            || self.built_threats != self.threats
+            || self.built_max_distance != self.max_distance
+    }
+
+    /// Integrates the costs of `grid` outwards from every threat on it and points each cell away
+    pub fn build(&mut self, grid: &Grid, connectivity: Connectivity, method: IntegrationMethod) {
+        // Reuses the cells of the last build
+        let mut cells = std::mem::take(&mut self.cells);
+        cells.clone_from(&grid.grid);
+        for cell in cells.iter_mut().flatten() {
+            cell.best_cost = UNREACHABLE;
+        }
+
+        let mut seeds = Vec::new();
+        for threat in self.threats.iter() {
+            let threat = grid.wrap_world(*threat);
+            let Some(idx) = coords::world_to_idx(threat, grid.size, grid.cell_diameter) else {
+                continue;
+            };
+
+            // Threats often stand in cells their own unit blocks, costs still spread from them
+            cells[idx.y as usize][idx.x as usize].best_cost = 0;
+            seeds.push(idx);
+        }
+
+        let neighbors = (connectivity, grid.edge_costs(), grid.edge_policies());
+        integrate(&mut cells, grid.size, seeds, neighbors, method);
+        derive_retreat_directions(&mut cells, grid.size, neighbors, self.max_distance);
+
+        self.cells = cells;
+        self.size = grid.size;
+        self.cell_diameter = grid.cell_diameter;
+        self.edge_policies = grid.edge_policies();
+        self.costfield_version = Some(grid.version);
+        self.built_threats = self.threats.clone();
+        self.built_max_distance = self.max_distance;
+    }
+}
+
+/// Points every passable cell closer than `max_distance` to a threat towards its passable
+/// neighbor furthest from the threats, if that's further than the cell itself
+fn derive_retreat_directions(
+    cells: &mut [Vec<Cell>],
+    size: IVec2,
+    (connectivity, edges, wrap): (Connectivity, &EdgeCosts, EdgePolicies),
+    max_distance: BestCost,
+) {
+    for y in 0..size.y {
+        for x in 0..size.x {
+            let idx = IVec2::new(x, y);
+            let cell = cells[y as usize][x as usize];
+            let mut best_direction = GridDirection::None;
+
+            if cell.cost != u8::MAX && cell.best_cost < max_distance {
+                let mut best_cost = cell.best_cost;
+                for &direction in connectivity.flow_directions(idx) {
+                    let Some(neighbor_idx) = wrap.wrap(idx + direction.vector(), size) else {
+                        continue;
+                    };
+
+                    let neighbor = &cells[neighbor_idx.y as usize][neighbor_idx.x as usize];
+                    let passable = neighbor.cost != u8::MAX
+                        && neighbor.best_cost != UNREACHABLE
+                        && edges.cost(idx, neighbor_idx) != u8::MAX;
+                    if passable && neighbor.best_cost > best_cost {
+                        best_cost = neighbor.best_cost;
+                        best_direction = direction;
+                    }
+                }
+            }
+
+            cells[y as usize][x as usize].best_direction = best_direction;
+        }
+    }
+}
+
+/// Rebuilds stale retreat fields
+fn refresh_retreat_fields(
+    grids: Grids,
+    connectivity: Res<Connectivity>,
+    method: Res<IntegrationMethod>,
+    mut q_fields: Query<&mut RetreatField>,
+) {
+    for mut field in q_fields.iter_mut() {
+        let Some(grid) = grids.get(field.map) else {
+            continue;
+        };
+
+        if field.is_stale(grid) {
+            field.build(grid, *connectivity, *method);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn units_flee_away_from_threats_until_safe() {
+        // A corridor along x, with a threat in its second cell
+        let grid = Grid::new(IVec2::new(8, 1), 1.0, |_| false);
+        let mut field = RetreatField::new(vec![Vec3::new(-2.5, 0.0, 0.0)], 3);
+        assert!(field.is_stale(&grid));
+
+        field.build(&grid, Connectivity::Cardinal4, IntegrationMethod::Breadth);
+        assert!(!field.is_stale(&grid));
+
+        let at = |x: f32| Vec3::new(x, 0.0, 0.0);
+        assert_eq!(field.direction_at(at(-1.5)), Vec2::X);
+        assert_eq!(field.direction_at(at(-0.5)), Vec2::X);
+        assert_eq!(field.threat_distance(at(-0.5)), Some(2));
+
+        // Three cells from the threat is far enough
+        assert!(field.is_safe(at(0.5)));
+        assert_eq!(field.direction_at(at(0.5)), Vec2::ZERO);
+
+        // The west end is cornered, with nowhere further away to go
+        assert!(!field.is_safe(at(-3.5)));
+        assert_eq!(field.direction_at(at(-3.5)), Vec2::ZERO);
+    }
+}
@@ -0,0 +1,168 @@
+use crate::flowfield::CostModifierFn;
+use crate::profiles::CostProfile;
+
+use bevy::prelude::*;
+use std::sync::Arc;
+use std::time::Duration;
+
+pub struct SchedulerPlugin;
+
+impl Plugin for SchedulerPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<FlowFieldScheduler>()
+            .register_type::<FlowFieldScheduler>()
+            .register_type::<OrderPriority>();
+    }
+}
+
+/// Who issued a move order. Queued player orders are built before AI orders.
+#[derive(Reflect, Default, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum OrderPriority {
+    Ai,
+    #[default]
+    Player,
+}
+
+/// A move order waiting in the `FlowFieldScheduler`
+#[derive(Reflect, Clone, Debug, PartialEq)]
+pub struct FlowFieldRequest {
+    pub units: Vec<Entity>,
+    pub destination: Vec3,
+    pub target: Option<Entity>,
+    pub map: Option<Entity>,
+    pub priority: OrderPriority,
+    /// The cell of `destination`. Queued orders towards the same cell share one flowfield.
+    pub goal_cell: IVec2,
+    #[reflect(ignore)]
+    pub cost_modifier: Option<CostModifierFn>,
+    /// The `CostProfile` of the units' `UnitClass`
+    #[reflect(ignore)]
+    pub cost_profile: Option<Arc<CostProfile>>,
+    /// Ignore the soft costs of parked units, see `InitializeFlowFieldAtEv::through_parked`
+    pub through_parked: bool,
+}
+
+impl FlowFieldRequest {
+    fn same_goal(&self, other: &FlowFieldRequest) -> bool {
+        self.map == other.map
+            && self.target == other.target
+            && self.cost_modifier == other.cost_modifier
+            && self.cost_profile == other.cost_profile
+            && self.through_parked == other.through_parked
+            && (self.target.is_some() || self.goal_cell == other.goal_cell)
+    }
+}
+
+/// Queues the flowfield builds of `InitializeFlowFieldAtEv`s, which run in
+/// `PathfindingSet::BuildFields`. Each frame builds at most `max_builds` fields, stopping early
+/// once `time_budget` is spent, but always builds at least one.
+#[derive(Resource, Reflect, Debug)]
+#[reflect(Resource)]
+pub struct FlowFieldScheduler {
+    pub max_builds: usize,
+    pub time_budget: Duration,
+    queue: Vec<FlowFieldRequest>,
+}
+
+impl Default for FlowFieldScheduler {
+    fn default() -> Self {
+        Self {
+            max_builds: 4,
+            time_budget: Duration::from_millis(4),
+            queue: Vec::new(),
+        }
+    }
+}
+
+impl FlowFieldScheduler {
+    /// Queues a request. Its units are taken out of earlier requests, as only their latest order
+    /// counts, and it's merged into a queued request towards the same goal.
+    pub fn push(&mut self, request: FlowFieldRequest) {
+        self.cancel(&request.units);
+
+        match self
+            .queue
+            .iter_mut()
+            .find(|queued| queued.same_goal(&request))
+        {
+            Some(queued) => {
+                queued.units.extend(request.units);
+                queued.priority = queued.priority.max(request.priority);
+            }
+            None => self.queue.push(request),
+        }
+    }
+
+    /// Takes the units out of the queued requests, dropping requests left without units
+    pub fn cancel(&mut self, units: &[Entity]) {
+        for queued in self.queue.iter_mut() {
+            queued.units.retain(|unit| !units.contains(unit));
+        }
+        self.queue.retain(|queued| !queued.units.is_empty());
+    }
+
+    /// Takes the oldest request of the highest priority
+    pub fn pop(&mut self) -> Option<FlowFieldRequest> {
+        let priority = self.queue.iter().map(|request| request.priority).max()?;
+        let idx = self
+            .queue
+            .iter()
+            .position(|request| request.priority == priority)?;
+
+        return Some(self.queue.remove(idx));
+    }
+
+    /// The queued requests, oldest first
+    pub fn requests(&self) -> impl Iterator<Item = &FlowFieldRequest> {
+        self.queue.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(units: &[u32], goal_cell: IVec2, priority: OrderPriority) -> FlowFieldRequest {
+        FlowFieldRequest {
+            units: units.iter().map(|unit| Entity::from_raw(*unit)).collect(),
+            destination: Vec3::new(goal_cell.x as f32, 0.0, goal_cell.y as f32),
+            target: None,
+            map: None,
+            priority,
+            goal_cell,
+            cost_modifier: None,
+            cost_profile: None,
+            through_parked: false,
+        }
+    }
+
+    #[test]
+    fn player_orders_go_first_and_latest_orders_win() {
+        let mut scheduler = FlowFieldScheduler::default();
+        scheduler.push(request(&[0, 1], IVec2::new(1, 1), OrderPriority::Ai));
+        scheduler.push(request(&[2], IVec2::new(5, 5), OrderPriority::Ai));
+        scheduler.push(request(&[3], IVec2::new(5, 5), OrderPriority::Ai));
+        scheduler.push(request(&[1], IVec2::new(9, 9), OrderPriority::Player));
+
+        // Orders towards the same cell are merged, unit 1 only follows its last order
+        assert_eq!(scheduler.len(), 3);
+
+        let first = scheduler.pop().unwrap();
+        assert_eq!(first.goal_cell, IVec2::new(9, 9));
+
+        let second = scheduler.pop().unwrap();
+        assert_eq!(second.units, vec![Entity::from_raw(0)]);
+
+        let third = scheduler.pop().unwrap();
+        assert_eq!(third.units, vec![Entity::from_raw(2), Entity::from_raw(3)]);
+        assert!(scheduler.pop().is_none());
+    }
+}
@@ -0,0 +1,43 @@
+//! Optional helpers for games that mark the player's selection with `Selected`. The rest of the
+//! crate never reads the selection, orders name their units explicitly, e.g. in
+//! `InitializeFlowFieldEv`. Add `SelectionPlugin` next to `BevyRtsPathFindingPlugin` to use them.
+
+use crate::components::Destination;
+use crate::events::{InitializeFlowFieldAtEv, InitializeFlowFieldEv, MoveSelectedEv};
+
+use bevy::prelude::*;
+
+pub struct SelectionPlugin;
+
+impl Plugin for SelectionPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Selected>().add_observer(move_selected);
+    }
+}
+
+/// Marks the units the player has selected, see `MoveSelectedEv`
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct Selected;
+
+/// Gives every `Selected` unit a `Destination` and orders them to the cursor, or to the event's
+/// destination when it has one
+fn move_selected(
+    trigger: Trigger<MoveSelectedEv>,
+    mut cmds: Commands,
+    q_selected: Query<Entity, With<Selected>>,
+) {
+    let units: Vec<Entity> = q_selected.iter().collect();
+    if units.is_empty() {
+        return;
+    }
+
+    for unit in units.iter() {
+        cmds.entity(*unit).insert(Destination);
+    }
+
+    match trigger.event().destination {
+        Some(destination) => cmds.trigger(InitializeFlowFieldAtEv::new(units, destination)),
+        None => cmds.trigger(InitializeFlowFieldEv(units)),
+    }
+}
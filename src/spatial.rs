@@ -0,0 +1,160 @@
+use crate::{
+    components::{OnGrid, RtsObj},
+    grid::{coords, Grid},
+    PathfindingSchedule, PathfindingSet,
+};
+
+use bevy::prelude::*;
+use std::collections::HashMap;
+
+pub struct SpatialPlugin;
+
+impl Plugin for SpatialPlugin {
+    fn build(&self, app: &mut App) {
+        let schedule = PathfindingSchedule::label(app);
+        app.init_resource::<UnitSpatialIndex>()
+            .register_type::<UnitSpatialIndex>()
+            .add_systems(
+                schedule,
+                rebuild_spatial_index.before(PathfindingSet::UpdateCosts),
+            );
+    }
+}
+
+/// Every `RtsObj` on the `Grid` resource bucketed by position on the XZ plane, rebuilt each
+/// frame before `PathfindingSet::UpdateCosts`. Buckets are one grid cell wide.
+#[derive(Resource, Reflect, Default)]
+#[reflect(Resource)]
+pub struct UnitSpatialIndex {
+    bucket_size: f32,
+    buckets: HashMap<IVec2, Vec<(Entity, Vec3)>>,
+}
+
+impl UnitSpatialIndex {
+    pub fn new(bucket_size: f32) -> Self {
+        UnitSpatialIndex {
+            bucket_size,
+            buckets: HashMap::new(),
+        }
+    }
+
+    fn bucket(&self, pos: Vec3) -> IVec2 {
+        return (pos.xz() / self.bucket_size).floor().as_ivec2();
+    }
+
+    pub fn clear(&mut self) {
+        self.buckets.clear();
+    }
+
+    pub fn insert(&mut self, entity: Entity, pos: Vec3) {
+        let bucket = self.bucket(pos);
+        self.buckets.entry(bucket).or_default().push((entity, pos));
+    }
+
+    /// Every indexed entity within `radius` of `pos` on the XZ plane, with its position
+    pub fn query_radius(&self, pos: Vec3, radius: f32) -> Vec<(Entity, Vec3)> {
+        if self.bucket_size <= 0.0 {
+            return Vec::new();
+        }
+
+        let min = self.bucket(pos - Vec3::new(radius, 0.0, radius));
+        let max = self.bucket(pos + Vec3::new(radius, 0.0, radius));
+        let radius_squared = radius * radius;
+        let mut found = Vec::new();
+
+        for y in min.y..=max.y {
+            for x in min.x..=max.x {
+                let Some(bucket) = self.buckets.get(&IVec2::new(x, y)) else {
+                    continue;
+                };
+
+                found.extend(
+                    bucket.iter().filter(|(_, other)| {
+                        other.xz().distance_squared(pos.xz()) <= radius_squared
+                    }),
+                );
+            }
+        }
+
+        return found;
+    }
+
+    /// The number of indexed entities standing in each cell of `grid`, without the empty cells
+    pub fn density(&self, grid: &Grid) -> HashMap<IVec2, u32> {
+        let mut density = HashMap::new();
+        for (_, pos) in self.buckets.values().flatten() {
+            let Some(idx) = coords::world_to_idx(*pos, grid.size, grid.cell_diameter) else {
+                continue;
+            };
+
+            *density.entry(idx).or_insert(0) += 1;
+        }
+
+        return density;
+    }
+
+    pub fn len(&self) -> usize {
+        self.buckets.values().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buckets.is_empty()
+    }
+}
+
+fn rebuild_spatial_index(
+    grid: Res<Grid>,
+    mut index: ResMut<UnitSpatialIndex>,
+    q_objs: Query<(Entity, &Transform), (With<RtsObj>, Without<OnGrid>)>,
+) {
+    index.bucket_size = grid.cell_diameter;
+    index.clear();
+
+    for (entity, transform) in q_objs.iter() {
+        index.insert(entity, transform.translation);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn query_radius_crosses_buckets() {
+        let mut index = UnitSpatialIndex::new(1.0);
+        let near = Entity::from_raw(0);
+        let across = Entity::from_raw(1);
+        let far = Entity::from_raw(2);
+
+        index.insert(near, Vec3::new(0.2, 0.0, 0.2));
+        index.insert(across, Vec3::new(-0.3, 5.0, 0.4));
+        index.insert(far, Vec3::new(3.0, 0.0, 0.0));
+
+        let mut found: Vec<Entity> = index
+            .query_radius(Vec3::ZERO, 1.0)
+            .into_iter()
+            .map(|(entity, _)| entity)
+            .collect();
+        found.sort();
+
+        assert_eq!(found, vec![near, across]);
+        assert_eq!(index.len(), 3);
+    }
+
+    #[test]
+    fn density_counts_units_per_cell() {
+        let grid = Grid::new(IVec2::new(4, 4), 1.0, |_| false);
+        let mut index = UnitSpatialIndex::new(1.0);
+
+        // Two units in the cell at the origin, one in a corner and one off the grid
+        index.insert(Entity::from_raw(0), Vec3::new(0.2, 0.0, 0.2));
+        index.insert(Entity::from_raw(1), Vec3::new(0.7, 0.0, 0.4));
+        index.insert(Entity::from_raw(2), Vec3::new(-1.5, 0.0, -1.5));
+        index.insert(Entity::from_raw(3), Vec3::new(10.0, 0.0, 0.0));
+
+        let density = index.density(&grid);
+        assert_eq!(density.len(), 2);
+        assert_eq!(density[&IVec2::new(2, 2)], 2);
+        assert_eq!(density[&IVec2::new(0, 0)], 1);
+    }
+}
@@ -0,0 +1,971 @@
+use crate::{
+    components::Destination,
+    events::{DestinationReachedEv, RepathNeededEv},
+    flowfield::FlowField,
+    grid::{coords, costs, DirectionSet, Grid, Grids},
+    interior::InteriorGrid,
+    layers::GridLayers,
+    reservations::CellReservations,
+    resources::PathfindingStats,
+    spatial::UnitSpatialIndex,
+    time::PathfindingTime,
+    PathfindingSchedule, PathfindingSet,
+};
+
+use bevy::{
+    prelude::*,
+    utils::{Instant, Parallel},
+};
+use std::collections::{HashMap, HashSet, VecDeque};
+
+pub struct SteeringPlugin;
+
+impl Plugin for SteeringPlugin {
+    fn build(&self, app: &mut App) {
+        let schedule = PathfindingSchedule::label(app);
+        app.init_resource::<GroupSteeringSettings>()
+            .init_resource::<SteeringSettings>()
+            .register_type::<GroupSteeringSettings>()
+            .register_type::<SteeringSettings>()
+            .register_type::<Steering>()
+            .register_type::<TurnRate>()
+            .register_type::<GroupLeader>()
+            .register_type::<FieldBlend>()
+            .register_type::<ArrivalMode>()
+            .register_type::<ArrivalSlot>()
+            .register_type::<HoldingPosition>()
+            .add_event::<RepathNeededEv>()
+            .add_event::<DestinationReachedEv>()
+            .add_systems(
+                schedule,
+                (
+                    track_field_blends.run_if(blending_enabled),
+                    assign_group_leaders,
+                    assign_arrival_slots,
+                    steer_units,
+                    limit_turn_rates,
+                    repath_blocked_units,
+                    release_holding_units,
+                    arrive_units,
+                )
+                    .chain()
+                    .in_set(PathfindingSet::Steering),
+            );
+    }
+}
+
+/// The direction a unit following a flowfield should move in, written every frame for units with
+/// a `Destination` and removed on arrival. Movement systems read it after `PathfindingSet::Steering`.
+#[derive(Component, Reflect, Clone, Copy, Debug)]
+#[reflect(Component)]
+pub struct Steering {
+    pub direction: Vec2,
+    /// Multiplier for the unit's speed from the terrain it's on, see `TerrainSpeed`
+    pub speed_factor: f32,
+    /// The way the unit faces, turning towards `direction` no faster than its `TurnRate`. Units
+    /// without one face `direction` right away. Kept while `direction` is zero.
+    pub heading: Vec2,
+}
+
+impl Default for Steering {
+    fn default() -> Self {
+        Steering {
+            direction: Vec2::ZERO,
+            speed_factor: 1.0,
+            heading: Vec2::ZERO,
+        }
+    }
+}
+
+/// Limits how fast a unit's `Steering::heading` turns, in radians per second, so vehicles like
+/// tanks swing around instead of snapping to every new flow direction. The heading starts out
+/// along the unit's `Transform::forward`.
+#[derive(Component, Reflect, Clone, Copy, Debug)]
+#[reflect(Component)]
+pub struct TurnRate(pub f32);
+
+/// Marks a unit that reached its destination and settled there. Removed again when the unit
+/// is given a new `Destination`.
+#[derive(Component, Reflect, Default, Clone, Copy, Debug)]
+#[reflect(Component)]
+pub struct HoldingPosition;
+
+#[derive(Resource, Reflect, Default)]
+#[reflect(Resource)]
+pub struct SteeringSettings {
+    /// Seconds over which units turn from a regenerated flowfield's old directions to its new
+    /// ones. Zero snaps to the new directions immediately.
+    pub blend_duration: f32,
+    /// How units settle once they reach a crowded destination
+    pub arrival: ArrivalMode,
+}
+
+/// How units following a flowfield decide they have arrived
+#[derive(Reflect, Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArrivalMode {
+    /// Settle within half a cell of the exact destination
+    #[default]
+    Hold,
+    /// Settle in the first free cell touching the destination or a unit already settled there
+    Stop,
+    /// Spread out into rings of slots around the destination, one unit per slot
+    Spread,
+    /// Settle in the destination cell, or the nearest free cell around it, claiming the cell in
+    /// `CellReservations` so later orders to the same point settle around it
+    Reserve,
+}
+
+/// The position a unit settles at with `ArrivalMode::Spread`
+#[derive(Component, Reflect, Clone, Copy, Debug)]
+#[reflect(Component)]
+pub struct ArrivalSlot(pub Vec3);
+
+/// Large selections can follow a virtual leader instead of each unit sampling the flowfield.
+/// Units keep their offset to the leader and push apart from each other, switching back to
+/// sampling the field themselves near the destination.
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
+pub struct GroupSteeringSettings {
+    pub enabled: bool,
+    /// Flowfields with fewer units always steer every unit
+    pub min_units: usize,
+    /// How fast the virtual leader moves along the field
+    pub leader_speed: f32,
+    /// The leader waits while units are on average further than this from their slot
+    pub max_slot_lag: f32,
+    /// Units closer than this to the destination sample the field themselves
+    pub fallback_distance: f32,
+    /// Units closer than this to each other push apart
+    pub separation_radius: f32,
+}
+
+impl Default for GroupSteeringSettings {
+    fn default() -> Self {
+        GroupSteeringSettings {
+            enabled: false,
+            min_units: 50,
+            leader_speed: 5.0,
+            max_slot_lag: 3.0,
+            fallback_distance: 8.0,
+            separation_radius: 1.0,
+        }
+    }
+}
+
+/// The virtual leader of a flowfield's units, with each unit's offset from it
+#[derive(Component, Reflect, Clone, Debug)]
+#[reflect(Component)]
+pub struct GroupLeader {
+    pub position: Vec3,
+    pub offsets: HashMap<Entity, Vec3>,
+}
+
+/// Tracks a flowfield's previous directions after it is regenerated in place, so steering
+/// can blend them out over `SteeringSettings::blend_duration`
+#[derive(Component, Reflect, Clone)]
+#[reflect(Component)]
+pub struct FieldBlend {
+    /// The field as steering last saw it
+    last: FlowField,
+    /// The field before its last regeneration, until the blend finishes
+    previous: Option<FlowField>,
+    elapsed: f32,
+}
+
+impl FieldBlend {
+    /// Samples the flowfield at `world_pos`, blended with its previous directions if it was
+    /// regenerated less than `duration` seconds ago
+    pub fn sample(&self, flowfield: &FlowField, world_pos: Vec3, duration: f32) -> Vec2 {
+        let new = flowfield.sample_direction_smooth(world_pos);
+        let Some(previous) = &self.previous else {
+            return new;
+        };
+
+        let old = previous.sample_direction_smooth(world_pos);
+        let t = (self.elapsed / duration).clamp(0.0, 1.0);
+        let blended = old.lerp(new, t).normalize_or_zero();
+
+        // Opposite directions cancel out, in which case the new field wins
+        if blended == Vec2::ZERO {
+            return new;
+        }
+
+        return blended;
+    }
+}
+
+fn blending_enabled(settings: Res<SteeringSettings>) -> bool {
+    settings.blend_duration > 0.0
+}
+
+fn track_field_blends(
+    mut cmds: Commands,
+    time: PathfindingTime,
+    settings: Res<SteeringSettings>,
+    mut q_flowfields: Query<(Entity, Ref<FlowField>, Option<&mut FieldBlend>)>,
+) {
+    for (flowfield_entity, flowfield, blend) in q_flowfields.iter_mut() {
+        let Some(mut blend) = blend else {
+            cmds.entity(flowfield_entity).insert(FieldBlend {
+                last: flowfield.clone(),
+                previous: None,
+                elapsed: 0.0,
+            });
+            continue;
+        };
+
+        // Losing units also changes the field, so only a change of directions restarts the blend
+        let regenerated = flowfield.is_changed()
+            && (blend.last.grid != flowfield.grid
+                || blend.last.layers != flowfield.layers
+                || blend.last.interiors != flowfield.interiors);
+
+        if regenerated {
+            let last = std::mem::replace(&mut blend.last, flowfield.clone());
+            blend.previous = Some(last);
+            blend.elapsed = 0.0;
+        } else if blend.previous.is_some() {
+            blend.elapsed += time.delta_secs();
+            if blend.elapsed >= settings.blend_duration {
+                blend.previous = None;
+            }
+        }
+    }
+}
+
+fn assign_group_leaders(
+    mut cmds: Commands,
+    settings: Res<GroupSteeringSettings>,
+    q_flowfields: Query<(Entity, &FlowField), Added<FlowField>>,
+    q_transform: Query<&Transform>,
+) {
+    if !settings.enabled {
+        return;
+    }
+
+    for (flowfield_entity, flowfield) in q_flowfields.iter() {
+        if flowfield.units.len() < settings.min_units {
+            continue;
+        }
+
+        let positions: Vec<(Entity, Vec3)> = flowfield
+            .units
+            .iter()
+            .filter_map(|unit| Some((*unit, q_transform.get(*unit).ok()?.translation)))
+            .collect();
+        if positions.is_empty() {
+            continue;
+        }
+
+        let center = positions.iter().map(|(_, pos)| *pos).sum::<Vec3>() / positions.len() as f32;
+        let offsets = positions
+            .into_iter()
+            .map(|(unit, pos)| (unit, pos - center))
+            .collect();
+
+        cmds.entity(flowfield_entity).insert(GroupLeader {
+            position: center,
+            offsets,
+        });
+    }
+}
+
+/// Steers the units of every flowfield. Units of fields without a `GroupLeader` sample their
+/// field in parallel, writing straight into their `Steering`.
+fn steer_units(
+    mut cmds: Commands,
+    time: PathfindingTime,
+    settings: Res<GroupSteeringSettings>,
+    steering: Res<SteeringSettings>,
+    index: Res<UnitSpatialIndex>,
+    grids: Grids,
+    mut repaths: EventWriter<RepathNeededEv>,
+    mut followed: Local<HashMap<Entity, Entity>>,
+    mut q_leaders: Query<(&FlowField, &mut GroupLeader, Option<&FieldBlend>)>,
+    q_flowfields: Query<(Entity, &FlowField, Option<&FieldBlend>), Without<GroupLeader>>,
+    mut q_steering: Query<(Entity, &Transform, &mut Steering), With<Destination>>,
+    q_units: Query<&Transform, With<Destination>>,
+) {
+    // The field each unit follows, rebuilt every frame as units join and leave fields
+    followed.clear();
+    for (flowfield_entity, flowfield, _) in q_flowfields.iter() {
+        followed.extend(flowfield.units.iter().map(|unit| (*unit, flowfield_entity)));
+    }
+
+    let steer = |unit: Entity, pos: Vec3, repaths: &mut Vec<RepathNeededEv>| {
+        let (_, flowfield, blend) = q_flowfields.get(*followed.get(&unit)?).ok()?;
+        let grid = grids.get(flowfield.map)?;
+        let direction = sample_field(flowfield, blend, pos, steering.blend_duration);
+        let direction = avoid_blocked_cells(grid, flowfield, unit, pos, direction, repaths);
+        let direction = slide_along_walls(flowfield, pos, direction);
+        let direction = flowfield.direction_to_world(direction);
+        Some((direction, flowfield.speed_factor(pos)))
+    };
+
+    let mut queued: Parallel<Vec<RepathNeededEv>> = Parallel::default();
+    q_steering
+        .par_iter_mut()
+        .for_each(|(unit, transform, mut unit_steering)| {
+            let pos = transform.translation;
+            let Some((direction, speed_factor)) = queued.scope(|repaths| steer(unit, pos, repaths))
+            else {
+                return;
+            };
+
+            unit_steering.direction = direction;
+            unit_steering.speed_factor = speed_factor;
+        });
+
+    let mut new_repaths: Vec<RepathNeededEv> = queued.drain().collect();
+    let mut directions = Vec::new();
+
+    // Units given an order this frame don't steer yet
+    for unit in followed.keys() {
+        if q_steering.contains(*unit) {
+            continue;
+        }
+        let Ok(transform) = q_units.get(*unit) else {
+            continue;
+        };
+
+        let pos = transform.translation;
+        if let Some((direction, speed_factor)) = steer(*unit, pos, &mut new_repaths) {
+            directions.push((*unit, direction, speed_factor));
+        }
+    }
+
+    for (flowfield, mut leader, blend) in q_leaders.iter_mut() {
+        let Some(grid) = grids.get(flowfield.map) else {
+            continue;
+        };
+
+        let mut steer = |unit: Entity, pos: Vec3, direction: Vec2| {
+            let direction =
+                avoid_blocked_cells(grid, flowfield, unit, pos, direction, &mut new_repaths);
+            let direction = slide_along_walls(flowfield, pos, direction);
+            let direction = flowfield.direction_to_world(direction);
+            directions.push((unit, direction, flowfield.speed_factor(pos)));
+        };
+
+        let sample = |pos: Vec3| sample_field(flowfield, blend, pos, steering.blend_duration);
+
+        let positions: Vec<(Entity, Vec3)> = flowfield
+            .units
+            .iter()
+            .filter_map(|unit| Some((*unit, q_units.get(*unit).ok()?.translation)))
+            .collect();
+
+        let destination = flowfield.destination;
+        let fallback_squared = settings.fallback_distance * settings.fallback_distance;
+        let separation_squared = settings.separation_radius * settings.separation_radius;
+        let mut total_lag = 0.0;
+
+        for (unit, pos) in positions.iter() {
+            let slot = leader
+                .offsets
+                .get(unit)
+                .map(|offset| leader.position + *offset);
+            let near_destination = pos.xz().distance_squared(destination.xz()) < fallback_squared;
+
+            // Units without a slot, near the destination or with a blocked slot steer themselves
+            let Some(slot) = slot.filter(|slot| {
+                !near_destination && flowfield.get_cell_from_world_position(*slot).cost != u8::MAX
+            }) else {
+                steer(*unit, *pos, sample(*pos));
+                continue;
+            };
+
+            let to_slot = (slot - *pos).xz();
+            total_lag += to_slot.length();
+
+            let mut separation = Vec2::ZERO;
+            let neighbors = index.query_radius(*pos, settings.separation_radius);
+            for (other, other_pos) in neighbors.iter() {
+                let away = (*pos - *other_pos).xz();
+                let distance_squared = away.length_squared();
+                if other != unit
+                    && leader.offsets.contains_key(other)
+                    && distance_squared > 0.0
+                    && distance_squared < separation_squared
+                {
+                    separation += away / distance_squared;
+                }
+            }
+
+            // Once in its slot, a unit moves along with the leader
+            let mut direction = to_slot + separation * settings.separation_radius;
+            if to_slot.length_squared() < 0.01 {
+                direction += sample(leader.position);
+            }
+            steer(*unit, *pos, direction.normalize_or_zero());
+        }
+
+        let lag = total_lag / positions.len().max(1) as f32;
+        if lag <= settings.max_slot_lag {
+            let leader_direction = sample(leader.position);
+            let step = leader_direction * settings.leader_speed * time.delta_secs();
+            leader.position += Vec3::new(step.x, 0.0, step.y);
+        }
+    }
+
+    repaths.send_batch(new_repaths);
+    for (unit, direction, speed_factor) in directions {
+        match q_steering.get_mut(unit) {
+            Ok((_, _, mut steering)) => {
+                steering.direction = direction;
+                steering.speed_factor = speed_factor;
+            }
+            Err(_) => {
+                cmds.entity(unit).insert(Steering {
+                    direction,
+                    speed_factor,
+                    ..default()
+                });
+            }
+        }
+    }
+}
+
+/// The direction of `flowfield` at `pos`, blended with its previous directions while `blend`
+/// lasts. Inside the destination cell units seek the exact destination.
+fn sample_field(
+    flowfield: &FlowField,
+    blend: Option<&FieldBlend>,
+    pos: Vec3,
+    blend_duration: f32,
+) -> Vec2 {
+    if flowfield.in_destination_cell(pos) {
+        return (flowfield.destination - pos).xz().normalize_or_zero();
+    }
+
+    return match blend {
+        Some(blend) => blend.sample(flowfield, pos, blend_duration),
+        None => flowfield.sample_direction_smooth(pos),
+    };
+}
+
+/// Turns every unit's heading towards its steering direction, as fast as its `TurnRate` allows
+pub(crate) fn limit_turn_rates(
+    time: PathfindingTime,
+    mut q_steering: Query<(&mut Steering, Option<&TurnRate>, &Transform)>,
+) {
+    let dt = time.delta_secs();
+
+    for (mut steering, turn_rate, transform) in q_steering.iter_mut() {
+        let direction = steering.direction.normalize_or_zero();
+        if direction == Vec2::ZERO {
+            continue;
+        }
+
+        let heading = match (turn_rate, steering.heading.normalize_or_zero()) {
+            (None, _) => direction,
+            (Some(_), Vec2::ZERO) => transform
+                .forward()
+                .xz()
+                .try_normalize()
+                .unwrap_or(direction),
+            (Some(turn_rate), heading) => turn_towards(heading, direction, turn_rate.0 * dt),
+        };
+
+        if steering.heading != heading {
+            steering.heading = heading;
+        }
+    }
+}
+
+/// Rotates the unit vector `from` towards `to` by at most `max_angle` radians
+fn turn_towards(from: Vec2, to: Vec2, max_angle: f32) -> Vec2 {
+    let angle = from.angle_to(to).clamp(-max_angle, max_angle);
+    return Vec2::from_angle(angle).rotate(from);
+}
+
+/// Pushes a unit out of a cell that became impassable since its flowfield was built, and holds it
+/// before steering into one, sending a `RepathNeededEv` either way. Units only ever block their
+/// cells for a moment, so their costs are left out.
+fn avoid_blocked_cells(
+    grid: &Grid,
+    flowfield: &FlowField,
+    unit: Entity,
+    pos: Vec3,
+    direction: Vec2,
+    repaths: &mut Vec<RepathNeededEv>,
+) -> Vec2 {
+    // Interiors and layers have cells of their own
+    if flowfield.layer_at(pos) != 0 || flowfield.interiors.iter().any(|i| i.contains(pos)) {
+        return direction;
+    }
+
+    // The grid's indices shift when it expands, so live cells are looked up by position and
+    // only the flowfield's own cells by its indices
+    let blocked = |world_pos: Vec3| {
+        let field_idx = coords::world_to_idx(world_pos, flowfield.size, flowfield.cell_diameter)?;
+        let idx = coords::world_to_idx(world_pos, grid.size, grid.cell_diameter)?;
+        let field_cell = &flowfield.grid[field_idx.y as usize][field_idx.x as usize];
+        let was_passable = field_cell.cost != u8::MAX;
+        let blocked = grid.cost_without(idx, &[costs::UNIT_LAYER]) == Some(u8::MAX);
+        return (was_passable && blocked).then_some(idx);
+    };
+
+    let Some(idx) = coords::world_to_idx(pos, grid.size, grid.cell_diameter) else {
+        return direction;
+    };
+    if blocked(pos).is_some() {
+        repaths.push(RepathNeededEv::new(unit, pos, idx).with_flowfield(flowfield.id));
+
+        let nearest_free = grid
+            .neighbors(idx, DirectionSet::All)
+            .filter(|cell| cell.cost != u8::MAX)
+            .min_by(|a, b| {
+                let a = a.world_pos.xz().distance_squared(pos.xz());
+                let b = b.world_pos.xz().distance_squared(pos.xz());
+                a.total_cmp(&b)
+            });
+
+        return match nearest_free {
+            Some(cell) => (cell.world_pos - pos).xz().normalize_or_zero(),
+            None => Vec2::ZERO,
+        };
+    }
+
+    let ahead = pos + Vec3::new(direction.x, 0.0, direction.y) * flowfield.cell_diameter;
+    if let Some(next) = blocked(ahead).filter(|next| *next != idx) {
+        repaths.push(RepathNeededEv::new(unit, pos, next).with_flowfield(flowfield.id));
+        return Vec2::ZERO;
+    }
+
+    return direction;
+}
+
+/// Turns a direction that runs into a cell the flowfield has blocked, e.g. from a stale field or
+/// smoothing that rounds a corner too tightly, along the wall so units skim it instead of jamming.
+/// The wall's normal comes from which neighbors of the unit's cell are blocked, and units heading
+/// straight at a wall slide towards the cheaper side.
+fn slide_along_walls(flowfield: &FlowField, pos: Vec3, direction: Vec2) -> Vec2 {
+    // Interiors and layers have cells of their own
+    if direction == Vec2::ZERO
+        || flowfield.layer_at(pos) != 0
+        || flowfield.interiors.iter().any(|i| i.contains(pos))
+    {
+        return direction;
+    }
+
+    let (size, wrap) = (flowfield.size, flowfield.edge_policies);
+    let Some(idx) = coords::world_to_idx(pos, size, flowfield.cell_diameter) else {
+        return direction;
+    };
+
+    // Cells off the grid count as walls
+    let neighbor = |offset: IVec2| {
+        let neighbor = wrap.wrap(idx + offset, size)?;
+        Some(flowfield.grid[neighbor.y as usize][neighbor.x as usize])
+    };
+    let blocked = |offset: IVec2| neighbor(offset).is_none_or(|cell| cell.cost == u8::MAX);
+    let heads_into_wall = |direction: Vec2| {
+        let ahead = pos + Vec3::new(direction.x, 0.0, direction.y) * flowfield.cell_radius;
+        let step = coords::world_to_idx_unclamped(ahead, size, flowfield.cell_diameter) - idx;
+        (step != IVec2::ZERO && blocked(step)).then_some(step)
+    };
+
+    let Some(step) = heads_into_wall(direction) else {
+        return direction;
+    };
+
+    let mut into_wall = Vec2::ZERO;
+    for axis in [IVec2::new(step.x, 0), IVec2::new(0, step.y)] {
+        if axis != IVec2::ZERO && blocked(axis) {
+            into_wall += axis.as_vec2();
+        }
+    }
+
+    let slid = if into_wall == Vec2::ZERO {
+        // Only the diagonal cell is blocked, so keep to the axis the unit mostly moves along
+        match direction.x.abs() >= direction.y.abs() {
+            true => Vec2::new(direction.x, 0.0),
+            false => Vec2::new(0.0, direction.y),
+        }
+    } else {
+        let into_wall = into_wall.normalize();
+        direction - into_wall * direction.dot(into_wall).max(0.0)
+    };
+
+    let slid = match slid.try_normalize() {
+        Some(slid) => slid,
+        // Head-on, slide towards the cheaper of the two cells along the wall
+        None => {
+            let tangent = into_wall.normalize().perp();
+            let cost_along = |tangent: Vec2| {
+                let offset = tangent.round().as_ivec2();
+                neighbor(offset)
+                    .filter(|cell| cell.cost != u8::MAX)
+                    .map(|cell| cell.best_cost)
+            };
+
+            match (cost_along(tangent), cost_along(-tangent)) {
+                (Some(left), Some(right)) if right < left => -tangent,
+                (Some(_), _) => tangent,
+                (None, Some(_)) => -tangent,
+                (None, None) => return direction,
+            }
+        }
+    };
+
+    if heads_into_wall(slid).is_some() {
+        return direction;
+    }
+
+    return slid;
+}
+
+/// Rebuilds the flowfields of units that ran into cells blocked since, once their grid changed
+fn repath_blocked_units(
+    grids: Grids,
+    layers: Res<GridLayers>,
+    mut stats: ResMut<PathfindingStats>,
+    mut events: EventReader<RepathNeededEv>,
+    mut q_flowfields: Query<&mut FlowField>,
+    q_interiors: Query<(Entity, &InteriorGrid)>,
+) {
+    let units: HashSet<Entity> = events.read().map(|ev| ev.unit).collect();
+    if units.is_empty() {
+        return;
+    }
+
+    let start = Instant::now();
+    let interiors: Vec<(Entity, &InteriorGrid)> = q_interiors.iter().collect();
+    let mut rebuilt = false;
+
+    for mut flowfield in q_flowfields.iter_mut() {
+        let stale = grids
+            .get(flowfield.map)
+            .is_some_and(|grid| flowfield.is_stale(grid));
+        if !stale || !flowfield.units.iter().any(|unit| units.contains(unit)) {
+            continue;
+        }
+
+        flowfield.rebuild(&grids, &layers, &interiors);
+        rebuilt = true;
+    }
+
+    if rebuilt {
+        stats.record_integration(start.elapsed());
+    }
+}
+
+/// Gives each unit of a new flowfield a slot around its destination, filling the inner slots
+/// first with the closest units. Slots in impassable cells are skipped.
+fn assign_arrival_slots(
+    mut cmds: Commands,
+    steering: Res<SteeringSettings>,
+    mut reservations: ResMut<CellReservations>,
+    q_flowfields: Query<&FlowField, Added<FlowField>>,
+    q_transform: Query<&Transform>,
+) {
+    if !matches!(steering.arrival, ArrivalMode::Spread | ArrivalMode::Reserve) {
+        return;
+    }
+
+    for flowfield in q_flowfields.iter() {
+        let mut units: Vec<(Entity, Vec3)> = flowfield
+            .units
+            .iter()
+            .filter_map(|unit| Some((*unit, q_transform.get(*unit).ok()?.translation)))
+            .collect();
+
+        // Units give up their old cell before claiming a new one
+        for (unit, _) in units.iter() {
+            reservations.release(*unit);
+        }
+
+        let slots = match steering.arrival {
+            ArrivalMode::Reserve => free_cells(flowfield, units.len(), &reservations),
+            _ => arrival_slots(flowfield, units.len())
+                .into_iter()
+                .map(|slot| (None, slot))
+                .collect(),
+        };
+        for (idx, slot) in slots {
+            let Some((i, _)) = units.iter().enumerate().min_by(|(_, (_, a)), (_, (_, b))| {
+                let a = a.xz().distance_squared(slot.xz());
+                let b = b.xz().distance_squared(slot.xz());
+                a.total_cmp(&b)
+            }) else {
+                break;
+            };
+
+            let (unit, _) = units.swap_remove(i);
+            if let Some(idx) = idx {
+                reservations.reserve(flowfield.map, idx, unit);
+            }
+            cmds.entity(unit).insert(ArrivalSlot(slot));
+        }
+    }
+}
+
+/// Up to `count` passable positions in rings one cell apart around the flowfield's destination
+fn arrival_slots(flowfield: &FlowField, count: usize) -> Vec<Vec3> {
+    let destination = flowfield.destination;
+    let max_ring = flowfield.size.max_element();
+    let mut slots = Vec::with_capacity(count);
+
+    for ring in 0..max_ring {
+        let radius = ring as f32 * flowfield.cell_diameter;
+        let ring_slots = (std::f32::consts::TAU * ring as f32).floor().max(1.0) as usize;
+
+        for i in 0..ring_slots {
+            if slots.len() == count {
+                return slots;
+            }
+
+            let angle = i as f32 / ring_slots as f32 * std::f32::consts::TAU;
+            let slot = destination + Vec3::new(angle.cos(), 0.0, angle.sin()) * radius;
+            let passable = coords::world_to_idx(slot, flowfield.size, flowfield.cell_diameter)
+                .is_some_and(|idx| flowfield.grid[idx.y as usize][idx.x as usize].cost != u8::MAX);
+            if passable {
+                slots.push(slot);
+            }
+        }
+    }
+
+    return slots;
+}
+
+/// Up to `count` passable cells nobody reserved, breadth first from the flowfield's destination
+/// cell. The destination cell's slot is the exact destination, the others' their center.
+fn free_cells(
+    flowfield: &FlowField,
+    count: usize,
+    reservations: &CellReservations,
+) -> Vec<(Option<IVec2>, Vec3)> {
+    let start = flowfield.destination_cell.idx;
+    let mut visited = HashSet::from([start]);
+    let mut queue = VecDeque::from([start]);
+    let mut cells = Vec::with_capacity(count);
+
+    while let Some(idx) = queue.pop_front() {
+        if cells.len() == count {
+            break;
+        }
+
+        let cell = &flowfield.grid[idx.y as usize][idx.x as usize];
+        if cell.cost == u8::MAX {
+            continue;
+        }
+
+        if !reservations.is_reserved(flowfield.map, idx) {
+            let slot = match idx == start {
+                true => flowfield.destination,
+                false => cell.world_pos,
+            };
+            cells.push((Some(idx), slot));
+        }
+
+        for direction in flowfield.connectivity.integration_directions(idx) {
+            let neighbor = idx + direction.vector();
+            if coords::in_bounds(neighbor, flowfield.size) && visited.insert(neighbor) {
+                queue.push_back(neighbor);
+            }
+        }
+    }
+
+    return cells;
+}
+
+fn release_holding_units(
+    mut cmds: Commands,
+    q_units: Query<Entity, (With<HoldingPosition>, Added<Destination>)>,
+) {
+    for unit in q_units.iter() {
+        cmds.entity(unit).remove::<HoldingPosition>();
+    }
+}
+
+/// Removes units that settled according to `SteeringSettings::arrival` from their flowfield
+fn arrive_units(
+    mut cmds: Commands,
+    steering: Res<SteeringSettings>,
+    mut q_flowfields: Query<&mut FlowField>,
+    mut q_units: Query<(&Transform, Option<&ArrivalSlot>, Option<&mut Steering>)>,
+    q_holding: Query<&Transform, With<HoldingPosition>>,
+    mut reached: EventWriter<DestinationReachedEv>,
+) {
+    let mut arrivals = Vec::new();
+    for mut flowfield in q_flowfields.iter_mut() {
+        let destination = flowfield.destination;
+        let held = match steering.arrival {
+            ArrivalMode::Stop => held_cells(&flowfield, &q_holding),
+            _ => HashSet::new(),
+        };
+        let mut arrived = Vec::new();
+
+        for unit in flowfield.units.iter() {
+            let Ok((transform, slot, unit_steering)) = q_units.get_mut(*unit) else {
+                continue;
+            };
+            let pos = transform.translation;
+
+            let settled = match (steering.arrival, slot) {
+                (ArrivalMode::Spread | ArrivalMode::Reserve, Some(slot)) => {
+                    let to_slot = (slot.0 - pos).xz();
+
+                    // Close to its slot, a unit heads straight for it instead of the destination
+                    if let Some(mut unit_steering) = unit_steering {
+                        if to_slot.length_squared() < flowfield.cell_diameter_squared * 4.0 {
+                            let to_slot = to_slot.normalize_or_zero();
+                            unit_steering.direction = flowfield.direction_to_world(to_slot);
+                        }
+                    }
+
+                    to_slot.length_squared() < flowfield.cell_radius * flowfield.cell_radius
+                }
+                (ArrivalMode::Stop, _) => {
+                    let idx =
+                        coords::world_to_idx_clamped(pos, flowfield.size, flowfield.cell_diameter);
+                    let near_destination =
+                        (idx - flowfield.destination_cell.idx).abs().max_element() <= 1;
+                    !held.contains(&idx) && (near_destination || touches(&held, idx))
+                }
+                _ => (destination - pos).xz().length() < flowfield.cell_radius,
+            };
+
+            if settled {
+                arrived.push(*unit);
+            }
+        }
+
+        for unit in arrived {
+            flowfield.remove_unit(unit, &mut cmds);
+            cmds.entity(unit)
+                .remove::<ArrivalSlot>()
+                .insert(HoldingPosition);
+            arrivals
+                .push(DestinationReachedEv::new(unit, destination).with_flowfield(flowfield.id));
+        }
+    }
+
+    // By flowfield id rather than query order, which depends on how the world stores the fields
+    arrivals.sort_by_key(|ev| ev.flowfield);
+    reached.send_batch(arrivals);
+}
+
+/// The cells holding units that are settled around the flowfield's destination, including ones
+/// settled next to each other in a cluster reaching the destination
+fn held_cells(
+    flowfield: &FlowField,
+    q_holding: &Query<&Transform, With<HoldingPosition>>,
+) -> HashSet<IVec2> {
+    let occupied: HashSet<IVec2> = q_holding
+        .iter()
+        .filter_map(|t| {
+            coords::world_to_idx(t.translation, flowfield.size, flowfield.cell_diameter)
+        })
+        .collect();
+
+    let start = flowfield.destination_cell.idx;
+    let mut cluster = HashSet::new();
+    let mut stack = vec![start];
+
+    while let Some(idx) = stack.pop() {
+        for y in -1..=1 {
+            for x in -1..=1 {
+                let neighbor = idx + IVec2::new(x, y);
+                if occupied.contains(&neighbor) && cluster.insert(neighbor) {
+                    stack.push(neighbor);
+                }
+            }
+        }
+    }
+
+    return cluster;
+}
+
+fn touches(cells: &HashSet<IVec2>, idx: IVec2) -> bool {
+    for y in -1..=1 {
+        for x in -1..=1 {
+            if cells.contains(&(idx + IVec2::new(x, y))) {
+                return true;
+            }
+        }
+    }
+
+    return false;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::FRAC_PI_2;
+
+    /// A field over a 6x6 grid leading east along a corridor between walls at z = -1 and z = 1
+    fn corridor_field() -> FlowField {
+        let grid = Grid::new(IVec2::new(6, 6), 1.0, |pos| {
+            pos.z.abs() > 0.9 && pos.z < 2.0
+        });
+        let mut flowfield = FlowField::new(grid.cell_radius, grid.size, Vec::new());
+        flowfield.create_fields(&grid, &GridLayers::default(), &[], Vec3::new(2.5, 0.0, 0.5));
+        flowfield
+    }
+
+    #[test]
+    fn blocked_cells_are_found_after_the_grid_expands() {
+        let mut grid = Grid::new(IVec2::new(6, 6), 1.0, |_| false);
+        let mut flowfield = FlowField::new(grid.cell_radius, grid.size, Vec::new());
+        flowfield.create_fields(&grid, &GridLayers::default(), &[], Vec3::new(2.5, 0.0, 0.5));
+
+        // The field isn't rebuilt, its indices are now 2 cells off the grid's
+        grid.expand(2, 1);
+        let pos = Vec3::new(-0.5, 0.0, 0.5);
+        let idx = grid.get_cell_from_world_position(pos).idx;
+        grid.set_base_cost(idx, u8::MAX);
+
+        let mut repaths = Vec::new();
+        let unit = Entity::PLACEHOLDER;
+        let pushed = avoid_blocked_cells(&grid, &flowfield, unit, pos, Vec2::X, &mut repaths);
+        assert_ne!(pushed, Vec2::X);
+        assert_eq!(repaths.len(), 1);
+        assert_eq!(repaths[0].cell, idx);
+
+        // The unit whose field index matches the blocked cell's grid index stands on free ground
+        let elsewhere = Vec3::new(1.5, 0.0, 2.5);
+        repaths.clear();
+        let free = avoid_blocked_cells(&grid, &flowfield, unit, elsewhere, Vec2::X, &mut repaths);
+        assert_eq!(free, Vec2::X);
+        assert!(repaths.is_empty());
+    }
+
+    #[test]
+    fn units_slide_along_walls_instead_of_into_them() {
+        let flowfield = corridor_field();
+        let pos = Vec3::new(-1.5, 0.0, 0.8);
+
+        // Glancing off the wall keeps the part of the direction along it
+        let glancing = slide_along_walls(&flowfield, pos, Vec2::new(1.0, 1.0).normalize());
+        assert!(glancing.distance(Vec2::X) < 1e-5);
+
+        // Head-on, the unit slides towards the destination
+        let head_on = slide_along_walls(&flowfield, pos, Vec2::Y);
+        assert!(head_on.distance(Vec2::X) < 1e-5);
+
+        // Directions away from walls are left alone
+        let free = Vec2::new(1.0, -0.2).normalize();
+        assert_eq!(slide_along_walls(&flowfield, pos, free), free);
+    }
+
+    #[test]
+    fn headings_turn_no_faster_than_the_turn_rate() {
+        // A quarter turn left at a rate of 30 degrees per step takes three steps
+        let mut heading = Vec2::X;
+        for _ in 0..2 {
+            heading = turn_towards(heading, Vec2::Y, FRAC_PI_2 / 3.0);
+            assert!(heading.angle_to(Vec2::Y) > 0.1);
+        }
+        heading = turn_towards(heading, Vec2::Y, FRAC_PI_2 / 3.0);
+        assert!(heading.distance(Vec2::Y) < 1e-5);
+
+        // Once facing the direction, it stays put
+        assert!(turn_towards(Vec2::Y, Vec2::Y, 1.0).distance(Vec2::Y) < 1e-6);
+
+        // Turns take the short way around
+        let right = turn_towards(Vec2::X, Vec2::NEG_Y, 0.1);
+        assert!(right.y < 0.0);
+    }
+}
@@ -0,0 +1,247 @@
+//! Chunk streaming for open worlds whose terrain costs can't all be known up front. The `Grid`
+//! resource is split into square chunks, and only the chunks around `StreamAnchor`s and the
+//! `GameCamera` hold terrain costs from the `GridStreaming` provider. The others cost
+//! `GridStreaming::unloaded_cost`, so flowfields only route over loaded chunks. The chunks a
+//! queued move order needs are faulted in before its flowfield is built.
+
+use crate::{
+    components::GameCamera, events::UpdateCostEv, grid::Grid, scheduler::FlowFieldScheduler,
+    PathfindingSchedule, PathfindingSet,
+};
+
+use bevy::prelude::*;
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::Arc;
+
+pub struct StreamingPlugin;
+
+impl Plugin for StreamingPlugin {
+    fn build(&self, app: &mut App) {
+        let schedule = PathfindingSchedule::label(app);
+        app.init_resource::<LoadedChunks>()
+            .register_type::<StreamAnchor>()
+            .add_systems(
+                schedule,
+                stream_chunks
+                    .run_if(resource_exists::<GridStreaming>)
+                    .in_set(PathfindingSet::UpdateCosts),
+            );
+    }
+}
+
+/// The terrain cost of the cell centered at a world position, see `GridStreaming`
+#[derive(Clone)]
+pub struct ChunkCostFn(pub Arc<dyn Fn(Vec3) -> u8 + Send + Sync>);
+
+impl fmt::Debug for ChunkCostFn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ChunkCostFn")
+    }
+}
+
+/// Insert to stream the terrain costs of the `Grid` resource in chunks
+#[derive(Resource, Clone, Debug)]
+pub struct GridStreaming {
+    /// The side of a chunk, in cells
+    pub chunk_size: u32,
+    /// How many chunks around an anchor stay loaded. Chunks unload one chunk further out, so
+    /// anchors on a chunk border don't reload them every frame.
+    pub load_radius: u32,
+    /// The cost of cells in unloaded chunks
+    pub unloaded_cost: u8,
+    pub costs: ChunkCostFn,
+}
+
+impl GridStreaming {
+    pub fn new(costs: impl Fn(Vec3) -> u8 + Send + Sync + 'static) -> Self {
+        Self {
+            chunk_size: 16,
+            load_radius: 2,
+            unloaded_cost: u8::MAX,
+            costs: ChunkCostFn(Arc::new(costs)),
+        }
+    }
+
+    /// The chunk holding `world_pos` on a grid of `cell_diameter`
+    pub fn chunk_of(&self, world_pos: Vec3, cell_diameter: f32) -> IVec2 {
+        let chunk_width = self.chunk_size as f32 * cell_diameter;
+        return (world_pos.xz() / chunk_width).floor().as_ivec2();
+    }
+
+    /// The chunks within `radius` chunks of `center`
+    fn chunks_around(center: IVec2, radius: i32) -> impl Iterator<Item = IVec2> {
+        (-radius..=radius)
+            .flat_map(move |y| (-radius..=radius).map(move |x| center + IVec2::new(x, y)))
+    }
+}
+
+/// Keeps chunks around the entity loaded
+#[derive(Component, Reflect, Default, Clone, Copy)]
+#[reflect(Component)]
+pub struct StreamAnchor;
+
+/// The chunks of the `Grid` resource currently holding their terrain costs
+#[derive(Resource, Default, Debug)]
+pub struct LoadedChunks(HashSet<IVec2>);
+
+impl LoadedChunks {
+    pub fn contains(&self, chunk: IVec2) -> bool {
+        self.0.contains(&chunk)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &IVec2> {
+        self.0.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// Writes the provider's terrain costs into the cells of `chunk`, or `unloaded_cost` when
+/// unloading it. Returns the cells whose cost changed.
+fn write_chunk(grid: &mut Grid, streaming: &GridStreaming, chunk: IVec2, load: bool) -> Vec<IVec2> {
+    let chunk_width = streaming.chunk_size as f32 * grid.cell_diameter;
+    let min = chunk.as_vec2() * chunk_width;
+    let max = min + Vec2::splat(chunk_width);
+
+    let cells: Vec<(IVec2, Vec3)> = grid
+        .cells_in_rect(Vec3::new(min.x, 0.0, min.y), Vec3::new(max.x, 0.0, max.y))
+        .filter(|cell| streaming.chunk_of(cell.world_pos, grid.cell_diameter) == chunk)
+        .map(|cell| (cell.idx, cell.world_pos))
+        .collect();
+
+    let mut changed = Vec::new();
+    for (idx, world_pos) in cells {
+        let cost = match load {
+            true => (streaming.costs.0)(world_pos),
+            false => streaming.unloaded_cost,
+        };
+
+        if grid.set_base_cost(idx, cost) {
+            changed.push(idx);
+        }
+    }
+
+    return changed;
+}
+
+/// Loads the chunks around anchors and the ones queued move orders cross, and unloads the rest
+fn stream_chunks(
+    streaming: Res<GridStreaming>,
+    scheduler: Res<FlowFieldScheduler>,
+    mut grid: ResMut<Grid>,
+    mut loaded: ResMut<LoadedChunks>,
+    mut events: EventWriter<UpdateCostEv>,
+    q_anchors: Query<&GlobalTransform, Or<(With<StreamAnchor>, With<GameCamera>)>>,
+    q_transforms: Query<&Transform>,
+) {
+    let cell_diameter = grid.cell_diameter;
+    let chunk_of = |world_pos: Vec3| streaming.chunk_of(world_pos, cell_diameter);
+    let radius = streaming.load_radius as i32;
+
+    let anchors: Vec<IVec2> = q_anchors
+        .iter()
+        .map(|transform| chunk_of(transform.translation()))
+        .collect();
+
+    // Fault in the chunks along the way from every unit of a queued order to its destination
+    let mut faulted = HashSet::new();
+    let step = streaming.chunk_size as f32 * cell_diameter * 0.5;
+    for request in scheduler.requests() {
+        for unit in request.units.iter() {
+            let Ok(transform) = q_transforms.get(*unit) else {
+                continue;
+            };
+
+            let from = transform.translation;
+            let steps = (from.distance(request.destination) / step).ceil() as usize;
+            for i in 0..=steps {
+                let t = i as f32 / steps.max(1) as f32;
+                faulted.insert(chunk_of(from.lerp(request.destination, t)));
+            }
+        }
+    }
+
+    let mut wanted: HashSet<IVec2> = anchors
+        .iter()
+        .flat_map(|anchor| GridStreaming::chunks_around(*anchor, radius))
+        .collect();
+    wanted.extend(faulted.iter());
+
+    let kept: HashSet<IVec2> = anchors
+        .iter()
+        .flat_map(|anchor| GridStreaming::chunks_around(*anchor, radius + 1))
+        .chain(faulted)
+        .collect();
+
+    let to_load: Vec<IVec2> = wanted.difference(&loaded.0).copied().collect();
+    let to_unload: Vec<IVec2> = loaded.0.difference(&kept).copied().collect();
+    if to_load.is_empty() && to_unload.is_empty() {
+        return;
+    }
+
+    let mut changed = Vec::new();
+    for chunk in to_load {
+        changed.extend(write_chunk(&mut grid, &streaming, chunk, true));
+        loaded.0.insert(chunk);
+    }
+    for chunk in to_unload {
+        changed.extend(write_chunk(&mut grid, &streaming, chunk, false));
+        loaded.0.remove(&chunk);
+    }
+
+    for idx in changed {
+        events.send(UpdateCostEv::new(grid.grid[idx.y as usize][idx.x as usize]));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chunks_load_provider_costs_and_unload_to_the_unloaded_cost() {
+        let mut grid = Grid::new(IVec2::new(8, 8), 1.0, |_| false);
+        let mut streaming = GridStreaming::new(|pos| if pos.x < 0.0 { 3 } else { 5 });
+        streaming.chunk_size = 4;
+
+        // The grid spans -4..4, so it holds the four chunks around the origin
+        assert_eq!(
+            streaming.chunk_of(Vec3::new(-0.5, 0.0, -0.5), 1.0),
+            IVec2::new(-1, -1)
+        );
+        assert_eq!(
+            streaming.chunk_of(Vec3::new(3.5, 0.0, 0.5), 1.0),
+            IVec2::new(0, 0)
+        );
+
+        let changed = write_chunk(&mut grid, &streaming, IVec2::new(-1, 0), true);
+        assert_eq!(changed.len(), 16);
+        assert_eq!(
+            grid.get_cell_from_world_position(Vec3::new(-2.5, 0.0, 2.5))
+                .cost,
+            3
+        );
+        assert_eq!(
+            grid.get_cell_from_world_position(Vec3::new(2.5, 0.0, 2.5))
+                .cost,
+            1
+        );
+
+        write_chunk(&mut grid, &streaming, IVec2::new(-1, 0), false);
+        assert_eq!(
+            grid.get_cell_from_world_position(Vec3::new(-2.5, 0.0, 2.5))
+                .cost,
+            u8::MAX
+        );
+
+        // Chunks beyond the grid have no cells to write
+        assert!(write_chunk(&mut grid, &streaming, IVec2::new(5, 5), true).is_empty());
+    }
+}
@@ -0,0 +1,191 @@
+use crate::{
+    astar::AStarPath,
+    components::Destination,
+    events::UnitStuckEv,
+    flowfield::{FlowField, FlowFieldId},
+    grid::{Connectivity, Grids},
+    interior::InteriorGrid,
+    layers::GridLayers,
+    resources::PathfindingStats,
+    time::PathfindingTime,
+    PathfindingSchedule, PathfindingSet,
+};
+
+use bevy::{prelude::*, utils::Instant};
+use std::collections::HashSet;
+
+pub struct StuckPlugin;
+
+impl Plugin for StuckPlugin {
+    fn build(&self, app: &mut App) {
+        let schedule = PathfindingSchedule::label(app);
+        app.init_resource::<StuckSettings>()
+            .register_type::<StuckSettings>()
+            .register_type::<StuckTimer>()
+            .add_event::<UnitStuckEv>()
+            .add_systems(
+                schedule,
+                (
+                    detect_stuck_units,
+                    repath_stuck_units.run_if(repath_enabled),
+                )
+                    .chain()
+                    .in_set(PathfindingSet::Steering),
+            );
+    }
+}
+
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
+pub struct StuckSettings {
+    /// A unit that stays within this distance of where it was is considered not moving
+    pub threshold: f32,
+    /// Seconds a moving unit can stand still before it's reported stuck
+    pub duration: f32,
+    /// Rebuild a stuck unit's flowfield, or re-plan its `AStarPath`, against the current costs
+    pub repath: bool,
+    /// Minimum seconds between two repaths triggered by the same unit
+    pub repath_cooldown: f32,
+}
+
+impl Default for StuckSettings {
+    fn default() -> Self {
+        StuckSettings {
+            threshold: 0.1,
+            duration: 2.0,
+            repath: true,
+            repath_cooldown: 5.0,
+        }
+    }
+}
+
+/// Tracks how long a unit with a `Destination` hasn't moved
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct StuckTimer {
+    anchor: Vec3,
+    still_for: f32,
+    last_repath: Option<f32>,
+}
+
+fn repath_enabled(settings: Res<StuckSettings>) -> bool {
+    settings.repath
+}
+
+fn detect_stuck_units(
+    mut cmds: Commands,
+    time: PathfindingTime,
+    settings: Res<StuckSettings>,
+    mut events: EventWriter<UnitStuckEv>,
+    mut q_units: Query<(Entity, &Transform, Option<&mut StuckTimer>), With<Destination>>,
+    q_idle: Query<Entity, (With<StuckTimer>, Without<Destination>)>,
+    q_flowfields: Query<&FlowField>,
+    q_paths: Query<&AStarPath>,
+) {
+    for unit in q_idle.iter() {
+        cmds.entity(unit).remove::<StuckTimer>();
+    }
+
+    for (unit, transform, timer) in q_units.iter_mut() {
+        let pos = transform.translation;
+        let Some(mut timer) = timer else {
+            cmds.entity(unit).insert(StuckTimer {
+                anchor: pos,
+                still_for: 0.0,
+                last_repath: None,
+            });
+            continue;
+        };
+
+        if pos.distance_squared(timer.anchor) > settings.threshold * settings.threshold {
+            timer.anchor = pos;
+            timer.still_for = 0.0;
+            continue;
+        }
+
+        timer.still_for += time.delta_secs();
+        if timer.still_for >= settings.duration {
+            timer.still_for = 0.0;
+            let mut stuck = UnitStuckEv::new(unit, pos);
+            stuck.flowfield = order_id(unit, &q_flowfields, &q_paths);
+            events.send(stuck);
+        }
+    }
+}
+
+/// The id of the flowfield or A* path `unit` follows
+fn order_id(
+    unit: Entity,
+    q_flowfields: &Query<&FlowField>,
+    q_paths: &Query<&AStarPath>,
+) -> Option<FlowFieldId> {
+    if let Ok(path) = q_paths.get(unit) {
+        return path.id;
+    }
+
+    return q_flowfields
+        .iter()
+        .find(|flowfield| flowfield.units.contains(&unit))
+        .map(|flowfield| flowfield.id);
+}
+
+/// Rebuilds the flowfields of stuck units and re-plans their A* paths, at most once per
+/// `repath_cooldown` per unit
+fn repath_stuck_units(
+    time: PathfindingTime,
+    settings: Res<StuckSettings>,
+    grids: Grids,
+    connectivity: Res<Connectivity>,
+    layers: Res<GridLayers>,
+    mut stats: ResMut<PathfindingStats>,
+    mut events: EventReader<UnitStuckEv>,
+    mut q_timers: Query<&mut StuckTimer>,
+    mut q_flowfields: Query<&mut FlowField>,
+    mut q_paths: Query<(&Transform, &mut AStarPath)>,
+    q_interiors: Query<(Entity, &InteriorGrid)>,
+) {
+    let now = time.elapsed_secs();
+    let mut units = HashSet::new();
+
+    for ev in events.read() {
+        let Ok(mut timer) = q_timers.get_mut(ev.unit) else {
+            continue;
+        };
+
+        if let Some(last) = timer.last_repath {
+            if now - last < settings.repath_cooldown {
+                continue;
+            }
+        }
+
+        timer.last_repath = Some(now);
+        units.insert(ev.unit);
+    }
+
+    if units.is_empty() {
+        return;
+    }
+
+    let start = Instant::now();
+    let interiors: Vec<(Entity, &InteriorGrid)> = q_interiors.iter().collect();
+
+    for mut flowfield in q_flowfields.iter_mut() {
+        if !flowfield.units.iter().any(|unit| units.contains(unit)) {
+            continue;
+        }
+
+        flowfield.rebuild(&grids, &layers, &interiors);
+    }
+
+    // Paths whose destination became unreachable are tried again after the cooldown
+    for unit in units.iter() {
+        let Ok((transform, mut path)) = q_paths.get_mut(*unit) else {
+            continue;
+        };
+
+        if let Some(grid) = grids.get(path.map) {
+            path.replan(grid, transform.translation, *connectivity);
+        }
+    }
+    stats.record_integration(start.elapsed());
+}
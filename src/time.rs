@@ -0,0 +1,61 @@
+//! The clock pathfinding runs on. By default it follows the game's `Time<Virtual>`: pausing it
+//! suspends steering and queued flowfield builds, and repath, congestion, obstacle and
+//! temporary cost timers speed up and slow down with its relative speed.
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+use std::time::Duration;
+
+#[derive(Resource, Reflect, Default, Clone, Copy, Debug, PartialEq, Eq)]
+#[reflect(Resource)]
+pub enum PathfindingTimeSource {
+    /// Follow the game's speed and pauses. Pausing `Time<Virtual>` leaves every unit's
+    /// `Steering` as it was, so move units by its delta to stop them too.
+    #[default]
+    Virtual,
+    /// Run in real time, ignoring game speed and pauses
+    Real,
+}
+
+/// The time of the `PathfindingTimeSource`. With `Virtual`, this is the schedule's own `Time`,
+/// which is the fixed clock in `FixedUpdate`.
+#[derive(SystemParam)]
+pub struct PathfindingTime<'w> {
+    source: Option<Res<'w, PathfindingTimeSource>>,
+    time: Res<'w, Time>,
+    real: Res<'w, Time<Real>>,
+    virt: Res<'w, Time<Virtual>>,
+}
+
+impl PathfindingTime<'_> {
+    pub fn source(&self) -> PathfindingTimeSource {
+        self.source.as_deref().copied().unwrap_or_default()
+    }
+
+    pub fn delta(&self) -> Duration {
+        match self.source() {
+            PathfindingTimeSource::Virtual => self.time.delta(),
+            PathfindingTimeSource::Real => self.real.delta(),
+        }
+    }
+
+    pub fn delta_secs(&self) -> f32 {
+        self.delta().as_secs_f32()
+    }
+
+    pub fn elapsed_secs(&self) -> f32 {
+        match self.source() {
+            PathfindingTimeSource::Virtual => self.time.elapsed_secs(),
+            PathfindingTimeSource::Real => self.real.elapsed_secs(),
+        }
+    }
+
+    /// True if the game is paused and pathfinding follows it
+    pub fn is_paused(&self) -> bool {
+        self.source() == PathfindingTimeSource::Virtual && self.virt.is_paused()
+    }
+}
+
+/// Run condition for work suspended while the game is paused
+pub fn pathfinding_running(time: PathfindingTime) -> bool {
+    !time.is_paused()
+}
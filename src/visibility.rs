@@ -0,0 +1,131 @@
+use crate::{
+    components::OnGrid,
+    grid::{coords, Grid},
+    PathfindingSchedule, PathfindingSet,
+};
+
+use bevy::prelude::*;
+use bitflags::bitflags;
+
+/// Opt-in fog of war on top of the grid. Insert a `VisibilityGrid` to enable it.
+pub struct VisibilityPlugin;
+
+impl Plugin for VisibilityPlugin {
+    fn build(&self, app: &mut App) {
+        let schedule = PathfindingSchedule::label(app);
+        app.register_type::<VisionRadius>()
+            .add_event::<CellVisibilityChangedEv>()
+            .add_systems(
+                schedule,
+                update_visibility
+                    .after(PathfindingSet::Steering)
+                    .run_if(resource_exists::<VisibilityGrid>),
+            );
+    }
+}
+
+bitflags! {
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+    pub struct CellVisibility: u8 {
+        /// Has been seen at least once
+        const EXPLORED = 1;
+        /// Is currently seen by a unit
+        const VISIBLE = 1 << 1;
+    }
+}
+
+/// How far a unit sees, in world units
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct VisionRadius(pub f32);
+
+/// The visibility of every cell of the `Grid`, indexed like it
+#[derive(Resource)]
+pub struct VisibilityGrid {
+    size: IVec2,
+    cells: Vec<Vec<CellVisibility>>,
+}
+
+impl VisibilityGrid {
+    pub fn new(grid: &Grid) -> Self {
+        VisibilityGrid {
+            size: grid.size,
+            cells: vec![vec![CellVisibility::empty(); grid.size.x as usize]; grid.size.y as usize],
+        }
+    }
+
+    /// The visibility of the cell at `idx`. Cells outside the grid are unexplored.
+    pub fn get(&self, idx: IVec2) -> CellVisibility {
+        if !coords::in_bounds(idx, self.size) {
+            return CellVisibility::empty();
+        }
+
+        return self.cells[idx.y as usize][idx.x as usize];
+    }
+
+    pub fn is_visible(&self, idx: IVec2) -> bool {
+        self.get(idx).contains(CellVisibility::VISIBLE)
+    }
+
+    pub fn is_explored(&self, idx: IVec2) -> bool {
+        self.get(idx).contains(CellVisibility::EXPLORED)
+    }
+}
+
+/// Sent for every cell whose visibility changed this frame
+#[derive(Event)]
+pub struct CellVisibilityChangedEv {
+    pub idx: IVec2,
+    pub old: CellVisibility,
+    pub new: CellVisibility,
+}
+
+impl CellVisibilityChangedEv {
+    pub fn new(idx: IVec2, old: CellVisibility, new: CellVisibility) -> Self {
+        Self { idx, old, new }
+    }
+}
+
+fn update_visibility(
+    grid: Res<Grid>,
+    mut visibility: ResMut<VisibilityGrid>,
+    mut events: EventWriter<CellVisibilityChangedEv>,
+    q_units: Query<(&Transform, &VisionRadius), Without<OnGrid>>,
+) {
+    if visibility.size != grid.size {
+        *visibility = VisibilityGrid::new(&grid);
+    }
+
+    // Explored cells stay explored, everything else is recomputed from the units
+    let mut cells: Vec<Vec<CellVisibility>> = visibility
+        .cells
+        .iter()
+        .map(|row| row.iter().map(|v| *v & CellVisibility::EXPLORED).collect())
+        .collect();
+
+    for (transform, radius) in q_units.iter() {
+        for cell in grid.cells_in_radius(transform.translation, radius.0) {
+            cells[cell.idx.y as usize][cell.idx.x as usize] |=
+                CellVisibility::VISIBLE | CellVisibility::EXPLORED;
+        }
+    }
+
+    let mut changed = false;
+    for (y, row) in cells.iter().enumerate() {
+        for (x, new) in row.iter().enumerate() {
+            let old = visibility.cells[y][x];
+            if old != *new {
+                changed = true;
+                events.send(CellVisibilityChangedEv::new(
+                    IVec2::new(x as i32, y as i32),
+                    old,
+                    *new,
+                ));
+            }
+        }
+    }
+
+    if changed {
+        visibility.cells = cells;
+    }
+}